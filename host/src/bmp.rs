@@ -0,0 +1,86 @@
+// Host copy of the firmware BMP codec, working on 0xRRGGBB pixels instead of
+// GOP BltPixels.
+
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+pub fn parse(data: &[u8]) -> Result<Bitmap, &'static str> {
+    if data.len() < 54 {
+        return Err("too small for BMP");
+    }
+    if data[0] != b'B' || data[1] != b'M' {
+        return Err("not a BMP file");
+    }
+
+    let pixel_offset = read_u32(data, 10) as usize;
+    let width = read_u32(data, 18) as i32;
+    let height = read_u32(data, 22) as i32;
+    let bpp = u16::from_le_bytes([data[28], data[29]]) as usize;
+    if read_u32(data, 30) != 0 {
+        return Err("compressed BMP not supported");
+    }
+    if bpp != 24 && bpp != 32 {
+        return Err("only 24/32-bit BMP supported");
+    }
+
+    let abs_w = width.unsigned_abs() as usize;
+    let abs_h = height.unsigned_abs() as usize;
+    let bottom_up = height > 0;
+    let bytes_per_px = bpp / 8;
+    let row_stride = (abs_w * bytes_per_px).div_ceil(4) * 4;
+
+    let mut pixels = Vec::with_capacity(abs_w * abs_h);
+    for row in 0..abs_h {
+        let src_row = if bottom_up { abs_h - 1 - row } else { row };
+        let row_off = pixel_offset + src_row * row_stride;
+        for col in 0..abs_w {
+            let off = row_off + col * bytes_per_px;
+            if off + bytes_per_px > data.len() {
+                return Err("BMP pixel data truncated");
+            }
+            let (b, g, r) = (data[off], data[off + 1], data[off + 2]);
+            pixels.push(((r as u32) << 16) | ((g as u32) << 8) | b as u32);
+        }
+    }
+
+    Ok(Bitmap {
+        width: abs_w,
+        height: abs_h,
+        pixels,
+    })
+}
+
+// Writes a bottom-up 24-bit BMP, the format `parse` reads back.
+pub fn encode(width: usize, height: usize, pixels: &[u32]) -> Vec<u8> {
+    let row_stride = (width * 3).div_ceil(4) * 4;
+    let pixel_offset = 54;
+    let file_size = pixel_offset + row_stride * height;
+
+    let mut out = Vec::with_capacity(file_size);
+    out.extend_from_slice(b"BM");
+    out.extend_from_slice(&(file_size as u32).to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&(pixel_offset as u32).to_le_bytes());
+    out.extend_from_slice(&40u32.to_le_bytes());
+    out.extend_from_slice(&(width as i32).to_le_bytes());
+    out.extend_from_slice(&(height as i32).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&24u16.to_le_bytes());
+    out.extend_from_slice(&[0; 24]);
+
+    for row in (0..height).rev() {
+        let start = out.len();
+        for &px in &pixels[row * width..(row + 1) * width] {
+            out.extend_from_slice(&[px as u8, (px >> 8) as u8, (px >> 16) as u8]);
+        }
+        out.resize(start + row_stride, 0);
+    }
+    out
+}
+
+fn read_u32(data: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes([data[off], data[off + 1], data[off + 2], data[off + 3]])
+}
@@ -0,0 +1,66 @@
+use std::io::{
+    BufRead,
+    BufReader,
+    Write,
+};
+use std::net::{
+    TcpListener,
+    TcpStream,
+};
+
+use jvm::DebugPort;
+
+// Stands in for the serial port: one client, one line per command.
+pub struct TcpPort {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl TcpPort {
+    pub fn accept(addr: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(addr).map_err(|e| format!("{}: {}", addr, e))?;
+        eprintln!("[host] waiting for a debugger on {}", addr);
+        let (stream, peer) = listener.accept().map_err(|e| e.to_string())?;
+        eprintln!("[host] debugger attached from {}", peer);
+        let writer = stream.try_clone().map_err(|e| e.to_string())?;
+        Ok(Self {
+            reader: BufReader::new(stream),
+            writer,
+        })
+    }
+
+    fn input_waiting(&mut self) -> bool {
+        if !self.reader.buffer().is_empty() {
+            return true;
+        }
+        let mut byte = [0u8];
+        if self.writer.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let waiting = matches!(self.writer.peek(&mut byte), Ok(n) if n > 0);
+        let _ = self.writer.set_nonblocking(false);
+        waiting
+    }
+}
+
+impl DebugPort for TcpPort {
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end().to_string()),
+        }
+    }
+
+    fn poll_line(&mut self) -> Option<String> {
+        if self.input_waiting() {
+            self.read_line()
+        } else {
+            None
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        let _ = writeln!(self.writer, "{}", line);
+    }
+}
@@ -0,0 +1,60 @@
+use std::io::BufRead;
+use std::sync::mpsc::{
+    self,
+    Receiver,
+};
+use std::thread;
+
+// Key codes shared with efi.Console.
+pub const KEY_UP: i32 = -1;
+pub const KEY_DOWN: i32 = -2;
+pub const KEY_ESCAPE: i32 = -3;
+pub const KEY_HOME: i32 = -4;
+pub const KEY_END: i32 = -5;
+pub const KEY_RIGHT: i32 = -6;
+pub const KEY_LEFT: i32 = -7;
+pub const KEY_ENTER: i32 = 13;
+
+// Keys arrive as whitespace-separated words on stdin so a session can be
+// typed live or scripted from a file. A blank line is Enter; any other word
+// that isn't a key name is typed out character by character.
+pub fn spawn_stdin() -> Receiver<i32> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                if tx.send(KEY_ENTER).is_err() {
+                    return;
+                }
+                continue;
+            }
+            for word in line.split_whitespace() {
+                for key in parse_word(word) {
+                    if tx.send(key).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+    rx
+}
+
+fn parse_word(word: &str) -> Vec<i32> {
+    let key = match word.to_ascii_lowercase().as_str() {
+        "up" => KEY_UP,
+        "down" => KEY_DOWN,
+        "esc" | "escape" => KEY_ESCAPE,
+        "home" => KEY_HOME,
+        "end" => KEY_END,
+        "right" => KEY_RIGHT,
+        "left" => KEY_LEFT,
+        "enter" => KEY_ENTER,
+        _ => return word.bytes().map(|b| b as i32).collect(),
+    };
+    vec![key]
+}
@@ -0,0 +1,313 @@
+// Desktop runner for the boot menu: loads the same classes the firmware
+// would from a local directory and backs the efi natives with stdio and,
+// with the `graphics` feature, a framebuffer saved as BMP screenshots.
+
+#[cfg(feature = "graphics")]
+mod bmp;
+mod debug;
+mod input;
+mod natives;
+#[cfg(feature = "graphics")]
+mod screen;
+
+use std::collections::HashMap;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::ExitCode;
+use std::{
+    env,
+    fs,
+};
+
+use jvm::{
+    ClassPath,
+    Debugger,
+    ExecutionObserver,
+    JvmError,
+    Limits,
+    LoadReport,
+    NativeLog,
+    Outcome,
+    Vm,
+    VmBuilder,
+};
+use shared::zip::DEFAULT_RELEASE;
+use shared::{
+    classfile,
+    disasm,
+};
+
+use crate::debug::TcpPort;
+use crate::natives::{
+    HostNatives,
+    HostOptions,
+};
+
+const USAGE: &str = "usage: duke-host [--esp DIR] [--main CLASS] [--entry NAME=PATH]... \
+                     [--set KEY=VALUE]... [--size WxH] [--text] [--screenshots DIR] \
+                     [--debug ADDR] [--record FILE] [--replay FILE] [--disasm CLASSFILE]";
+
+struct Args {
+    // None runs the firmware's pick: a JAR's Main-Class, else BootMenu.
+    main_class: Option<String>,
+    // Serve the debugger on this TCP address, as the firmware does on serial.
+    debug: Option<String>,
+    // Log the run's nondeterministic natives here, and/or play back a log
+    // taken by an earlier run or by the firmware's `deterministic = 1`.
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    // Print this class file javap-style and exit instead of running.
+    disasm: Option<PathBuf>,
+    opts: HostOptions,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        main_class: None,
+        debug: None,
+        record: None,
+        replay: None,
+        disasm: None,
+        opts: HostOptions {
+            esp_root: PathBuf::from("."),
+            entries: Vec::new(),
+            config: HashMap::new(),
+            screen_size: Some((1024, 768)),
+            screenshots: None,
+        },
+    };
+    let mut it = env::args().skip(1);
+    while let Some(flag) = it.next() {
+        if flag == "--text" {
+            args.opts.screen_size = None;
+            continue;
+        }
+        let value = it.next().ok_or_else(|| format!("{} needs a value", flag))?;
+        match flag.as_str() {
+            "--esp" => args.opts.esp_root = PathBuf::from(value),
+            "--main" => args.main_class = Some(value),
+            "--debug" => args.debug = Some(value),
+            "--record" => args.record = Some(PathBuf::from(value)),
+            "--replay" => args.replay = Some(PathBuf::from(value)),
+            "--disasm" => args.disasm = Some(PathBuf::from(value)),
+            "--screenshots" => args.opts.screenshots = Some(PathBuf::from(value)),
+            "--entry" => {
+                let (name, path) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("bad entry {}", value))?;
+                args.opts.entries.push((name.into(), path.into()));
+            }
+            "--set" => {
+                let (key, val) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("bad setting {}", value))?;
+                args.opts
+                    .config
+                    .insert(key.trim().into(), val.trim().into());
+            }
+            "--size" => {
+                let size = value
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                    .ok_or_else(|| format!("bad size {}", value))?;
+                args.opts.screen_size = Some(size);
+            }
+            _ => return Err(format!("unknown option {}", flag)),
+        }
+    }
+    Ok(args)
+}
+
+// Same layout the firmware loads from: the .class files in \EFI\duke, then
+// its JARs, the ones `classpath` names first, then the JARs their
+// manifests' Class-Path entries name.
+fn class_path(root: &Path, order: &str) -> Result<ClassPath, String> {
+    let dir = root.join("EFI").join("duke");
+    let dir = if dir.is_dir() {
+        dir
+    } else {
+        root.to_path_buf()
+    };
+    let listing = fs::read_dir(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let (mut jars, mut classes): (Vec<String>, Vec<String>) = listing
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".class") || name.ends_with(".jar"))
+        .partition(|name| name.ends_with(".jar"));
+    classes.sort();
+    ClassPath::sort_jars(&mut jars, order);
+
+    let read = |name: &str| fs::read(dir.join(name)).map_err(|e| format!("{}: {}", name, e));
+    let mut class_path = ClassPath::new();
+    let loose = classes
+        .into_iter()
+        .map(|name| read(&name).map(|data| (name, data)))
+        .collect::<Result<Vec<_>, _>>()?;
+    class_path.push_classes(dir.display().to_string(), loose);
+    for jar in jars {
+        let data = read(&jar)?;
+        class_path.push_jar(jar, data);
+    }
+    class_path.follow_class_path(|jar| read(jar).ok());
+    Ok(class_path)
+}
+
+fn print_report(report: &LoadReport) {
+    for warning in &report.warnings {
+        eprintln!("[host] class path: {}", warning);
+    }
+}
+
+fn run(args: Args) -> Result<Outcome, String> {
+    let esp_root = args.opts.esp_root.clone();
+    let order = args
+        .opts
+        .config
+        .get("classpath")
+        .cloned()
+        .unwrap_or_default();
+    let class_path = class_path(&esp_root, &order)?;
+    let limits = Limits {
+        max_backward_branches: args.opts.config.get("budget").and_then(|v| v.parse().ok()),
+        max_call_depth: args
+            .opts
+            .config
+            .get("max_depth")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(Limits::default().max_call_depth),
+        ..Limits::default()
+    };
+    let profiling = args.opts.config.get("profile").map(String::as_str) == Some("1");
+    let assertions = args.opts.config.get("assertions").map(String::as_str) == Some("1");
+    let lenient = args.opts.config.get("lenient").map(String::as_str) == Some("1");
+    let release = args
+        .opts
+        .config
+        .get("release")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_RELEASE);
+    let natives = HostNatives::new(args.opts, input::spawn_stdin());
+    let mut builder = VmBuilder::new()
+        .natives(natives)
+        .limits(limits)
+        .profiling(profiling)
+        .enable_assertions(assertions)
+        .release(release)
+        .lenient_parsing(lenient)
+        .deterministic(args.record.is_some());
+    if let Some(path) = &args.replay {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let log = NativeLog::parse(&text).map_err(|e| e.to_string())?;
+        builder = builder.replay(log);
+    }
+    let record = args.record.as_deref();
+    // The firmware runs the Main-Class of the first JAR that names one,
+    // and BootMenu otherwise.
+    let main_class = args
+        .main_class
+        .clone()
+        .or_else(|| class_path.main_class())
+        .unwrap_or_else(|| String::from("BootMenu"));
+    let reload_from = (esp_root.as_path(), order.as_str());
+    match &args.debug {
+        Some(addr) => {
+            let port = TcpPort::accept(addr)?;
+            execute(
+                builder.observer(Debugger::new(port)),
+                class_path,
+                &main_class,
+                record,
+                reload_from,
+            )
+        }
+        None => execute(builder, class_path, &main_class, record, reload_from),
+    }
+}
+
+// Debug.reload: swaps every class for the one now on disk.
+fn reload<O: ExecutionObserver>(
+    vm: &mut Vm<HostNatives, O>,
+    (root, order): (&Path, &str),
+) -> Result<(), String> {
+    let report = class_path(root, order)?
+        .load_into(vm, true)
+        .map_err(|e| e.to_string())?;
+    print_report(&report);
+    Ok(())
+}
+
+fn disassemble(path: &Path) -> Result<(), String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let class = classfile::parse_class(&data).map_err(|e| format!("{}: {}", path.display(), e))?;
+    print!("{}", disasm::disassemble(&class));
+    Ok(())
+}
+
+fn execute<O: ExecutionObserver>(
+    builder: VmBuilder<'_, HostNatives, O>,
+    class_path: ClassPath,
+    main_class: &str,
+    record: Option<&Path>,
+    reload_from: (&Path, &str),
+) -> Result<Outcome, String> {
+    let mut vm = builder.build().map_err(|e: JvmError| e.to_string())?;
+    let report = class_path
+        .load_into(&mut vm, false)
+        .map_err(|e| e.to_string())?;
+    print_report(&report);
+    for warning in vm.take_parse_warnings() {
+        eprintln!("[host] lenient parse: {}", warning);
+    }
+    vm.set_uncaught_handler(|_, exception| {
+        eprintln!("Exception in thread \"main\" {}", exception);
+    });
+    vm.set_exit_hook(|_, status| eprintln!("[host] System.exit({})", status));
+    let outcome = loop {
+        let outcome = vm
+            .run_main(main_class, &[])
+            .map_err(|e| vm.fault(e).to_string());
+        if !(matches!(outcome, Ok(Outcome::Exited(_))) && vm.natives.take_reload()) {
+            break outcome;
+        }
+        reload(&mut vm, reload_from)?;
+    };
+    if let Some(report) = vm.profile() {
+        eprint!("[host] profile\n{}", report);
+    }
+    if vm.is_deterministic() {
+        eprintln!("[host] executed {} instructions", vm.instructions());
+    }
+    if let (Some(path), Some(log)) = (record, vm.native_log()) {
+        fs::write(path, log.to_string()).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    outcome
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if let Some(path) = &args.disasm {
+        return match disassemble(path) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("[host] ERROR: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+    match run(args) {
+        Ok(Outcome::Returned(_)) => ExitCode::SUCCESS,
+        Ok(Outcome::Exited(code)) => ExitCode::from(code as u8),
+        Err(e) => {
+            eprintln!("[host] ERROR: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,972 @@
+use std::collections::HashMap;
+use std::fs::{
+    self,
+    File,
+};
+use std::io::{
+    Read,
+    Seek,
+    SeekFrom,
+    Write,
+};
+use std::path::PathBuf;
+use std::sync::mpsc::{
+    Receiver,
+    TryRecvError,
+};
+use std::thread;
+use std::time::{
+    Duration,
+    Instant,
+    SystemTime,
+    UNIX_EPOCH,
+};
+
+use jvm::{
+    Heap,
+    JvmError,
+    JvmValue,
+    NativeArgs,
+    NativeBridge,
+    Upcall,
+    jvm_value_to_string,
+};
+use shared::entries::{
+    EntryFacts,
+    EntryPolicy,
+};
+#[cfg(feature = "graphics")]
+use shared::font;
+use shared::grub::GrubMenu;
+use shared::pe::ImageInfo;
+use shared::preview::{
+    Crc32,
+    EntryDetails,
+    Timestamp,
+};
+#[cfg(feature = "graphics")]
+use shared::term::Font;
+use shared::term::Terminal;
+use shared::theme::{
+    Selection,
+    Theme,
+};
+use shared::{
+    pe,
+    protocols,
+};
+
+#[cfg(feature = "graphics")]
+use crate::bmp;
+#[cfg(feature = "graphics")]
+use crate::screen::Screen;
+
+pub struct HostOptions {
+    pub esp_root: PathBuf,
+    pub entries: Vec<(String, String)>,
+    pub config: HashMap<String, String>,
+    pub screen_size: Option<(usize, usize)>,
+    pub screenshots: Option<PathBuf>,
+}
+
+// What Debug.handles() lists on the host: the ESP directory, the stdio
+// console, the framebuffer and Duke's own image, by protocol name.
+const HOST_HANDLES: &[(&str, &[&str])] = &[
+    (
+        "VenHw(host-esp)",
+        &["DevicePath", "BlockIo", "DiskIo", "SimpleFileSystem"],
+    ),
+    (
+        "VenHw(host-console)",
+        &["SimpleTextInput", "SimpleTextInputEx", "SimpleTextOutput"],
+    ),
+    ("VenHw(host-screen)", &["DevicePath", "GraphicsOutput"]),
+    ("duke.efi", &["LoadedImage", "LoadedImageDevicePath"]),
+];
+
+// Mirrors the firmware's efi/Timer: period, next due time and callback.
+struct HostTimer {
+    period: Duration,
+    due: Instant,
+    upcall: Upcall,
+}
+
+// Mirrors the firmware's TermState.
+struct HostTerminal {
+    term: Terminal,
+    window: Option<(i32, i32, usize, usize)>,
+    redirect: bool,
+    scroll: usize,
+    fg: u32,
+    bg: u32,
+}
+
+// Stands in for UefiNatives so BootMenu and themes run unchanged on a
+// desktop: the console is stdio, the ESP is a local directory and, with the
+// `graphics` feature, the GOP is a framebuffer dumped to BMPs on endFrame.
+pub struct HostNatives {
+    opts: HostOptions,
+    terminal: HostTerminal,
+    keys: Receiver<i32>,
+    #[cfg(feature = "graphics")]
+    screen: Option<Screen>,
+    #[cfg(feature = "graphics")]
+    frame: u32,
+    timeout: i32,
+    started: Instant,
+    theme: Theme,
+    selection: Selection,
+    hour: Option<u8>,
+    timer: Option<HostTimer>,
+    reload: bool,
+    // Command lines edited in the menu, by entry index.
+    edited: HashMap<usize, String>,
+}
+
+impl HostNatives {
+    pub fn new(mut opts: HostOptions, keys: Receiver<i32>) -> Self {
+        opts.entries = arrange(&opts.config, std::mem::take(&mut opts.entries));
+        let timeout = opts
+            .config
+            .get("timeout")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        let selection = Selection::from_settings(|key| opts.config.get(key).map(String::as_str));
+        let theme = match opts.config.get("theme") {
+            Some(name) => {
+                let mut path = opts.esp_root.join("EFI").join("duke").join("themes");
+                path.push(format!("{}.theme", name));
+                match std::fs::read_to_string(&path) {
+                    Ok(text) => Theme::parse(name, &text),
+                    Err(e) => {
+                        eprintln!("[host] theme {}: {}", path.display(), e);
+                        Theme::builtin()
+                    }
+                }
+            }
+            None => Theme::builtin(),
+        };
+        Self {
+            opts,
+            terminal: HostTerminal {
+                term: Terminal::new(80, 25, 500),
+                window: None,
+                redirect: false,
+                scroll: 0,
+                fg: 0xC0C0C0,
+                bg: 0x000000,
+            },
+            keys,
+            #[cfg(feature = "graphics")]
+            screen: None,
+            #[cfg(feature = "graphics")]
+            frame: 0,
+            timeout,
+            started: Instant::now(),
+            theme,
+            selection,
+            // No time zone database here, so UTC stands in for the RTC.
+            hour: timestamp(SystemTime::now()).map(|t| t.hour),
+            timer: None,
+            reload: false,
+            edited: HashMap::new(),
+        }
+    }
+
+    pub fn take_reload(&mut self) -> bool {
+        std::mem::take(&mut self.reload)
+    }
+
+    // Maps an ESP path like `\EFI\duke\theme.bmp` onto the local root.
+    fn esp_path(&self, path: &str) -> PathBuf {
+        let mut out = self.opts.esp_root.clone();
+        for part in path.split(['\\', '/']).filter(|p| !p.is_empty()) {
+            out.push(part);
+        }
+        out
+    }
+
+    #[cfg(feature = "graphics")]
+    fn read_image(&self, path: &str) -> Option<bmp::Bitmap> {
+        let data = std::fs::read(self.esp_path(path)).ok()?;
+        bmp::parse(&data).ok()
+    }
+
+    fn console(&mut self, method: &str, args: &[JvmValue]) -> Result<Option<JvmValue>, JvmError> {
+        let mut out = std::io::stdout();
+        match method {
+            "print" | "println" => {
+                let mut text = args.first().map(jvm_value_to_string).unwrap_or_default();
+                if method == "println" {
+                    text.push('\n');
+                }
+                if self.terminal.redirect {
+                    self.terminal.term.write(&text);
+                } else {
+                    print!("{}", text);
+                    let _ = out.flush();
+                }
+                Ok(None)
+            }
+            // stdin closing ends the session the way a reset would.
+            "readKey" => match self.keys.recv() {
+                Ok(key) => Ok(Some(JvmValue::Int(key))),
+                Err(_) => Err(JvmError::SystemExit(0)),
+            },
+            "pollKey" => match self.keys.try_recv() {
+                Ok(key) => Ok(Some(JvmValue::Int(key))),
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => Ok(Some(JvmValue::Int(0))),
+            },
+            _ => Err(unknown("efi/Console", method)),
+        }
+    }
+
+    #[cfg(feature = "graphics")]
+    fn graphics(&mut self, method: &str, args: &[JvmValue]) -> Result<Option<JvmValue>, JvmError> {
+        let int = |i: usize| match args.get(i) {
+            Some(JvmValue::Int(v)) => *v,
+            _ => 0,
+        };
+        let string = |i: usize| match args.get(i) {
+            Some(JvmValue::StringRef(s)) => s.as_str(),
+            _ => "",
+        };
+        match method {
+            "initGraphics" => {
+                let Some((w, h)) = self.opts.screen_size else {
+                    return Ok(Some(JvmValue::Int(0)));
+                };
+                self.screen = Some(Screen::new(w, h));
+                Ok(Some(JvmValue::Int(1)))
+            }
+            "fontWidth" => Ok(Some(JvmValue::Int(font::GLYPH_W as i32))),
+            "fontHeight" => Ok(Some(JvmValue::Int(font::GLYPH_H as i32))),
+            "imageWidth" | "imageHeight" => {
+                let size = self.read_image(string(0)).map_or(0, |b| {
+                    if method == "imageWidth" {
+                        b.width
+                    } else {
+                        b.height
+                    }
+                });
+                Ok(Some(JvmValue::Int(size as i32)))
+            }
+            // There is no firmware logo to borrow on a desktop.
+            "drawBgrt" => Ok(Some(JvmValue::Int(0))),
+            "endFrame" => {
+                self.end_frame();
+                Ok(None)
+            }
+            _ => {
+                let image = match method {
+                    "drawImage" => self.read_image(string(0)),
+                    _ => None,
+                };
+                let screen = self
+                    .screen
+                    .as_mut()
+                    .ok_or_else(|| JvmError::IoError(String::from("Graphics not initialized")))?;
+                match method {
+                    "screenWidth" => return Ok(Some(JvmValue::Int(screen.width as i32))),
+                    "screenHeight" => return Ok(Some(JvmValue::Int(screen.height as i32))),
+                    "clearScreen" => screen.clear(int(0) as u32),
+                    "fillRect" => screen.fill_rect(int(0), int(1), int(2), int(3), int(4) as u32),
+                    "drawText" => {
+                        screen.draw_text(string(0), int(1), int(2), int(3) as u32, int(4) as usize)
+                    }
+                    "drawImage" => {
+                        if let Some(image) = image {
+                            screen.blit(int(1), int(2), &image);
+                        }
+                    }
+                    _ => return Err(unknown("efi/Graphics", method)),
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    // Without a framebuffer BootMenu falls back to its text menu.
+    #[cfg(not(feature = "graphics"))]
+    fn graphics(&mut self, method: &str, _args: &[JvmValue]) -> Result<Option<JvmValue>, JvmError> {
+        match method {
+            "initGraphics" => Ok(Some(JvmValue::Int(0))),
+            _ => Err(JvmError::IoError(String::from("Graphics not initialized"))),
+        }
+    }
+
+    fn terminal(&mut self, method: &str, args: &[JvmValue]) -> Result<Option<JvmValue>, JvmError> {
+        let int = |i: usize| match args.get(i) {
+            Some(JvmValue::Int(v)) => *v,
+            _ => 0,
+        };
+        let t = &mut self.terminal;
+        match method {
+            "open" => {
+                let (w, h) = (int(2).max(0) as usize, int(3).max(0) as usize);
+                #[cfg(feature = "graphics")]
+                t.term.resize(w / font::GLYPH_W, h / font::GLYPH_H);
+                t.window = Some((int(0), int(1), w, h));
+                t.scroll = 0;
+            }
+            "setColors" => {
+                t.fg = int(0) as u32 & 0xFFFFFF;
+                t.bg = int(1) as u32 & 0xFFFFFF;
+            }
+            "redirect" => t.redirect = int(0) != 0,
+            "write" => {
+                if let Some(JvmValue::StringRef(text)) = args.first() {
+                    t.term.write(text);
+                }
+            }
+            "clear" => {
+                t.term.clear();
+                t.scroll = 0;
+            }
+            "scroll" => {
+                let back = t.scroll as i64 + int(0) as i64;
+                t.scroll = back.clamp(0, t.term.history_len() as i64) as usize;
+                return Ok(Some(JvmValue::Int(t.scroll as i32)));
+            }
+            "render" =>
+            {
+                #[cfg(feature = "graphics")]
+                if let (Some((x, y, w, h)), Some(screen)) = (t.window, &mut self.screen) {
+                    let font = Font {
+                        width: font::GLYPH_W,
+                        height: font::GLYPH_H,
+                        glyph: font::glyph,
+                    };
+                    let mut pixels = vec![0u32; w * h];
+                    t.term.render(t.scroll, t.fg, t.bg, &font, &mut pixels, w);
+                    screen.blit(
+                        x,
+                        y,
+                        &bmp::Bitmap {
+                            width: w,
+                            height: h,
+                            pixels,
+                        },
+                    );
+                }
+            }
+            "close" => t.window = None,
+            _ => return Err(unknown("efi/Terminal", method)),
+        }
+        Ok(None)
+    }
+
+    #[cfg(feature = "graphics")]
+    fn end_frame(&mut self) {
+        let (Some(dir), Some(screen)) = (&self.opts.screenshots, &self.screen) else {
+            return;
+        };
+        let path = dir.join(format!("frame{:05}.bmp", self.frame));
+        if let Err(e) =
+            std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&path, screen.to_bmp()))
+        {
+            eprintln!("[host] screenshot {}: {}", path.display(), e);
+        }
+        self.frame += 1;
+    }
+
+    fn boot_services(
+        &mut self,
+        method: &str,
+        args: &[JvmValue],
+    ) -> Result<Option<JvmValue>, JvmError> {
+        let slot = match args.first() {
+            Some(JvmValue::Int(i)) => Some(*i as usize),
+            _ => None,
+        };
+        let index = slot.and_then(|i| self.opts.entries.get(i));
+        match method {
+            "discoverEntries" => Ok(Some(JvmValue::Int(self.opts.entries.len() as i32))),
+            "entryName" | "entryPath" => Ok(Some(match index {
+                Some((name, path)) => {
+                    JvmValue::StringRef(if method == "entryName" { name } else { path }.clone())
+                }
+                None => JvmValue::Null,
+            })),
+            "entryBootCount" => Ok(Some(JvmValue::Int(0))),
+            // Only a UKI's os-release; the host has no root filesystems.
+            "entryIcon" => Ok(Some(
+                match index
+                    .and_then(|(_, path)| self.inspect(path))
+                    .and_then(|image| image.os_release)
+                {
+                    Some(release) => JvmValue::StringRef(String::from(release.icon())),
+                    None => JvmValue::Null,
+                },
+            )),
+            "entryDetails" => Ok(Some(match index {
+                Some((_, path)) => JvmValue::StringRef(self.entry_details(path)),
+                None => JvmValue::Null,
+            })),
+            "stall" => {
+                if let Some(JvmValue::Int(ms)) = args.first() {
+                    thread::sleep(Duration::from_millis((*ms).max(0) as u64));
+                }
+                Ok(None)
+            }
+            // Nothing to hand control to; report the choice and stop.
+            "chainloadEntry" | "chainload" => {
+                let path = match (index, args.first()) {
+                    (Some((_, path)), _) => path.clone(),
+                    (None, Some(JvmValue::StringRef(path))) => path.clone(),
+                    _ => return Err(JvmError::IoError(String::from("no such entry"))),
+                };
+                match slot.and_then(|i| self.edited.get(&i)) {
+                    Some(options) => println!("[host] chainload {} {}", path, options),
+                    None => println!("[host] chainload {}", path),
+                }
+                Err(JvmError::SystemExit(0))
+            }
+            "chainloadWithOptions" => match (args.first(), args.get(1)) {
+                (Some(JvmValue::StringRef(path)), Some(JvmValue::StringRef(options))) => {
+                    println!("[host] chainload {} {}", path, options);
+                    Err(JvmError::SystemExit(0))
+                }
+                _ => Err(JvmError::NullPointerException),
+            },
+            // --entry never makes a firmware Boot#### option.
+            "setBootNext" => Ok(Some(JvmValue::Int(0))),
+            // --entry never makes a loader entry.
+            "entryTriesLeft" => Ok(Some(JvmValue::Int(-1))),
+            // An edit, else a UKI's built-in command line.
+            "entryOptions" => Ok(Some(match index {
+                Some((_, path)) => JvmValue::StringRef(
+                    slot.and_then(|i| self.edited.get(&i))
+                        .cloned()
+                        .or_else(|| self.inspect(path).and_then(|image| image.cmdline))
+                        .unwrap_or_default(),
+                ),
+                None => JvmValue::Null,
+            })),
+            "setEntryOptions" => {
+                let Some(i) = slot.filter(|_| index.is_some()) else {
+                    return Ok(Some(JvmValue::Int(0)));
+                };
+                match args.get(1) {
+                    Some(JvmValue::StringRef(options)) => self.edited.insert(i, options.clone()),
+                    _ => self.edited.remove(&i),
+                };
+                Ok(Some(JvmValue::Int(1)))
+            }
+            _ => Err(unknown("efi/BootServices", method)),
+        }
+    }
+
+    fn inspect(&self, path: &str) -> Option<ImageInfo> {
+        inspect_file(&mut File::open(self.esp_path(path)).ok()?)
+    }
+
+    // The same facts the firmware side gathers, read from the local copy.
+    fn entry_details(&self, path: &str) -> String {
+        let mut details = EntryDetails {
+            path: String::from(path),
+            ..EntryDetails::default()
+        };
+        let Ok(mut file) = File::open(self.esp_path(path)) else {
+            return details.render();
+        };
+        if let Ok(meta) = file.metadata() {
+            details.size = Some(meta.len());
+            details.modified = meta.modified().ok().and_then(timestamp);
+        }
+        details.image = inspect_file(&mut file);
+        details.command_line = details.image.as_ref().and_then(|i| i.cmdline.clone());
+        // The firmware reads the vendor directory's grub.cfg; the loader
+        // normally sits beside it.
+        if let Ok(text) = fs::read_to_string(self.esp_path(path).with_file_name("grub.cfg")) {
+            let submenus = self.opts.config.get("grub_submenus").map(String::as_str) == Some("1");
+            details.menu = GrubMenu::parse(&text).titles(submenus);
+        }
+        if self.opts.config.get("preview_hash").map(String::as_str) == Some("1") {
+            let mut crc = Crc32::new();
+            let mut buf = vec![0u8; 1024 * 1024];
+            if file.seek(SeekFrom::Start(0)).is_ok() {
+                while let Ok(n @ 1..) = file.read(&mut buf) {
+                    crc.update(&buf[..n]);
+                }
+                details.crc32 = Some(crc.finish());
+            }
+        }
+        details.render()
+    }
+
+    fn config(&mut self, method: &str, args: &[JvmValue]) -> Result<Option<JvmValue>, JvmError> {
+        let value = match args.first() {
+            Some(JvmValue::StringRef(key)) => self.opts.config.get(key),
+            _ => None,
+        };
+        match method {
+            "get" => Ok(Some(match value {
+                Some(v) => JvmValue::StringRef(v.clone()),
+                None => JvmValue::Null,
+            })),
+            "getInt" => {
+                let default = match args.get(1) {
+                    Some(JvmValue::Int(v)) => *v,
+                    _ => 0,
+                };
+                let v = value.and_then(|v| v.parse().ok()).unwrap_or(default);
+                Ok(Some(JvmValue::Int(v)))
+            }
+            "machineId" => Ok(Some(JvmValue::StringRef(String::from("host")))),
+            "machineModel" => Ok(Some(JvmValue::Null)),
+            _ => Err(unknown("efi/Config", method)),
+        }
+    }
+
+    fn timeout(&mut self, method: &str, args: &[JvmValue]) -> Result<Option<JvmValue>, JvmError> {
+        match method {
+            "seconds" => Ok(Some(JvmValue::Int(self.timeout))),
+            "set" => {
+                if let Some(JvmValue::Int(v)) = args.first() {
+                    self.timeout = (*v).max(0);
+                }
+                Ok(None)
+            }
+            "touch" => Ok(None),
+            "defaultEntry" => Ok(Some(JvmValue::Int(0))),
+            // No boots recorded here, so nothing ever fails.
+            "markBootSuccess" => Ok(None),
+            "fallbackEntry" => Ok(Some(JvmValue::Int(-1))),
+            _ => Err(unknown("efi/Timeout", method)),
+        }
+    }
+
+    // Testing the host's RAM from here would prove nothing; the tool just
+    // runs to completion over zero bytes so its UI can be exercised.
+    fn mem_test(&mut self, method: &str) -> Result<Option<JvmValue>, JvmError> {
+        match method {
+            "start" | "errors" => Ok(Some(JvmValue::Int(0))),
+            "step" => Ok(Some(JvmValue::Int(100))),
+            "finish" => Ok(Some(JvmValue::StringRef(String::from(
+                "Not available on the host",
+            )))),
+            _ => Err(unknown("efi/MemTest", method)),
+        }
+    }
+
+    // No pass-through protocols on a desktop: no drives, no partitions.
+    fn disk_health(&mut self, method: &str) -> Result<Option<JvmValue>, JvmError> {
+        match method {
+            "probe" | "partitions" | "warning" | "scanStart" | "scanErrors" => {
+                Ok(Some(JvmValue::Int(0)))
+            }
+            "scanStep" => Ok(Some(JvmValue::Int(100))),
+            "summary" | "partitionName" | "scanFinish" => Ok(Some(JvmValue::Null)),
+            _ => Err(unknown("efi/DiskHealth", method)),
+        }
+    }
+
+    fn debug(&mut self, method: &str, args: &[JvmValue]) -> Result<Option<JvmValue>, JvmError> {
+        match method {
+            "writeHeapDump" => {
+                if let Some(JvmValue::StringRef(text)) = args.first() {
+                    let path = self.esp_path("\\EFI\\duke\\heap.dump");
+                    std::fs::write(&path, text)
+                        .map_err(|e| JvmError::IoError(format!("{}: {}", path.display(), e)))?;
+                    eprintln!("[host] heap dump written to {}", path.display());
+                }
+                Ok(None)
+            }
+            "writeProfile" => {
+                if let Some(JvmValue::StringRef(text)) = args.first() {
+                    let path = self.esp_path("\\EFI\\duke\\profile.txt");
+                    std::fs::write(&path, text)
+                        .map_err(|e| JvmError::IoError(format!("{}: {}", path.display(), e)))?;
+                    eprintln!("[host] profile written to {}", path.display());
+                }
+                Ok(None)
+            }
+            "handles" => Ok(Some(JvmValue::Int(HOST_HANDLES.len() as i32))),
+            "handleName" | "handleProtocols" => {
+                let handle = match args.first() {
+                    Some(JvmValue::Int(i)) if *i >= 0 => HOST_HANDLES.get(*i as usize),
+                    _ => None,
+                };
+                Ok(Some(match handle {
+                    Some((name, _)) if method == "handleName" => {
+                        JvmValue::StringRef((*name).into())
+                    }
+                    Some((_, names)) => JvmValue::StringRef(protocols::describe(
+                        names.iter().filter_map(|n| protocols::guid(n)),
+                    )),
+                    None => JvmValue::Null,
+                }))
+            }
+            "reload" => {
+                if self.opts.config.get("dev").map(String::as_str) != Some("1") {
+                    return Err(JvmError::NativeMethodError(
+                        "Debug.reload needs dev = 1".into(),
+                    ));
+                }
+                eprintln!("[host] reloading classes");
+                self.reload = true;
+                Err(JvmError::SystemExit(0))
+            }
+            _ => Err(unknown("efi/Debug", method)),
+        }
+    }
+
+    fn file_system(
+        &mut self,
+        heap: &mut Heap,
+        method: &str,
+        args: &[JvmValue],
+    ) -> Result<Option<JvmValue>, JvmError> {
+        let Some(JvmValue::StringRef(path)) = args.first() else {
+            return Ok(Some(JvmValue::Null));
+        };
+        let path = self.esp_path(path);
+        let array = match method {
+            "fileSize" => {
+                let size = match std::fs::metadata(&path) {
+                    Ok(meta) if meta.is_file() => meta.len() as i64,
+                    _ => -1,
+                };
+                return Ok(Some(JvmValue::Long(size)));
+            }
+            // FAT attributes don't survive onto the host's filesystem; only
+            // the directory and read-only bits are filled in.
+            "fileInfo" => match std::fs::metadata(&path) {
+                Ok(meta) => {
+                    let modified = meta
+                        .modified()
+                        .ok()
+                        .and_then(timestamp)
+                        .map_or(0, |t| t.sortable());
+                    let attributes = if meta.is_dir() { 0x10 } else { 0 }
+                        | if meta.permissions().readonly() {
+                            0x01
+                        } else {
+                            0
+                        };
+                    heap.alloc_longs(&[meta.len() as i64, modified, attributes])?
+                }
+                Err(_) => return Ok(Some(JvmValue::Null)),
+            },
+            "exists" => return Ok(Some(JvmValue::Int(path.exists() as i32))),
+            "isDirectory" => return Ok(Some(JvmValue::Int(path.is_dir() as i32))),
+            "delete" | "rename" | "mkdir" => {
+                let changed = match method {
+                    "delete" if path.is_dir() => fs::remove_dir(&path),
+                    "delete" => fs::remove_file(&path),
+                    "mkdir" => fs::create_dir_all(&path),
+                    _ => {
+                        let Some(JvmValue::StringRef(to)) = args.get(1) else {
+                            return Err(JvmError::NullPointerException);
+                        };
+                        let to = self.esp_path(to);
+                        // As on UEFI: a directory only renamed within its
+                        // parent, and never over another directory.
+                        if to.is_dir() {
+                            return Err(JvmError::IoError(format!(
+                                "{}: {} is a directory",
+                                path.display(),
+                                to.display()
+                            )));
+                        }
+                        if path.is_dir() && path.parent() != to.parent() {
+                            return Err(JvmError::IoError(format!(
+                                "{}: a directory can only be renamed within its parent",
+                                path.display()
+                            )));
+                        }
+                        to.parent()
+                            .map_or(Ok(()), fs::create_dir_all)
+                            .and_then(|_| fs::rename(&path, &to))
+                    }
+                };
+                return match changed {
+                    Ok(()) => Ok(None),
+                    Err(e) => Err(JvmError::IoError(format!("{}: {}", path.display(), e))),
+                };
+            }
+            "writeFile" | "appendFile" => {
+                let data = NativeArgs::new(method, "", args).byte_array(heap, 1)?;
+                let written = path
+                    .parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| {
+                        let mut file = File::options()
+                            .create(true)
+                            .write(true)
+                            .append(method == "appendFile")
+                            .truncate(method == "writeFile")
+                            .open(&path)?;
+                        file.write_all(&data)
+                    });
+                return match written {
+                    Ok(()) => Ok(None),
+                    Err(e) => Err(JvmError::IoError(format!("{}: {}", path.display(), e))),
+                };
+            }
+            "readFile" => match std::fs::read(&path) {
+                Ok(data) => heap.alloc_bytes(&data)?,
+                Err(_) => return Ok(Some(JvmValue::Null)),
+            },
+            "listEntries" => match std::fs::read_dir(&path) {
+                Ok(dir) => {
+                    let mut names: Vec<(bool, String)> = dir
+                        .filter_map(|e| e.ok())
+                        .map(|e| {
+                            let dir = e.file_type().is_ok_and(|t| t.is_dir());
+                            (dir, e.file_name().to_string_lossy().into_owned())
+                        })
+                        .collect();
+                    names.sort_by_cached_key(|(dir, name)| (!dir, name.to_lowercase()));
+                    let names: Vec<String> = names
+                        .into_iter()
+                        .map(|(dir, name)| if dir { format!("{}\\", name) } else { name })
+                        .collect();
+                    heap.alloc_strings(&names)?
+                }
+                Err(_) => return Ok(Some(JvmValue::Null)),
+            },
+            "listDirectory" => match std::fs::read_dir(&path) {
+                Ok(dir) => {
+                    let names: Vec<String> = dir
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.file_name().to_string_lossy().into_owned())
+                        .collect();
+                    heap.alloc_strings(&names)?
+                }
+                Err(_) => return Ok(Some(JvmValue::Null)),
+            },
+            _ => return Err(unknown("efi/FileSystem", method)),
+        };
+        Ok(Some(JvmValue::ArrayRef(array)))
+    }
+
+    fn timer(&mut self, method: &str, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+        match method {
+            "every" => {
+                let (ms, class_name, method_name) =
+                    (args.int(0)?, args.string(1)?, args.string(2)?);
+                let period = Duration::from_millis(ms.max(1) as u64);
+                self.timer = Some(HostTimer {
+                    period,
+                    due: Instant::now() + period,
+                    upcall: Upcall {
+                        class_name: class_name.into(),
+                        method_name: method_name.into(),
+                        descriptor: "()V".into(),
+                        args: Vec::new(),
+                    },
+                });
+                Ok(None)
+            }
+            "cancel" => {
+                self.timer = None;
+                Ok(None)
+            }
+            _ => Err(unknown("efi/Timer", method)),
+        }
+    }
+
+    fn theme(&mut self, method: &str, args: &[JvmValue]) -> Result<Option<JvmValue>, JvmError> {
+        let variant = match method {
+            "name" => return Ok(Some(JvmValue::StringRef(self.theme.name.clone()))),
+            "toggle" => self.selection.toggle(self.hour),
+            _ => self.selection.active(self.hour),
+        };
+        match method {
+            "activeVariant" | "toggle" => Ok(Some(JvmValue::StringRef(variant.name().into()))),
+            "color" => {
+                let Some(JvmValue::StringRef(role)) = args.first() else {
+                    return Err(JvmError::NativeMethodError(
+                        "Theme.color needs a role".into(),
+                    ));
+                };
+                match self.theme.palette(variant).get(role) {
+                    Some(c) => Ok(Some(JvmValue::Int(c as i32))),
+                    None => Err(JvmError::NativeMethodError(format!(
+                        "no theme colour {}",
+                        role
+                    ))),
+                }
+            }
+            _ => Err(unknown("efi/Theme", method)),
+        }
+    }
+
+    fn system_info(&mut self, method: &str) -> Result<Option<JvmValue>, JvmError> {
+        match method {
+            "firmwareVendor" => Ok(Some(JvmValue::StringRef(String::from("Duke host")))),
+            "firmwareRevision" => Ok(Some(JvmValue::Int(0))),
+            "uefiRevision" => Ok(Some(JvmValue::StringRef(String::from("host")))),
+            _ => Err(unknown("efi/SystemInfo", method)),
+        }
+    }
+
+    // No firmware to enforce anything.
+    fn secure_boot(&mut self, method: &str) -> Result<Option<JvmValue>, JvmError> {
+        match method {
+            "enabled" | "setupMode" | "entryBlocked" | "shimPresent" => Ok(Some(JvmValue::Int(0))),
+            _ => Err(unknown("efi/SecureBoot", method)),
+        }
+    }
+
+    // Reports the choice and stops, as a chainload does.
+    fn power(&mut self, method: &str, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+        match method {
+            "reset" => {
+                let kind = match args.int(0)? {
+                    1 => "warm reboot",
+                    2 => "cold reboot",
+                    3 => "power off",
+                    other => {
+                        return Err(JvmError::NativeMethodError(format!(
+                            "reset: unknown kind {}",
+                            other
+                        )));
+                    }
+                };
+                println!("[host] {}", kind);
+                Err(JvmError::SystemExit(0))
+            }
+            // No firmware to reboot into.
+            "rebootToFirmware" => Ok(Some(JvmValue::Int(0))),
+            _ => Err(unknown("efi/Power", method)),
+        }
+    }
+
+    fn tpm(&mut self, method: &str) -> Result<Option<JvmValue>, JvmError> {
+        match method {
+            "present" => Ok(Some(JvmValue::Int(0))),
+            _ => Err(unknown("efi/Tpm", method)),
+        }
+    }
+}
+
+// UTC, like the FAT timestamps the firmware reports.
+fn timestamp(time: SystemTime) -> Option<Timestamp> {
+    let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    // Howard Hinnant's civil_from_days.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = (yoe + era * 400 + i64::from(month <= 2)) as u16;
+    Some(Timestamp {
+        year,
+        month,
+        day,
+        hour: (rem / 3600) as u8,
+        minute: (rem / 60 % 60) as u8,
+    })
+}
+
+fn unknown(class_name: &str, method_name: &str) -> JvmError {
+    JvmError::NativeMethodError(format!(
+        "no host native for {}::{}",
+        class_name, method_name
+    ))
+}
+
+impl NativeBridge for HostNatives {
+    fn call_native(
+        &mut self,
+        heap: &mut Heap,
+        class_name: &str,
+        method_name: &str,
+        descriptor: &str,
+        args: &[JvmValue],
+    ) -> Result<Option<JvmValue>, JvmError> {
+        match class_name {
+            "efi/Console" => self.console(method_name, args),
+            "efi/FileSystem" => self.file_system(heap, method_name, args),
+            "efi/Graphics" => self.graphics(method_name, args),
+            "efi/BootServices" => self.boot_services(method_name, args),
+            "efi/Config" => self.config(method_name, args),
+            "efi/Timeout" => self.timeout(method_name, args),
+            "efi/SystemInfo" => self.system_info(method_name),
+            "efi/SecureBoot" => self.secure_boot(method_name),
+            "efi/Tpm" => self.tpm(method_name),
+            "efi/Power" => self.power(method_name, &NativeArgs::new(method_name, descriptor, args)),
+            "efi/MemTest" => self.mem_test(method_name),
+            "efi/DiskHealth" => self.disk_health(method_name),
+            "efi/Debug" => self.debug(method_name, args),
+            "efi/Terminal" => self.terminal(method_name, args),
+            "efi/Theme" => self.theme(method_name, args),
+            "efi/Timer" => self.timer(method_name, &NativeArgs::new(method_name, descriptor, args)),
+            _ => Err(unknown(class_name, method_name)),
+        }
+    }
+
+    fn now_micros(&mut self) -> Option<u64> {
+        Some(self.started.elapsed().as_micros() as u64)
+    }
+
+    // Same set as the firmware's, so its replay logs play back here.
+    fn nondeterministic(&self, class_name: &str, method_name: &str) -> bool {
+        matches!(
+            (class_name, method_name),
+            ("efi/Console", "readKey" | "pollKey")
+                | ("efi/Timeout", "seconds" | "defaultEntry" | "fallbackEntry")
+                | ("efi/BootServices", "entryBootCount")
+                | ("efi/Theme", "activeVariant" | "color" | "toggle")
+        )
+    }
+
+    // Like the firmware, ticks missed while Java was busy collapse into one.
+    fn take_upcall(&mut self) -> Option<Upcall> {
+        let timer = self.timer.as_mut()?;
+        let now = Instant::now();
+        if now < timer.due {
+            return None;
+        }
+        timer.due = now + timer.period;
+        Some(timer.upcall.clone())
+    }
+}
+
+fn inspect_file(file: &mut File) -> Option<ImageInfo> {
+    pe::inspect(&mut |offset, len| {
+        let mut buf = Vec::new();
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        file.take(len as u64).read_to_end(&mut buf).ok()?;
+        Some(buf)
+    })
+}
+
+// The firmware's entry_* rules over the --entry list. There are no boot
+// stats or icons here, so last-booted order and grouping change nothing.
+fn arrange(
+    config: &HashMap<String, String>,
+    entries: Vec<(String, String)>,
+) -> Vec<(String, String)> {
+    let policy = EntryPolicy::from_settings(|key| config.get(key).map(String::as_str));
+    let entries: Vec<(String, String)> = entries
+        .into_iter()
+        .map(|(name, path)| match policy.rename(&name, &path) {
+            Some(to) => (String::from(to), path),
+            None => (name, path),
+        })
+        .collect();
+    let facts: Vec<EntryFacts> = entries
+        .iter()
+        .map(|(name, path)| EntryFacts {
+            name,
+            path,
+            family: None,
+            boots: 0,
+            last: false,
+        })
+        .collect();
+    policy
+        .arrange(&facts)
+        .into_iter()
+        .map(|i| entries[i].clone())
+        .collect()
+}
@@ -0,0 +1,81 @@
+use shared::font;
+
+use crate::bmp::{
+    self,
+    Bitmap,
+};
+
+// Software framebuffer standing in for the GOP. Pixels are 0xRRGGBB, the
+// same packing the Java side passes colours in.
+pub struct Screen {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<u32>,
+}
+
+impl Screen {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        self.pixels.fill(color);
+    }
+
+    pub fn fill_rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: u32) {
+        let x0 = x.clamp(0, self.width as i32) as usize;
+        let y0 = y.clamp(0, self.height as i32) as usize;
+        let x1 = x.saturating_add(w).clamp(0, self.width as i32) as usize;
+        let y1 = y.saturating_add(h).clamp(0, self.height as i32) as usize;
+        for row in y0..y1 {
+            self.pixels[row * self.width + x0..row * self.width + x1].fill(color);
+        }
+    }
+
+    fn put(&mut self, x: usize, y: usize, color: u32) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    pub fn draw_text(&mut self, text: &str, x: i32, y: i32, color: u32, scale: usize) {
+        let (x, y) = (x.max(0) as usize, y.max(0) as usize);
+        let char_w = font::GLYPH_W * scale;
+        for (ci, ch) in text.bytes().enumerate() {
+            let gly = font::glyph(ch);
+            for (row, bits) in gly.iter().enumerate().take(font::GLYPH_H) {
+                for col in 0..font::GLYPH_W {
+                    if bits & (0x8000 >> col) == 0 {
+                        continue;
+                    }
+                    for sy in 0..scale {
+                        for sx in 0..scale {
+                            self.put(
+                                x + ci * char_w + col * scale + sx,
+                                y + row * scale + sy,
+                                color,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn blit(&mut self, x: i32, y: i32, image: &Bitmap) {
+        let (x, y) = (x.max(0) as usize, y.max(0) as usize);
+        for row in 0..image.height {
+            for col in 0..image.width {
+                self.put(x + col, y + row, image.pixels[row * image.width + col]);
+            }
+        }
+    }
+
+    pub fn to_bmp(&self) -> Vec<u8> {
+        bmp::encode(self.width, self.height, &self.pixels)
+    }
+}
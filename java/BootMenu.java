@@ -1,18 +1,44 @@
+import efi.Config;
 import efi.Console;
 import efi.BootServices;
+import efi.Debug;
+import efi.DiskHealth;
 import efi.Graphics;
+import efi.MemTest;
+import efi.Power;
+import efi.Terminal;
+import efi.Theme;
+import efi.Timeout;
 
 public class BootMenu {
-    static final int BG = 0x0F0F12;
-    static final int SURFACE = 0x17171C;
-    static final int CARD = 0x212126;
-    static final int BORDER = 0x2E2E36;
-    static final int TEXT = 0xE0E0E6;
-    static final int TEXT_DIM = 0x7A7A8A;
-    static final int TEXT_DK = 0x4D4D59;
-    static final int ACCENT = 0x8C8FA6;
+    // Set from the active theme variant by loadTheme.
+    static int BG;
+    static int SURFACE;
+    static int CARD;
+    static int BORDER;
+    static int TEXT;
+    static int TEXT_DIM;
+    static int TEXT_DK;
+    static int ACCENT;
+    static int ERROR;
+
+    static final int TOOLS = 5;
+    static final int PREVIEW_LINES = 7;
+
+    static void loadTheme() {
+        BG = Theme.color("background");
+        SURFACE = Theme.color("surface");
+        CARD = Theme.color("card");
+        BORDER = Theme.color("border");
+        TEXT = Theme.color("text");
+        TEXT_DIM = Theme.color("text_dim");
+        TEXT_DK = Theme.color("text_faint");
+        ACCENT = Theme.color("accent");
+        ERROR = Theme.color("error");
+    }
 
     public static void main(String[] args) {
+        loadTheme();
         int count = BootServices.discoverEntries();
 
         if (count == 0) {
@@ -26,39 +52,112 @@ public class BootMenu {
             return;
         }
 
+        // From here on Console output goes to the console window (C) rather
+        // than over the framebuffer.
+        Terminal.redirect(true);
+        for (int i = 0; i < count; i++) {
+            Console.println("Entry " + i + ": " + BootServices.entryName(i) + "  " + BootServices.entryPath(i));
+        }
+
         int sw = Graphics.screenWidth();
         int sh = Graphics.screenHeight();
         int fw = Graphics.fontWidth();
         int fh = Graphics.fontHeight();
 
-        Graphics.clearScreen(BG);
-
         int pad = 40;
-
-        int titleScale = 2;
-        int titleY = pad;
-        Graphics.drawText("Duke", pad, titleY, TEXT, titleScale);
-
-        int sepY = titleY + fh * titleScale + 12;
-        Graphics.fillRect(pad, sepY, sw / 3, 1, BORDER);
+        drawChrome(sw, sh, pad, fh);
 
         int itemH = fh + 10;
-        int menuY = sepY + 16;
+        int menuY = pad + fh * 2 + 28;
 
-        int selected = 0;
+        int selected = Timeout.defaultEntry();
         drawMenu(count, selected, pad, menuY, sw - pad * 2, itemH, fw, fh);
+        Graphics.endFrame();
 
         int footerY = sh - pad;
-        Graphics.drawText("Up/Down  Select    Enter  Boot", pad, footerY, TEXT_DK, 1);
+
+        int countdownY = footerY - fh - 8;
+        int ticks = Timeout.seconds() * 10;
+        boolean counting = ticks > 0;
 
         while (true) {
-            int key = Console.readKey();
+            int key;
+            if (counting) {
+                key = Console.pollKey();
+                if (key == 0) {
+                    if (ticks <= 0) {
+                        Graphics.clearScreen(0x000000);
+                        BootServices.chainloadEntry(selected);
+                        break;
+                    }
+                    if (ticks % 10 == 0) {
+                        drawCountdown(ticks / 10, pad, countdownY, sw - pad * 2, fh);
+                        Graphics.endFrame();
+                    }
+                    BootServices.stall(100);
+                    ticks = ticks - 1;
+                    continue;
+                }
+                counting = false;
+                Timeout.touch();
+                Graphics.fillRect(pad, countdownY, sw - pad * 2, fh, BG);
+            } else {
+                key = Console.readKey();
+            }
+
             if (key == Console.KEY_UP && selected > 0) {
                 selected = selected - 1;
                 drawMenu(count, selected, pad, menuY, sw - pad * 2, itemH, fw, fh);
-            } else if (key == Console.KEY_DOWN && selected < count - 1) {
+                Graphics.endFrame();
+            } else if (key == Console.KEY_DOWN && selected < count + TOOLS - 1) {
                 selected = selected + 1;
                 drawMenu(count, selected, pad, menuY, sw - pad * 2, itemH, fw, fh);
+                Graphics.endFrame();
+            } else if (key == 'h' || key == 'H') {
+                Debug.dumpHeap();
+                Graphics.fillRect(pad, countdownY, sw - pad * 2, fh, BG);
+                Graphics.drawText(Debug.heapSummary(), pad, countdownY, TEXT_DIM, 1);
+                Graphics.endFrame();
+            } else if (key == 'c' || key == 'C') {
+                runConsole(sw, sh, pad, fh);
+                drawChrome(sw, sh, pad, fh);
+                drawMenu(count, selected, pad, menuY, sw - pad * 2, itemH, fw, fh);
+                Graphics.endFrame();
+            } else if (key == 't' || key == 'T') {
+                Theme.toggle();
+                loadTheme();
+                drawChrome(sw, sh, pad, fh);
+                drawMenu(count, selected, pad, menuY, sw - pad * 2, itemH, fw, fh);
+                Graphics.endFrame();
+            } else if (key == 'p' || key == 'P') {
+                Debug.writeProfile();
+                Graphics.fillRect(pad, countdownY, sw - pad * 2, fh, BG);
+                Graphics.drawText(Debug.profileSummary(), pad, countdownY, TEXT_DIM, 1);
+                Graphics.endFrame();
+            } else if ((key == 'n' || key == 'N') && selected < count) {
+                // One-time boot through the firmware; only returns when the
+                // entry isn't one of its Boot#### options.
+                if (!BootServices.setBootNext(selected)) {
+                    Graphics.fillRect(pad, countdownY, sw - pad * 2, fh, BG);
+                    Graphics.drawText("Not a firmware boot option", pad, countdownY, TEXT_DIM, 1);
+                    Graphics.endFrame();
+                }
+            } else if ((key == 'r' || key == 'R') && Config.getInt("dev", 0) == 1) {
+                Debug.reload();
+            } else if (key == Console.KEY_ENTER && selected >= count + 3) {
+                Graphics.clearScreen(0x000000);
+                Power.reset(selected == count + 3 ? Power.COLD_REBOOT : Power.POWER_OFF);
+            } else if (key == Console.KEY_ENTER && selected >= count) {
+                if (selected == count) {
+                    runMemTest(sw, pad, fh);
+                } else if (selected == count + 1) {
+                    runDiskHealth(sw, sh, pad, fh);
+                } else {
+                    runHandles(sw, sh, pad, fw, fh);
+                }
+                drawChrome(sw, sh, pad, fh);
+                drawMenu(count, selected, pad, menuY, sw - pad * 2, itemH, fw, fh);
+                Graphics.endFrame();
             } else if (key == Console.KEY_ENTER) {
                 Graphics.clearScreen(0x000000);
                 BootServices.chainloadEntry(selected);
@@ -67,10 +166,45 @@ public class BootMenu {
         }
     }
 
+    static void drawChrome(int sw, int sh, int pad, int fh) {
+        if ("firmware-logo".equals(Config.get("background"))) {
+            Graphics.clearScreen(0x000000);
+            Graphics.drawBgrt();
+        } else {
+            Graphics.clearScreen(BG);
+        }
+
+        int titleScale = 2;
+        Graphics.drawText("Duke", pad, pad, TEXT, titleScale);
+
+        int sepY = pad + fh * titleScale + 12;
+        Graphics.fillRect(pad, sepY, sw / 3, 1, BORDER);
+
+        Graphics.drawText("Up/Down  Select    Enter  Boot    C  Console    T  Theme", pad, sh - pad, TEXT_DK, 1);
+    }
+
+    static void drawCountdown(int seconds, int x, int y, int w, int fh) {
+        Graphics.fillRect(x, y, w, fh, BG);
+        Graphics.drawText("Booting in " + seconds + "s", x, y, TEXT_DIM, 1);
+    }
+
+    // Boot entries first, then the built-in tools.
     static void drawMenu(int count, int selected, int x, int y, int w, int itemH, int fw, int fh) {
-        for (int i = 0; i < count; i++) {
+        for (int i = 0; i < count + TOOLS; i++) {
             int iy = y + i * itemH;
-            String name = BootServices.entryName(i);
+            String name;
+            if (i < count) {
+                name = BootServices.entryName(i);
+                int tries = BootServices.entryTriesLeft(i);
+                if (tries == 1) {
+                    name = name + " (1 try left)";
+                } else if (tries >= 0) {
+                    name = name + " (" + tries + " tries left)";
+                }
+            } else {
+                iy = iy + 8;
+                name = toolName(i - count);
+            }
 
             if (i == selected) {
                 Graphics.fillRect(x, iy, w, itemH - 2, CARD);
@@ -81,6 +215,306 @@ public class BootMenu {
                 Graphics.drawText(name, x + 12, iy + 4, TEXT_DIM, 1);
             }
         }
+        drawPreview(count, selected, x, y + (count + TOOLS) * itemH + 24, w, fh);
+    }
+
+    // Details of the highlighted entry, one "Label: value" line each.
+    static void drawPreview(int count, int selected, int x, int y, int w, int fh) {
+        int lineH = fh + 4;
+        Graphics.fillRect(x, y, w, lineH * PREVIEW_LINES + 16, BG);
+        if (selected >= count) {
+            return;
+        }
+        String details = BootServices.entryDetails(selected);
+        if (details == null) {
+            return;
+        }
+        Graphics.fillRect(x, y, w, lineH * PREVIEW_LINES + 16, SURFACE);
+        int ly = y + 8;
+        int lines = 0;
+        while (lines < PREVIEW_LINES) {
+            int nl = details.indexOf('\n');
+            String line = nl < 0 ? details : details.substring(0, nl);
+            Graphics.drawText(line, x + 12, ly, lines == 0 ? TEXT : TEXT_DIM, 1);
+            ly = ly + lineH;
+            lines = lines + 1;
+            if (nl < 0) {
+                break;
+            }
+            details = details.substring(nl + 1);
+        }
+    }
+
+    static String toolName(int tool) {
+        if (tool == 0) {
+            return "Memory test";
+        }
+        if (tool == 1) {
+            return "Disk health";
+        }
+        if (tool == 2) {
+            return "Device handles";
+        }
+        if (tool == 3) {
+            return "Reboot";
+        }
+        return "Shut down";
+    }
+
+    // Everything printed since graphics came up, in a themed window.
+    static void runConsole(int sw, int sh, int pad, int fh) {
+        drawChrome(sw, sh, pad, fh);
+        int top = pad + fh * 2 + 28;
+        int w = sw - pad * 2;
+        int h = sh - pad * 2 - top;
+        Graphics.fillRect(pad, top, w, h, BORDER);
+        Graphics.fillRect(pad + 1, top + 1, w - 2, h - 2, SURFACE);
+        Graphics.drawText("Console    Up/Down  Scroll    Esc  Close", pad + 12, top + 8, TEXT_DIM, 1);
+        Terminal.setColors(TEXT, SURFACE);
+        Terminal.open(pad + 12, top + fh + 16, w - 24, h - fh - 24);
+        Terminal.render();
+        Graphics.endFrame();
+
+        while (true) {
+            int key = Console.readKey();
+            if (key == Console.KEY_UP) {
+                Terminal.scroll(1);
+            } else if (key == Console.KEY_DOWN) {
+                Terminal.scroll(-1);
+            } else if (key == Console.KEY_HOME) {
+                Terminal.scroll(100000);
+            } else if (key == Console.KEY_END) {
+                Terminal.scroll(-100000);
+            } else if (key == Console.KEY_ESCAPE || key == Console.KEY_ENTER) {
+                break;
+            } else {
+                continue;
+            }
+            Terminal.render();
+            Graphics.endFrame();
+        }
+        Terminal.close();
+    }
+
+    static void runMemTest(int sw, int pad, int fh) {
+        Graphics.clearScreen(BG);
+        Graphics.drawText("Memory test", pad, pad, TEXT, 2);
+
+        int mb = MemTest.start(Config.getInt("memtest_mb", 0));
+        int y = pad + fh * 2 + 28;
+        int barW = sw - pad * 2;
+        Graphics.drawText("Testing " + mb + " MiB    Esc  Stop", pad, y, TEXT_DIM, 1);
+        Graphics.fillRect(pad, y + fh + 8, barW, 6, CARD);
+        Graphics.endFrame();
+
+        try {
+            int done = 0;
+            int drawn = 0;
+            while (done < 100) {
+                done = MemTest.step();
+                if (done != drawn) {
+                    Graphics.fillRect(pad, y + fh + 8, barW * done / 100, 6, ACCENT);
+                    Graphics.endFrame();
+                    drawn = done;
+                }
+            }
+        } catch (Exception e) {
+            // Esc: fall through and report what was covered.
+        }
+
+        int errors = MemTest.errors();
+        String result = MemTest.finish();
+        Console.println("Memory test: " + result);
+        Graphics.drawText(result, pad, y + fh * 2 + 24, errors == 0 ? TEXT : ERROR, 1);
+        Graphics.drawText("Press any key", pad, y + fh * 3 + 32, TEXT_DK, 1);
+        Graphics.endFrame();
+        Console.readKey();
+    }
+
+    static void runDiskHealth(int sw, int sh, int pad, int fh) {
+        Graphics.clearScreen(BG);
+        Graphics.drawText("Disk health", pad, pad, TEXT, 2);
+
+        int y = pad + fh * 2 + 28;
+        int lineH = fh + 6;
+        int disks = DiskHealth.probe();
+        if (disks == 0) {
+            Graphics.drawText("No drives answered SMART queries", pad, y, TEXT_DIM, 1);
+            y = y + lineH;
+        }
+        for (int i = 0; i < disks; i++) {
+            int color = DiskHealth.warning(i) != 0 ? ERROR : TEXT;
+            Graphics.drawText(DiskHealth.summary(i), pad, y, color, 1);
+            y = y + lineH;
+        }
+
+        y = y + lineH;
+        Graphics.drawText("Surface scan", pad, y, TEXT_DIM, 1);
+        y = y + lineH;
+        int parts = DiskHealth.partitions();
+        int listY = y;
+        int resultY = listY + parts * lineH + lineH;
+        int w = sw - pad * 2;
+        Graphics.drawText("Up/Down  Select    Enter  Scan    Esc  Back", pad, sh - pad, TEXT_DK, 1);
+
+        int selected = 0;
+        drawPartitions(parts, selected, pad, listY, w, lineH);
+        Graphics.endFrame();
+
+        while (true) {
+            int key = Console.readKey();
+            if (key == Console.KEY_ESCAPE) {
+                return;
+            } else if (key == Console.KEY_UP && selected > 0) {
+                selected = selected - 1;
+            } else if (key == Console.KEY_DOWN && selected < parts - 1) {
+                selected = selected + 1;
+            } else if (key == Console.KEY_ENTER && parts > 0) {
+                scanPartition(selected, pad, resultY, w, fh);
+            }
+            drawPartitions(parts, selected, pad, listY, w, lineH);
+            Graphics.endFrame();
+        }
+    }
+
+    static void drawPartitions(int parts, int selected, int x, int y, int w, int lineH) {
+        for (int i = 0; i < parts; i++) {
+            int iy = y + i * lineH;
+            if (i == selected) {
+                Graphics.fillRect(x, iy - 2, w, lineH, CARD);
+                Graphics.fillRect(x, iy - 2, 2, lineH, ACCENT);
+                Graphics.drawText(DiskHealth.partitionName(i), x + 12, iy, TEXT, 1);
+            } else {
+                Graphics.fillRect(x, iy - 2, w, lineH, BG);
+                Graphics.drawText(DiskHealth.partitionName(i), x + 12, iy, TEXT_DIM, 1);
+            }
+        }
+    }
+
+    static void scanPartition(int index, int x, int y, int w, int fh) {
+        Graphics.fillRect(x, y, w, fh * 3 + 16, BG);
+        int mb = DiskHealth.scanStart(index);
+        Graphics.drawText("Reading " + mb + " MiB    Esc  Stop", x, y, TEXT_DIM, 1);
+        Graphics.fillRect(x, y + fh + 8, w, 6, CARD);
+        Graphics.endFrame();
+
+        try {
+            int done = 0;
+            int drawn = 0;
+            while (done < 100) {
+                done = DiskHealth.scanStep();
+                if (done != drawn) {
+                    Graphics.fillRect(x, y + fh + 8, w * done / 100, 6, ACCENT);
+                    Graphics.endFrame();
+                    drawn = done;
+                }
+            }
+        } catch (Exception e) {
+            // Esc: fall through and report how far the scan got.
+        }
+
+        int errors = DiskHealth.scanErrors();
+        String result = DiskHealth.scanFinish();
+        Console.println("Surface scan: " + result);
+        Graphics.drawText(result, x, y + fh * 2 + 16, errors == 0 ? TEXT : ERROR, 1);
+    }
+
+    // Every firmware handle, with the protocols of the highlighted one below:
+    // for working out why a disk or NIC isn't showing up.
+    static void runHandles(int sw, int sh, int pad, int fw, int fh) {
+        Graphics.clearScreen(BG);
+        Graphics.drawText("Device handles", pad, pad, TEXT, 2);
+        Graphics.drawText("Up/Down  Select    Home/End  Jump    Esc  Back", pad, sh - pad, TEXT_DK, 1);
+
+        int handles = Debug.handles();
+        int lineH = fh + 6;
+        int listY = pad + fh * 2 + 28;
+        int w = sw - pad * 2;
+        int panelLines = 12;
+        int panelH = lineH * panelLines + 16;
+        int panelY = sh - pad - fh - 16 - panelH;
+        int rows = (panelY - 12 - listY) / lineH;
+        int cols = (w - 24) / fw;
+        if (handles == 0) {
+            Graphics.drawText("No handles", pad, listY, TEXT_DIM, 1);
+            Graphics.endFrame();
+            Console.readKey();
+            return;
+        }
+
+        int selected = 0;
+        int top = 0;
+        while (true) {
+            if (selected < top) {
+                top = selected;
+            } else if (selected >= top + rows) {
+                top = selected - rows + 1;
+            }
+            drawHandles(handles, selected, top, rows, cols, pad, listY, w, lineH);
+            drawProtocols(selected, pad, panelY, w, lineH, panelLines, cols);
+            Graphics.endFrame();
+
+            int key = Console.readKey();
+            if (key == Console.KEY_ESCAPE) {
+                return;
+            } else if (key == Console.KEY_UP && selected > 0) {
+                selected = selected - 1;
+            } else if (key == Console.KEY_DOWN && selected < handles - 1) {
+                selected = selected + 1;
+            } else if (key == Console.KEY_HOME) {
+                selected = 0;
+            } else if (key == Console.KEY_END) {
+                selected = handles - 1;
+            }
+        }
+    }
+
+    static void drawHandles(int handles, int selected, int top, int rows, int cols, int x, int y, int w, int lineH) {
+        for (int r = 0; r < rows; r++) {
+            int i = top + r;
+            int iy = y + r * lineH;
+            Graphics.fillRect(x, iy - 2, w, lineH, i == selected ? CARD : BG);
+            if (i >= handles) {
+                continue;
+            }
+            String name = clip(i + "  " + Debug.handleName(i), cols);
+            if (i == selected) {
+                Graphics.fillRect(x, iy - 2, 2, lineH, ACCENT);
+                Graphics.drawText(name, x + 12, iy, TEXT, 1);
+            } else {
+                Graphics.drawText(name, x + 12, iy, TEXT_DIM, 1);
+            }
+        }
+    }
+
+    static void drawProtocols(int index, int x, int y, int w, int lineH, int lines, int cols) {
+        Graphics.fillRect(x, y, w, lineH * lines + 16, SURFACE);
+        String protocols = Debug.handleProtocols(index);
+        if (protocols == null || protocols.length() == 0) {
+            Graphics.drawText("No protocols", x + 12, y + 8, TEXT_DIM, 1);
+            return;
+        }
+        int ly = y + 8;
+        for (int n = 0; n < lines; n++) {
+            int nl = protocols.indexOf('\n');
+            String line = nl < 0 ? protocols : protocols.substring(0, nl);
+            if (n == lines - 1 && nl >= 0) {
+                line = "...";
+            }
+            Graphics.drawText(clip(line, cols), x + 12, ly, TEXT, 1);
+            ly = ly + lineH;
+            if (nl < 0) {
+                break;
+            }
+            protocols = protocols.substring(nl + 1);
+        }
+    }
+
+    static String clip(String s, int cols) {
+        if (s.length() <= cols) {
+            return s;
+        }
+        return s.substring(0, cols - 3) + "...";
     }
 
     static void textFallback(int count) {
@@ -93,12 +527,34 @@ public class BootMenu {
             Console.print(". ");
             Console.println(BootServices.entryName(i));
         }
+        Console.println("  M. Memory test");
+        Console.println("  D. Disk health");
+        Console.println("  R. Reboot");
+        Console.println("  S. Shut down");
 
         Console.println("");
         Console.print("Select> ");
 
         while (true) {
             int key = Console.readKey();
+            if (key == 'm' || key == 'M') {
+                Console.println("");
+                textMemTest();
+                Console.print("Select> ");
+                continue;
+            }
+            if (key == 'd' || key == 'D') {
+                Console.println("");
+                textDiskHealth();
+                Console.print("Select> ");
+                continue;
+            }
+            if (key == 'r' || key == 'R') {
+                Power.reset(Power.COLD_REBOOT);
+            }
+            if (key == 's' || key == 'S') {
+                Power.reset(Power.POWER_OFF);
+            }
             int choice = key - 49;
             if (choice >= 0 && choice < count) {
                 Console.println("");
@@ -107,4 +563,36 @@ public class BootMenu {
             }
         }
     }
-}
\ No newline at end of file
+
+    static void textMemTest() {
+        int mb = MemTest.start(Config.getInt("memtest_mb", 0));
+        Console.println("Testing " + mb + " MiB, Esc to stop");
+        try {
+            int done = 0;
+            int shown = 0;
+            while (done < 100) {
+                done = MemTest.step();
+                if (done >= shown + 10) {
+                    shown = done - done % 10;
+                    Console.println("  " + shown + "%");
+                }
+            }
+        } catch (Exception e) {
+            // Esc: fall through and report what was covered.
+        }
+        Console.println(MemTest.finish());
+        Console.println("");
+    }
+
+    static void textDiskHealth() {
+        int disks = DiskHealth.probe();
+        if (disks == 0) {
+            Console.println("No drives answered SMART queries");
+        }
+        for (int i = 0; i < disks; i++) {
+            Console.print(DiskHealth.warning(i) != 0 ? "! " : "  ");
+            Console.println(DiskHealth.summary(i));
+        }
+        Console.println("");
+    }
+}
@@ -3,8 +3,23 @@ package efi;
 public class BootServices {
     public static native void chainload(String path);
     public static native void chainloadEntry(int index);
+    public static native void chainloadWithOptions(String path, String options);
     public static native void stall(int milliseconds);
     public static native int discoverEntries();
     public static native String entryName(int index);
     public static native String entryPath(int index);
+    public static native int entryBootCount(int index);
+    public static native String entryDetails(int index);
+    // An icon key such as "fedora" or "linux", or null.
+    public static native String entryIcon(int index);
+    // Boots a firmware Boot#### entry once and resets; false for any other.
+    public static native boolean setBootNext(int index);
+    // The command line the entry boots with; null for a firmware entry.
+    public static native String entryOptions(int index);
+    // Overrides it until the entries are discovered again, null to undo;
+    // false for a firmware entry.
+    public static native boolean setEntryOptions(int index, String options);
+    // Tries a boot-counted loader entry has left, 0 once it is bad; -1 for
+    // an entry without a counter.
+    public static native int entryTriesLeft(int index);
 }
\ No newline at end of file
@@ -0,0 +1,8 @@
+package efi;
+
+public class Config {
+    public static native String get(String key);
+    public static native int getInt(String key, int defaultValue);
+    public static native String machineId();
+    public static native String machineModel();
+}
\ No newline at end of file
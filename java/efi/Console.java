@@ -14,4 +14,5 @@ public class Console {
     public static native void println(String text);
     public static native void println();
     public static native int readKey();
+    public static native int pollKey();
 }
\ No newline at end of file
@@ -0,0 +1,19 @@
+package efi;
+
+public class Debug {
+    public static native void dumpHeap();
+    public static native String heapSummary();
+    public static native void writeProfile();
+    public static native String profileSummary();
+    // javap-style listing of a loaded class, or null if there is none.
+    public static native String disassemble(String className);
+
+    // Snapshots every handle; the others index that snapshot.
+    public static native int handles();
+    public static native String handleName(int index);
+    public static native String handleProtocols(int index);
+
+    // Dev mode only (dev = 1): ends the menu and starts it again from the
+    // classes now in \EFI\duke. Does not return.
+    public static native void reload();
+}
@@ -0,0 +1,13 @@
+package efi;
+
+public class DiskHealth {
+    public static native int probe();
+    public static native String summary(int index);
+    public static native int warning(int index);
+    public static native int partitions();
+    public static native String partitionName(int index);
+    public static native int scanStart(int index);
+    public static native int scanStep();
+    public static native int scanErrors();
+    public static native String scanFinish();
+}
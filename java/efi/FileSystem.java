@@ -2,5 +2,34 @@ package efi;
 
 public class FileSystem {
     public static native byte[] readFile(String path);
+    // Without reading the file; -1 when there is none.
+    public static native long fileSize(String path);
+
+    // fileInfo's fields: bytes, the modification time as yyyyMMddHHmm (0 if
+    // unknown), and the attribute bits below. Null when there is no file.
+    public static final int INFO_SIZE = 0;
+    public static final int INFO_MODIFIED = 1;
+    public static final int INFO_ATTRIBUTES = 2;
+    public static final long READ_ONLY = 0x01;
+    public static final long HIDDEN = 0x02;
+    public static final long SYSTEM = 0x04;
+    public static final long DIRECTORY = 0x10;
+    public static final long ARCHIVE = 0x20;
+    public static native long[] fileInfo(String path);
     public static native String[] listDirectory(String path);
+    // Directories first, each ending in a backslash, then files; both
+    // sorted. Null like listDirectory.
+    public static native String[] listEntries(String path);
+    // Both create the directories on the way and throw when the write
+    // fails. writeFile replaces the file; appendFile adds to its end.
+    public static native void writeFile(String path, byte[] data);
+    public static native void appendFile(String path, byte[] data);
+    // These throw when the change can't be made. delete takes a file or an
+    // empty directory; rename replaces a file at `to`, and moves a directory
+    // only within its parent; mkdir creates any missing parents.
+    public static native void delete(String path);
+    public static native void rename(String from, String to);
+    public static native void mkdir(String path);
+    public static native boolean exists(String path);
+    public static native boolean isDirectory(String path);
 }
\ No newline at end of file
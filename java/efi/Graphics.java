@@ -12,4 +12,6 @@ public class Graphics {
     public static native void drawImage(String path, int x, int y);
     public static native int imageWidth(String path);
     public static native int imageHeight(String path);
+    public static native int drawBgrt();
+    public static native void endFrame();
 }
\ No newline at end of file
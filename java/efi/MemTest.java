@@ -0,0 +1,8 @@
+package efi;
+
+public class MemTest {
+    public static native int start(int maxMegabytes);
+    public static native int step();
+    public static native int errors();
+    public static native String finish();
+}
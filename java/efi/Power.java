@@ -0,0 +1,14 @@
+package efi;
+
+public class Power {
+    public static final int WARM_REBOOT = 1;
+    public static final int COLD_REBOOT = 2;
+    public static final int POWER_OFF = 3;
+
+    // Takes one of the constants above and doesn't return; any other value
+    // throws without touching the machine.
+    public static native void reset(int kind);
+    // Reboots into the firmware's setup screens; false when the firmware
+    // doesn't offer that.
+    public static native boolean rebootToFirmware();
+}
@@ -0,0 +1,19 @@
+package efi;
+
+public class Reflect {
+    // Annotations as javac keeps them for @Retention(RUNTIME) types. Class
+    // and annotation names go like "BootMenu" or "efi.Hotkey"; member is a
+    // method or field name, or null for the class itself.
+    public static native boolean hasAnnotation(String className, String member, String annotation);
+
+    // The element as a String (a char as that one character, an enum
+    // constant as its name), or the annotation type's default when the use
+    // leaves it out; null if there is neither.
+    public static native String annotationValue(String className, String member, String annotation, String element);
+
+    // The methods of className carrying the annotation, in declaration order.
+    public static native String[] annotatedMethods(String className, String annotation);
+
+    // Calls the static void method className.methodName().
+    public static native void invoke(String className, String methodName);
+}
@@ -0,0 +1,12 @@
+package efi;
+
+public class SecureBoot {
+    public static native boolean enabled();
+    // No platform key enrolled yet, so nothing is enforced.
+    public static native boolean setupMode();
+    // Whether the firmware would refuse to start the entry. Loads the whole
+    // image to find out, so ask once per entry.
+    public static native boolean entryBlocked(int index);
+    // Duke was started through shim, which vouches for what it has signed.
+    public static native boolean shimPresent();
+}
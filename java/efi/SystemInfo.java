@@ -0,0 +1,7 @@
+package efi;
+
+public class SystemInfo {
+    public static native String firmwareVendor();
+    public static native int firmwareRevision();
+    public static native String uefiRevision();
+}
\ No newline at end of file
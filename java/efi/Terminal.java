@@ -0,0 +1,12 @@
+package efi;
+
+public class Terminal {
+    public static native void open(int x, int y, int width, int height);
+    public static native void setColors(int fg, int bg);
+    public static native void redirect(boolean on);
+    public static native void write(String text);
+    public static native void clear();
+    public static native int scroll(int lines);
+    public static native void render();
+    public static native void close();
+}
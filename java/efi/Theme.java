@@ -0,0 +1,8 @@
+package efi;
+
+public class Theme {
+    public static native String name();
+    public static native String activeVariant();
+    public static native int color(String role);
+    public static native String toggle();
+}
@@ -0,0 +1,13 @@
+package efi;
+
+public class Timeout {
+    public static native int seconds();
+    public static native void set(int seconds);
+    public static native void touch();
+    public static native int defaultEntry();
+    // The last boot came up fine; an OS-side tool does the same by creating
+    // the DukeBootOk variable.
+    public static native void markBootSuccess();
+    // The last good entry defaultEntry fell back to, or -1.
+    public static native int fallbackEntry();
+}
\ No newline at end of file
@@ -0,0 +1,9 @@
+package efi;
+
+public class Timer {
+    // Calls the static void method className.methodName() every periodMs
+    // milliseconds, between native calls, until cancel(). One timer at a
+    // time; a second every() replaces the first.
+    public static native void every(int periodMs, String className, String methodName);
+    public static native void cancel();
+}
@@ -0,0 +1,6 @@
+package efi;
+
+public class Tpm {
+    // A TPM 2.0 the firmware exposes; boots are measured into it.
+    public static native boolean present();
+}
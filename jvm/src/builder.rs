@@ -0,0 +1,240 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use shared::classfile::ClassFile;
+use shared::types::JvmError;
+use shared::zip::DEFAULT_RELEASE;
+
+use crate::interpreter::Vm;
+use crate::native::{
+    NativeBridge,
+    NoopNatives,
+};
+use crate::observer::{
+    ExecutionObserver,
+    NoObserver,
+};
+use crate::replay::NativeLog;
+
+/// Bounds the interpreter enforces while running guest code.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Deepest chain of nested Java calls before `StackOverflow` is raised.
+    pub max_call_depth: usize,
+    /// Most bytes of native stack the interpreter may use below the outermost
+    /// Java call before `StackOverflow` is raised. Each Java frame is a Rust
+    /// call, so on a small stack (firmware) this trips well before
+    /// `max_call_depth` would. `None` leaves only the depth limit.
+    pub max_stack_bytes: Option<usize>,
+    /// Backward branches one [`Vm::run`] or [`Vm::execute`] call (or their
+    /// descriptor variants) may take before `BudgetExceeded` is raised, so a
+    /// guest stuck in a loop can be stopped. `None` is unlimited.
+    pub max_backward_branches: Option<u64>,
+    /// Most elements a single `new` array may have before `OutOfMemory` is
+    /// raised.
+    pub max_array_length: usize,
+    /// Most bytes (UTF-8) a string built by guest code may reach before
+    /// `OutOfMemory` is raised.
+    pub max_string_length: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_call_depth: 256,
+            max_stack_bytes: None,
+            max_backward_branches: None,
+            max_array_length: 1 << 20,
+            max_string_length: 1 << 20,
+        }
+    }
+}
+
+/// Where the builder gets a class from.
+pub enum ClassSource<'a> {
+    Class(Box<ClassFile>),
+    Bytes(&'a [u8]),
+    Jar(&'a [u8]),
+}
+
+/// Configures and creates a [`Vm`].
+pub struct VmBuilder<'a, N: NativeBridge, O: ExecutionObserver = NoObserver> {
+    natives: N,
+    observer: O,
+    limits: Limits,
+    profiling: bool,
+    deterministic: bool,
+    assertions: bool,
+    release: u16,
+    lenient: bool,
+    replay: Option<NativeLog>,
+    sources: Vec<ClassSource<'a>>,
+}
+
+impl<'a> VmBuilder<'a, NoopNatives> {
+    pub fn new() -> Self {
+        Self {
+            natives: NoopNatives,
+            observer: NoObserver,
+            limits: Limits::default(),
+            profiling: false,
+            deterministic: false,
+            assertions: false,
+            release: DEFAULT_RELEASE,
+            lenient: false,
+            replay: None,
+            sources: Vec::new(),
+        }
+    }
+}
+
+impl Default for VmBuilder<'_, NoopNatives> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, N: NativeBridge, O: ExecutionObserver> VmBuilder<'a, N, O> {
+    /// Replaces the native bridge that receives calls the VM cannot run itself.
+    pub fn natives<M: NativeBridge>(self, natives: M) -> VmBuilder<'a, M, O> {
+        VmBuilder {
+            natives,
+            observer: self.observer,
+            limits: self.limits,
+            profiling: self.profiling,
+            deterministic: self.deterministic,
+            assertions: self.assertions,
+            release: self.release,
+            lenient: self.lenient,
+            replay: self.replay,
+            sources: self.sources,
+        }
+    }
+
+    /// Attaches an observer that is told about every instruction, call,
+    /// return and throw.
+    pub fn observer<P: ExecutionObserver>(self, observer: P) -> VmBuilder<'a, N, P> {
+        VmBuilder {
+            natives: self.natives,
+            observer,
+            limits: self.limits,
+            profiling: self.profiling,
+            deterministic: self.deterministic,
+            assertions: self.assertions,
+            release: self.release,
+            lenient: self.lenient,
+            replay: self.replay,
+            sources: self.sources,
+        }
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// See [`Limits::max_call_depth`].
+    pub fn max_call_depth(mut self, depth: usize) -> Self {
+        self.limits.max_call_depth = depth;
+        self
+    }
+
+    /// See [`Limits::max_stack_bytes`].
+    pub fn max_stack_bytes(mut self, bytes: usize) -> Self {
+        self.limits.max_stack_bytes = Some(bytes);
+        self
+    }
+
+    /// See [`Limits::max_array_length`].
+    pub fn max_array_length(mut self, len: usize) -> Self {
+        self.limits.max_array_length = len;
+        self
+    }
+
+    /// See [`Limits::max_string_length`].
+    pub fn max_string_length(mut self, len: usize) -> Self {
+        self.limits.max_string_length = len;
+        self
+    }
+
+    /// Starts the VM with the method profiler on; see [`Vm::profile`].
+    pub fn profiling(mut self, on: bool) -> Self {
+        self.profiling = on;
+        self
+    }
+
+    /// Starts the VM in deterministic mode; see [`Vm::set_deterministic`].
+    pub fn deterministic(mut self, on: bool) -> Self {
+        self.deterministic = on;
+        self
+    }
+
+    /// Starts the VM with `assert` statements checked; see
+    /// [`Vm::set_assertions`].
+    pub fn enable_assertions(mut self, on: bool) -> Self {
+        self.assertions = on;
+        self
+    }
+
+    /// Reads multi-release JARs as Java `release`; see [`Vm::set_release`].
+    pub fn release(mut self, release: u16) -> Self {
+        self.release = release;
+        self
+    }
+
+    /// Parses classes leniently; see [`Vm::set_lenient_parsing`].
+    pub fn lenient_parsing(mut self, on: bool) -> Self {
+        self.lenient = on;
+        self
+    }
+
+    /// Starts the VM replaying a recorded log; see [`Vm::replay`].
+    pub fn replay(mut self, log: NativeLog) -> Self {
+        self.replay = Some(log);
+        self
+    }
+
+    pub fn source(mut self, source: ClassSource<'a>) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    pub fn class(self, class: ClassFile) -> Self {
+        self.source(ClassSource::Class(Box::new(class)))
+    }
+
+    pub fn class_bytes(self, data: &'a [u8]) -> Self {
+        self.source(ClassSource::Bytes(data))
+    }
+
+    pub fn jar(self, data: &'a [u8]) -> Self {
+        self.source(ClassSource::Jar(data))
+    }
+
+    /// Parses every queued source and returns the ready VM.
+    pub fn build(self) -> Result<Vm<N, O>, JvmError> {
+        let mut vm = Vm::with_observer(self.natives, self.observer);
+        vm.limits = self.limits;
+        vm.set_profiling(self.profiling);
+        vm.set_assertions(self.assertions);
+        vm.set_release(self.release);
+        vm.set_lenient_parsing(self.lenient);
+        match self.replay {
+            Some(log) => vm.replay(log),
+            None => vm.set_deterministic(self.deterministic),
+        }
+        for source in self.sources {
+            match source {
+                ClassSource::Class(class) => vm.load_class(*class)?,
+                ClassSource::Bytes(data) => {
+                    let class = vm.parse_class(data)?;
+                    vm.load_class(class)?
+                }
+                ClassSource::Jar(data) => {
+                    vm.load_jar(data)?;
+                }
+            }
+        }
+        Ok(vm)
+    }
+}
@@ -0,0 +1,315 @@
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use shared::types::JvmError;
+use shared::zip::{
+    self,
+    ZipArchive,
+};
+
+use crate::interpreter::Vm;
+use crate::native::NativeBridge;
+use crate::observer::ExecutionObserver;
+
+enum Source {
+    Classes(Vec<(String, Vec<u8>)>),
+    Jar(Vec<u8>),
+}
+
+struct Entry {
+    name: String,
+    source: Source,
+}
+
+/// The JARs and loose class files a [`Vm`] loads from, in the order they
+/// are searched. As with the JDK's `-cp`, the first entry to define a class
+/// wins; a later definition is left out with a warning.
+pub struct ClassPath {
+    entries: Vec<Entry>,
+    verify_crc: bool,
+    warnings: Vec<String>,
+}
+
+/// What [`ClassPath::load_into`] did.
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    /// Each class loaded, in load order.
+    pub classes: Vec<LoadedClass>,
+    /// Classes left out as duplicates, Class-Path JARs that weren't there,
+    /// and, in lenient mode, entries that couldn't be read.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadedClass {
+    /// Internal name, such as `efi/menu/Main`.
+    pub name: String,
+    /// The class path entry it came from, `outer.jar!/inner.jar` for a JAR
+    /// nested in another.
+    pub source: String,
+    pub size: usize,
+}
+
+impl ClassPath {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            verify_crc: true,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Adds loose class files, such as a directory's, as one entry called
+    /// `name`; among themselves they load in the order given.
+    pub fn push_classes(&mut self, name: impl Into<String>, classes: Vec<(String, Vec<u8>)>) {
+        self.entries.push(Entry {
+            name: name.into(),
+            source: Source::Classes(classes),
+        });
+    }
+
+    /// Adds a JAR under `name`, its path with `/` separators, which its
+    /// manifest's Class-Path entries are resolved against.
+    pub fn push_jar(&mut self, name: impl Into<String>, data: Vec<u8>) {
+        self.entries.push(Entry {
+            name: name.into(),
+            source: Source::Jar(data),
+        });
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.iter().any(|e| e.name == name)
+    }
+
+    /// Entry names in search order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|e| e.name.as_str())
+    }
+
+    /// Whether JAR entries are checked against their CRC-32; see
+    /// [`ZipArchive::set_verify_crc`].
+    pub fn set_verify_crc(&mut self, on: bool) {
+        self.verify_crc = on;
+    }
+
+    /// Puts `jars` in search order: the ones `first` names, separated by
+    /// spaces, in that order, then the rest by name.
+    pub fn sort_jars(jars: &mut [String], first: &str) {
+        let rank = |jar: &String| first.split_ascii_whitespace().position(|f| f == jar);
+        jars.sort_by(|a, b| match (rank(a), rank(b)) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            (Some(_), None) => core::cmp::Ordering::Less,
+            (None, Some(_)) => core::cmp::Ordering::Greater,
+            (None, None) => a.cmp(b),
+        });
+    }
+
+    /// Adds the JARs the manifests' Class-Path entries name, reading each
+    /// with `read`, at the end of the path as the JDK does, and then the
+    /// ones those name in turn. One `read` can't find is skipped with a
+    /// warning.
+    pub fn follow_class_path(&mut self, mut read: impl FnMut(&str) -> Option<Vec<u8>>) {
+        let mut missing: Vec<String> = Vec::new();
+        let mut i = 0;
+        while i < self.entries.len() {
+            let entry = &self.entries[i];
+            i += 1;
+            let Source::Jar(data) = &entry.source else {
+                continue;
+            };
+            let Some(manifest) = ZipArchive::new(data).ok().and_then(|a| a.manifest()) else {
+                continue;
+            };
+            let mut wanted = Vec::new();
+            for path in manifest.class_path() {
+                match zip::resolve_class_path(&entry.name, path) {
+                    Some(jar) => wanted.push(jar),
+                    None => self.warnings.push(format!(
+                        "{}: ignoring Class-Path entry {}",
+                        entry.name, path
+                    )),
+                }
+            }
+            for jar in wanted {
+                if self.contains(&jar) || missing.contains(&jar) {
+                    continue;
+                }
+                match read(&jar) {
+                    Some(data) => self.push_jar(jar, data),
+                    None => {
+                        self.warnings
+                            .push(format!("{}: not found, skipping it", jar));
+                        missing.push(jar);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The Main-Class of the first JAR whose manifest names one.
+    pub fn main_class(&self) -> Option<String> {
+        self.entries.iter().find_map(|e| match &e.source {
+            Source::Jar(data) => ZipArchive::new(data).ok()?.manifest()?.main_class(),
+            Source::Classes(_) => None,
+        })
+    }
+
+    /// Loads every class into `vm`, first definition first, keeping each
+    /// JAR for [`Vm::add_archive`]'s resources. A JAR's classes are read as
+    /// [`Vm::release`] would see them, and the JARs stored in it one level
+    /// down follow it. With `redefine` set, the classes replace the ones
+    /// already loaded, as [`Vm::redefine_class`] does, and the JARs kept
+    /// before are dropped; otherwise a class the VM already has is left
+    /// out. Entries that can't be read fail the load unless the VM parses
+    /// leniently, when they are left out with a warning.
+    pub fn load_into<N: NativeBridge, O: ExecutionObserver>(
+        self,
+        vm: &mut Vm<N, O>,
+        redefine: bool,
+    ) -> Result<LoadReport, JvmError> {
+        let lenient = vm.parser.lenient;
+        let mut loader = Loader {
+            vm,
+            redefine,
+            lenient,
+            defined: BTreeMap::new(),
+            report: LoadReport {
+                classes: Vec::new(),
+                warnings: self.warnings,
+            },
+        };
+        if redefine {
+            loader.vm.clear_archives();
+        }
+        for entry in self.entries {
+            match entry.source {
+                Source::Classes(classes) => {
+                    for (file, data) in classes {
+                        let at = format!("{}/{}", entry.name, file);
+                        loader.class(&entry.name, &at, Cow::Owned(data))?;
+                    }
+                }
+                Source::Jar(data) => {
+                    let mut archive = match ZipArchive::from_vec(data) {
+                        Ok(archive) => archive,
+                        Err(e) => {
+                            loader.fail(format!("{}: {}", entry.name, e))?;
+                            continue;
+                        }
+                    };
+                    archive.set_verify_crc(self.verify_crc);
+                    let mut nested = Vec::new();
+                    for inner in archive.nested_jars() {
+                        match archive.open_nested(inner) {
+                            Ok(jar) => nested.push((
+                                format!("{}!/{}", entry.name, inner.name),
+                                jar.into_owned(),
+                            )),
+                            Err(e) => loader.fail(format!("{}: {}", entry.name, e))?,
+                        }
+                    }
+                    let classes = loader.jar(&entry.name, &archive)?;
+                    loader.vm.add_archive(archive, classes);
+                    for (name, jar) in nested {
+                        let classes = loader.jar(&name, &jar)?;
+                        loader.vm.add_archive(jar, classes);
+                    }
+                }
+            }
+        }
+        Ok(loader.report)
+    }
+}
+
+impl Default for ClassPath {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Loader<'v, N: NativeBridge, O: ExecutionObserver> {
+    vm: &'v mut Vm<N, O>,
+    redefine: bool,
+    lenient: bool,
+    // Each class loaded so far and where it came from.
+    defined: BTreeMap<String, String>,
+    report: LoadReport,
+}
+
+impl<N: NativeBridge, O: ExecutionObserver> Loader<'_, N, O> {
+    fn fail(&mut self, message: String) -> Result<(), JvmError> {
+        if !self.lenient {
+            return Err(JvmError::IoError(message));
+        }
+        self.report.warnings.push(format!("{}: left out", message));
+        Ok(())
+    }
+
+    // The names of the classes it loaded.
+    fn jar(&mut self, name: &str, archive: &ZipArchive) -> Result<Vec<String>, JvmError> {
+        let mut loaded = Vec::new();
+        for entry in archive.class_entries_for(self.vm.release()) {
+            let at = format!("{}!/{}", name, entry.name);
+            match archive.read_entry(entry) {
+                Ok(data) => {
+                    if let Some(class) = self.class(name, &at, data)? {
+                        loaded.push(class);
+                    }
+                }
+                Err(e) => self.fail(format!("{}: {}", at, e))?,
+            }
+        }
+        Ok(loaded)
+    }
+
+    // Loads one class file found at `at` in entry `source`, unless an
+    // earlier entry defined the class.
+    fn class(
+        &mut self,
+        source: &str,
+        at: &str,
+        data: Cow<'_, [u8]>,
+    ) -> Result<Option<String>, JvmError> {
+        let size = data.len();
+        let class = match self.vm.parse_class(data) {
+            Ok(class) => class,
+            Err(e) => {
+                self.fail(format!("{}: {}", at, e))?;
+                return Ok(None);
+            }
+        };
+        if class.is_module() {
+            return Ok(None);
+        }
+        let name = String::from(class.class_name()?);
+        if let Some(first) = self.defined.get(&name) {
+            self.report.warnings.push(format!(
+                "{} in {} is left out; {} defined it first",
+                name, source, first
+            ));
+            return Ok(None);
+        }
+        if !self.redefine && self.vm.has_class(&name) {
+            self.report.warnings.push(format!(
+                "{} in {} is left out; it was already loaded",
+                name, source
+            ));
+            return Ok(None);
+        }
+        if self.redefine {
+            self.vm.redefine_class(class)?;
+        } else {
+            self.vm.load_class(class)?;
+        }
+        self.defined.insert(name.clone(), String::from(source));
+        self.report.classes.push(LoadedClass {
+            name: name.clone(),
+            source: String::from(source),
+            size,
+        });
+        Ok(Some(name))
+    }
+}
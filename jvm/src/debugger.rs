@@ -0,0 +1,348 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use shared::types::JvmValue;
+
+use crate::observer::{
+    ExecutionObserver,
+    FrameView,
+    Location,
+};
+
+// Instructions between checks for a `pause` from the client; polling a
+// serial port on every instruction would dominate the run.
+const POLL_INTERVAL: u32 = 1024;
+const ARRAY_LIMIT: usize = 256;
+
+const HELP: &[&str] = &[
+    "break CLASS METHOD [PC]   stop at PC (default 0) of a method",
+    "clear [ID]                drop one breakpoint, or all",
+    "breakpoints               list breakpoints",
+    "continue | c              run until a breakpoint or pause",
+    "step | s                  run one instruction",
+    "next | n                  step over calls",
+    "finish                    run until the current method returns",
+    "where | bt                list frames, innermost first",
+    "locals                    the current frame's locals",
+    "stack                     the current frame's operand stack",
+    "object ID                 an object's class and fields",
+    "array ID                  an array's elements",
+    "detach                    drop breakpoints and stop debugging",
+];
+
+/// A line-oriented link to the debugging client, such as a serial port or
+/// a socket.
+pub trait DebugPort {
+    /// Blocks until a full line arrives; `None` once the client is gone.
+    fn read_line(&mut self) -> Option<String>;
+
+    /// A line if one has already arrived, without blocking.
+    fn poll_line(&mut self) -> Option<String>;
+
+    fn write_line(&mut self, line: &str);
+}
+
+struct Breakpoint {
+    id: u32,
+    class_name: String,
+    method_name: String,
+    pc: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Run,
+    Step,
+    // Stop once the call stack is at most this deep.
+    Next(usize),
+    Finish(usize),
+    Detached,
+}
+
+struct FrameInfo {
+    class_name: String,
+    method: String,
+    pc: usize,
+}
+
+/// An [`ExecutionObserver`] that serves a small debugging protocol over a
+/// [`DebugPort`]. The VM starts suspended at its first instruction.
+///
+/// Commands are single lines (`help` lists them). Every reply ends with a
+/// line reading `ok` or `error <reason>`; when execution stops the server
+/// sends `stopped <reason> <class>.<method><descriptor> pc=<n>` and waits
+/// for commands.
+pub struct Debugger<P: DebugPort> {
+    port: P,
+    breakpoints: Vec<Breakpoint>,
+    next_id: u32,
+    mode: Mode,
+    frames: Vec<FrameInfo>,
+    since_poll: u32,
+}
+
+impl<P: DebugPort> Debugger<P> {
+    pub fn new(mut port: P) -> Self {
+        port.write_line("duke-debug 1");
+        Self {
+            port,
+            breakpoints: Vec::new(),
+            next_id: 1,
+            mode: Mode::Step,
+            frames: Vec::new(),
+            since_poll: 0,
+        }
+    }
+
+    fn stop_reason(&mut self, at: &Location<'_>) -> Option<String> {
+        let depth = self.frames.len();
+        match self.mode {
+            Mode::Step => return Some(String::from("step")),
+            Mode::Next(d) if depth <= d => return Some(String::from("step")),
+            Mode::Finish(d) if depth < d => return Some(String::from("step")),
+            _ => {}
+        }
+        if let Some(bp) = self.breakpoints.iter().find(|bp| {
+            bp.pc == at.pc && bp.method_name == at.method_name && bp.class_name == at.class_name
+        }) {
+            return Some(format!("breakpoint {}", bp.id));
+        }
+        self.since_poll += 1;
+        if self.since_poll >= POLL_INTERVAL {
+            self.since_poll = 0;
+            if let Some(line) = self.port.poll_line() {
+                if line.trim() == "pause" {
+                    return Some(String::from("pause"));
+                }
+                // Anything else is answered without stopping, so breakpoints
+                // can be set while the guest runs.
+                self.command(&line, None);
+            }
+        }
+        None
+    }
+
+    // Serves commands until one resumes execution.
+    fn command_loop(&mut self, frame: &FrameView<'_>) {
+        while self.mode != Mode::Detached {
+            let Some(line) = self.port.read_line() else {
+                self.detach();
+                return;
+            };
+            if self.command(&line, Some(frame)) {
+                return;
+            }
+        }
+    }
+
+    // Runs one command and returns whether it resumed execution.
+    fn command(&mut self, line: &str, frame: Option<&FrameView<'_>>) -> bool {
+        let mut words = line.split_whitespace();
+        let Some(cmd) = words.next() else {
+            return false;
+        };
+        let args: Vec<&str> = words.collect();
+        let depth = self.frames.len();
+        let resume = match cmd {
+            "continue" | "c" => Some(Mode::Run),
+            "step" | "s" => Some(Mode::Step),
+            "next" | "n" => Some(Mode::Next(depth)),
+            "finish" => Some(Mode::Finish(depth)),
+            _ => None,
+        };
+        if let Some(mode) = resume {
+            if frame.is_none() {
+                self.port.write_line("error not stopped");
+                return false;
+            }
+            self.mode = mode;
+            self.port.write_line("ok");
+            return true;
+        }
+        let result = match cmd {
+            "break" | "b" => self.add_breakpoint(&args),
+            "clear" => self.clear(&args),
+            "breakpoints" => {
+                for bp in &self.breakpoints {
+                    self.port.write_line(&format!(
+                        "breakpoint {} {} {} {}",
+                        bp.id, bp.class_name, bp.method_name, bp.pc
+                    ));
+                }
+                Ok(())
+            }
+            "where" | "bt" => {
+                for (i, f) in self.frames.iter().rev().enumerate() {
+                    self.port.write_line(&format!(
+                        "frame {} {}.{} pc={}",
+                        i, f.class_name, f.method, f.pc
+                    ));
+                }
+                Ok(())
+            }
+            "locals" | "stack" | "object" | "array" => match frame {
+                Some(frame) => self.inspect(cmd, &args, frame),
+                None => Err(String::from("not stopped")),
+            },
+            "detach" => {
+                self.detach();
+                self.port.write_line("ok");
+                return true;
+            }
+            "help" => {
+                for line in HELP {
+                    self.port.write_line(line);
+                }
+                Ok(())
+            }
+            _ => Err(format!("unknown command {}", cmd)),
+        };
+        match result {
+            Ok(()) => self.port.write_line("ok"),
+            Err(e) => self.port.write_line(&format!("error {}", e)),
+        }
+        false
+    }
+
+    fn add_breakpoint(&mut self, args: &[&str]) -> Result<(), String> {
+        let (class_name, method_name) = match args {
+            [class, method] | [class, method, _] => (*class, *method),
+            _ => return Err(String::from("usage: break CLASS METHOD [PC]")),
+        };
+        let pc = match args.get(2) {
+            Some(pc) => pc.parse().map_err(|_| format!("bad pc {}", pc))?,
+            None => 0,
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push(Breakpoint {
+            id,
+            class_name: class_name.replace('.', "/"),
+            method_name: String::from(method_name),
+            pc,
+        });
+        self.port.write_line(&format!("breakpoint {}", id));
+        Ok(())
+    }
+
+    fn clear(&mut self, args: &[&str]) -> Result<(), String> {
+        match args.first() {
+            None => self.breakpoints.clear(),
+            Some(id) => {
+                let id: u32 = id.parse().map_err(|_| format!("bad id {}", id))?;
+                let before = self.breakpoints.len();
+                self.breakpoints.retain(|bp| bp.id != id);
+                if self.breakpoints.len() == before {
+                    return Err(format!("no breakpoint {}", id));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn inspect(&mut self, cmd: &str, args: &[&str], frame: &FrameView<'_>) -> Result<(), String> {
+        let id = || -> Result<u32, String> {
+            let arg = args.first().ok_or_else(|| format!("usage: {} ID", cmd))?;
+            arg.parse().map_err(|_| format!("bad id {}", arg))
+        };
+        match cmd {
+            "locals" => {
+                for (i, v) in frame.locals.iter().enumerate() {
+                    self.port
+                        .write_line(&format!("local {} {}", i, describe(v)));
+                }
+            }
+            "stack" => {
+                for (i, v) in frame.stack.iter().enumerate() {
+                    self.port
+                        .write_line(&format!("stack {} {}", i, describe(v)));
+                }
+            }
+            "object" => {
+                let obj = frame.heap.get_object(id()?).map_err(|e| format!("{}", e))?;
+                self.port.write_line(&format!("class {}", obj.class_name));
+                for (name, v) in &obj.fields {
+                    self.port
+                        .write_line(&format!("field {} {}", name, describe(v)));
+                }
+            }
+            _ => {
+                let arr = frame.heap.get_array(id()?).map_err(|e| format!("{}", e))?;
+                self.port.write_line(&format!(
+                    "array {} length={}",
+                    arr.element_type,
+                    arr.elements.len()
+                ));
+                for (i, v) in arr.elements.iter().take(ARRAY_LIMIT).enumerate() {
+                    self.port
+                        .write_line(&format!("element {} {}", i, describe(v)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn detach(&mut self) {
+        self.breakpoints.clear();
+        self.mode = Mode::Detached;
+    }
+}
+
+// `type value`, e.g. `int 5`, `object 3`, `string "Linux"`.
+fn describe(v: &JvmValue) -> String {
+    match v {
+        JvmValue::Int(i) => format!("int {}", i),
+        JvmValue::Long(l) => format!("long {}", l),
+        JvmValue::Float(f) => format!("float {}", f),
+        JvmValue::Double(d) => format!("double {}", d),
+        JvmValue::Null => String::from("null"),
+        JvmValue::ObjectRef(id) => format!("object {}", id),
+        JvmValue::ArrayRef(id) => format!("array {}", id),
+        JvmValue::StringRef(s) => format!("string {:?}", s),
+        JvmValue::ReturnAddress(pc) => format!("returnAddress {}", pc),
+    }
+}
+
+impl<P: DebugPort> ExecutionObserver for Debugger<P> {
+    fn on_frame(&mut self, at: &Location<'_>, frame: &FrameView<'_>) {
+        if self.mode == Mode::Detached {
+            return;
+        }
+        if let Some(top) = self.frames.last_mut() {
+            top.pc = at.pc;
+        }
+        if let Some(reason) = self.stop_reason(at) {
+            self.port.write_line(&format!(
+                "stopped {} {}.{}{} pc={}",
+                reason, at.class_name, at.method_name, at.descriptor, at.pc
+            ));
+            self.command_loop(frame);
+        }
+    }
+
+    fn on_invoke(&mut self, method: &Location<'_>, _args: &[JvmValue]) {
+        if self.mode == Mode::Detached {
+            return;
+        }
+        self.frames.push(FrameInfo {
+            class_name: String::from(method.class_name),
+            method: format!("{}{}", method.method_name, method.descriptor),
+            pc: 0,
+        });
+    }
+
+    fn on_return(&mut self, _method: &Location<'_>, _value: Option<&JvmValue>) {
+        self.frames.pop();
+    }
+
+    fn on_throw(&mut self, at: &Location<'_>, exception: &str) {
+        if self.mode == Mode::Detached {
+            return;
+        }
+        self.port.write_line(&format!(
+            "thrown {} {}.{}{} pc={}",
+            exception, at.class_name, at.method_name, at.descriptor, at.pc
+        ));
+    }
+}
@@ -1,12 +1,22 @@
 use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::vec::Vec;
+use alloc::{
+    format,
+    vec,
+};
+use core::fmt;
 
 use shared::types::{
     JvmError,
     JvmValue,
 };
 
+use crate::snapshot::{
+    Reader,
+    Writer,
+};
+
 #[derive(Debug, Clone)]
 pub struct JvmObject {
     pub class_name: String,
@@ -67,6 +77,49 @@ impl<T> SlabHeap<T> {
         }
     }
 
+    fn iter(&self) -> impl Iterator<Item = (u32, &T)> {
+        self.slots.iter().enumerate().filter_map(|(i, s)| match s {
+            HeapSlot::Live(v) => Some((i as u32, v)),
+            HeapSlot::Free(_) => None,
+        })
+    }
+
+    // Free slots are kept, with the free list, so ids stay the same.
+    fn write(&self, w: &mut Writer, item: impl Fn(&mut Writer, &T)) {
+        w.u32(self.slots.len() as u32);
+        for slot in &self.slots {
+            match slot {
+                HeapSlot::Live(v) => {
+                    w.u8(1);
+                    item(w, v);
+                }
+                HeapSlot::Free(next) => {
+                    w.u8(0);
+                    w.u32(next.unwrap_or(u32::MAX));
+                }
+            }
+        }
+        w.u32(self.free_head.unwrap_or(u32::MAX));
+    }
+
+    fn read(
+        r: &mut Reader,
+        item: impl Fn(&mut Reader) -> Result<T, JvmError>,
+    ) -> Result<Self, JvmError> {
+        let link = |v: u32| (v != u32::MAX).then_some(v);
+        let mut slots = Vec::new();
+        for _ in 0..r.u32()? {
+            slots.push(match r.u8()? {
+                1 => HeapSlot::Live(item(r)?),
+                _ => HeapSlot::Free(link(r.u32()?)),
+            });
+        }
+        Ok(Self {
+            slots,
+            free_head: link(r.u32()?),
+        })
+    }
+
     #[allow(dead_code)]
     fn free(&mut self, id: u32) {
         if (id as usize) < self.slots.len() {
@@ -81,6 +134,12 @@ pub struct Heap {
     arrays: SlabHeap<JvmArray>,
 }
 
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Heap {
     pub fn new() -> Self {
         Self {
@@ -117,9 +176,16 @@ impl Heap {
             "double" => JvmValue::Double(0.0),
             _ => JvmValue::Null,
         };
+        // Firmware pools run out long before usize does; fail the allocation
+        // instead of aborting.
+        let mut elements = Vec::new();
+        elements
+            .try_reserve_exact(size)
+            .map_err(|_| JvmError::OutOfMemory)?;
+        elements.resize(size, default);
         Ok(self.arrays.alloc(JvmArray {
             element_type,
-            elements: alloc::vec![default; size],
+            elements,
         }))
     }
 
@@ -135,4 +201,227 @@ impl Heap {
     pub fn free_array(&mut self, id: u32) {
         self.arrays.free(id);
     }
+
+    pub(crate) fn write(&self, w: &mut Writer) {
+        self.objects.write(w, |w, o| {
+            w.str(&o.class_name);
+            w.u32(o.fields.len() as u32);
+            for (name, value) in &o.fields {
+                w.str(name);
+                w.value(value);
+            }
+        });
+        self.arrays.write(w, |w, a| {
+            w.str(&a.element_type);
+            w.u32(a.elements.len() as u32);
+            for value in &a.elements {
+                w.value(value);
+            }
+        });
+    }
+
+    pub(crate) fn read(r: &mut Reader) -> Result<Self, JvmError> {
+        let objects = SlabHeap::read(r, |r| {
+            let class_name = r.string()?;
+            let mut fields = BTreeMap::new();
+            for _ in 0..r.u32()? {
+                let name = r.string()?;
+                fields.insert(name, r.value()?);
+            }
+            Ok(JvmObject { class_name, fields })
+        })?;
+        let arrays = SlabHeap::read(r, |r| {
+            let element_type = r.string()?;
+            let mut elements = Vec::new();
+            for _ in 0..r.u32()? {
+                elements.push(r.value()?);
+            }
+            Ok(JvmArray {
+                element_type,
+                elements,
+            })
+        })?;
+        Ok(Self { objects, arrays })
+    }
+
+    /// A new `byte[]` holding `data`.
+    pub fn alloc_bytes(&mut self, data: &[u8]) -> Result<u32, JvmError> {
+        Ok(self.arrays.alloc(JvmArray {
+            element_type: String::from("byte"),
+            elements: data
+                .iter()
+                .map(|&b| JvmValue::Int(b as i8 as i32))
+                .collect(),
+        }))
+    }
+
+    /// The contents of a `byte[]`.
+    pub fn bytes(&self, id: u32) -> Result<Vec<u8>, JvmError> {
+        self.get_array(id)?
+            .elements
+            .iter()
+            .map(|v| v.as_int().map(|b| b as u8))
+            .collect()
+    }
+
+    /// A new `long[]` holding `values`.
+    pub fn alloc_longs(&mut self, values: &[i64]) -> Result<u32, JvmError> {
+        Ok(self.arrays.alloc(JvmArray {
+            element_type: String::from("long"),
+            elements: values.iter().map(|&v| JvmValue::Long(v)).collect(),
+        }))
+    }
+
+    /// A new `String[]` holding `strings`.
+    pub fn alloc_strings(&mut self, strings: &[String]) -> Result<u32, JvmError> {
+        Ok(self.arrays.alloc(JvmArray {
+            element_type: String::from("java/lang/String"),
+            elements: strings.iter().cloned().map(JvmValue::StringRef).collect(),
+        }))
+    }
+}
+
+/// A reference to a live heap entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HeapRef {
+    Object(u32),
+    Array(u32),
+}
+
+impl HeapRef {
+    fn of(value: &JvmValue) -> Option<Self> {
+        match value {
+            JvmValue::ObjectRef(id) => Some(HeapRef::Object(*id)),
+            JvmValue::ArrayRef(id) => Some(HeapRef::Array(*id)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HeapRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeapRef::Object(id) => write!(f, "obj#{}", id),
+            HeapRef::Array(id) => write!(f, "arr#{}", id),
+        }
+    }
+}
+
+/// One live object or array in a [`HeapDump`].
+pub struct DumpEntry {
+    pub id: HeapRef,
+    /// Class name for objects, `<element type>[]` for arrays.
+    pub class_name: String,
+    pub refs: Vec<HeapRef>,
+    /// Element count; `None` for objects.
+    pub length: Option<usize>,
+    /// Entries reachable from this one, itself included. The VM does not
+    /// track roots, so this is an upper bound on what freeing it would free.
+    pub retained: usize,
+}
+
+/// Snapshot of every live heap entry, from [`Heap::dump`]. `Display` gives
+/// the full one-line-per-entry listing.
+pub struct HeapDump {
+    pub entries: Vec<DumpEntry>,
+}
+
+impl HeapDump {
+    /// Entry counts and the classes with the most instances, short enough to
+    /// draw on screen.
+    pub fn summary(&self) -> String {
+        let mut by_class: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut arrays = 0;
+        for e in &self.entries {
+            *by_class.entry(e.class_name.as_str()).or_insert(0) += 1;
+            if e.length.is_some() {
+                arrays += 1;
+            }
+        }
+        let mut top: Vec<(&str, usize)> = by_class.into_iter().collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        let mut s = format!("{} objects, {} arrays", self.entries.len() - arrays, arrays);
+        for (name, count) in top.iter().take(5) {
+            s.push_str(&format!("; {} x{}", name, count));
+        }
+        s
+    }
+}
+
+impl fmt::Display for HeapDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for e in &self.entries {
+            match e.length {
+                Some(len) => write!(f, "{} {} len={}", e.id, e.class_name, len)?,
+                None => write!(f, "{} {}", e.id, e.class_name)?,
+            }
+            write!(f, " retained={} refs=[", e.retained)?;
+            for (i, r) in e.refs.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}", r)?;
+            }
+            writeln!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
+impl Heap {
+    /// Walks every live object and array, recording outgoing references and
+    /// how many entries each one keeps reachable.
+    pub fn dump(&self) -> HeapDump {
+        let mut entries = Vec::new();
+        for (id, obj) in self.objects.iter() {
+            entries.push(DumpEntry {
+                id: HeapRef::Object(id),
+                class_name: obj.class_name.clone(),
+                refs: obj.fields.values().filter_map(HeapRef::of).collect(),
+                length: None,
+                retained: 0,
+            });
+        }
+        for (id, arr) in self.arrays.iter() {
+            entries.push(DumpEntry {
+                id: HeapRef::Array(id),
+                class_name: format!("{}[]", arr.element_type),
+                refs: arr.elements.iter().filter_map(HeapRef::of).collect(),
+                length: Some(arr.elements.len()),
+                retained: 0,
+            });
+        }
+
+        // Entries are sorted by id, so references resolve by binary search.
+        // A stamp per entry avoids clearing a visited set for every walk.
+        let edges: Vec<Vec<usize>> = entries
+            .iter()
+            .map(|e| {
+                e.refs
+                    .iter()
+                    .filter_map(|r| entries.binary_search_by(|x| x.id.cmp(r)).ok())
+                    .collect()
+            })
+            .collect();
+        let mut stamp = vec![usize::MAX; entries.len()];
+        let mut stack = Vec::new();
+        for (start, entry) in entries.iter_mut().enumerate() {
+            let mut count = 0;
+            stack.push(start);
+            stamp[start] = start;
+            while let Some(n) = stack.pop() {
+                count += 1;
+                for &next in &edges[n] {
+                    if stamp[next] != start {
+                        stamp[next] = start;
+                        stack.push(next);
+                    }
+                }
+            }
+            entry.retained = count;
+        }
+
+        HeapDump { entries }
+    }
 }
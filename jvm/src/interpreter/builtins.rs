@@ -16,8 +16,9 @@ use super::{
     jvm_value_to_string,
 };
 use crate::native::NativeBridge;
+use crate::observer::ExecutionObserver;
 
-impl<N: NativeBridge> Vm<N> {
+impl<N: NativeBridge, O: ExecutionObserver> Vm<N, O> {
     pub(crate) fn handle_string_method(
         &mut self,
         f: &mut Frame,
@@ -76,6 +77,7 @@ impl<N: NativeBridge> Vm<N> {
                     _ => Vec::new(),
                 };
                 let result = self.do_string_format(&format_str, &format_args)?;
+                self.check_string(&result)?;
                 f.push(JvmValue::StringRef(result));
                 Ok(true)
             }
@@ -88,6 +90,7 @@ impl<N: NativeBridge> Vm<N> {
                     (Some(JvmValue::StringRef(a)), _) => a.clone(),
                     _ => String::new(),
                 };
+                self.check_string(&result)?;
                 f.push(JvmValue::StringRef(result));
                 Ok(true)
             }
@@ -101,7 +104,9 @@ impl<N: NativeBridge> Vm<N> {
                             f.push(JvmValue::StringRef(s.replace(old_c, &new_c.to_string())));
                         }
                         (Some(JvmValue::StringRef(old)), Some(JvmValue::StringRef(new))) => {
-                            f.push(JvmValue::StringRef(s.replace(old.as_str(), new.as_str())));
+                            let replaced = s.replace(old.as_str(), new.as_str());
+                            self.check_string(&replaced)?;
+                            f.push(JvmValue::StringRef(replaced));
                         }
                         _ => f.push(JvmValue::StringRef(s.clone())),
                     }
@@ -320,46 +325,22 @@ impl<N: NativeBridge> Vm<N> {
     ) -> Result<bool, JvmError> {
         match method_name {
             "parseInt" => {
-                if let Some(JvmValue::StringRef(s)) = args.first() {
-                    let radix = args.get(1).and_then(|v| v.as_int().ok()).unwrap_or(10);
-                    match i32::from_str_radix(s.trim(), radix as u32) {
-                        Ok(v) => f.push(JvmValue::Int(v)),
-                        Err(_) => {
-                            return Err(JvmError::NativeMethodError(format!(
-                                "NumberFormatException: {}",
-                                s
-                            )));
-                        }
-                    }
-                } else {
-                    f.push(JvmValue::Int(0));
-                }
+                let v = self.parse_integer(args, i32::MIN as i64, i32::MAX as i64)?;
+                f.push(JvmValue::Int(v as i32));
                 Ok(true)
             }
 
             "valueOf" => {
-                if let Some(JvmValue::Int(v)) = args.first() {
-                    let id = self.heap.alloc_object(String::from("java/lang/Integer"))?;
-                    {
-                        let obj = self.heap.get_object_mut(id)?;
-                        obj.fields.insert(String::from("value"), JvmValue::Int(*v));
-                    }
-                    f.push(JvmValue::ObjectRef(id));
-                } else if let Some(JvmValue::StringRef(s)) = args.first() {
-                    match s.trim().parse::<i32>() {
-                        Ok(v) => {
-                            let id = self.heap.alloc_object(String::from("java/lang/Integer"))?;
-                            {
-                                let obj = self.heap.get_object_mut(id)?;
-                                obj.fields.insert(String::from("value"), JvmValue::Int(v));
-                            }
-                            f.push(JvmValue::ObjectRef(id));
-                        }
-                        Err(_) => f.push(JvmValue::Null),
-                    }
-                } else {
-                    f.push(JvmValue::Null);
+                let v = match args.first() {
+                    Some(JvmValue::Int(v)) => *v,
+                    _ => self.parse_integer(args, i32::MIN as i64, i32::MAX as i64)? as i32,
+                };
+                let id = self.heap.alloc_object(String::from("java/lang/Integer"))?;
+                {
+                    let obj = self.heap.get_object_mut(id)?;
+                    obj.fields.insert(String::from("value"), JvmValue::Int(v));
                 }
+                f.push(JvmValue::ObjectRef(id));
                 Ok(true)
             }
 
@@ -376,6 +357,43 @@ impl<N: NativeBridge> Vm<N> {
                 Ok(true)
             }
 
+            "toHexString" | "toBinaryString" | "toOctalString" => {
+                let v = args.first().and_then(|v| v.as_int().ok()).unwrap_or(0) as u32;
+                let s = match method_name {
+                    "toHexString" => format!("{:x}", v),
+                    "toBinaryString" => format!("{:b}", v),
+                    _ => format!("{:o}", v),
+                };
+                f.push(JvmValue::StringRef(s));
+                Ok(true)
+            }
+
+            "bitCount"
+            | "numberOfLeadingZeros"
+            | "numberOfTrailingZeros"
+            | "highestOneBit"
+            | "lowestOneBit"
+            | "reverse"
+            | "reverseBytes"
+            | "rotateLeft"
+            | "rotateRight" => {
+                let v = args.first().and_then(|v| v.as_int().ok()).unwrap_or(0);
+                let distance = args.get(1).and_then(|v| v.as_int().ok()).unwrap_or(0) as u32;
+                let result = match method_name {
+                    "bitCount" => v.count_ones() as i32,
+                    "numberOfLeadingZeros" => v.leading_zeros() as i32,
+                    "numberOfTrailingZeros" => v.trailing_zeros() as i32,
+                    "highestOneBit" => v & (i32::MIN as u32 >> v.leading_zeros().min(31)) as i32,
+                    "lowestOneBit" => v & v.wrapping_neg(),
+                    "reverse" => v.reverse_bits(),
+                    "reverseBytes" => v.swap_bytes(),
+                    "rotateLeft" => v.rotate_left(distance),
+                    _ => v.rotate_right(distance),
+                };
+                f.push(JvmValue::Int(result));
+                Ok(true)
+            }
+
             "toString" => {
                 if let Some(JvmValue::Int(v)) = args.first() {
                     f.push(JvmValue::StringRef(format!("{}", v)));
@@ -396,6 +414,104 @@ impl<N: NativeBridge> Vm<N> {
         }
     }
 
+    // The (String[, int radix]) parsers of Integer and Long. What they
+    // reject is thrown as a NumberFormatException worded as OpenJDK words
+    // it, so a bad config value can be caught in Java.
+    fn parse_integer(&mut self, args: &[JvmValue], min: i64, max: i64) -> Result<i64, JvmError> {
+        const NFE: &str = "java/lang/NumberFormatException";
+        let radix = args.get(1).and_then(|v| v.as_int().ok()).unwrap_or(10);
+        let Some(JvmValue::StringRef(s)) = args.first() else {
+            return Err(self.throw(NFE, "Cannot parse null string: null"));
+        };
+        if radix < 2 {
+            let message = format!("radix {} less than Character.MIN_RADIX", radix);
+            return Err(self.throw(NFE, &message));
+        }
+        if radix > 36 {
+            let message = format!("radix {} greater than Character.MAX_RADIX", radix);
+            return Err(self.throw(NFE, &message));
+        }
+        match i64::from_str_radix(s.trim(), radix as u32) {
+            Ok(v) if (min..=max).contains(&v) => Ok(v),
+            _ => {
+                let mut message = format!("For input string: \"{}\"", s);
+                if radix != 10 {
+                    message.push_str(&format!(" under radix {}", radix));
+                }
+                Err(self.throw(NFE, &message))
+            }
+        }
+    }
+
+    pub(crate) fn handle_long_method(
+        &mut self,
+        f: &mut Frame,
+        method_name: &str,
+        args: &[JvmValue],
+    ) -> Result<bool, JvmError> {
+        match method_name {
+            "parseLong" => {
+                let v = self.parse_integer(args, i64::MIN, i64::MAX)?;
+                f.push(JvmValue::Long(v));
+                Ok(true)
+            }
+
+            // valueOf(long) is left to the boxing below.
+            "valueOf" if !matches!(args.first(), Some(JvmValue::Long(_))) => {
+                let v = self.parse_integer(args, i64::MIN, i64::MAX)?;
+                let id = self.heap.alloc_object(String::from("java/lang/Long"))?;
+                {
+                    let obj = self.heap.get_object_mut(id)?;
+                    obj.fields.insert(String::from("value"), JvmValue::Long(v));
+                }
+                f.push(JvmValue::ObjectRef(id));
+                Ok(true)
+            }
+
+            "toHexString" | "toBinaryString" | "toOctalString" => {
+                let v = args.first().and_then(|v| v.as_long().ok()).unwrap_or(0) as u64;
+                let s = match method_name {
+                    "toHexString" => format!("{:x}", v),
+                    "toBinaryString" => format!("{:b}", v),
+                    _ => format!("{:o}", v),
+                };
+                f.push(JvmValue::StringRef(s));
+                Ok(true)
+            }
+
+            // These return int in Java even on Long.
+            "bitCount" | "numberOfLeadingZeros" | "numberOfTrailingZeros" | "signum" => {
+                let v = args.first().and_then(|v| v.as_long().ok()).unwrap_or(0);
+                let result = match method_name {
+                    "bitCount" => v.count_ones() as i32,
+                    "numberOfLeadingZeros" => v.leading_zeros() as i32,
+                    "numberOfTrailingZeros" => v.trailing_zeros() as i32,
+                    _ => v.signum() as i32,
+                };
+                f.push(JvmValue::Int(result));
+                Ok(true)
+            }
+
+            "highestOneBit" | "lowestOneBit" | "reverse" | "reverseBytes" | "rotateLeft"
+            | "rotateRight" => {
+                let v = args.first().and_then(|v| v.as_long().ok()).unwrap_or(0);
+                let distance = args.get(1).and_then(|v| v.as_int().ok()).unwrap_or(0) as u32;
+                let result = match method_name {
+                    "highestOneBit" => v & (i64::MIN as u64 >> v.leading_zeros().min(63)) as i64,
+                    "lowestOneBit" => v & v.wrapping_neg(),
+                    "reverse" => v.reverse_bits(),
+                    "reverseBytes" => v.swap_bytes(),
+                    "rotateLeft" => v.rotate_left(distance),
+                    _ => v.rotate_right(distance),
+                };
+                f.push(JvmValue::Long(result));
+                Ok(true)
+            }
+
+            _ => Ok(false),
+        }
+    }
+
     pub(crate) fn handle_boxing(
         &mut self,
         f: &mut Frame,
@@ -456,53 +572,93 @@ impl<N: NativeBridge> Vm<N> {
         args: &[JvmValue],
     ) -> Result<Option<JvmValue>, JvmError> {
         match method_name {
-            "<init>" => Ok(None),
-            "append" => {
+            "<init>" => {
                 let obj_ref = args[0].as_object_ref()?;
-                let current = {
-                    let obj = self.heap.get_object(obj_ref)?;
-                    obj.fields
-                        .get("value")
-                        .and_then(|v| {
-                            if let JvmValue::StringRef(s) = v {
-                                Some(s.clone())
-                            } else {
-                                None
-                            }
-                        })
-                        .unwrap_or_default()
+                let initial = match args.get(1) {
+                    Some(JvmValue::StringRef(s)) => s.clone(),
+                    Some(JvmValue::Null) => return Err(JvmError::NullPointerException),
+                    _ => String::new(),
                 };
+                self.set_builder_value(obj_ref, initial)?;
+                Ok(None)
+            }
+            "append" => {
+                let obj_ref = args[0].as_object_ref()?;
+                let current = self.builder_value(obj_ref)?;
                 let appended = if args.len() > 1 {
                     let piece = jvm_value_to_string(&args[1]);
                     format!("{}{}", current, piece)
                 } else {
                     current
                 };
-                let obj = self.heap.get_object_mut(obj_ref)?;
-                obj.fields
-                    .insert(String::from("value"), JvmValue::StringRef(appended));
+                self.set_builder_value(obj_ref, appended)?;
+                Ok(Some(JvmValue::ObjectRef(obj_ref)))
+            }
+            "deleteCharAt" => {
+                let obj_ref = args[0].as_object_ref()?;
+                let index = args.get(1).and_then(|v| v.as_int().ok()).unwrap_or(0);
+                let mut current = self.builder_value(obj_ref)?;
+                let len = current.chars().count();
+                if index < 0 || index as usize >= len {
+                    return Err(JvmError::ArrayIndexOutOfBounds(index, len));
+                }
+                let at = char_offset(&current, index as usize);
+                current.remove(at);
+                self.set_builder_value(obj_ref, current)?;
+                Ok(Some(JvmValue::ObjectRef(obj_ref)))
+            }
+            "replace" => {
+                let obj_ref = args[0].as_object_ref()?;
+                let start = args.get(1).and_then(|v| v.as_int().ok()).unwrap_or(0);
+                let end = args.get(2).and_then(|v| v.as_int().ok()).unwrap_or(0);
+                let with = match args.get(3) {
+                    Some(JvmValue::StringRef(s)) => s.clone(),
+                    _ => return Err(JvmError::NullPointerException),
+                };
+                let mut current = self.builder_value(obj_ref)?;
+                let len = current.chars().count();
+                if start < 0 || start as usize > len || start > end {
+                    return Err(JvmError::ArrayIndexOutOfBounds(start, len));
+                }
+                let end = (end as usize).min(len);
+                let from = char_offset(&current, start as usize);
+                let to = char_offset(&current, end);
+                current.replace_range(from..to, &with);
+                self.set_builder_value(obj_ref, current)?;
                 Ok(Some(JvmValue::ObjectRef(obj_ref)))
             }
             "toString" => {
                 let obj_ref = args[0].as_object_ref()?;
-                let obj = self.heap.get_object(obj_ref)?;
-                let s = obj
-                    .fields
-                    .get("value")
-                    .and_then(|v| {
-                        if let JvmValue::StringRef(s) = v {
-                            Some(s.clone())
-                        } else {
-                            None
-                        }
-                    })
-                    .unwrap_or_default();
+                let s = self.builder_value(obj_ref)?;
                 Ok(Some(JvmValue::StringRef(s)))
             }
             _ => Ok(None),
         }
     }
 
+    fn builder_value(&self, obj_ref: u32) -> Result<String, JvmError> {
+        let obj = self.heap.get_object(obj_ref)?;
+        Ok(obj
+            .fields
+            .get("value")
+            .and_then(|v| {
+                if let JvmValue::StringRef(s) = v {
+                    Some(s.clone())
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_default())
+    }
+
+    fn set_builder_value(&mut self, obj_ref: u32, value: String) -> Result<(), JvmError> {
+        self.check_string(&value)?;
+        let obj = self.heap.get_object_mut(obj_ref)?;
+        obj.fields
+            .insert(String::from("value"), JvmValue::StringRef(value));
+        Ok(())
+    }
+
     pub(crate) fn handle_math(
         &self,
         f: &mut Frame,
@@ -535,6 +691,99 @@ impl<N: NativeBridge> Vm<N> {
                 }
                 _ => f.push(JvmValue::Int(0)),
             },
+            "addExact" => match (args.first(), args.get(1)) {
+                (Some(JvmValue::Int(a)), Some(JvmValue::Int(b))) => {
+                    f.push(JvmValue::Int(int_exact(a.checked_add(*b))?));
+                }
+                (Some(JvmValue::Long(a)), Some(JvmValue::Long(b))) => {
+                    f.push(JvmValue::Long(long_exact(a.checked_add(*b))?));
+                }
+                _ => f.push(JvmValue::Int(0)),
+            },
+            "subtractExact" => match (args.first(), args.get(1)) {
+                (Some(JvmValue::Int(a)), Some(JvmValue::Int(b))) => {
+                    f.push(JvmValue::Int(int_exact(a.checked_sub(*b))?));
+                }
+                (Some(JvmValue::Long(a)), Some(JvmValue::Long(b))) => {
+                    f.push(JvmValue::Long(long_exact(a.checked_sub(*b))?));
+                }
+                _ => f.push(JvmValue::Int(0)),
+            },
+            "multiplyExact" => match (args.first(), args.get(1)) {
+                (Some(JvmValue::Int(a)), Some(JvmValue::Int(b))) => {
+                    f.push(JvmValue::Int(int_exact(a.checked_mul(*b))?));
+                }
+                (Some(JvmValue::Long(a)), Some(JvmValue::Long(b))) => {
+                    f.push(JvmValue::Long(long_exact(a.checked_mul(*b))?));
+                }
+                (Some(JvmValue::Long(a)), Some(JvmValue::Int(b))) => {
+                    f.push(JvmValue::Long(long_exact(a.checked_mul(*b as i64))?));
+                }
+                _ => f.push(JvmValue::Int(0)),
+            },
+            "incrementExact" => match args.first() {
+                Some(JvmValue::Int(a)) => f.push(JvmValue::Int(int_exact(a.checked_add(1))?)),
+                Some(JvmValue::Long(a)) => f.push(JvmValue::Long(long_exact(a.checked_add(1))?)),
+                _ => f.push(JvmValue::Int(0)),
+            },
+            "decrementExact" => match args.first() {
+                Some(JvmValue::Int(a)) => f.push(JvmValue::Int(int_exact(a.checked_sub(1))?)),
+                Some(JvmValue::Long(a)) => f.push(JvmValue::Long(long_exact(a.checked_sub(1))?)),
+                _ => f.push(JvmValue::Int(0)),
+            },
+            "negateExact" => match args.first() {
+                Some(JvmValue::Int(a)) => f.push(JvmValue::Int(int_exact(a.checked_neg())?)),
+                Some(JvmValue::Long(a)) => f.push(JvmValue::Long(long_exact(a.checked_neg())?)),
+                _ => f.push(JvmValue::Int(0)),
+            },
+            "toIntExact" => match args.first() {
+                Some(JvmValue::Long(a)) => {
+                    f.push(JvmValue::Int(int_exact(i32::try_from(*a).ok())?))
+                }
+                _ => f.push(JvmValue::Int(0)),
+            },
+            "floorDiv" => match (args.first(), args.get(1)) {
+                (Some(JvmValue::Int(a)), Some(JvmValue::Int(b))) => {
+                    if *b == 0 {
+                        return Err(JvmError::DivisionByZero);
+                    }
+                    f.push(JvmValue::Int(floor_div_i32(*a, *b)));
+                }
+                (Some(JvmValue::Long(a)), Some(JvmValue::Long(b))) => {
+                    if *b == 0 {
+                        return Err(JvmError::DivisionByZero);
+                    }
+                    f.push(JvmValue::Long(floor_div_i64(*a, *b)));
+                }
+                (Some(JvmValue::Long(a)), Some(JvmValue::Int(b))) => {
+                    if *b == 0 {
+                        return Err(JvmError::DivisionByZero);
+                    }
+                    f.push(JvmValue::Long(floor_div_i64(*a, *b as i64)));
+                }
+                _ => f.push(JvmValue::Int(0)),
+            },
+            "floorMod" => match (args.first(), args.get(1)) {
+                (Some(JvmValue::Int(a)), Some(JvmValue::Int(b))) => {
+                    if *b == 0 {
+                        return Err(JvmError::DivisionByZero);
+                    }
+                    f.push(JvmValue::Int(floor_mod_i32(*a, *b)));
+                }
+                (Some(JvmValue::Long(a)), Some(JvmValue::Long(b))) => {
+                    if *b == 0 {
+                        return Err(JvmError::DivisionByZero);
+                    }
+                    f.push(JvmValue::Long(floor_mod_i64(*a, *b)));
+                }
+                (Some(JvmValue::Long(a)), Some(JvmValue::Int(b))) => {
+                    if *b == 0 {
+                        return Err(JvmError::DivisionByZero);
+                    }
+                    f.push(JvmValue::Int(floor_mod_i64(*a, *b as i64) as i32));
+                }
+                _ => f.push(JvmValue::Int(0)),
+            },
             _ => {
                 f.push(JvmValue::Int(0));
             }
@@ -545,10 +794,10 @@ impl<N: NativeBridge> Vm<N> {
     pub(crate) fn unbox_if_needed(&self, val: &JvmValue) -> JvmValue {
         match val {
             JvmValue::ObjectRef(id) => {
-                if let Ok(obj) = self.heap.get_object(*id) {
-                    if let Some(v) = obj.fields.get("value") {
-                        return v.clone();
-                    }
+                if let Ok(obj) = self.heap.get_object(*id)
+                    && let Some(v) = obj.fields.get("value")
+                {
+                    return v.clone();
                 }
                 val.clone()
             }
@@ -574,110 +823,139 @@ impl<N: NativeBridge> Vm<N> {
         while i < bytes.len() {
             if bytes[i] == b'%' && i + 1 < bytes.len() {
                 i += 1;
-                while i < bytes.len()
-                    && (bytes[i] == b'-'
-                        || bytes[i] == b'+'
-                        || bytes[i] == b' '
-                        || bytes[i] == b'0'
-                        || bytes[i] == b'#'
-                        || bytes[i] == b'.'
-                        || (bytes[i] >= b'0' && bytes[i] <= b'9'))
-                {
+                let mut spec = FormatSpec::default();
+                while i < bytes.len() {
+                    match bytes[i] {
+                        b'-' => spec.left = true,
+                        b'0' => spec.zero = true,
+                        b'+' => spec.plus = true,
+                        b' ' => spec.space = true,
+                        b'#' => spec.alt = true,
+                        _ => break,
+                    }
+                    i += 1;
+                }
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    spec.width = spec
+                        .width
+                        .saturating_mul(10)
+                        .saturating_add((bytes[i] - b'0') as usize);
                     i += 1;
                 }
+                if i < bytes.len() && bytes[i] == b'.' {
+                    i += 1;
+                    let mut precision: usize = 0;
+                    while i < bytes.len() && bytes[i].is_ascii_digit() {
+                        precision = precision
+                            .saturating_mul(10)
+                            .saturating_add((bytes[i] - b'0') as usize);
+                        i += 1;
+                    }
+                    spec.precision = Some(precision);
+                }
+                // Padding is allocated up front, so `%999999999d` is refused
+                // before it is built rather than after.
+                let padded = spec.width.max(spec.precision.unwrap_or(0));
+                if padded > self.limits.max_string_length {
+                    return Err(JvmError::OutOfMemory);
+                }
                 if i >= bytes.len() {
                     break;
                 }
-                match bytes[i] {
-                    b's' => {
-                        if let Some(arg) = args.get(arg_idx) {
-                            result.push_str(&self.format_arg_as_string(arg));
-                        }
+                let conv = bytes[i];
+                let body = match conv {
+                    b's' | b'S' => {
+                        let mut s = args
+                            .get(arg_idx)
+                            .map(|a| self.format_arg_as_string(a))
+                            .unwrap_or_default();
                         arg_idx += 1;
+                        if let Some(p) = spec.precision {
+                            s = s.chars().take(p).collect();
+                        }
+                        if conv == b'S' {
+                            s = s.to_uppercase();
+                        }
+                        Some(s)
                     }
                     b'd' => {
-                        if let Some(arg) = args.get(arg_idx) {
-                            let val = self.unbox_if_needed(arg);
-                            result.push_str(&jvm_value_to_string(&val));
-                        }
+                        let val = args.get(arg_idx).map(|a| self.unbox_if_needed(a));
                         arg_idx += 1;
+                        val.map(|v| match v {
+                            JvmValue::Int(n) => spec.signed(n < 0, format!("{}", n.unsigned_abs())),
+                            JvmValue::Long(n) => {
+                                spec.signed(n < 0, format!("{}", n.unsigned_abs()))
+                            }
+                            other => jvm_value_to_string(&other),
+                        })
                     }
                     b'f' => {
-                        if let Some(arg) = args.get(arg_idx) {
-                            let val = self.unbox_if_needed(arg);
-                            match &val {
-                                JvmValue::Float(v) => result.push_str(&format!("{:.6}", v)),
-                                JvmValue::Double(v) => result.push_str(&format!("{:.6}", v)),
-                                _ => result.push_str(&jvm_value_to_string(&val)),
-                            }
-                        }
+                        let val = args.get(arg_idx).map(|a| self.unbox_if_needed(a));
                         arg_idx += 1;
-                    }
-                    b'x' => {
-                        if let Some(arg) = args.get(arg_idx) {
-                            let val = self.unbox_if_needed(arg);
-                            if let JvmValue::Int(v) = val {
-                                result.push_str(&format!("{:x}", v));
-                            } else {
-                                result.push_str(&jvm_value_to_string(&val));
+                        let p = spec.precision.unwrap_or(6);
+                        val.map(|v| match v {
+                            JvmValue::Float(x) => {
+                                spec.signed(x.is_sign_negative(), format!("{:.*}", p, x.abs()))
                             }
-                        }
-                        arg_idx += 1;
-                    }
-                    b'X' => {
-                        if let Some(arg) = args.get(arg_idx) {
-                            let val = self.unbox_if_needed(arg);
-                            if let JvmValue::Int(v) = val {
-                                result.push_str(&format!("{:X}", v));
-                            } else {
-                                result.push_str(&jvm_value_to_string(&val));
+                            JvmValue::Double(x) => {
+                                spec.signed(x.is_sign_negative(), format!("{:.*}", p, x.abs()))
                             }
-                        }
-                        arg_idx += 1;
+                            other => jvm_value_to_string(&other),
+                        })
                     }
-                    b'o' => {
-                        if let Some(arg) = args.get(arg_idx) {
-                            let val = self.unbox_if_needed(arg);
-                            if let JvmValue::Int(v) = val {
-                                result.push_str(&format!("{:o}", v));
-                            } else {
-                                result.push_str(&jvm_value_to_string(&val));
-                            }
-                        }
+                    b'x' | b'X' | b'o' => {
+                        let val = args.get(arg_idx).map(|a| self.unbox_if_needed(a));
                         arg_idx += 1;
+                        val.map(|v| {
+                            let digits = match (conv, &v) {
+                                (b'x', JvmValue::Int(n)) => format!("{:x}", n),
+                                (b'x', JvmValue::Long(n)) => format!("{:x}", n),
+                                (b'X', JvmValue::Int(n)) => format!("{:X}", n),
+                                (b'X', JvmValue::Long(n)) => format!("{:X}", n),
+                                (b'o', JvmValue::Int(n)) => format!("{:o}", n),
+                                (b'o', JvmValue::Long(n)) => format!("{:o}", n),
+                                _ => return jvm_value_to_string(&v),
+                            };
+                            let prefix = match (spec.alt, conv) {
+                                (true, b'x') => "0x",
+                                (true, b'X') => "0X",
+                                (true, _) => "0",
+                                _ => "",
+                            };
+                            spec.prefixed(prefix, digits)
+                        })
                     }
                     b'c' => {
-                        if let Some(arg) = args.get(arg_idx) {
-                            let val = self.unbox_if_needed(arg);
-                            if let JvmValue::Int(v) = val {
-                                if let Some(c) = char::from_u32(v as u32) {
-                                    result.push(c);
-                                }
-                            }
-                        }
+                        let val = args.get(arg_idx).map(|a| self.unbox_if_needed(a));
                         arg_idx += 1;
+                        match val {
+                            Some(JvmValue::Int(v)) => char::from_u32(v as u32).map(String::from),
+                            _ => None,
+                        }
                     }
                     b'b' => {
-                        if let Some(arg) = args.get(arg_idx) {
-                            let s = match arg {
-                                JvmValue::Null => "false",
-                                JvmValue::Int(0) => "false",
-                                _ => "true",
-                            };
-                            result.push_str(s);
-                        }
+                        let s = args.get(arg_idx).map(|arg| match arg {
+                            JvmValue::Null => "false",
+                            JvmValue::Int(0) => "false",
+                            _ => "true",
+                        });
                         arg_idx += 1;
+                        s.map(|s| match spec.precision {
+                            Some(p) => s.chars().take(p).collect(),
+                            None => String::from(s),
+                        })
                     }
-                    b'n' => {
-                        result.push('\n');
-                    }
-                    b'%' => {
-                        result.push('%');
-                    }
+                    b'n' => Some(String::from("\n")),
+                    b'%' => Some(String::from("%")),
                     other => {
-                        result.push('%');
-                        result.push(other as char);
+                        let mut s = String::from("%");
+                        s.push(other as char);
+                        Some(s)
                     }
+                };
+                if let Some(body) = body {
+                    let numeric = matches!(conv, b'd' | b'f' | b'x' | b'X' | b'o');
+                    result.push_str(&spec.pad(body, numeric));
                 }
                 i += 1;
             } else {
@@ -689,3 +967,101 @@ impl<N: NativeBridge> Vm<N> {
         Ok(result)
     }
 }
+
+#[derive(Default)]
+struct FormatSpec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl FormatSpec {
+    fn signed(&self, negative: bool, magnitude: String) -> String {
+        let sign = if negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        };
+        self.prefixed(sign, magnitude)
+    }
+
+    fn prefixed(&self, prefix: &str, digits: String) -> String {
+        let len = prefix.len() + digits.len();
+        if self.zero && !self.left && len < self.width {
+            let mut s = String::from(prefix);
+            s.extend(core::iter::repeat_n('0', self.width - len));
+            s.push_str(&digits);
+            s
+        } else {
+            format!("{}{}", prefix, digits)
+        }
+    }
+
+    fn pad(&self, body: String, numeric: bool) -> String {
+        let len = body.chars().count();
+        if len >= self.width || (numeric && self.zero && !self.left) {
+            return body;
+        }
+        let fill = self.width - len;
+        let mut s = String::with_capacity(self.width);
+        if self.left {
+            s.push_str(&body);
+            s.extend(core::iter::repeat_n(' ', fill));
+        } else {
+            s.extend(core::iter::repeat_n(' ', fill));
+            s.push_str(&body);
+        }
+        s
+    }
+}
+
+fn char_offset(s: &str, index: usize) -> usize {
+    s.char_indices()
+        .nth(index)
+        .map(|(i, _)| i)
+        .unwrap_or(s.len())
+}
+
+fn int_exact(v: Option<i32>) -> Result<i32, JvmError> {
+    v.ok_or_else(|| JvmError::ArithmeticOverflow(String::from("integer overflow")))
+}
+
+fn long_exact(v: Option<i64>) -> Result<i64, JvmError> {
+    v.ok_or_else(|| JvmError::ArithmeticOverflow(String::from("long overflow")))
+}
+
+fn floor_div_i32(a: i32, b: i32) -> i32 {
+    let q = a.wrapping_div(b);
+    if (a ^ b) < 0 && q.wrapping_mul(b) != a {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn floor_div_i64(a: i64, b: i64) -> i64 {
+    let q = a.wrapping_div(b);
+    if (a ^ b) < 0 && q.wrapping_mul(b) != a {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn floor_mod_i32(a: i32, b: i32) -> i32 {
+    let m = a.wrapping_rem(b);
+    if m != 0 && (m ^ b) < 0 { m + b } else { m }
+}
+
+fn floor_mod_i64(a: i64, b: i64) -> i64 {
+    let m = a.wrapping_rem(b);
+    if m != 0 && (m ^ b) < 0 { m + b } else { m }
+}
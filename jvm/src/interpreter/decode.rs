@@ -0,0 +1,317 @@
+use alloc::format;
+use alloc::vec::Vec;
+
+use shared::classfile::CodeAttribute;
+use shared::opcodes::*;
+use shared::types::JvmError;
+
+// One predecoded instruction. `a` and `b` hold the operands already decoded
+// from the bytecode: local slots, constant-pool indices, immediates, and
+// branch targets rewritten as indices into `Code::insns`.
+#[derive(Clone, Copy)]
+pub(crate) struct Insn {
+    pub(crate) op: u8,
+    pub(crate) a: i32,
+    pub(crate) b: i32,
+}
+
+impl Insn {
+    fn new(op: u8, a: i32, b: i32) -> Self {
+        Self { op, a, b }
+    }
+}
+
+pub(crate) enum Switch {
+    Table {
+        low: i32,
+        targets: Vec<u32>,
+        default: u32,
+    },
+    Lookup {
+        pairs: Vec<(i32, u32)>,
+        default: u32,
+    },
+}
+
+impl Switch {
+    pub(crate) fn target(&self, key: i32) -> u32 {
+        match self {
+            Switch::Table {
+                low,
+                targets,
+                default,
+            } => {
+                let idx = (key as i64) - (*low as i64);
+                if idx >= 0 && (idx as usize) < targets.len() {
+                    targets[idx as usize]
+                } else {
+                    *default
+                }
+            }
+            Switch::Lookup { pairs, default } => pairs
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, t)| *t)
+                .unwrap_or(*default),
+        }
+    }
+}
+
+pub(crate) struct Handler {
+    pub(crate) start: u32,
+    pub(crate) end: u32,
+    pub(crate) handler: u32,
+    pub(crate) catch_type: u16,
+}
+
+pub(crate) struct Code {
+    pub(crate) insns: Vec<Insn>,
+    pub(crate) switches: Vec<Switch>,
+    pub(crate) handlers: Vec<Handler>,
+    // Bytecode offset each instruction came from, for observers.
+    pub(crate) offsets: Vec<u32>,
+    pub(crate) max_stack: usize,
+    pub(crate) max_locals: usize,
+    // The local each argument goes in, receiver first; long and double
+    // arguments take two. Filled in from the descriptor by the caller.
+    pub(crate) arg_slots: Vec<usize>,
+}
+
+pub(crate) fn decode(attr: &CodeAttribute) -> Result<Code, JvmError> {
+    let code = &attr.code[..];
+
+    let mut starts = Vec::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        starts.push(pc as u32);
+        pc += insn_len(code, pc)?;
+    }
+
+    let index_of = |pc: i64| -> Result<u32, JvmError> {
+        if pc >= 0
+            && let Ok(i) = starts.binary_search(&(pc as u32))
+        {
+            return Ok(i as u32);
+        }
+        Err(JvmError::ClassFormatError(format!(
+            "branch into the middle of an instruction at {}",
+            pc
+        )))
+    };
+
+    let mut insns = Vec::with_capacity(starts.len());
+    let mut switches = Vec::new();
+    for &start in &starts {
+        let pc = start as usize;
+        let op = code[pc];
+        let branch = |off: i32| index_of(pc as i64 + off as i64).map(|t| t as i32);
+        let insn = match op {
+            BIPUSH => Insn::new(op, code[pc + 1] as i8 as i32, 0),
+            SIPUSH => Insn::new(op, read_u16(code, pc + 1)? as i16 as i32, 0),
+            LDC | NEWARRAY => Insn::new(op, code[pc + 1] as i32, 0),
+            ILOAD..=ALOAD | ISTORE..=ASTORE | RET => Insn::new(op, code[pc + 1] as i32, 0),
+            ILOAD_0..=ALOAD_3 => Insn::new(op, ((op - ILOAD_0) % 4) as i32, 0),
+            ISTORE_0..=ASTORE_3 => Insn::new(op, ((op - ISTORE_0) % 4) as i32, 0),
+            IINC => Insn::new(op, code[pc + 1] as i32, code[pc + 2] as i8 as i32),
+            LDC_W
+            | LDC2_W
+            | GETSTATIC..=INVOKEDYNAMIC
+            | NEW
+            | ANEWARRAY
+            | CHECKCAST
+            | INSTANCEOF => Insn::new(op, read_u16(code, pc + 1)? as i32, 0),
+            MULTIANEWARRAY => Insn::new(op, read_u16(code, pc + 1)? as i32, code[pc + 3] as i32),
+            IFEQ..=JSR | IFNULL | IFNONNULL => {
+                Insn::new(op, branch(read_u16(code, pc + 1)? as i16 as i32)?, 0)
+            }
+            GOTO_W | JSR_W => Insn::new(op, branch(read_i32(code, pc + 1)?)?, 0),
+            TABLESWITCH => {
+                let base = (pc + 4) & !3;
+                let default = branch(read_i32(code, base)?)? as u32;
+                let low = read_i32(code, base + 4)?;
+                let high = read_i32(code, base + 8)?;
+                let mut targets = Vec::new();
+                for i in 0..(high as i64 - low as i64 + 1).max(0) as usize {
+                    targets.push(branch(read_i32(code, base + 12 + i * 4)?)? as u32);
+                }
+                switches.push(Switch::Table {
+                    low,
+                    targets,
+                    default,
+                });
+                Insn::new(op, (switches.len() - 1) as i32, 0)
+            }
+            LOOKUPSWITCH => {
+                let base = (pc + 4) & !3;
+                let default = branch(read_i32(code, base)?)? as u32;
+                let npairs = read_i32(code, base + 4)?.max(0) as usize;
+                let mut pairs = Vec::with_capacity(npairs);
+                for i in 0..npairs {
+                    let key = read_i32(code, base + 8 + i * 8)?;
+                    let target = branch(read_i32(code, base + 12 + i * 8)?)? as u32;
+                    pairs.push((key, target));
+                }
+                switches.push(Switch::Lookup { pairs, default });
+                Insn::new(op, (switches.len() - 1) as i32, 0)
+            }
+            // The widened form decodes to the plain opcode with a 16-bit
+            // operand, so execution never sees WIDE itself.
+            WIDE => {
+                let wide_op = code[pc + 1];
+                let idx = read_u16(code, pc + 2)? as i32;
+                match wide_op {
+                    IINC => Insn::new(wide_op, idx, read_u16(code, pc + 4)? as i16 as i32),
+                    ILOAD..=ALOAD | ISTORE..=ASTORE | RET => Insn::new(wide_op, idx, 0),
+                    _ => return Err(JvmError::UnsupportedOpcode(wide_op)),
+                }
+            }
+            _ => Insn::new(op, 0, 0),
+        };
+        insns.push(insn);
+    }
+    // Execution mustn't run off the end (JVMS 4.9.2), so the last
+    // instruction has to leave the method or jump.
+    match insns.last().map(|i| i.op) {
+        Some(GOTO | RET | TABLESWITCH | LOOKUPSWITCH | IRETURN..=RETURN | ATHROW | GOTO_W) => {}
+        Some(op) => {
+            return Err(JvmError::ClassFormatError(format!(
+                "code falls off its end after 0x{:02x}",
+                op
+            )));
+        }
+        None => return Err(JvmError::ClassFormatError("empty code".into())),
+    }
+
+    let mut handlers = Vec::with_capacity(attr.exception_table.len());
+    for entry in &attr.exception_table {
+        // Classes built in memory never went through parse_class's checks.
+        let at = |pc: u16| {
+            index_of(pc as i64).map_err(|_| {
+                JvmError::ClassFormatError(format!(
+                    "exception handler {}..{} -> {}: {} is not an instruction",
+                    entry.start_pc, entry.end_pc, entry.handler_pc, pc
+                ))
+            })
+        };
+        let end = if entry.end_pc as usize == code.len() {
+            insns.len() as u32
+        } else {
+            at(entry.end_pc)?
+        };
+        let start = at(entry.start_pc)?;
+        if start >= end {
+            return Err(JvmError::ClassFormatError(format!(
+                "exception handler {}..{} is empty",
+                entry.start_pc, entry.end_pc
+            )));
+        }
+        handlers.push(Handler {
+            start,
+            end,
+            handler: at(entry.handler_pc)?,
+            catch_type: entry.catch_type,
+        });
+    }
+
+    Ok(Code {
+        insns,
+        switches,
+        handlers,
+        offsets: starts,
+        max_stack: attr.max_stack as usize,
+        max_locals: attr.max_locals as usize,
+        arg_slots: Vec::new(),
+    })
+}
+
+fn insn_len(code: &[u8], pc: usize) -> Result<usize, JvmError> {
+    let op = code[pc];
+    let len = match op {
+        BIPUSH | LDC | ILOAD..=ALOAD | ISTORE..=ASTORE | NEWARRAY | RET => 2,
+        SIPUSH
+        | LDC_W
+        | LDC2_W
+        | IINC
+        | IFEQ..=JSR
+        | GETSTATIC..=INVOKESTATIC
+        | NEW
+        | ANEWARRAY
+        | CHECKCAST
+        | INSTANCEOF
+        | IFNULL
+        | IFNONNULL => 3,
+        MULTIANEWARRAY => 4,
+        INVOKEINTERFACE | INVOKEDYNAMIC | GOTO_W | JSR_W => 5,
+        WIDE => {
+            if code.get(pc + 1) == Some(&IINC) {
+                6
+            } else {
+                4
+            }
+        }
+        TABLESWITCH => {
+            let base = (pc + 4) & !3;
+            let low = read_i32(code, base + 4)? as i64;
+            let high = read_i32(code, base + 8)? as i64;
+            base + 12 + (high - low + 1).max(0) as usize * 4 - pc
+        }
+        LOOKUPSWITCH => {
+            let base = (pc + 4) & !3;
+            let npairs = read_i32(code, base + 4)?.max(0) as usize;
+            base + 8 + npairs * 8 - pc
+        }
+        NOP..=JSR_W => 1,
+        _ => return Err(JvmError::UnsupportedOpcode(op)),
+    };
+    if pc + len > code.len() {
+        return Err(JvmError::ClassFormatError(format!(
+            "truncated instruction 0x{:02x} at {}",
+            op, pc
+        )));
+    }
+    Ok(len)
+}
+
+fn read_u16(code: &[u8], pos: usize) -> Result<u16, JvmError> {
+    match code.get(pos..pos + 2) {
+        Some(b) => Ok(u16::from_be_bytes([b[0], b[1]])),
+        None => Err(JvmError::ClassFormatError(format!(
+            "truncated operand at {}",
+            pos
+        ))),
+    }
+}
+
+fn read_i32(code: &[u8], pos: usize) -> Result<i32, JvmError> {
+    match code.get(pos..pos + 4) {
+        Some(b) => Ok(i32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(JvmError::ClassFormatError(format!(
+            "truncated operand at {}",
+            pos
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::classfile::write::CodeBuilder;
+
+    use super::*;
+
+    fn decoded(ops: &[u8]) -> Result<Code, JvmError> {
+        let mut code = CodeBuilder::new(1, 0);
+        for &op in ops {
+            code.op(op);
+        }
+        decode(&code.finish()?)
+    }
+
+    #[test]
+    fn code_must_not_fall_off_its_end() {
+        for ops in [&[][..], &[ICONST_0], &[ICONST_0, POP]] {
+            assert!(matches!(decoded(ops), Err(JvmError::ClassFormatError(_))));
+        }
+        assert!(decoded(&[RETURN]).is_ok());
+        assert!(decoded(&[ACONST_NULL, ATHROW]).is_ok());
+    }
+}
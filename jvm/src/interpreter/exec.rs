@@ -9,20 +9,18 @@ use shared::types::{
     JvmValue,
 };
 
+use super::decode::Insn;
 use super::{
     ExecAction,
     Frame,
     Vm,
 };
 use crate::native::NativeBridge;
+use crate::observer::ExecutionObserver;
 
-impl<N: NativeBridge> Vm<N> {
-    pub(crate) fn exec_one(
-        &mut self,
-        f: &mut Frame,
-        op: u8,
-        op_pc: usize,
-    ) -> Result<ExecAction, JvmError> {
+impl<N: NativeBridge, O: ExecutionObserver> Vm<N, O> {
+    pub(crate) fn exec_one(&mut self, f: &mut Frame, insn: Insn) -> Result<ExecAction, JvmError> {
+        let op = insn.op;
         match op {
             NOP => {}
             ACONST_NULL => f.push(JvmValue::Null),
@@ -41,24 +39,10 @@ impl<N: NativeBridge> Vm<N> {
             DCONST_0 => f.push(JvmValue::Double(0.0)),
             DCONST_1 => f.push(JvmValue::Double(1.0)),
 
-            BIPUSH => {
-                let v = f.read_u8() as i8 as i32;
-                f.push(JvmValue::Int(v));
-            }
-            SIPUSH => {
-                let v = f.read_i16() as i32;
-                f.push(JvmValue::Int(v));
-            }
-            LDC => {
-                let idx = f.read_u8() as u16;
-                self.push_ldc(f, idx)?;
-            }
-            LDC_W => {
-                let idx = f.read_u16();
-                self.push_ldc(f, idx)?;
-            }
+            BIPUSH | SIPUSH => f.push(JvmValue::Int(insn.a)),
+            LDC | LDC_W => self.push_ldc(f, insn.a as u16)?,
             LDC2_W => {
-                let idx = f.read_u16();
+                let idx = insn.a as u16;
                 let class = &self.classes[f.class_idx];
                 match &class.constant_pool[idx as usize] {
                     CpEntry::Long(v) => f.push(JvmValue::Long(*v)),
@@ -72,14 +56,7 @@ impl<N: NativeBridge> Vm<N> {
                 }
             }
 
-            ILOAD | ALOAD | LLOAD | FLOAD | DLOAD => {
-                let idx = f.read_u8() as usize;
-                f.push(f.locals[idx].clone());
-            }
-            ILOAD_0 | ALOAD_0 | FLOAD_0 | DLOAD_0 | LLOAD_0 => f.push(f.locals[0].clone()),
-            ILOAD_1 | ALOAD_1 | FLOAD_1 | DLOAD_1 | LLOAD_1 => f.push(f.locals[1].clone()),
-            ILOAD_2 | ALOAD_2 | FLOAD_2 | DLOAD_2 | LLOAD_2 => f.push(f.locals[2].clone()),
-            ILOAD_3 | ALOAD_3 | FLOAD_3 | DLOAD_3 | LLOAD_3 => f.push(f.locals[3].clone()),
+            ILOAD..=ALOAD | ILOAD_0..=ALOAD_3 => f.push(f.locals[insn.a as usize].clone()),
 
             IALOAD | AALOAD | BALOAD | CALOAD | SALOAD | LALOAD | FALOAD | DALOAD => {
                 let index = f.pop_int()?;
@@ -91,26 +68,9 @@ impl<N: NativeBridge> Vm<N> {
                 f.push(arr.elements[index as usize].clone());
             }
 
-            ISTORE | ASTORE | LSTORE | FSTORE | DSTORE => {
-                let idx = f.read_u8() as usize;
-                let v = f.pop()?;
-                f.locals[idx] = v;
-            }
-            ISTORE_0 | ASTORE_0 | FSTORE_0 | DSTORE_0 | LSTORE_0 => {
+            ISTORE..=ASTORE | ISTORE_0..=ASTORE_3 => {
                 let v = f.pop()?;
-                f.locals[0] = v;
-            }
-            ISTORE_1 | ASTORE_1 | FSTORE_1 | DSTORE_1 | LSTORE_1 => {
-                let v = f.pop()?;
-                f.locals[1] = v;
-            }
-            ISTORE_2 | ASTORE_2 | FSTORE_2 | DSTORE_2 | LSTORE_2 => {
-                let v = f.pop()?;
-                f.locals[2] = v;
-            }
-            ISTORE_3 | ASTORE_3 | FSTORE_3 | DSTORE_3 | LSTORE_3 => {
-                let v = f.pop()?;
-                f.locals[3] = v;
+                f.locals[insn.a as usize] = v;
             }
 
             IASTORE | BASTORE | CASTORE | SASTORE | LASTORE | FASTORE | DASTORE => {
@@ -138,8 +98,10 @@ impl<N: NativeBridge> Vm<N> {
                 f.pop()?;
             }
             POP2 => {
-                f.pop()?;
-                f.pop()?;
+                // A long or double is one value here but two slots to javac.
+                if !matches!(f.pop()?, JvmValue::Long(_) | JvmValue::Double(_)) {
+                    f.pop()?;
+                }
             }
             DUP => {
                 let v = f.pop()?;
@@ -398,10 +360,8 @@ impl<N: NativeBridge> Vm<N> {
                 f.push(JvmValue::Long(a ^ b));
             }
             IINC => {
-                let idx = f.read_u8() as usize;
-                let inc = f.read_u8() as i8 as i32;
-                if let JvmValue::Int(v) = &mut f.locals[idx] {
-                    *v = v.wrapping_add(inc);
+                if let JvmValue::Int(v) = &mut f.locals[insn.a as usize] {
+                    *v = v.wrapping_add(insn.b);
                 }
             }
 
@@ -429,6 +389,8 @@ impl<N: NativeBridge> Vm<N> {
                 let v = f.pop_long()?;
                 f.push(JvmValue::Double(v as f64));
             }
+            // Float-to-integer `as` casts saturate and map NaN to 0, which is
+            // exactly JLS 5.1.3 narrowing, so F2I/F2L/D2I/D2L need no special cases.
             F2I => {
                 let v = f.pop_float()?;
                 f.push(JvmValue::Int(v as i32));
@@ -495,9 +457,7 @@ impl<N: NativeBridge> Vm<N> {
             FCMPG => {
                 let b = f.pop_float()?;
                 let a = f.pop_float()?;
-                let r = if a.is_nan() || b.is_nan() {
-                    1
-                } else if a > b {
+                let r = if a.is_nan() || b.is_nan() || a > b {
                     1
                 } else if a == b {
                     0
@@ -523,9 +483,7 @@ impl<N: NativeBridge> Vm<N> {
             DCMPG => {
                 let b = f.pop_double()?;
                 let a = f.pop_double()?;
-                let r = if a.is_nan() || b.is_nan() {
-                    1
-                } else if a > b {
+                let r = if a.is_nan() || b.is_nan() || a > b {
                     1
                 } else if a == b {
                     0
@@ -536,175 +494,117 @@ impl<N: NativeBridge> Vm<N> {
             }
 
             IFEQ => {
-                let off = f.read_i16();
                 let v = f.pop_int()?;
                 if v == 0 {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
             IFNE => {
-                let off = f.read_i16();
                 let v = f.pop_int()?;
                 if v != 0 {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
             IFLT => {
-                let off = f.read_i16();
                 let v = f.pop_int()?;
                 if v < 0 {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
             IFGE => {
-                let off = f.read_i16();
                 let v = f.pop_int()?;
                 if v >= 0 {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
             IFGT => {
-                let off = f.read_i16();
                 let v = f.pop_int()?;
                 if v > 0 {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
             IFLE => {
-                let off = f.read_i16();
                 let v = f.pop_int()?;
                 if v <= 0 {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
 
             IF_ICMPEQ => {
-                let off = f.read_i16();
                 let b = f.pop_int()?;
                 let a = f.pop_int()?;
                 if a == b {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
             IF_ICMPNE => {
-                let off = f.read_i16();
                 let b = f.pop_int()?;
                 let a = f.pop_int()?;
                 if a != b {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
             IF_ICMPLT => {
-                let off = f.read_i16();
                 let b = f.pop_int()?;
                 let a = f.pop_int()?;
                 if a < b {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
             IF_ICMPGE => {
-                let off = f.read_i16();
                 let b = f.pop_int()?;
                 let a = f.pop_int()?;
                 if a >= b {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
             IF_ICMPGT => {
-                let off = f.read_i16();
                 let b = f.pop_int()?;
                 let a = f.pop_int()?;
                 if a > b {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
             IF_ICMPLE => {
-                let off = f.read_i16();
                 let b = f.pop_int()?;
                 let a = f.pop_int()?;
                 if a <= b {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
 
             IF_ACMPEQ => {
-                let off = f.read_i16();
                 let b = f.pop()?;
                 let a = f.pop()?;
                 if self.refs_equal(&a, &b) {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
             IF_ACMPNE => {
-                let off = f.read_i16();
                 let b = f.pop()?;
                 let a = f.pop()?;
                 if !self.refs_equal(&a, &b) {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
             IFNULL => {
-                let off = f.read_i16();
                 let v = f.pop()?;
                 if v.is_null() {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
             IFNONNULL => {
-                let off = f.read_i16();
                 let v = f.pop()?;
                 if !v.is_null() {
-                    f.pc = (op_pc as isize + off as isize) as usize;
+                    f.pc = insn.a as usize;
                 }
             }
 
-            GOTO => {
-                let off = f.read_i16();
-                f.pc = (op_pc as isize + off as isize) as usize;
-            }
-            GOTO_W => {
-                let off = f.read_i32();
-                f.pc = (op_pc as isize + off as isize) as usize;
-            }
+            GOTO | GOTO_W => f.pc = insn.a as usize,
 
-            TABLESWITCH => {
-                let base = op_pc + 1;
-                f.pc = (base + 3) & !3;
-                let default_off = f.read_i32();
-                let low = f.read_i32();
-                let high = f.read_i32();
-                let index = f.pop_int()?;
-                if index >= low && index <= high {
-                    let entry = (index - low) as usize;
-                    f.pc = (base + 3) & !3;
-                    f.pc += 12 + entry * 4;
-                    let off = f.read_i32();
-                    f.pc = (op_pc as isize + off as isize) as usize;
-                } else {
-                    f.pc = (op_pc as isize + default_off as isize) as usize;
-                }
-            }
-            LOOKUPSWITCH => {
-                let base = op_pc + 1;
-                f.pc = (base + 3) & !3;
-                let default_off = f.read_i32();
-                let npairs = f.read_i32();
+            TABLESWITCH | LOOKUPSWITCH => {
                 let key = f.pop_int()?;
-                let pairs_start = f.pc;
-                let mut found = false;
-                for i in 0..npairs as usize {
-                    f.pc = pairs_start + i * 8;
-                    let match_val = f.read_i32();
-                    let off = f.read_i32();
-                    if key == match_val {
-                        f.pc = (op_pc as isize + off as isize) as usize;
-                        found = true;
-                        break;
-                    }
-                }
-                if !found {
-                    f.pc = (op_pc as isize + default_off as isize) as usize;
-                }
+                f.pc = f.code.switches[insn.a as usize].target(key) as usize;
             }
 
             IRETURN | LRETURN | FRETURN | DRETURN | ARETURN => {
@@ -715,62 +615,48 @@ impl<N: NativeBridge> Vm<N> {
             }
 
             GETSTATIC => {
-                let idx = f.read_u16();
+                let idx = insn.a as u16;
                 self.do_getstatic(f, idx)?;
             }
             PUTSTATIC => {
-                let idx = f.read_u16();
-                let val = f.pop()?;
-                let class = &self.classes[f.class_idx];
-                if let CpEntry::Fieldref {
-                    class_index,
-                    name_and_type_index,
-                } = &class.constant_pool[idx as usize]
-                {
-                    let cn = class.get_class_name(*class_index)?;
-                    let (field_name, _) = class.resolve_name_and_type(*name_and_type_index)?;
-                    let key = format!("{}.{}", cn, field_name);
-                    self.statics.insert(key, val);
-                }
+                let idx = insn.a as u16;
+                self.do_putstatic(f, idx)?;
             }
             GETFIELD => {
-                let idx = f.read_u16();
+                let idx = insn.a as u16;
                 self.do_getfield(f, idx)?;
             }
             PUTFIELD => {
-                let idx = f.read_u16();
+                let idx = insn.a as u16;
                 self.do_putfield(f, idx)?;
             }
 
             INVOKEVIRTUAL | INVOKESPECIAL | INVOKESTATIC => {
-                let idx = f.read_u16();
+                let idx = insn.a as u16;
                 self.do_invoke(f, op, idx)?;
             }
 
             INVOKEINTERFACE => {
-                let idx = f.read_u16();
-                let _count = f.read_u8();
-                let _zero = f.read_u8();
+                let idx = insn.a as u16;
                 self.do_invoke(f, INVOKEVIRTUAL, idx)?;
             }
 
             INVOKEDYNAMIC => {
-                let idx = f.read_u16();
-                let _zero = f.read_u16();
+                let idx = insn.a as u16;
                 self.do_invokedynamic(f, idx)?;
             }
 
             NEW => {
-                let idx = f.read_u16();
+                let idx = insn.a as u16;
                 let class = &self.classes[f.class_idx];
                 let name = class.get_class_name(idx)?;
                 let cn = String::from(name);
-                let id = self.heap.alloc_object(cn)?;
+                let id = self.new_object(&cn)?;
                 f.push(JvmValue::ObjectRef(id));
             }
 
             NEWARRAY => {
-                let atype = f.read_u8();
+                let atype = insn.a as u8;
                 let count = f.pop_int()?;
                 let elem = match atype {
                     4 => "boolean",
@@ -788,28 +674,23 @@ impl<N: NativeBridge> Vm<N> {
                         )));
                     }
                 };
-                let id = self.heap.alloc_array(String::from(elem), count as usize)?;
+                let len = self.array_length(count)?;
+                let id = self.heap.alloc_array(String::from(elem), len)?;
                 f.push(JvmValue::ArrayRef(id));
             }
             ANEWARRAY => {
-                let _class_idx = f.read_u16();
-                let count = f.pop_int()?;
-                let id = self
-                    .heap
-                    .alloc_array(String::from("object"), count as usize)?;
+                let len = self.array_length(f.pop_int()?)?;
+                let id = self.heap.alloc_array(String::from("object"), len)?;
                 f.push(JvmValue::ArrayRef(id));
             }
             MULTIANEWARRAY => {
-                let _class_idx = f.read_u16();
-                let dimensions = f.read_u8() as usize;
+                let dimensions = insn.b as usize;
                 let mut counts = Vec::with_capacity(dimensions);
                 for _ in 0..dimensions {
-                    counts.push(f.pop_int()?);
+                    counts.push(self.array_length(f.pop_int()?)?);
                 }
                 counts.reverse();
-                let id = self
-                    .heap
-                    .alloc_array(String::from("object"), counts[0] as usize)?;
+                let id = self.heap.alloc_array(String::from("object"), counts[0])?;
                 f.push(JvmValue::ArrayRef(id));
             }
             ARRAYLENGTH => {
@@ -819,19 +700,12 @@ impl<N: NativeBridge> Vm<N> {
             }
 
             ATHROW => {
-                let exc_val = f.pop()?;
-                let exc_class = match &exc_val {
-                    JvmValue::ObjectRef(id) => {
-                        let obj = self.heap.get_object(*id)?;
-                        obj.class_name.clone()
-                    }
-                    _ => String::from("java/lang/Throwable"),
-                };
-                return Ok(ExecAction::Throw(exc_class, exc_val));
+                let id = f.pop()?.as_object_ref()?;
+                return Err(JvmError::Thrown(self.throwable_string(id)?, id));
             }
 
             CHECKCAST => {
-                let idx = f.read_u16();
+                let idx = insn.a as u16;
                 let val = f.pop()?;
                 if !val.is_null() {
                     let class = &self.classes[f.class_idx];
@@ -845,19 +719,23 @@ impl<N: NativeBridge> Vm<N> {
                         _ => true,
                     };
                     if !ok {
-                        let exc_id = self
-                            .heap
-                            .alloc_object(String::from("java/lang/ClassCastException"))?;
-                        return Ok(ExecAction::Throw(
-                            String::from("java/lang/ClassCastException"),
-                            JvmValue::ObjectRef(exc_id),
-                        ));
+                        let from = match &val {
+                            JvmValue::ObjectRef(id) => {
+                                self.heap.get_object(*id)?.class_name.clone()
+                            }
+                            _ => String::from("?"),
+                        };
+                        return Err(JvmError::ClassCast(format!(
+                            "class {} cannot be cast to class {}",
+                            from.replace('/', "."),
+                            target_owned.replace('/', ".")
+                        )));
                     }
                 }
                 f.push(val);
             }
             INSTANCEOF => {
-                let idx = f.read_u16();
+                let idx = insn.a as u16;
                 let val = f.pop()?;
                 if val.is_null() {
                     f.push(JvmValue::Int(0));
@@ -880,29 +758,6 @@ impl<N: NativeBridge> Vm<N> {
                 f.pop()?;
             }
 
-            WIDE => {
-                let wide_op = f.read_u8();
-                match wide_op {
-                    ILOAD | LLOAD | FLOAD | DLOAD | ALOAD => {
-                        let idx = f.read_u16() as usize;
-                        f.push(f.locals[idx].clone());
-                    }
-                    ISTORE | LSTORE | FSTORE | DSTORE | ASTORE => {
-                        let idx = f.read_u16() as usize;
-                        let v = f.pop()?;
-                        f.locals[idx] = v;
-                    }
-                    IINC => {
-                        let idx = f.read_u16() as usize;
-                        let inc = f.read_i16() as i32;
-                        if let JvmValue::Int(v) = &mut f.locals[idx] {
-                            *v = v.wrapping_add(inc);
-                        }
-                    }
-                    _ => return Err(JvmError::UnsupportedOpcode(wide_op)),
-                }
-            }
-
             _ => return Err(JvmError::UnsupportedOpcode(op)),
         }
         Ok(ExecAction::Continue)
@@ -917,7 +772,7 @@ impl<N: NativeBridge> Vm<N> {
         }
     }
 
-    fn push_ldc(&self, f: &mut Frame, idx: u16) -> Result<(), JvmError> {
+    fn push_ldc(&mut self, f: &mut Frame, idx: u16) -> Result<(), JvmError> {
         let class = &self.classes[f.class_idx];
         match &class.constant_pool[idx as usize] {
             CpEntry::Integer(v) => f.push(JvmValue::Int(*v)),
@@ -929,7 +784,8 @@ impl<N: NativeBridge> Vm<N> {
                 f.push(JvmValue::StringRef(String::from(s)));
             }
             CpEntry::Class { .. } => {
-                f.push(JvmValue::Null);
+                let name = String::from(class.get_class_name(idx)?);
+                f.push(JvmValue::ObjectRef(self.class_object(&name)?));
             }
             _ => {
                 return Err(JvmError::ClassFormatError(format!(
@@ -941,3 +797,122 @@ impl<N: NativeBridge> Vm<N> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use shared::classfile::write::{
+        ClassBuilder,
+        CodeBuilder,
+    };
+    use shared::classfile::{
+        ACC_PUBLIC,
+        ACC_STATIC,
+    };
+
+    use super::*;
+    use crate::native::NoopNatives;
+
+    // One static method per conversion, each loading its argument, narrowing
+    // it and returning the result.
+    fn narrowing_vm() -> Result<Vm<NoopNatives>, JvmError> {
+        let mut class = ClassBuilder::new("Narrow", "java/lang/Object")?;
+        let methods = [
+            ("f2i", "(F)I", FLOAD_0, F2I, IRETURN),
+            ("f2l", "(F)J", FLOAD_0, F2L, LRETURN),
+            ("d2i", "(D)I", DLOAD_0, D2I, IRETURN),
+            ("d2l", "(D)J", DLOAD_0, D2L, LRETURN),
+        ];
+        for (name, descriptor, load, convert, ret) in methods {
+            let mut code = CodeBuilder::new(2, 2);
+            code.op(load).op(convert).op(ret);
+            class.method(
+                ACC_PUBLIC | ACC_STATIC,
+                name,
+                descriptor,
+                Some(code.finish()?),
+            )?;
+        }
+        let mut vm = Vm::new(NoopNatives);
+        vm.load_class(class.build()?)?;
+        Ok(vm)
+    }
+
+    fn run(
+        vm: &mut Vm<NoopNatives>,
+        name: &str,
+        descriptor: &str,
+        arg: JvmValue,
+    ) -> Option<JvmValue> {
+        vm.execute_method("Narrow", name, descriptor, alloc::vec![arg])
+            .unwrap()
+    }
+
+    // JLS 5.1.3: NaN becomes 0, and anything past the target's range,
+    // infinities included, its nearest bound.
+    #[test]
+    fn float_to_integer_narrowing() -> Result<(), JvmError> {
+        let mut vm = narrowing_vm()?;
+        let floats = [
+            (f32::NAN, 0, 0),
+            (f32::INFINITY, i32::MAX, i64::MAX),
+            (f32::NEG_INFINITY, i32::MIN, i64::MIN),
+            (1e20, i32::MAX, i64::MAX),
+            (-1e20, i32::MIN, i64::MIN),
+            (f32::MAX, i32::MAX, i64::MAX),
+            (f32::MIN, i32::MIN, i64::MIN),
+            (-2.9, -2, -2),
+        ];
+        for (v, int, long) in floats {
+            let arg = JvmValue::Float(v);
+            assert_eq!(
+                run(&mut vm, "f2i", "(F)I", arg.clone()),
+                Some(JvmValue::Int(int)),
+                "f2i {}",
+                v
+            );
+            assert_eq!(
+                run(&mut vm, "f2l", "(F)J", arg),
+                Some(JvmValue::Long(long)),
+                "f2l {}",
+                v
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn double_to_integer_narrowing() -> Result<(), JvmError> {
+        let mut vm = narrowing_vm()?;
+        let doubles = [
+            (f64::NAN, 0, 0),
+            (f64::INFINITY, i32::MAX, i64::MAX),
+            (f64::NEG_INFINITY, i32::MIN, i64::MIN),
+            (1e20, i32::MAX, i64::MAX),
+            (-1e20, i32::MIN, i64::MIN),
+            (f64::MAX, i32::MAX, i64::MAX),
+            (f64::MIN, i32::MIN, i64::MIN),
+            // Just inside and just outside the int range.
+            (2147483647.9, i32::MAX, 2147483647),
+            (2147483648.0, i32::MAX, 2147483648),
+            (-2147483648.9, i32::MIN, -2147483648),
+            (-2147483649.0, i32::MIN, -2147483649),
+            (-2.9, -2, -2),
+        ];
+        for (v, int, long) in doubles {
+            let arg = JvmValue::Double(v);
+            assert_eq!(
+                run(&mut vm, "d2i", "(D)I", arg.clone()),
+                Some(JvmValue::Int(int)),
+                "d2i {}",
+                v
+            );
+            assert_eq!(
+                run(&mut vm, "d2l", "(D)J", arg),
+                Some(JvmValue::Long(long)),
+                "d2l {}",
+                v
+            );
+        }
+        Ok(())
+    }
+}
@@ -3,56 +3,58 @@ use alloc::string::String;
 use alloc::vec::Vec;
 
 use shared::classfile::{
-    self,
+    ACC_PRIVATE,
     CpEntry,
 };
-use shared::opcodes::INVOKESTATIC;
+use shared::descriptor::MethodDescriptor;
+use shared::disasm;
+use shared::opcodes::{
+    INVOKESTATIC,
+    INVOKEVIRTUAL,
+};
 use shared::types::{
     JvmError,
     JvmValue,
 };
 
+use super::resolve::MemberRef;
 use super::{
     Frame,
     Vm,
+    fits,
     jvm_value_to_string,
 };
 use crate::native::NativeBridge;
+use crate::observer::ExecutionObserver;
 
-impl<N: NativeBridge> Vm<N> {
+impl<N: NativeBridge, O: ExecutionObserver> Vm<N, O> {
     pub(crate) fn do_getstatic(&mut self, f: &mut Frame, idx: u16) -> Result<(), JvmError> {
-        let class = &self.classes[f.class_idx];
-        if let CpEntry::Fieldref {
-            class_index,
-            name_and_type_index,
-        } = &class.constant_pool[idx as usize]
-        {
-            let class_name = class.get_class_name(*class_index)?;
-            let (field_name, _desc) = class.resolve_name_and_type(*name_and_type_index)?;
-
-            if class_name == "java/lang/System" && field_name == "out" {
-                let id = self
-                    .heap
-                    .alloc_object(String::from("java/io/PrintStream"))?;
-                f.push(JvmValue::ObjectRef(id));
-            } else if class_name == "java/lang/System" && field_name == "err" {
-                let id = self
-                    .heap
-                    .alloc_object(String::from("java/io/PrintStream"))?;
-                f.push(JvmValue::ObjectRef(id));
-            } else {
-                let key = format!("{}.{}", class_name, field_name);
-                if let Some(val) = self.statics.get(&key) {
-                    f.push(val.clone());
-                } else {
-                    let result = self.natives.call_native(
-                        class_name,
-                        &format!("getstatic_{}", field_name),
-                        "",
-                        &[],
-                    )?;
-                    f.push(result.unwrap_or(JvmValue::Null));
-                }
+        let r = self.member_ref(f.class_idx, idx)?;
+
+        if r.class_name == "java/lang/System" && (r.name == "out" || r.name == "err") {
+            let id = self
+                .heap
+                .alloc_object(String::from("java/io/PrintStream"))?;
+            f.push(JvmValue::ObjectRef(id));
+        } else if let Some(val) = self.statics.get(&r.static_key) {
+            f.push(val.clone());
+        } else if r.name == "$assertionsDisabled" {
+            // Stands in for the static initializer javac emits for `assert`.
+            f.push(JvmValue::Int(!self.assertions as i32));
+        } else {
+            let result = self.native(&r.class_name, &format!("getstatic_{}", r.name), "", &[])?;
+            f.push(result.unwrap_or(JvmValue::Null));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn do_putstatic(&mut self, f: &mut Frame, idx: u16) -> Result<(), JvmError> {
+        let val = f.pop()?;
+        let r = self.member_ref(f.class_idx, idx)?;
+        match self.statics.get_mut(&r.static_key) {
+            Some(slot) => *slot = val,
+            None => {
+                self.statics.insert(r.static_key.clone(), val);
             }
         }
         Ok(())
@@ -60,37 +62,33 @@ impl<N: NativeBridge> Vm<N> {
 
     pub(crate) fn do_getfield(&mut self, f: &mut Frame, idx: u16) -> Result<(), JvmError> {
         let obj_ref = f.pop()?.as_object_ref()?;
-        let class = &self.classes[f.class_idx];
-        if let CpEntry::Fieldref {
-            name_and_type_index,
-            ..
-        } = &class.constant_pool[idx as usize]
-        {
-            let (field_name, _) = class.resolve_name_and_type(*name_and_type_index)?;
-            let obj = self.heap.get_object(obj_ref)?;
-            let val = obj
-                .fields
-                .get(field_name)
-                .cloned()
-                .unwrap_or(JvmValue::Int(0));
-            f.push(val);
-        }
+        let r = self.member_ref(f.class_idx, idx)?;
+        let obj = self.heap.get_object(obj_ref)?;
+        // Objects the VM makes itself only carry the fields it sets.
+        let val = match obj.fields.get(&r.name) {
+            Some(v) => v.clone(),
+            None => JvmValue::default_for(&r.descriptor),
+        };
+        f.push(val);
         Ok(())
     }
 
     pub(crate) fn do_putfield(&mut self, f: &mut Frame, idx: u16) -> Result<(), JvmError> {
         let val = f.pop()?;
         let obj_ref = f.pop()?.as_object_ref()?;
-        let class = &self.classes[f.class_idx];
-        if let CpEntry::Fieldref {
-            name_and_type_index,
-            ..
-        } = &class.constant_pool[idx as usize]
-        {
-            let (field_name, _) = class.resolve_name_and_type(*name_and_type_index)?;
-            let field_owned = String::from(field_name);
-            let obj = self.heap.get_object_mut(obj_ref)?;
-            obj.fields.insert(field_owned, val);
+        let r = self.member_ref(f.class_idx, idx)?;
+        if !fits(&r.descriptor, &val) {
+            return Err(JvmError::TypeError(format!(
+                "{}.{} is {}, got {:?}",
+                r.class_name, r.name, r.descriptor, val
+            )));
+        }
+        let obj = self.heap.get_object_mut(obj_ref)?;
+        match obj.fields.get_mut(&r.name) {
+            Some(slot) => *slot = val,
+            None => {
+                obj.fields.insert(r.name.clone(), val);
+            }
         }
         Ok(())
     }
@@ -116,7 +114,7 @@ impl<N: NativeBridge> Vm<N> {
         let descriptor = String::from(descriptor);
 
         if method_name == "makeConcatWithConstants" {
-            let arg_count = classfile::count_descriptor_args(&descriptor);
+            let arg_count = MethodDescriptor::parse(&descriptor)?.params.len();
             let mut args = Vec::with_capacity(arg_count);
             for _ in 0..arg_count {
                 args.push(f.pop()?);
@@ -130,7 +128,7 @@ impl<N: NativeBridge> Vm<N> {
                         CpEntry::StringRef { string_index } => {
                             String::from(class.get_utf8(*string_index).unwrap_or(""))
                         }
-                        CpEntry::Utf8(s) => s.clone(),
+                        CpEntry::Utf8(s) => String::from(&**s),
                         _ => String::new(),
                     }
                 } else {
@@ -152,6 +150,7 @@ impl<N: NativeBridge> Vm<N> {
             for arg in arg_iter {
                 result.push_str(&jvm_value_to_string(arg));
             }
+            self.check_string(&result)?;
 
             f.push(JvmValue::StringRef(result));
             Ok(())
@@ -160,33 +159,36 @@ impl<N: NativeBridge> Vm<N> {
         }
     }
 
-    pub(crate) fn do_invoke(&mut self, f: &mut Frame, op: u8, idx: u16) -> Result<(), JvmError> {
-        let (class_name, method_name, descriptor) = {
-            let class = &self.classes[f.class_idx];
-            let (ci, nti) = match &class.constant_pool[idx as usize] {
-                CpEntry::Methodref {
-                    class_index,
-                    name_and_type_index,
-                } => (*class_index, *name_and_type_index),
-                CpEntry::InterfaceMethodref {
-                    class_index,
-                    name_and_type_index,
-                } => (*class_index, *name_and_type_index),
-                _ => {
-                    return Err(JvmError::ClassFormatError(format!(
-                        "expected Methodref at cp#{}",
-                        idx
-                    )));
-                }
-            };
-            let cn = String::from(class.get_class_name(ci)?);
-            let (mn, desc) = class.resolve_name_and_type(nti)?;
-            (cn, String::from(mn), String::from(desc))
+    // Picks the override on the receiver's runtime class, if it is a loaded
+    // class other than the one named at the call site.
+    fn virtual_target(
+        &self,
+        r: &MemberRef,
+        args: &[JvmValue],
+    ) -> Result<Option<(usize, usize)>, JvmError> {
+        let Some(JvmValue::ObjectRef(id)) = args.first() else {
+            return Ok(None);
         };
+        let runtime = &self.heap.get_object(*id)?.class_name;
+        if *runtime == r.class_name {
+            return Ok(None);
+        }
+        Ok(self
+            .find_class_index(runtime)
+            .and_then(|ci| self.find_virtual(ci, &r.name, &r.descriptor)))
+    }
+
+    pub(crate) fn do_invoke(&mut self, f: &mut Frame, op: u8, idx: u16) -> Result<(), JvmError> {
+        if let Some(call) = self.calls.last_mut() {
+            call.pc = f.code.offsets[f.op_pc] as usize;
+        }
+        let r = self.member_ref(f.class_idx, idx)?;
+        let class_name = r.class_name.as_str();
+        let method_name = r.name.as_str();
+        let descriptor = r.descriptor.as_str();
 
-        let arg_count = classfile::count_descriptor_args(&descriptor);
         let has_receiver = op != INVOKESTATIC;
-        let total = arg_count + if has_receiver { 1 } else { 0 };
+        let total = r.arg_count + if has_receiver { 1 } else { 0 };
 
         let mut args = Vec::with_capacity(total);
         for _ in 0..total {
@@ -195,11 +197,65 @@ impl<N: NativeBridge> Vm<N> {
         args.reverse();
 
         // System methods
-        if class_name == "java/lang/System" && method_name == "exit" {
-            let code = args.first().and_then(|v| v.as_int().ok()).unwrap_or(0);
+        let runtime = class_name == "java/lang/Runtime";
+        if (class_name == "java/lang/System" || runtime) && method_name == "exit"
+            || runtime && method_name == "halt"
+        {
+            // The status is the last argument; Runtime's come after the
+            // receiver. halt skips the exit hook as it skips shutdown hooks.
+            let code = args.last().and_then(|v| v.as_int().ok()).unwrap_or(0);
+            if method_name == "exit"
+                && let Some(hook) = &mut self.exit_hook
+            {
+                hook(&mut self.natives, code);
+            }
             return Err(JvmError::SystemExit(code));
         }
 
+        if runtime && method_name == "getRuntime" {
+            let id = self.heap.alloc_object(String::from("java/lang/Runtime"))?;
+            f.push(JvmValue::ObjectRef(id));
+            return Ok(());
+        }
+
+        if class_name == "java/lang/Class" && method_name == "desiredAssertionStatus" {
+            f.push(JvmValue::Int(self.assertions as i32));
+            return Ok(());
+        }
+
+        // Object.getClass, final and so never a loaded class's own method.
+        if method_name == "getClass" && descriptor == "()Ljava/lang/Class;" {
+            let runtime = match args.first() {
+                Some(JvmValue::ObjectRef(id)) => self.heap.get_object(*id)?.class_name.clone(),
+                Some(JvmValue::StringRef(_)) => String::from("java/lang/String"),
+                Some(JvmValue::Null) | None => return Err(JvmError::NullPointerException),
+                Some(_) => String::from("java/lang/Object"),
+            };
+            f.push(JvmValue::ObjectRef(self.class_object(&runtime)?));
+            return Ok(());
+        }
+
+        if class_name == "java/lang/Class" {
+            if let Some(val) = self.handle_class(method_name, &args)? {
+                f.push(val);
+            }
+            return Ok(());
+        }
+
+        if class_name == "java/net/URL" {
+            if let Some(val) = self.handle_url(method_name, &args)? {
+                f.push(val);
+            }
+            return Ok(());
+        }
+
+        if class_name == "java/io/InputStream" || class_name == "java/io/ByteArrayInputStream" {
+            if let Some(val) = self.handle_input_stream(method_name, descriptor, &args)? {
+                f.push(val);
+            }
+            return Ok(());
+        }
+
         if class_name == "java/lang/System" && method_name == "currentTimeMillis" {
             f.push(JvmValue::Long(0));
             return Ok(());
@@ -217,9 +273,68 @@ impl<N: NativeBridge> Vm<N> {
                     src.elements[src_pos..src_pos + length].to_vec()
                 };
                 let dst = self.heap.get_array_mut(dst_ref)?;
-                for i in 0..length {
-                    dst.elements[dst_pos + i] = values[i].clone();
+                dst.elements[dst_pos..dst_pos + length].clone_from_slice(&values);
+            }
+            return Ok(());
+        }
+
+        // The dump is rendered by the VM, which knows the heap's layout;
+        // only writing it out is left to the bridge.
+        if class_name == "efi/Debug" && method_name == "dumpHeap" {
+            let text = format!("{}", self.heap.dump());
+            self.natives.call_native(
+                &mut self.heap,
+                class_name,
+                "writeHeapDump",
+                "(Ljava/lang/String;)V",
+                &[JvmValue::StringRef(text)],
+            )?;
+            return Ok(());
+        }
+
+        if class_name == "efi/Debug" && method_name == "disassemble" {
+            let text = match args.first() {
+                Some(JvmValue::StringRef(name)) => {
+                    self.class(&name.replace('.', "/")).map(disasm::disassemble)
                 }
+                _ => return Err(JvmError::NullPointerException),
+            };
+            f.push(text.map_or(JvmValue::Null, JvmValue::StringRef));
+            return Ok(());
+        }
+
+        if class_name == "efi/Debug" && method_name == "heapSummary" {
+            f.push(JvmValue::StringRef(self.heap.dump().summary()));
+            return Ok(());
+        }
+
+        // Same split for the profiler; with profiling off there is nothing
+        // to write.
+        if class_name == "efi/Debug" && method_name == "writeProfile" {
+            if let Some(report) = self.profile() {
+                self.natives.call_native(
+                    &mut self.heap,
+                    class_name,
+                    "writeProfile",
+                    "(Ljava/lang/String;)V",
+                    &[JvmValue::StringRef(format!("{}", report))],
+                )?;
+            }
+            return Ok(());
+        }
+
+        if class_name == "efi/Debug" && method_name == "profileSummary" {
+            let summary = match self.profile() {
+                Some(report) => report.summary(),
+                None => String::from("profiling off"),
+            };
+            f.push(JvmValue::StringRef(summary));
+            return Ok(());
+        }
+
+        if class_name == "efi/Reflect" {
+            if let Some(val) = self.handle_reflect(method_name, &args)? {
+                f.push(val);
             }
             return Ok(());
         }
@@ -229,8 +344,13 @@ impl<N: NativeBridge> Vm<N> {
             && (method_name == "println" || method_name == "print")
         {
             let print_args = if has_receiver { &args[1..] } else { &args };
-            self.natives
-                .call_native("efi/Console", &method_name, &descriptor, print_args)?;
+            self.natives.call_native(
+                &mut self.heap,
+                "efi/Console",
+                method_name,
+                descriptor,
+                print_args,
+            )?;
             return Ok(());
         }
 
@@ -248,6 +368,7 @@ impl<N: NativeBridge> Vm<N> {
                 };
                 let result = self.do_string_format(fmt, &arr_vals)?;
                 self.natives.call_native(
+                    &mut self.heap,
                     "efi/Console",
                     "print",
                     "(Ljava/lang/String;)V",
@@ -262,7 +383,7 @@ impl<N: NativeBridge> Vm<N> {
 
         // StringBuilder
         if class_name == "java/lang/StringBuilder" {
-            let result = self.handle_string_builder(&method_name, &descriptor, &args)?;
+            let result = self.handle_string_builder(method_name, descriptor, &args)?;
             if let Some(val) = result {
                 f.push(val);
             }
@@ -270,47 +391,96 @@ impl<N: NativeBridge> Vm<N> {
         }
 
         // String methods
-        if class_name == "java/lang/String" {
-            if self.handle_string_method(f, &method_name, &descriptor, &args)? {
-                return Ok(());
-            }
+        if class_name == "java/lang/String"
+            && self.handle_string_method(f, method_name, descriptor, &args)?
+        {
+            return Ok(());
         }
 
         // Integer methods
-        if class_name == "java/lang/Integer" {
-            if self.handle_integer_method(f, &method_name, &descriptor, &args)? {
-                return Ok(());
-            }
+        if class_name == "java/lang/Integer"
+            && self.handle_integer_method(f, method_name, descriptor, &args)?
+        {
+            return Ok(());
+        }
+
+        // Long methods
+        if class_name == "java/lang/Long" && self.handle_long_method(f, method_name, &args)? {
+            return Ok(());
         }
 
         // Boxing (Boolean, Byte, Short, Character, Long)
-        if self.handle_boxing(f, &class_name, &method_name, &args)? {
+        if self.handle_boxing(f, class_name, method_name, &args)? {
             return Ok(());
         }
 
         // Math
         if class_name == "java/lang/Math" {
-            return self.handle_math(f, &method_name, &args);
+            return self.handle_math(f, method_name, &args);
+        }
+
+        // Throwable, for exception classes that don't override the method
+        if r.method.is_none() && self.is_throwable(class_name) {
+            let overridden = op == INVOKEVIRTUAL && self.virtual_target(&r, &args)?.is_some();
+            if !overridden
+                && let Some(result) = self.handle_throwable(method_name, descriptor, &args)?
+            {
+                if let Some(val) = result {
+                    f.push(val);
+                }
+                return Ok(());
+            }
+        }
+
+        if class_name == "java/lang/StackTraceElement" {
+            if let Some(val) = self.handle_stack_trace_element(method_name, &args)? {
+                f.push(val);
+            }
+            return Ok(());
         }
 
         // Unknown <init> — skip
-        if method_name == "<init>" && self.find_class_index(&class_name).is_none() {
+        if method_name == "<init>" && r.class_idx.is_none() {
             return Ok(());
         }
 
-        // Generic dispatch
-        if self.find_class_index(&class_name).is_some() {
-            let result = self.execute(&class_name, &method_name, args)?;
-            if let Some(val) = result {
-                f.push(val);
+        // Generic dispatch. Private methods are only reachable from the
+        // class's nest, and aren't overridden, so they bind statically.
+        let private = match r.method {
+            Some((ci, mi)) if self.classes[ci].methods[mi].access_flags & ACC_PRIVATE != 0 => {
+                if !self.is_nestmate(f.class_idx, ci) {
+                    return Err(JvmError::IllegalAccess(format!(
+                        "class {} tried to access private method {}.{}{}",
+                        self.classes[f.class_idx].class_name()?,
+                        class_name,
+                        method_name,
+                        descriptor
+                    )));
+                }
+                true
             }
+            _ => false,
+        };
+        let target = if op == INVOKEVIRTUAL && !private {
+            self.virtual_target(&r, &args)?.or(r.method)
         } else {
-            let result = self
-                .natives
-                .call_native(&class_name, &method_name, &descriptor, &args)?;
-            if let Some(val) = result {
-                f.push(val);
+            r.method
+        };
+        let result = match (r.class_idx, target) {
+            (_, Some((ci, mi))) => self.invoke_method(ci, mi, args)?,
+            (Some(_), None) => {
+                return Err(JvmError::MethodNotFound(format!(
+                    "{}::{}",
+                    class_name, method_name
+                )));
             }
+            (None, _) => self.call_bridge(class_name, method_name, descriptor, &args)?,
+        };
+        // The descriptor, not the callee, says whether a value comes back:
+        // a bridge may answer a void call with a value, or give nothing
+        // for a method it only stubs.
+        if let Some(ty) = &r.result {
+            f.push(result.unwrap_or_else(|| ty.default_value()));
         }
 
         Ok(())
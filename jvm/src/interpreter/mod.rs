@@ -1,71 +1,94 @@
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
 use alloc::format;
+use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::vec::Vec;
 
 use shared::classfile::{
+    self,
     ACC_NATIVE,
+    ACC_STATIC,
     ClassFile,
-    ExceptionTableEntry,
+    ClassParser,
 };
+use shared::descriptor::MethodDescriptor;
 use shared::types::{
     JvmError,
     JvmValue,
 };
+use shared::zip::{
+    DEFAULT_RELEASE,
+    ZipArchive,
+    ZipArchiveOwned,
+};
 
+use self::decode::Code;
+use self::resolve::{
+    MemberRef,
+    MethodIndex,
+};
+use crate::builder::Limits;
 use crate::heap::Heap;
 use crate::native::NativeBridge;
+use crate::observer::{
+    ExecutionObserver,
+    FrameView,
+    Location,
+    NoObserver,
+};
+use crate::profile::{
+    ProfileReport,
+    Profiler,
+};
+use crate::replay::{
+    Determinism,
+    NativeLog,
+};
+use crate::trace::{
+    FrameSnapshot,
+    UncaughtException,
+    VmFault,
+};
 
 mod builtins;
+mod decode;
 mod exec;
 mod invoke;
+mod reflect;
+mod resolve;
+mod resources;
+mod throwable;
+
+// Where a live frame is. `pc` is only brought up to date when the frame
+// calls out or throws, which is all a stack trace needs.
+#[derive(Clone, Copy)]
+pub(crate) struct CallSite {
+    pub(crate) class_idx: usize,
+    pub(crate) method_idx: usize,
+    pub(crate) pc: usize,
+}
 
 pub(crate) enum ExecAction {
     Continue,
     ReturnVal(JvmValue),
     ReturnVoid,
-    Throw(String, JvmValue),
 }
 
 pub(crate) struct Frame {
     pub(crate) stack: Vec<JvmValue>,
     pub(crate) locals: Vec<JvmValue>,
-    pub(crate) code: Vec<u8>,
+    pub(crate) code: Rc<Code>,
     pub(crate) pc: usize,
     pub(crate) class_idx: usize,
-    pub(crate) exception_table: Vec<ExceptionTableEntry>,
+    pub(crate) method_idx: usize,
+    pub(crate) executed: u64,
+    // Index of the instruction being run, kept for stack traces.
+    pub(crate) op_pc: usize,
 }
 
 impl Frame {
-    pub(crate) fn read_u8(&mut self) -> u8 {
-        let v = self.code[self.pc];
-        self.pc += 1;
-        v
-    }
-
-    pub(crate) fn read_i16(&mut self) -> i16 {
-        let hi = self.code[self.pc] as i16;
-        let lo = self.code[self.pc + 1] as i16;
-        self.pc += 2;
-        (hi << 8) | (lo & 0xFF)
-    }
-
-    pub(crate) fn read_u16(&mut self) -> u16 {
-        let hi = self.code[self.pc] as u16;
-        let lo = self.code[self.pc + 1] as u16;
-        self.pc += 2;
-        (hi << 8) | lo
-    }
-
-    pub(crate) fn read_i32(&mut self) -> i32 {
-        let b1 = self.code[self.pc] as i32;
-        let b2 = self.code[self.pc + 1] as i32;
-        let b3 = self.code[self.pc + 2] as i32;
-        let b4 = self.code[self.pc + 3] as i32;
-        self.pc += 4;
-        (b1 << 24) | (b2 << 16) | (b3 << 8) | b4
-    }
-
     pub(crate) fn push(&mut self, val: JvmValue) {
         self.stack.push(val);
     }
@@ -101,31 +124,435 @@ impl Frame {
     }
 }
 
-pub struct Vm<N: NativeBridge> {
+/// How a call made through [`Vm::run`] ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Returned(Option<JvmValue>),
+    Exited(i32),
+}
+
+type UncaughtHandler<N> = Box<dyn FnMut(&mut N, &UncaughtException)>;
+type ExitHook<N> = Box<dyn FnMut(&mut N, i32)>;
+
+pub struct Vm<N: NativeBridge, O: ExecutionObserver = NoObserver> {
     pub(crate) classes: Vec<ClassFile>,
+    pub(crate) class_map: BTreeMap<String, usize>,
+    pub(crate) method_index: Vec<MethodIndex>,
     pub heap: Heap,
     pub natives: N,
+    pub observer: O,
     pub(crate) statics: BTreeMap<String, JvmValue>,
+    pub(crate) limits: Limits,
+    // One entry per live Java frame, innermost last; exceptions read their
+    // stack trace off it.
+    pub(crate) calls: Vec<CallSite>,
+    // Address of a local in the outermost call_method, for max_stack_bytes.
+    pub(crate) stack_base: usize,
+    pub(crate) branches: u64,
+    pub(crate) assertions: bool,
+    pub(crate) release: u16,
+    // Shared by every class parsed through the VM; see Vm::parse_class.
+    pub(crate) parser: ClassParser,
+    // JARs kept for Class.getResource, and the one each class came from.
+    pub(crate) archives: Vec<ZipArchiveOwned>,
+    pub(crate) class_archives: BTreeMap<String, usize>,
+    pub(crate) class_objects: BTreeMap<String, u32>,
+    pub(crate) profiler: Option<Profiler>,
+    pub(crate) instructions: u64,
+    pub(crate) determinism: Option<Determinism>,
+    pub(crate) unwound: Vec<FrameSnapshot>,
+    pub(crate) code_cache: BTreeMap<(usize, usize), Rc<Code>>,
+    pub(crate) cp_cache: Vec<Vec<Option<Rc<MemberRef>>>>,
+    pub(crate) uncaught_handler: Option<UncaughtHandler<N>>,
+    pub(crate) exit_hook: Option<ExitHook<N>>,
 }
 
 impl<N: NativeBridge> Vm<N> {
     pub fn new(natives: N) -> Self {
+        Self::with_observer(natives, NoObserver)
+    }
+}
+
+impl<N: NativeBridge, O: ExecutionObserver> Vm<N, O> {
+    pub fn with_observer(natives: N, observer: O) -> Self {
         Self {
             classes: Vec::new(),
+            class_map: BTreeMap::new(),
+            method_index: Vec::new(),
             heap: Heap::new(),
             natives,
+            observer,
             statics: BTreeMap::new(),
+            limits: Limits::default(),
+            calls: Vec::new(),
+            stack_base: 0,
+            branches: 0,
+            assertions: false,
+            release: DEFAULT_RELEASE,
+            parser: ClassParser::new(),
+            archives: Vec::new(),
+            class_archives: BTreeMap::new(),
+            class_objects: BTreeMap::new(),
+            profiler: None,
+            instructions: 0,
+            determinism: None,
+            unwound: Vec::new(),
+            code_cache: BTreeMap::new(),
+            cp_cache: Vec::new(),
+            uncaught_handler: None,
+            exit_hook: None,
+        }
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Turns the method profiler on, dropping any earlier counts, or off.
+    pub fn set_profiling(&mut self, on: bool) {
+        self.profiler = on.then(Profiler::default);
+    }
+
+    /// The profile gathered so far, or `None` while profiling is off.
+    pub fn profile(&self) -> Option<ProfileReport> {
+        let profiler = self.profiler.as_ref()?;
+        Some(profiler.report(|ci, mi| {
+            let at = location(&self.classes, ci, mi, 0);
+            format!("{}.{}{}", at.class_name, at.method_name, at.descriptor)
+        }))
+    }
+
+    /// The Java frames that were live when the last call into the VM failed,
+    /// innermost first, for printing a stack trace next to the
+    /// [`JvmError`]. Empty after a call that returned or exited.
+    pub fn frames(&self) -> &[FrameSnapshot] {
+        &self.unwound
+    }
+
+    /// Pairs an error a call into the VM returned with the frames it came
+    /// through; see [`VmFault`].
+    pub fn fault(&self, error: JvmError) -> VmFault {
+        VmFault {
+            error,
+            frames: self.unwound.clone(),
         }
     }
 
-    pub fn load_class(&mut self, class: ClassFile) {
+    /// Turns `assert` statements on or off, as `-ea` does, for every class.
+    /// javac guards each one with a `$assertionsDisabled` static set from
+    /// `desiredAssertionStatus`; both answer from this flag.
+    pub fn set_assertions(&mut self, on: bool) {
+        self.assertions = on;
+    }
+
+    /// The Java release [`Vm::load_jar`] reads multi-release JARs as; see
+    /// [`ZipArchive::class_entries_for`].
+    pub fn set_release(&mut self, release: u16) {
+        self.release = release;
+    }
+
+    pub fn release(&self) -> u16 {
+        self.release
+    }
+
+    /// Has `handler` called, with the natives, for every exception that
+    /// escapes [`Vm::execute`] or [`Vm::execute_method`], before the error is
+    /// returned; the frames it unwound are still in [`Vm::frames`].
+    pub fn set_uncaught_handler(
+        &mut self,
+        handler: impl FnMut(&mut N, &UncaughtException) + 'static,
+    ) {
+        self.uncaught_handler = Some(Box::new(handler));
+    }
+
+    /// Has `hook` called, with the natives and the status, when Java calls
+    /// `System.exit` or `Runtime.exit`, before the run ends with
+    /// [`Outcome::Exited`]; the place to flush what the natives hold.
+    /// `Runtime.halt` ends the run without it.
+    pub fn set_exit_hook(&mut self, hook: impl FnMut(&mut N, i32) + 'static) {
+        self.exit_hook = Some(Box::new(hook));
+    }
+
+    /// Instructions executed since the VM was created.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Turns deterministic mode on, starting a fresh [`NativeLog`], or off.
+    /// While it is on, every native the bridge calls
+    /// [`nondeterministic`](NativeBridge::nondeterministic) is logged with
+    /// the instruction count it was made at.
+    pub fn set_deterministic(&mut self, on: bool) {
+        self.determinism = on.then(Determinism::record);
+    }
+
+    /// Turns deterministic mode on and feeds `log` back in: each
+    /// nondeterministic native returns its logged value instead of reaching
+    /// the bridge. A call that doesn't match the next entry fails the run;
+    /// once the log runs out, calls go to the bridge and are recorded again.
+    pub fn replay(&mut self, log: NativeLog) {
+        self.determinism = Some(Determinism::replay(log));
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.determinism.is_some()
+    }
+
+    /// The calls logged so far, or `None` outside deterministic mode.
+    pub fn native_log(&self) -> Option<&NativeLog> {
+        self.determinism.as_ref().map(|d| &d.log)
+    }
+
+    /// Adds a class, after [`ClassFile::validate`] has passed it. A module
+    /// descriptor is checked and then dropped, there being no module system
+    /// to hand it to.
+    pub fn load_class(&mut self, class: ClassFile) -> Result<(), JvmError> {
+        class.validate()?;
+        if class.is_module() {
+            return Ok(());
+        }
+        let idx = self.classes.len();
+        // The first class loaded under a name wins, as with a linear scan.
+        if let Ok(name) = class.class_name() {
+            self.class_map.entry(String::from(name)).or_insert(idx);
+        }
+        self.method_index.push(MethodIndex::build(&class));
         self.classes.push(class);
+        // Resolved refs may have missed the new class; resolve them again.
+        self.cp_cache.clear();
+        Ok(())
+    }
+
+    /// Swaps in a new version of an already loaded class, or loads it if
+    /// there is none. Meant for between runs: a frame still inside the old
+    /// class would go on resolving its constant pool against the new one.
+    /// Statics are kept when the new class still declares a static field of
+    /// that name whose type fits the current value, and dropped otherwise.
+    /// Objects already on the heap are left as they are.
+    pub fn redefine_class(&mut self, class: ClassFile) -> Result<(), JvmError> {
+        if class.is_module() {
+            return self.load_class(class);
+        }
+        let name = String::from(class.class_name()?);
+        let Some(idx) = self.find_class_index(&name) else {
+            return self.load_class(class);
+        };
+        class.validate()?;
+        let mut kept = BTreeMap::new();
+        for field in &class.fields {
+            if field.access_flags & classfile::ACC_STATIC == 0 {
+                continue;
+            }
+            let key = format!("{}.{}", name, class.get_utf8(field.name_index)?);
+            let descriptor = class.get_utf8(field.descriptor_index)?;
+            if let Some(value) = self.statics.get(&key)
+                && fits(descriptor, value)
+            {
+                kept.insert(key, value.clone());
+            }
+        }
+        let prefix = format!("{}.", name);
+        self.statics.retain(|key, _| !key.starts_with(&prefix));
+        self.statics.append(&mut kept);
+
+        self.method_index[idx] = MethodIndex::build(&class);
+        self.classes[idx] = class;
+        self.code_cache.retain(|&(c, _), _| c != idx);
+        self.cp_cache.clear();
+        Ok(())
+    }
+
+    /// Parses a class file for loading here. Its Utf8 constants come from
+    /// a pool kept across every class parsed this way, so names that many
+    /// classes repeat are held once. Bytes handed over owned are kept for
+    /// the methods' code instead of copying it out.
+    pub fn parse_class<'d>(
+        &mut self,
+        data: impl Into<Cow<'d, [u8]>>,
+    ) -> Result<ClassFile, JvmError> {
+        match data.into() {
+            Cow::Borrowed(data) => self.parser.parse(data),
+            Cow::Owned(data) => self.parser.parse_owned(data),
+        }
+    }
+
+    /// Has [`Vm::parse_class`] and [`Vm::load_jar`] keep going past what
+    /// they can't read, as [`ClassParser::lenient`] describes; what they let
+    /// through is in [`Vm::take_parse_warnings`]. In a JAR, a class that
+    /// fails anyway is left out rather than failing the JAR.
+    pub fn set_lenient_parsing(&mut self, on: bool) {
+        self.parser.lenient = on;
+    }
+
+    pub fn take_parse_warnings(&mut self) -> Vec<String> {
+        self.parser.take_warnings()
+    }
+
+    /// Loads every class in a JAR, and in the JARs stored inside it one
+    /// level down, and returns how many were added. The JARs are kept, as
+    /// [`Vm::add_archive`] keeps them, for their resources.
+    pub fn load_jar(&mut self, data: &[u8]) -> Result<usize, JvmError> {
+        let archive = ZipArchive::new(data)?;
+        let mut loaded = 0;
+        for entry in archive.nested_jars() {
+            let nested = archive.open_nested(entry)?.into_owned();
+            let names = self.load_archive(&nested)?;
+            loaded += names.len();
+            self.add_archive(nested, names);
+        }
+        let names = self.load_archive(&archive)?;
+        loaded += names.len();
+        self.add_archive(archive.into_owned(), names);
+        Ok(loaded)
+    }
+
+    // The names of the classes it loaded.
+    fn load_archive(&mut self, archive: &ZipArchive) -> Result<Vec<String>, JvmError> {
+        let mut loaded = Vec::new();
+        for entry in archive.class_entries_for(self.release) {
+            let bytes = archive.read_entry(entry)?;
+            let class = match self.parse_class(bytes) {
+                Ok(class) => class,
+                Err(e) if self.parser.lenient => {
+                    let warning = format!("{}: left out: {}", entry.name, e);
+                    self.parser.warnings.push(warning);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let name = String::from(class.class_name()?);
+            self.load_class(class)?;
+            loaded.push(name);
+        }
+        Ok(loaded)
+    }
+
+    pub fn has_class(&self, name: &str) -> bool {
+        self.find_class_index(name).is_some()
+    }
+
+    /// Runs a static method, turning `System.exit` into [`Outcome::Exited`].
+    pub fn run(
+        &mut self,
+        class_name: &str,
+        method_name: &str,
+        args: Vec<JvmValue>,
+    ) -> Result<Outcome, JvmError> {
+        match self.execute(class_name, method_name, args) {
+            Ok(v) => Ok(Outcome::Returned(v)),
+            Err(JvmError::SystemExit(code)) => Ok(Outcome::Exited(code)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// [`Vm::run`] for the overload with the given descriptor; see
+    /// [`Vm::execute_method`].
+    pub fn run_method(
+        &mut self,
+        class_name: &str,
+        method_name: &str,
+        descriptor: &str,
+        args: Vec<JvmValue>,
+    ) -> Result<Outcome, JvmError> {
+        match self.execute_method(class_name, method_name, descriptor, args) {
+            Ok(v) => Ok(Outcome::Returned(v)),
+            Err(JvmError::SystemExit(code)) => Ok(Outcome::Exited(code)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Runs a static `void name()`.
+    pub fn call_static_void(
+        &mut self,
+        class_name: &str,
+        method_name: &str,
+    ) -> Result<Outcome, JvmError> {
+        self.run_method(class_name, method_name, "()V", Vec::new())
+    }
+
+    /// Runs `main(String[])` on `class_name` with the given arguments.
+    pub fn run_main(&mut self, class_name: &str, args: &[&str]) -> Result<Outcome, JvmError> {
+        let arr = self
+            .heap
+            .alloc_array(String::from("java/lang/String"), args.len())?;
+        {
+            let arr = self.heap.get_array_mut(arr)?;
+            for (slot, arg) in arr.elements.iter_mut().zip(args) {
+                *slot = JvmValue::StringRef(String::from(*arg));
+            }
+        }
+        self.run_method(
+            class_name,
+            "main",
+            "([Ljava/lang/String;)V",
+            alloc::vec![JvmValue::ArrayRef(arr)],
+        )
+    }
+
+    /// [`Vm::run_main`] for owned arguments.
+    pub fn call_main(&mut self, class_name: &str, args: &[String]) -> Result<Outcome, JvmError> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.run_main(class_name, &args)
     }
 
     pub(crate) fn find_class_index(&self, name: &str) -> Option<usize> {
-        self.classes
-            .iter()
-            .position(|c| c.class_name().ok() == Some(name))
+        self.class_map.get(name).copied()
+    }
+
+    // Nestmates (JVMS 5.4.4) may use each other's private members. A class
+    // that names a host the host doesn't list back is a nest of its own.
+    pub(crate) fn is_nestmate(&self, a: usize, b: usize) -> bool {
+        a == b
+            || self
+                .nest_host(a)
+                .is_some_and(|h| self.nest_host(b) == Some(h))
+    }
+
+    fn nest_host(&self, class_idx: usize) -> Option<&str> {
+        let class = &self.classes[class_idx];
+        let name = class.class_name().ok()?;
+        let host = class.nest_host_name().ok()?;
+        if host != name
+            && let Some(h) = self.find_class_index(host)
+            && !self.classes[h].lists_nest_member(name)
+        {
+            return Some(name);
+        }
+        Some(host)
+    }
+
+    // A new instance with the instance fields its class and superclasses
+    // declare at their zero values, so a field read before it's written
+    // has the declared type. The walk is bounded in case a malformed class
+    // names itself as an ancestor.
+    pub(crate) fn new_object(&mut self, class_name: &str) -> Result<u32, JvmError> {
+        let id = self.heap.alloc_object(String::from(class_name))?;
+        let mut next = self.find_class_index(class_name);
+        for _ in 0..self.classes.len() {
+            let Some(idx) = next else {
+                break;
+            };
+            let class = &self.classes[idx];
+            let obj = self.heap.get_object_mut(id)?;
+            for field in &class.fields {
+                if field.access_flags & ACC_STATIC != 0 {
+                    continue;
+                }
+                let name = class.get_utf8(field.name_index)?;
+                if !obj.fields.contains_key(name) {
+                    let value = JvmValue::default_for(class.get_utf8(field.descriptor_index)?);
+                    obj.fields.insert(String::from(name), value);
+                }
+            }
+            next = class
+                .super_class_name()
+                .and_then(|s| self.find_class_index(s));
+        }
+        Ok(id)
     }
 
     pub(crate) fn is_subclass(&self, child: &str, parent: &str) -> bool {
@@ -142,8 +569,21 @@ impl<N: NativeBridge> Vm<N> {
             "java/lang/ArrayIndexOutOfBoundsException",
             "java/lang/ClassCastException",
             "java/lang/IllegalArgumentException",
+            "java/lang/NumberFormatException",
             "java/lang/UnsupportedOperationException",
             "java/lang/IndexOutOfBoundsException",
+            "java/lang/NegativeArraySizeException",
+            "java/lang/IllegalStateException",
+            "java/lang/InterruptedException",
+            "java/io/IOException",
+        ];
+        let errors = [
+            "java/lang/Error",
+            "java/lang/VirtualMachineError",
+            "java/lang/StackOverflowError",
+            "java/lang/OutOfMemoryError",
+            "java/lang/AssertionError",
+            "java/lang/IllegalAccessError",
         ];
         if child == parent {
             return true;
@@ -151,9 +591,18 @@ impl<N: NativeBridge> Vm<N> {
         if parent == "java/lang/Object" {
             return true;
         }
-        if parent == "java/lang/Throwable" && well_known.contains(&child) {
+        if parent == "java/lang/Throwable"
+            && (well_known.contains(&child) || errors.contains(&child))
+        {
             return true;
         }
+        if parent == "java/lang/Error" && errors.contains(&child) {
+            return true;
+        }
+        if parent == "java/lang/VirtualMachineError" {
+            return child == "java/lang/StackOverflowError"
+                || child == "java/lang/OutOfMemoryError";
+        }
         if parent == "java/lang/Exception" {
             return child != "java/lang/Throwable" && well_known.contains(&child);
         }
@@ -164,8 +613,11 @@ impl<N: NativeBridge> Vm<N> {
                 "java/lang/ArrayIndexOutOfBoundsException",
                 "java/lang/ClassCastException",
                 "java/lang/IllegalArgumentException",
+                "java/lang/NumberFormatException",
                 "java/lang/UnsupportedOperationException",
                 "java/lang/IndexOutOfBoundsException",
+                "java/lang/NegativeArraySizeException",
+                "java/lang/IllegalStateException",
             ];
             return runtime_excs.contains(&child);
         }
@@ -174,11 +626,16 @@ impl<N: NativeBridge> Vm<N> {
         {
             return true;
         }
-        if let Some(idx) = self.find_class_index(child) {
-            if let Some(super_name) = self.classes[idx].super_class_name() {
-                let sn = String::from(super_name);
-                return self.is_subclass(&sn, parent);
-            }
+        if parent == "java/lang/IllegalArgumentException"
+            && child == "java/lang/NumberFormatException"
+        {
+            return true;
+        }
+        if let Some(idx) = self.find_class_index(child)
+            && let Some(super_name) = self.classes[idx].super_class_name()
+        {
+            let sn = String::from(super_name);
+            return self.is_subclass(&sn, parent);
         }
         false
     }
@@ -189,123 +646,558 @@ impl<N: NativeBridge> Vm<N> {
         method_name: &str,
         args: Vec<JvmValue>,
     ) -> Result<Option<JvmValue>, JvmError> {
+        self.branches = 0;
+        self.unwound.clear();
         let class_idx = match self.find_class_index(class_name) {
             Some(idx) => idx,
             None => {
-                return self.natives.call_native(class_name, method_name, "", &args);
+                return self.native(class_name, method_name, "", &args);
             }
         };
 
-        let class = &self.classes[class_idx];
-        let method = class
-            .find_method_by_name(method_name)
+        let method_idx = self.method_index[class_idx]
+            .find_by_name(method_name)
             .ok_or_else(|| JvmError::MethodNotFound(format!("{}::{}", class_name, method_name)))?;
 
+        let result = self.invoke_method(class_idx, method_idx, args);
+        self.report_uncaught(result)
+    }
+
+    /// Like [`Vm::execute`], but picks the overload by `descriptor` (such as
+    /// `(ILjava/lang/String;)V`) and checks `args` against it first: one
+    /// value per parameter, plus the receiver ahead of them for an instance
+    /// method, each of the kind the parameter type needs.
+    pub fn execute_method(
+        &mut self,
+        class_name: &str,
+        method_name: &str,
+        descriptor: &str,
+        args: Vec<JvmValue>,
+    ) -> Result<Option<JvmValue>, JvmError> {
+        self.branches = 0;
+        self.unwound.clear();
+        let result = self.call_by_descriptor(class_name, method_name, descriptor, args);
+        self.report_uncaught(result)
+    }
+
+    fn report_uncaught(
+        &mut self,
+        result: Result<Option<JvmValue>, JvmError>,
+    ) -> Result<Option<JvmValue>, JvmError> {
+        if let Err(e) = &result
+            && self.uncaught_handler.is_some()
+            && let Some(exception) = self.uncaught(e)
+            && let Some(handler) = &mut self.uncaught_handler
+        {
+            handler(&mut self.natives, &exception);
+        }
+        result
+    }
+
+    fn call_by_descriptor(
+        &mut self,
+        class_name: &str,
+        method_name: &str,
+        descriptor: &str,
+        args: Vec<JvmValue>,
+    ) -> Result<Option<JvmValue>, JvmError> {
+        let params = classfile::descriptor_params(descriptor)
+            .ok_or_else(|| JvmError::TypeError(format!("bad descriptor {}", descriptor)))?;
+        let Some(class_idx) = self.find_class_index(class_name) else {
+            check_args(method_name, &params, &args)?;
+            return self.native(class_name, method_name, descriptor, &args);
+        };
+        let method_idx = self.method_index[class_idx]
+            .find(method_name, descriptor)
+            .ok_or_else(|| {
+                JvmError::MethodNotFound(format!("{}::{}{}", class_name, method_name, descriptor))
+            })?;
+
+        let method = &self.classes[class_idx].methods[method_idx];
+        let values = if method.access_flags & ACC_STATIC != 0 {
+            &args[..]
+        } else {
+            match args.split_first() {
+                Some((JvmValue::ObjectRef(_), rest)) => rest,
+                _ => {
+                    return Err(JvmError::TypeError(format!(
+                        "{}{} needs an object receiver first",
+                        method_name, descriptor
+                    )));
+                }
+            }
+        };
+        check_args(method_name, &params, values)?;
+        self.invoke_method(class_idx, method_idx, args)
+    }
+
+    pub(crate) fn invoke_method(
+        &mut self,
+        class_idx: usize,
+        method_idx: usize,
+        args: Vec<JvmValue>,
+    ) -> Result<Option<JvmValue>, JvmError> {
+        if self.profiler.is_none() {
+            return self.observe_method(class_idx, method_idx, args);
+        }
+        let start = self.natives.now_micros();
+        if let Some(p) = &mut self.profiler {
+            p.enter_method(class_idx, method_idx);
+        }
+        let result = self.observe_method(class_idx, method_idx, args);
+        let elapsed = elapsed_since(start, self.natives.now_micros());
+        if let Some(p) = &mut self.profiler {
+            p.leave_method(class_idx, method_idx, elapsed);
+        }
+        result
+    }
+
+    // Calls the bridge for a method no loaded class provides, timing it
+    // when the profiler is on.
+    pub(crate) fn call_bridge(
+        &mut self,
+        class_name: &str,
+        method_name: &str,
+        descriptor: &str,
+        args: &[JvmValue],
+    ) -> Result<Option<JvmValue>, JvmError> {
+        if self.profiler.is_none() {
+            return self.native(class_name, method_name, descriptor, args);
+        }
+        let start = self.natives.now_micros();
+        let result = self.native(class_name, method_name, descriptor, args);
+        let elapsed = elapsed_since(start, self.natives.now_micros());
+        if let Some(p) = &mut self.profiler {
+            p.record_native(&format!("{}.{}", class_name, method_name), elapsed);
+        }
+        result
+    }
+
+    // Every native call the guest makes comes through here, so deterministic
+    // mode sees all of them.
+    pub(crate) fn native(
+        &mut self,
+        class_name: &str,
+        method_name: &str,
+        descriptor: &str,
+        args: &[JvmValue],
+    ) -> Result<Option<JvmValue>, JvmError> {
+        let value = self.logged_native(class_name, method_name, descriptor, args)?;
+        self.run_upcalls()?;
+        Ok(value)
+    }
+
+    pub(crate) fn run_upcalls(&mut self) -> Result<(), JvmError> {
+        while let Some(up) = self.natives.take_upcall() {
+            let value = self.call_by_descriptor(
+                &up.class_name,
+                &up.method_name,
+                &up.descriptor,
+                up.args.clone(),
+            )?;
+            self.natives.upcall_returned(&up, value);
+        }
+        Ok(())
+    }
+
+    fn logged_native(
+        &mut self,
+        class_name: &str,
+        method_name: &str,
+        descriptor: &str,
+        args: &[JvmValue],
+    ) -> Result<Option<JvmValue>, JvmError> {
+        let Some(d) = &mut self.determinism else {
+            return self.natives.call_native(
+                &mut self.heap,
+                class_name,
+                method_name,
+                descriptor,
+                args,
+            );
+        };
+        if !self.natives.nondeterministic(class_name, method_name) {
+            return self.natives.call_native(
+                &mut self.heap,
+                class_name,
+                method_name,
+                descriptor,
+                args,
+            );
+        }
+        let at = self.instructions;
+        let entry = match d.inject(at, class_name, method_name)? {
+            Some(entry) => entry,
+            None => {
+                let value = self.natives.call_native(
+                    &mut self.heap,
+                    class_name,
+                    method_name,
+                    descriptor,
+                    args,
+                )?;
+                d.record_call(at, class_name, method_name, value)
+            }
+        };
+        self.natives.logged(entry);
+        Ok(entry.value.clone())
+    }
+
+    fn observe_method(
+        &mut self,
+        class_idx: usize,
+        method_idx: usize,
+        args: Vec<JvmValue>,
+    ) -> Result<Option<JvmValue>, JvmError> {
+        if !O::ENABLED {
+            return self.call_method(class_idx, method_idx, args);
+        }
+        let at = location(&self.classes, class_idx, method_idx, 0);
+        self.observer.on_invoke(&at, &args);
+        let result = self.call_method(class_idx, method_idx, args);
+        if let Ok(v) = &result {
+            let at = location(&self.classes, class_idx, method_idx, 0);
+            self.observer.on_return(&at, v.as_ref());
+        }
+        result
+    }
+
+    fn call_method(
+        &mut self,
+        class_idx: usize,
+        method_idx: usize,
+        args: Vec<JvmValue>,
+    ) -> Result<Option<JvmValue>, JvmError> {
+        let class = &self.classes[class_idx];
+        let method = &class.methods[method_idx];
+
         if method.access_flags & ACC_NATIVE != 0 {
+            let class_name = class.class_name()?;
+            let method_name = class.get_utf8(method.name_index)?;
             let desc = class.get_utf8(method.descriptor_index).unwrap_or("()V");
-            return self
-                .natives
-                .call_native(class_name, method_name, desc, &args);
+            if self.determinism.is_none() {
+                let value = self.natives.call_native(
+                    &mut self.heap,
+                    class_name,
+                    method_name,
+                    desc,
+                    &args,
+                )?;
+                self.run_upcalls()?;
+                return Ok(value);
+            }
+            let (class_name, method_name, desc) = (
+                String::from(class_name),
+                String::from(method_name),
+                String::from(desc),
+            );
+            return self.native(&class_name, &method_name, &desc, &args);
         }
 
-        let code_attr = method.code.as_ref().ok_or_else(|| {
-            JvmError::MethodNotFound(format!("{}::{} has no Code", class_name, method_name))
-        })?;
+        let code = self.method_code(class_idx, method_idx)?;
 
-        let mut locals = alloc::vec![JvmValue::Int(0); code_attr.max_locals as usize];
-        for (i, arg) in args.into_iter().enumerate() {
-            if i < locals.len() {
-                locals[i] = arg;
-            }
+        let mut locals = alloc::vec![JvmValue::Int(0); code.max_locals];
+        // method_code has checked the slots fit in max_locals.
+        for (arg, &slot) in args.into_iter().zip(&code.arg_slots) {
+            locals[slot] = arg;
         }
 
         let mut frame = Frame {
-            stack: Vec::with_capacity(code_attr.max_stack as usize),
+            stack: Vec::with_capacity(code.max_stack),
             locals,
-            code: code_attr.code.clone(),
+            code,
             pc: 0,
             class_idx,
-            exception_table: code_attr.exception_table.clone(),
+            method_idx,
+            executed: 0,
+            op_pc: 0,
         };
 
-        self.interpret(&mut frame)
+        if self.calls.len() >= self.limits.max_call_depth {
+            return Err(JvmError::StackOverflow);
+        }
+        if let Some(max) = self.limits.max_stack_bytes {
+            let here = core::ptr::addr_of!(frame) as usize;
+            if self.calls.is_empty() {
+                self.stack_base = here;
+            } else if self.stack_base.saturating_sub(here) > max {
+                return Err(JvmError::StackOverflow);
+            }
+        }
+        self.calls.push(CallSite {
+            class_idx,
+            method_idx,
+            pc: 0,
+        });
+        let result = self.interpret(&mut frame);
+        self.calls.pop();
+        // The error passes each frame on its way out; keep them for frames().
+        if let Err(e) = &result
+            && !matches!(e, JvmError::SystemExit(_))
+        {
+            let snapshot = self.frame_snapshot(&frame);
+            self.unwound.push(snapshot);
+        }
+        if let Some(p) = &mut self.profiler {
+            p.add_bytecodes(class_idx, method_idx, frame.executed);
+        }
+        result
     }
 
-    fn find_exception_handler(&self, frame: &Frame, pc: usize, exc_class: &str) -> Option<u16> {
-        for entry in &frame.exception_table {
-            if pc >= entry.start_pc as usize && pc < entry.end_pc as usize {
-                if entry.catch_type == 0 {
-                    return Some(entry.handler_pc);
-                }
-                let class = &self.classes[frame.class_idx];
-                if let Ok(catch_name) = class.get_class_name(entry.catch_type) {
-                    if self.is_subclass(exc_class, catch_name) {
-                        return Some(entry.handler_pc);
-                    }
-                }
+    // Methods are decoded on first call and shared by every later frame.
+    fn method_code(&mut self, class_idx: usize, method_idx: usize) -> Result<Rc<Code>, JvmError> {
+        if let Some(code) = self.code_cache.get(&(class_idx, method_idx)) {
+            return Ok(code.clone());
+        }
+        let class = &self.classes[class_idx];
+        let method = &class.methods[method_idx];
+        let code_attr = method.code.as_ref().ok_or_else(|| {
+            JvmError::MethodNotFound(format!(
+                "{}::{} has no Code",
+                class.class_name().unwrap_or("?"),
+                class.get_utf8(method.name_index).unwrap_or("?")
+            ))
+        })?;
+        let mut code = decode::decode(code_attr)?;
+        let desc = MethodDescriptor::parse(class.get_utf8(method.descriptor_index)?)?;
+        let receiver = usize::from(method.access_flags & ACC_STATIC == 0);
+        let needed = receiver + desc.param_slots();
+        if needed > code.max_locals {
+            return Err(JvmError::ClassFormatError(format!(
+                "{}.{} has max_locals {} but its arguments take {} slots",
+                class.class_name().unwrap_or("?"),
+                class.get_utf8(method.name_index).unwrap_or("?"),
+                code.max_locals,
+                needed
+            )));
+        }
+        let mut slot = receiver;
+        code.arg_slots.extend((receiver == 1).then_some(0));
+        for param in &desc.params {
+            code.arg_slots.push(slot);
+            slot += param.slots();
+        }
+        let code = Rc::new(code);
+        self.code_cache
+            .insert((class_idx, method_idx), code.clone());
+        Ok(code)
+    }
+
+    // A handler that doesn't point at an instruction, or a catch type that
+    // doesn't name a class, is a broken class file rather than a miss.
+    fn find_exception_handler(
+        &self,
+        frame: &Frame,
+        pc: usize,
+        exc_class: &str,
+    ) -> Result<Option<u32>, JvmError> {
+        for entry in &frame.code.handlers {
+            if pc < entry.start as usize || pc >= entry.end as usize {
+                continue;
+            }
+            if entry.handler as usize >= frame.code.insns.len() {
+                return Err(JvmError::ClassFormatError(format!(
+                    "exception handler target {} past the end of the code",
+                    entry.handler
+                )));
+            }
+            if entry.catch_type == 0 {
+                return Ok(Some(entry.handler));
             }
+            let catch_name = self.classes[frame.class_idx].get_class_name(entry.catch_type)?;
+            if self.is_subclass(exc_class, catch_name) {
+                return Ok(Some(entry.handler));
+            }
+        }
+        Ok(None)
+    }
+
+    fn frame_snapshot(&self, f: &Frame) -> FrameSnapshot {
+        let class = &self.classes[f.class_idx];
+        let pc = f.code.offsets.get(f.op_pc).copied().unwrap_or(0) as usize;
+        let at = location(&self.classes, f.class_idx, f.method_idx, pc);
+        let code = class.methods[f.method_idx].code.as_ref();
+        FrameSnapshot {
+            class_name: String::from(at.class_name),
+            method_name: String::from(at.method_name),
+            descriptor: String::from(at.descriptor),
+            pc,
+            opcode: code.and_then(|c| c.code.get(pc)).copied().unwrap_or(0),
+            source_file: class.source_file_name().map(String::from),
+            line: class.line_for_pc(f.method_idx, pc),
+            locals: f.locals.clone(),
+            stack: f.stack.clone(),
+        }
+    }
+
+    // Sizes come straight off the operand stack, so they are checked before
+    // anything is allocated.
+    pub(crate) fn array_length(&self, count: i32) -> Result<usize, JvmError> {
+        if count < 0 {
+            return Err(JvmError::NegativeArraySize(count));
+        }
+        if count as usize > self.limits.max_array_length {
+            return Err(JvmError::OutOfMemory);
+        }
+        Ok(count as usize)
+    }
+
+    pub(crate) fn check_string(&self, s: &str) -> Result<(), JvmError> {
+        if s.len() > self.limits.max_string_length {
+            return Err(JvmError::OutOfMemory);
+        }
+        Ok(())
+    }
+
+    fn take_backward_branch(&mut self) -> Result<(), JvmError> {
+        self.branches += 1;
+        match self.limits.max_backward_branches {
+            Some(max) if self.branches > max => Err(JvmError::BudgetExceeded(max)),
+            _ => Ok(()),
+        }
+    }
+
+    fn observe_throw(&mut self, f: &Frame, op_pc: usize, exception: &str) {
+        if O::ENABLED {
+            let pc = f.code.offsets[op_pc] as usize;
+            let at = location(&self.classes, f.class_idx, f.method_idx, pc);
+            self.observer.on_throw(&at, exception);
         }
-        None
     }
 
     fn interpret(&mut self, f: &mut Frame) -> Result<Option<JvmValue>, JvmError> {
         loop {
             let op_pc = f.pc;
-            let op = f.read_u8();
+            f.op_pc = op_pc;
+            let insn = f.code.insns[op_pc];
+            f.pc += 1;
+            f.executed += 1;
+            self.instructions += 1;
 
-            let result = self.exec_one(f, op, op_pc);
+            if O::ENABLED {
+                let pc = f.code.offsets[op_pc] as usize;
+                let at = location(&self.classes, f.class_idx, f.method_idx, pc);
+                self.observer.on_instruction(&at, insn.op);
+                let view = FrameView {
+                    locals: &f.locals,
+                    stack: &f.stack,
+                    heap: &self.heap,
+                };
+                self.observer.on_frame(&at, &view);
+            }
+
+            let result = self.exec_one(f, insn);
 
             match result {
                 Ok(action) => match action {
-                    ExecAction::Continue => {}
-                    ExecAction::ReturnVal(v) => return Ok(Some(v)),
-                    ExecAction::ReturnVoid => return Ok(None),
-                    ExecAction::Throw(exc_class, exc_obj) => {
-                        if let Some(handler_pc) = self.find_exception_handler(f, op_pc, &exc_class)
-                        {
-                            f.stack.clear();
-                            f.push(exc_obj);
-                            f.pc = handler_pc as usize;
-                        } else {
-                            return Err(JvmError::NativeMethodError(format!(
-                                "Unhandled exception: {}",
-                                exc_class
-                            )));
+                    // Every loop iteration jumps back, so counting those is
+                    // enough to catch a runaway guest without a per-insn cost.
+                    ExecAction::Continue => {
+                        if f.pc <= op_pc {
+                            self.take_backward_branch()?;
                         }
                     }
+                    ExecAction::ReturnVal(v) => return Ok(Some(v)),
+                    ExecAction::ReturnVoid => return Ok(None),
                 },
+                // Java exceptions and the VM faults Java can catch unwind
+                // frame by frame until one has a handler for them.
                 Err(e) => {
                     let exc_class = match &e {
-                        JvmError::NullPointerException => Some("java/lang/NullPointerException"),
-                        JvmError::DivisionByZero => Some("java/lang/ArithmeticException"),
-                        JvmError::ArrayIndexOutOfBounds(_, _) => {
-                            Some("java/lang/ArrayIndexOutOfBoundsException")
-                        }
-                        _ => None,
+                        JvmError::Thrown(_, id) => self.heap.get_object(*id)?.class_name.clone(),
+                        _ => match throwable::fault_class(&e) {
+                            Some(ec) => String::from(ec),
+                            None => return Err(e),
+                        },
+                    };
+                    // Only where it was raised, not in every frame it leaves.
+                    if self.unwound.is_empty() {
+                        self.observe_throw(f, op_pc, &exc_class);
+                    }
+                    let Some(handler_pc) = self.find_exception_handler(f, op_pc, &exc_class)?
+                    else {
+                        return Err(e);
                     };
-                    if let Some(ec) = exc_class {
-                        if let Some(handler_pc) = self.find_exception_handler(f, op_pc, ec) {
-                            let exc_id = self.heap.alloc_object(String::from(ec))?;
-                            {
-                                let exc_obj = self.heap.get_object_mut(exc_id)?;
-                                exc_obj.fields.insert(
-                                    String::from("detailMessage"),
-                                    JvmValue::StringRef(format!("{}", e)),
-                                );
+                    let exc_id = match e {
+                        JvmError::Thrown(_, id) => id,
+                        _ => {
+                            if let Some(call) = self.calls.last_mut() {
+                                call.pc = f.code.offsets[op_pc] as usize;
                             }
-                            f.stack.clear();
-                            f.push(JvmValue::ObjectRef(exc_id));
-                            f.pc = handler_pc as usize;
-                            continue;
+                            self.new_throwable(&exc_class, throwable::fault_message(&e))?
                         }
-                    }
-                    return Err(e);
+                    };
+                    f.stack.clear();
+                    f.push(JvmValue::ObjectRef(exc_id));
+                    f.pc = handler_pc as usize;
+                    // Caught, so the callee frames it unwound don't matter.
+                    self.unwound.clear();
                 }
             }
         }
     }
 }
 
+fn location(classes: &[ClassFile], class_idx: usize, method_idx: usize, pc: usize) -> Location<'_> {
+    let class = &classes[class_idx];
+    let method = &class.methods[method_idx];
+    Location {
+        class_name: class.class_name().unwrap_or("?"),
+        method_name: class.get_utf8(method.name_index).unwrap_or("?"),
+        descriptor: class.get_utf8(method.descriptor_index).unwrap_or("?"),
+        pc,
+    }
+}
+
+fn elapsed_since(start: Option<u64>, end: Option<u64>) -> Option<u64> {
+    Some(end?.saturating_sub(start?))
+}
+
+// Whether each argument could be passed for its parameter type.
+fn check_args(method_name: &str, params: &[&str], args: &[JvmValue]) -> Result<(), JvmError> {
+    if params.len() != args.len() {
+        return Err(JvmError::TypeError(format!(
+            "{} takes {} arguments, got {}",
+            method_name,
+            params.len(),
+            args.len()
+        )));
+    }
+    for (i, (param, arg)) in params.iter().zip(args).enumerate() {
+        if !fits(param, arg) {
+            return Err(JvmError::TypeError(format!(
+                "{} argument {} is {}, got {:?}",
+                method_name, i, param, arg
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Whether a value can live in a slot of the given field type.
+pub(crate) fn fits(descriptor: &str, value: &JvmValue) -> bool {
+    // The types every array is assignable to.
+    if let JvmValue::ArrayRef(_) = value
+        && matches!(
+            descriptor,
+            "Ljava/lang/Object;" | "Ljava/lang/Cloneable;" | "Ljava/io/Serializable;"
+        )
+    {
+        return true;
+    }
+    match descriptor.as_bytes().first() {
+        Some(b'B' | b'C' | b'I' | b'S' | b'Z') => matches!(value, JvmValue::Int(_)),
+        Some(b'J') => matches!(value, JvmValue::Long(_)),
+        Some(b'F') => matches!(value, JvmValue::Float(_)),
+        Some(b'D') => matches!(value, JvmValue::Double(_)),
+        Some(b'[') => matches!(value, JvmValue::Null | JvmValue::ArrayRef(_)),
+        _ => matches!(
+            value,
+            JvmValue::Null | JvmValue::ObjectRef(_) | JvmValue::StringRef(_)
+        ),
+    }
+}
+
 pub fn jvm_value_to_string(val: &JvmValue) -> String {
     match val {
         JvmValue::Int(i) => format!("{}", i),
@@ -0,0 +1,170 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use shared::classfile::{
+    Annotation,
+    ClassFile,
+    ElementValue,
+};
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+
+use super::{
+    Vm,
+    jvm_value_to_string,
+};
+use crate::native::NativeBridge;
+use crate::observer::ExecutionObserver;
+
+const CLASS: &str = "efi/Reflect";
+
+// Class and annotation names may come dotted, the way Java writes them.
+fn class_arg(args: &[JvmValue], i: usize) -> Result<String, JvmError> {
+    match args.get(i) {
+        Some(JvmValue::StringRef(s)) => Ok(s.replace('.', "/")),
+        _ => Err(JvmError::NullPointerException),
+    }
+}
+
+// The annotations on the class itself when `member` is null, else on its
+// first method or field of that name.
+fn annotations_of<'a>(class: &'a ClassFile, member: &JvmValue) -> &'a [Annotation] {
+    let JvmValue::StringRef(name) = member else {
+        return &class.annotations;
+    };
+    let name = Some(name.as_str());
+    if let Some(m) = class
+        .methods
+        .iter()
+        .find(|m| class.get_utf8(m.name_index).ok() == name)
+    {
+        return &m.annotations;
+    }
+    class
+        .fields
+        .iter()
+        .find(|f| class.get_utf8(f.name_index).ok() == name)
+        .map_or(&[], |f| &f.annotations)
+}
+
+impl<N: NativeBridge, O: ExecutionObserver> Vm<N, O> {
+    /// The loaded class called `name`, such as `BootMenu`, for reading its
+    /// annotations and other metadata.
+    pub fn class(&self, name: &str) -> Option<&ClassFile> {
+        self.classes.get(self.find_class_index(name)?)
+    }
+
+    // efi/Reflect: the annotations javac keeps for @Retention(RUNTIME)
+    // types, read straight off the loaded class files.
+    pub(crate) fn handle_reflect(
+        &mut self,
+        method_name: &str,
+        args: &[JvmValue],
+    ) -> Result<Option<JvmValue>, JvmError> {
+        match method_name {
+            "hasAnnotation" => {
+                let found = self.annotation_arg(args)?.is_some();
+                Ok(Some(JvmValue::Int(found as i32)))
+            }
+            "annotationValue" => {
+                let element = match args.get(3) {
+                    Some(JvmValue::StringRef(s)) => s.clone(),
+                    _ => return Err(JvmError::NullPointerException),
+                };
+                let value = match self.annotation_arg(args)? {
+                    Some((class, annotation, type_name)) => {
+                        self.annotation_string(class, annotation, &type_name, &element)
+                    }
+                    None => None,
+                };
+                Ok(Some(value.map_or(JvmValue::Null, JvmValue::StringRef)))
+            }
+            "annotatedMethods" => {
+                let class_name = class_arg(args, 0)?;
+                let type_name = class_arg(args, 1)?;
+                let names: Vec<String> = match self.class(&class_name) {
+                    Some(class) => class
+                        .methods
+                        .iter()
+                        .filter(|m| class.find_annotation(&m.annotations, &type_name).is_some())
+                        .filter_map(|m| class.get_utf8(m.name_index).ok())
+                        .map(String::from)
+                        .collect(),
+                    None => Vec::new(),
+                };
+                let id = self
+                    .heap
+                    .alloc_array(String::from("java/lang/String"), names.len())?;
+                let array = self.heap.get_array_mut(id)?;
+                for (slot, name) in array.elements.iter_mut().zip(names) {
+                    *slot = JvmValue::StringRef(name);
+                }
+                Ok(Some(JvmValue::ArrayRef(id)))
+            }
+            "invoke" => {
+                let class_name = class_arg(args, 0)?;
+                let method = match args.get(1) {
+                    Some(JvmValue::StringRef(s)) => s.clone(),
+                    _ => return Err(JvmError::NullPointerException),
+                };
+                self.call_by_descriptor(&class_name, &method, "()V", Vec::new())?;
+                Ok(None)
+            }
+            _ => Err(JvmError::MethodNotFound(format!(
+                "{}::{}",
+                CLASS, method_name
+            ))),
+        }
+    }
+
+    // The (className, member, annotation) leading arguments, resolved to the
+    // annotation when the member has one of that type.
+    fn annotation_arg(
+        &self,
+        args: &[JvmValue],
+    ) -> Result<Option<(&ClassFile, &Annotation, String)>, JvmError> {
+        let class_name = class_arg(args, 0)?;
+        let type_name = class_arg(args, 2)?;
+        let member = args.get(1).unwrap_or(&JvmValue::Null);
+        let Some(class) = self.class(&class_name) else {
+            return Ok(None);
+        };
+        Ok(class
+            .find_annotation(annotations_of(class, member), &type_name)
+            .map(|a| (class, a, type_name)))
+    }
+
+    // An element as Java code would print it, falling back to the default
+    // the annotation type declares when the use doesn't give one.
+    fn annotation_string(
+        &self,
+        class: &ClassFile,
+        annotation: &Annotation,
+        type_name: &str,
+        element: &str,
+    ) -> Option<String> {
+        let (owner, value) = match class.annotation_element(annotation, element) {
+            Some(value) => (class, value),
+            None => {
+                let owner = self.class(type_name)?;
+                let value = owner
+                    .find_method_by_name(element)?
+                    .annotation_default
+                    .as_ref()?;
+                (owner, value)
+            }
+        };
+        let tag = match value {
+            ElementValue::Const { tag, .. } => *tag,
+            _ => 0,
+        };
+        Some(match (tag, owner.element_value(value)?) {
+            (b'C', JvmValue::Int(c)) => char::from_u32(c as u32)?.into(),
+            (b'Z', JvmValue::Int(z)) => String::from(if z != 0 { "true" } else { "false" }),
+            (_, v) => jvm_value_to_string(&v),
+        })
+    }
+}
@@ -0,0 +1,158 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+
+use shared::classfile::{
+    ClassFile,
+    CpEntry,
+};
+use shared::descriptor::{
+    FieldType,
+    MethodDescriptor,
+};
+use shared::types::JvmError;
+
+use super::Vm;
+use crate::native::NativeBridge;
+use crate::observer::ExecutionObserver;
+
+// Built once per class at load time: name -> descriptor -> method index.
+pub(crate) struct MethodIndex {
+    methods: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+impl MethodIndex {
+    pub(crate) fn build(class: &ClassFile) -> Self {
+        let mut methods: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+        for (i, m) in class.methods.iter().enumerate() {
+            if let (Ok(name), Ok(desc)) = (
+                class.get_utf8(m.name_index),
+                class.get_utf8(m.descriptor_index),
+            ) {
+                methods
+                    .entry(String::from(name))
+                    .or_default()
+                    .entry(String::from(desc))
+                    .or_insert(i);
+            }
+        }
+        Self { methods }
+    }
+
+    pub(crate) fn find(&self, name: &str, descriptor: &str) -> Option<usize> {
+        self.methods.get(name)?.get(descriptor).copied()
+    }
+
+    // Overloads are ignored; the first declared method with the name wins.
+    pub(crate) fn find_by_name(&self, name: &str) -> Option<usize> {
+        self.methods.get(name)?.values().min().copied()
+    }
+}
+
+// A Fieldref/Methodref with its strings pulled out of the constant pool and,
+// for methods, the loaded class and method it lands on.
+pub(crate) struct MemberRef {
+    pub(crate) class_name: String,
+    pub(crate) name: String,
+    pub(crate) descriptor: String,
+    pub(crate) static_key: String,
+    pub(crate) arg_count: usize,
+    // What a method call leaves on the stack; None for void, and for fields.
+    pub(crate) result: Option<FieldType>,
+    pub(crate) class_idx: Option<usize>,
+    pub(crate) method: Option<(usize, usize)>,
+}
+
+impl<N: NativeBridge, O: ExecutionObserver> Vm<N, O> {
+    pub(crate) fn member_ref(
+        &mut self,
+        class_idx: usize,
+        idx: u16,
+    ) -> Result<Rc<MemberRef>, JvmError> {
+        if let Some(Some(r)) = self
+            .cp_cache
+            .get(class_idx)
+            .and_then(|t| t.get(idx as usize))
+        {
+            return Ok(r.clone());
+        }
+
+        let r = Rc::new(self.resolve_member(class_idx, idx)?);
+        if self.cp_cache.len() <= class_idx {
+            self.cp_cache
+                .resize_with(self.classes.len(), alloc::vec::Vec::new);
+        }
+        let table = &mut self.cp_cache[class_idx];
+        if table.is_empty() {
+            table.resize(self.classes[class_idx].constant_pool.len(), None);
+        }
+        table[idx as usize] = Some(r.clone());
+        Ok(r)
+    }
+
+    fn resolve_member(&self, class_idx: usize, idx: u16) -> Result<MemberRef, JvmError> {
+        let class = &self.classes[class_idx];
+        let (ci, nti) = match class.constant_pool.get(idx as usize) {
+            Some(CpEntry::Methodref {
+                class_index,
+                name_and_type_index,
+            })
+            | Some(CpEntry::InterfaceMethodref {
+                class_index,
+                name_and_type_index,
+            })
+            | Some(CpEntry::Fieldref {
+                class_index,
+                name_and_type_index,
+            }) => (*class_index, *name_and_type_index),
+            _ => {
+                return Err(JvmError::ClassFormatError(format!(
+                    "expected member ref at cp#{}",
+                    idx
+                )));
+            }
+        };
+        let class_name = class.get_class_name(ci)?;
+        let (name, descriptor) = class.resolve_name_and_type(nti)?;
+
+        let target_idx = self.find_class_index(class_name);
+        let method = target_idx.and_then(|t| self.find_virtual(t, name, descriptor));
+
+        let (arg_count, result) = if descriptor.starts_with('(') {
+            let desc = MethodDescriptor::parse(descriptor)?;
+            (desc.params.len(), desc.result)
+        } else {
+            (0, None)
+        };
+        Ok(MemberRef {
+            class_name: String::from(class_name),
+            name: String::from(name),
+            descriptor: String::from(descriptor),
+            static_key: format!("{}.{}", class_name, name),
+            arg_count,
+            result,
+            class_idx: target_idx,
+            method,
+        })
+    }
+
+    // Looks the method up on `class_idx` and then up its superclass chain,
+    // returning the class that actually declares it.
+    pub(crate) fn find_virtual(
+        &self,
+        class_idx: usize,
+        name: &str,
+        descriptor: &str,
+    ) -> Option<(usize, usize)> {
+        let mut ci = class_idx;
+        for _ in 0..self.classes.len() {
+            if let Some(mi) = self.method_index[ci].find(name, descriptor) {
+                return Some((ci, mi));
+            }
+            let super_name = self.classes[ci].super_class_name()?;
+            ci = self.find_class_index(super_name)?;
+        }
+        None
+    }
+}
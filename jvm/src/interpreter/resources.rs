@@ -0,0 +1,256 @@
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use shared::zip::ZipArchiveOwned;
+
+use super::Vm;
+use crate::native::NativeBridge;
+use crate::observer::ExecutionObserver;
+
+const STREAM: &str = "java/io/ByteArrayInputStream";
+
+// The entry `name` names relative to `class`: from the JAR root with a
+// leading slash, else from the class's package, as Class.getResource reads
+// it.
+fn resolve_name(class: &str, name: &str) -> String {
+    if let Some(absolute) = name.strip_prefix('/') {
+        return String::from(absolute);
+    }
+    match class.rfind('/') {
+        Some(i) => format!("{}/{}", &class[..i], name),
+        None => String::from(name),
+    }
+}
+
+fn string_arg(args: &[JvmValue], i: usize) -> Result<&str, JvmError> {
+    match args.get(i) {
+        Some(JvmValue::StringRef(s)) => Ok(s),
+        _ => Err(JvmError::NullPointerException),
+    }
+}
+
+impl<N: NativeBridge, O: ExecutionObserver> Vm<N, O> {
+    /// Keeps a JAR for reading resources from: the loaded classes named in
+    /// `classes` look in it first, and every class falls back to it after
+    /// its own, in the order JARs were added.
+    pub fn add_archive(
+        &mut self,
+        archive: ZipArchiveOwned,
+        classes: impl IntoIterator<Item = String>,
+    ) {
+        let idx = self.archives.len();
+        self.archives.push(archive);
+        for class in classes {
+            self.class_archives.insert(class, idx);
+        }
+    }
+
+    /// Drops every JAR [`Vm::add_archive`] kept, as before loading them all
+    /// again.
+    pub fn clear_archives(&mut self) {
+        self.archives.clear();
+        self.class_archives.clear();
+    }
+
+    /// The contents of resource `name` as `class_name`'s `getResource`
+    /// finds it.
+    pub fn resource(
+        &self,
+        class_name: &str,
+        name: &str,
+    ) -> Result<Option<Cow<'_, [u8]>>, JvmError> {
+        let path = resolve_name(class_name, name);
+        match self.find_resource(class_name, &path) {
+            Some(idx) => self.read_resource(idx, &path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // The JAR that has entry `path`, the class's own first.
+    fn find_resource(&self, class_name: &str, path: &str) -> Option<usize> {
+        let own = self.class_archives.get(class_name).copied();
+        let others = (0..self.archives.len()).filter(|&i| Some(i) != own);
+        own.into_iter()
+            .chain(others)
+            .find(|&idx| self.archives[idx].find(path).is_some())
+    }
+
+    fn read_resource(&self, archive: usize, path: &str) -> Result<Cow<'_, [u8]>, JvmError> {
+        let archive = self
+            .archives
+            .get(archive)
+            .ok_or_else(|| JvmError::IoError(format!("{}: JAR no longer loaded", path)))?;
+        let entry = archive
+            .find(path)
+            .ok_or_else(|| JvmError::IoError(format!("{}: not in its JAR", path)))?;
+        archive.read_entry(entry)
+    }
+
+    // The one java/lang/Class object for `name`, made on first use.
+    pub(crate) fn class_object(&mut self, name: &str) -> Result<u32, JvmError> {
+        if let Some(&id) = self.class_objects.get(name) {
+            return Ok(id);
+        }
+        let id = self.heap.alloc_object(String::from("java/lang/Class"))?;
+        self.set_field(id, "name", JvmValue::StringRef(String::from(name)))?;
+        self.class_objects.insert(String::from(name), id);
+        Ok(id)
+    }
+
+    // java/lang/Class, as far as reading resources goes. The streams are
+    // ByteArrayInputStreams over a copy of the entry, and a URL is kept as
+    // the JAR and entry it points at.
+    pub(crate) fn handle_class(
+        &mut self,
+        method_name: &str,
+        args: &[JvmValue],
+    ) -> Result<Option<JvmValue>, JvmError> {
+        let id = args
+            .first()
+            .ok_or(JvmError::NullPointerException)?
+            .as_object_ref()?;
+        let JvmValue::StringRef(class_name) = self.field(id, "name")? else {
+            return Err(JvmError::NullPointerException);
+        };
+        match method_name {
+            "getName" => Ok(Some(JvmValue::StringRef(class_name.replace('/', ".")))),
+            "getResourceAsStream" => {
+                let name = string_arg(args, 1)?;
+                let data = match self.resource(&class_name, name)? {
+                    Some(data) => data.into_owned(),
+                    None => return Ok(Some(JvmValue::Null)),
+                };
+                self.new_stream(&data).map(Some)
+            }
+            "getResource" => {
+                let name = string_arg(args, 1)?;
+                let path = resolve_name(&class_name, name);
+                let Some(archive) = self.find_resource(&class_name, &path) else {
+                    return Ok(Some(JvmValue::Null));
+                };
+                let url = self.heap.alloc_object(String::from("java/net/URL"))?;
+                self.set_field(url, "archive", JvmValue::Int(archive as i32))?;
+                self.set_field(url, "path", JvmValue::StringRef(path))?;
+                Ok(Some(JvmValue::ObjectRef(url)))
+            }
+            _ => Err(JvmError::MethodNotFound(format!(
+                "java/lang/Class::{}",
+                method_name
+            ))),
+        }
+    }
+
+    pub(crate) fn handle_url(
+        &mut self,
+        method_name: &str,
+        args: &[JvmValue],
+    ) -> Result<Option<JvmValue>, JvmError> {
+        let id = args
+            .first()
+            .ok_or(JvmError::NullPointerException)?
+            .as_object_ref()?;
+        let JvmValue::StringRef(path) = self.field(id, "path")? else {
+            return Err(JvmError::NullPointerException);
+        };
+        match method_name {
+            "getPath" | "getFile" | "toString" | "toExternalForm" => {
+                Ok(Some(JvmValue::StringRef(format!("/{}", path))))
+            }
+            "openStream" => {
+                let archive = self.field(id, "archive")?.as_int()? as usize;
+                let data = self.read_resource(archive, &path)?.into_owned();
+                self.new_stream(&data).map(Some)
+            }
+            _ => Err(JvmError::MethodNotFound(format!(
+                "java/net/URL::{}",
+                method_name
+            ))),
+        }
+    }
+
+    fn new_stream(&mut self, data: &[u8]) -> Result<JvmValue, JvmError> {
+        let buf = self.heap.alloc_bytes(data)?;
+        let id = self.heap.alloc_object(String::from(STREAM))?;
+        self.set_field(id, "buf", JvmValue::ArrayRef(buf))?;
+        self.set_field(id, "pos", JvmValue::Int(0))?;
+        Ok(JvmValue::ObjectRef(id))
+    }
+
+    // java/io/InputStream over the streams above: the reads, skip and
+    // available, each moving `pos` through `buf`.
+    pub(crate) fn handle_input_stream(
+        &mut self,
+        method_name: &str,
+        descriptor: &str,
+        args: &[JvmValue],
+    ) -> Result<Option<JvmValue>, JvmError> {
+        let id = args
+            .first()
+            .ok_or(JvmError::NullPointerException)?
+            .as_object_ref()?;
+        if method_name == "close" {
+            return Ok(None);
+        }
+        let buf = self.field(id, "buf")?.as_array_ref()?;
+        let pos = self.field(id, "pos")?.as_int()? as usize;
+        let len = self.heap.get_array(buf)?.elements.len();
+        let left = len - pos.min(len);
+        let (result, moved) = match (method_name, descriptor) {
+            ("read", "()I") => match left {
+                0 => (JvmValue::Int(-1), 0),
+                _ => {
+                    let b = self.heap.get_array(buf)?.elements[pos].as_int()?;
+                    (JvmValue::Int(b & 0xff), 1)
+                }
+            },
+            ("read", _) => {
+                let dst = args
+                    .get(1)
+                    .ok_or(JvmError::NullPointerException)?
+                    .as_array_ref()?;
+                let dst_len = self.heap.get_array(dst)?.elements.len();
+                let (off, want) = match (args.get(2), args.get(3)) {
+                    (Some(off), Some(n)) => (off.as_int()?, n.as_int()?),
+                    _ => (0, dst_len as i32),
+                };
+                if off < 0 || want < 0 || off as usize + want as usize > dst_len {
+                    return Err(JvmError::ArrayIndexOutOfBounds(off + want, dst_len));
+                }
+                let n = (want as usize).min(left);
+                if want > 0 && n == 0 {
+                    (JvmValue::Int(-1), 0)
+                } else {
+                    let chunk = self.heap.get_array(buf)?.elements[pos..pos + n].to_vec();
+                    let off = off as usize;
+                    self.heap.get_array_mut(dst)?.elements[off..off + n].clone_from_slice(&chunk);
+                    (JvmValue::Int(n as i32), n)
+                }
+            }
+            ("readAllBytes", _) => {
+                let rest = self.heap.get_array(buf)?.elements[pos..].to_vec();
+                let out = self.heap.alloc_array(String::from("byte"), rest.len())?;
+                self.heap.get_array_mut(out)?.elements = rest;
+                (JvmValue::ArrayRef(out), left)
+            }
+            ("available", _) => (JvmValue::Int(left as i32), 0),
+            ("skip", _) => {
+                let want = args.get(1).map_or(Ok(0), JvmValue::as_long)?;
+                let n = want.clamp(0, left as i64) as usize;
+                (JvmValue::Long(n as i64), n)
+            }
+            _ => {
+                return Err(JvmError::MethodNotFound(format!(
+                    "java/io/InputStream::{}",
+                    method_name
+                )));
+            }
+        };
+        self.set_field(id, "pos", JvmValue::Int((pos + moved) as i32))?;
+        Ok(Some(result))
+    }
+}
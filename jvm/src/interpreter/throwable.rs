@@ -0,0 +1,483 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+
+use super::Vm;
+use crate::native::NativeBridge;
+use crate::observer::ExecutionObserver;
+use crate::trace::UncaughtException;
+
+const ELEMENT_CLASS: &str = "java/lang/StackTraceElement";
+const THROWABLE: &str = "java/lang/Throwable";
+const ASSERTION_ERROR: &str = "java/lang/AssertionError";
+
+// How deep printStackTrace follows causes and suppressed exceptions, in
+// case they loop back.
+const MAX_NESTING: usize = 16;
+
+// The exception class a VM fault raises in Java, or None for the faults
+// that end the run instead.
+pub(crate) fn fault_class(e: &JvmError) -> Option<&'static str> {
+    Some(match e {
+        JvmError::NullPointerException => "java/lang/NullPointerException",
+        JvmError::DivisionByZero | JvmError::ArithmeticOverflow(_) => {
+            "java/lang/ArithmeticException"
+        }
+        JvmError::ArrayIndexOutOfBounds(..) => "java/lang/ArrayIndexOutOfBoundsException",
+        JvmError::NegativeArraySize(_) => "java/lang/NegativeArraySizeException",
+        JvmError::ClassCast(_) => "java/lang/ClassCastException",
+        JvmError::IllegalAccess(_) => "java/lang/IllegalAccessError",
+        JvmError::Interrupted(_) => "java/lang/InterruptedException",
+        JvmError::IoError(_) => "java/io/IOException",
+        JvmError::StackOverflow => "java/lang/StackOverflowError",
+        JvmError::OutOfMemory => "java/lang/OutOfMemoryError",
+        _ => return None,
+    })
+}
+
+// The detailMessage OpenJDK gives the same fault.
+pub(crate) fn fault_message(e: &JvmError) -> Option<String> {
+    match e {
+        JvmError::DivisionByZero => Some(String::from("/ by zero")),
+        JvmError::ArithmeticOverflow(msg)
+        | JvmError::ClassCast(msg)
+        | JvmError::IllegalAccess(msg)
+        | JvmError::Interrupted(msg)
+        | JvmError::IoError(msg) => Some(msg.clone()),
+        JvmError::ArrayIndexOutOfBounds(index, len) => {
+            Some(format!("Index {} out of bounds for length {}", index, len))
+        }
+        JvmError::NegativeArraySize(n) => Some(format!("{}", n)),
+        _ => None,
+    }
+}
+
+struct TraceLine {
+    class_name: String,
+    method_name: String,
+    file: Option<String>,
+    line: Option<u16>,
+}
+
+// `efi.Console.println(Console.java:12)`, as StackTraceElement.toString.
+fn frame_string(
+    class_name: &str,
+    method_name: &str,
+    file: Option<&str>,
+    line: Option<u16>,
+) -> String {
+    let at = match (file, line) {
+        (Some(file), Some(line)) => format!("{}:{}", file, line),
+        (Some(file), None) => String::from(file),
+        (None, _) => String::from("Unknown Source"),
+    };
+    format!("{}.{}({})", class_name.replace('/', "."), method_name, at)
+}
+
+impl<N: NativeBridge, O: ExecutionObserver> Vm<N, O> {
+    // Exception classes the VM stands in for: the well-known ones, loaded
+    // subclasses of them, and any other unloaded class named like one.
+    pub(crate) fn is_throwable(&self, class_name: &str) -> bool {
+        self.is_subclass(class_name, "java/lang/Throwable")
+            || (self.find_class_index(class_name).is_none()
+                && (class_name.ends_with("Exception") || class_name.ends_with("Error")))
+    }
+
+    // An exception raised by the VM itself, filled in as Throwable's
+    // constructor would fill in one made by `new`.
+    pub(crate) fn new_throwable(
+        &mut self,
+        class_name: &str,
+        message: Option<String>,
+    ) -> Result<u32, JvmError> {
+        let id = self.heap.alloc_object(String::from(class_name))?;
+        let trace = self.stack_trace()?;
+        let obj = self.heap.get_object_mut(id)?;
+        obj.fields.insert(
+            String::from("detailMessage"),
+            message.map_or(JvmValue::Null, JvmValue::StringRef),
+        );
+        obj.fields.insert(String::from("stackTrace"), trace);
+        Ok(id)
+    }
+
+    // The frames an error is unwinding from, then the live ones, innermost
+    // first; the constructors of the exception itself are left out, as
+    // fillInStackTrace does.
+    fn trace_lines(&self) -> Vec<TraceLine> {
+        let unwound = self.unwound.iter().map(|s| TraceLine {
+            class_name: s.class_name.clone(),
+            method_name: s.method_name.clone(),
+            file: s.source_file.clone(),
+            line: s.line,
+        });
+        let live = self.calls.iter().rev().map(|c| {
+            let class = &self.classes[c.class_idx];
+            let method = &class.methods[c.method_idx];
+            TraceLine {
+                class_name: String::from(class.class_name().unwrap_or("?")),
+                method_name: String::from(class.get_utf8(method.name_index).unwrap_or("?")),
+                file: class.source_file_name().map(String::from),
+                line: method.line_for_pc(c.pc),
+            }
+        });
+        unwound
+            .chain(live)
+            .skip_while(|t| t.method_name == "<init>" && self.is_throwable(&t.class_name))
+            .collect()
+    }
+
+    fn stack_trace(&mut self) -> Result<JvmValue, JvmError> {
+        let lines = self.trace_lines();
+        let array = self
+            .heap
+            .alloc_array(String::from(ELEMENT_CLASS), lines.len())?;
+        for (i, line) in lines.into_iter().enumerate() {
+            let id = self.heap.alloc_object(String::from(ELEMENT_CLASS))?;
+            let obj = self.heap.get_object_mut(id)?;
+            obj.fields.insert(
+                String::from("declaringClass"),
+                JvmValue::StringRef(line.class_name.replace('/', ".")),
+            );
+            obj.fields.insert(
+                String::from("methodName"),
+                JvmValue::StringRef(line.method_name),
+            );
+            obj.fields.insert(
+                String::from("fileName"),
+                line.file.map_or(JvmValue::Null, JvmValue::StringRef),
+            );
+            obj.fields.insert(
+                String::from("lineNumber"),
+                JvmValue::Int(line.line.map_or(-1, i32::from)),
+            );
+            self.heap.get_array_mut(array)?.elements[i] = JvmValue::ObjectRef(id);
+        }
+        Ok(JvmValue::ArrayRef(array))
+    }
+
+    pub(crate) fn field(&self, id: u32, name: &str) -> Result<JvmValue, JvmError> {
+        Ok(self
+            .heap
+            .get_object(id)?
+            .fields
+            .get(name)
+            .cloned()
+            .unwrap_or(JvmValue::Null))
+    }
+
+    pub(crate) fn set_field(
+        &mut self,
+        id: u32,
+        name: &str,
+        value: JvmValue,
+    ) -> Result<(), JvmError> {
+        self.heap
+            .get_object_mut(id)?
+            .fields
+            .insert(String::from(name), value);
+        Ok(())
+    }
+
+    // `java.lang.IllegalStateException: no entries`, as Throwable.toString.
+    pub(crate) fn throwable_string(&self, id: u32) -> Result<String, JvmError> {
+        let name = self.heap.get_object(id)?.class_name.replace('/', ".");
+        Ok(match self.field(id, "detailMessage")? {
+            JvmValue::StringRef(message) => format!("{}: {}", name, message),
+            _ => name,
+        })
+    }
+
+    fn element_string(&self, id: u32) -> Result<String, JvmError> {
+        let class_name = super::jvm_value_to_string(&self.field(id, "declaringClass")?);
+        let method_name = super::jvm_value_to_string(&self.field(id, "methodName")?);
+        let file = match self.field(id, "fileName")? {
+            JvmValue::StringRef(file) => Some(file),
+            _ => None,
+        };
+        let line = match self.field(id, "lineNumber")? {
+            JvmValue::Int(line) => u16::try_from(line).ok(),
+            _ => None,
+        };
+        Ok(frame_string(
+            &class_name,
+            &method_name,
+            file.as_deref(),
+            line,
+        ))
+    }
+
+    // What an error escaping the outermost frame looks like as a Java
+    // exception, or None when it isn't one Java could have caught.
+    pub(crate) fn uncaught(&self, e: &JvmError) -> Option<UncaughtException> {
+        if let JvmError::Thrown(_, id) = e {
+            return self.exception_view(*id, 0).ok();
+        }
+        let class_name = fault_class(e)?;
+        Some(UncaughtException {
+            class_name: class_name.replace('/', "."),
+            message: fault_message(e),
+            trace: self
+                .trace_lines()
+                .iter()
+                .map(|t| frame_string(&t.class_name, &t.method_name, t.file.as_deref(), t.line))
+                .collect(),
+            cause: None,
+        })
+    }
+
+    fn exception_view(&self, id: u32, nesting: usize) -> Result<UncaughtException, JvmError> {
+        let mut trace = Vec::new();
+        if let JvmValue::ArrayRef(array) = self.field(id, "stackTrace")? {
+            for element in &self.heap.get_array(array)?.elements {
+                if let JvmValue::ObjectRef(e) = element {
+                    trace.push(self.element_string(*e)?);
+                }
+            }
+        }
+        let cause = match self.field(id, "cause")? {
+            JvmValue::ObjectRef(cause) if nesting < MAX_NESTING => {
+                Some(Box::new(self.exception_view(cause, nesting + 1)?))
+            }
+            _ => None,
+        };
+        Ok(UncaughtException {
+            class_name: self.heap.get_object(id)?.class_name.replace('/', "."),
+            message: match self.field(id, "detailMessage")? {
+                JvmValue::StringRef(message) => Some(message),
+                _ => None,
+            },
+            trace,
+            cause,
+        })
+    }
+
+    fn print_line(&mut self, line: String) -> Result<(), JvmError> {
+        self.natives.call_native(
+            &mut self.heap,
+            "efi/Console",
+            "println",
+            "(Ljava/lang/String;)V",
+            &[JvmValue::StringRef(line)],
+        )?;
+        Ok(())
+    }
+
+    // As OpenJDK lays it out: the trace, then each suppressed exception one
+    // tab further in, then the cause chain.
+    fn print_stack_trace(
+        &mut self,
+        id: u32,
+        caption: &str,
+        indent: &str,
+        nesting: usize,
+    ) -> Result<(), JvmError> {
+        let header = format!("{}{}{}", indent, caption, self.throwable_string(id)?);
+        self.print_line(header)?;
+        if let JvmValue::ArrayRef(array) = self.field(id, "stackTrace")? {
+            let elements = self.heap.get_array(array)?.elements.clone();
+            for element in elements {
+                if let JvmValue::ObjectRef(e) = element {
+                    let line = format!("{}\tat {}", indent, self.element_string(e)?);
+                    self.print_line(line)?;
+                }
+            }
+        }
+        if nesting == MAX_NESTING {
+            return Ok(());
+        }
+        if let JvmValue::ArrayRef(array) = self.field(id, "suppressedExceptions")? {
+            let suppressed = self.heap.get_array(array)?.elements.clone();
+            let inner = format!("{}\t", indent);
+            for s in suppressed {
+                if let JvmValue::ObjectRef(s) = s {
+                    self.print_stack_trace(s, "Suppressed: ", &inner, nesting + 1)?;
+                }
+            }
+        }
+        if let JvmValue::ObjectRef(cause) = self.field(id, "cause")? {
+            self.print_stack_trace(cause, "Caused by: ", indent, nesting + 1)?;
+        }
+        Ok(())
+    }
+
+    // Raises a Java exception from one of the builtins.
+    pub(crate) fn throw(&mut self, class_name: &str, message: &str) -> JvmError {
+        let thrown = self
+            .new_throwable(class_name, Some(String::from(message)))
+            .and_then(|id| Ok(JvmError::Thrown(self.throwable_string(id)?, id)));
+        match thrown {
+            Ok(e) | Err(e) => e,
+        }
+    }
+
+    // Throwable's methods for exception classes that don't define them. The
+    // message, cause, suppressed list and stack trace live in the object's
+    // fields under the names OpenJDK uses.
+    pub(crate) fn handle_throwable(
+        &mut self,
+        method_name: &str,
+        descriptor: &str,
+        args: &[JvmValue],
+    ) -> Result<Option<Option<JvmValue>>, JvmError> {
+        let Some(JvmValue::ObjectRef(id)) = args.first() else {
+            return Ok(None);
+        };
+        let id = *id;
+        let result = match method_name {
+            // AssertionError(detail), as `assert cond : detail` builds it:
+            // the detail becomes the message, and the cause too when it is
+            // an exception.
+            "<init>"
+                if args.len() == 2 && self.heap.get_object(id)?.class_name == ASSERTION_ERROR =>
+            {
+                let detail = &args[1];
+                let message = match (descriptor, detail) {
+                    ("(Z)V", JvmValue::Int(b)) => {
+                        String::from(if *b != 0 { "true" } else { "false" })
+                    }
+                    ("(C)V", JvmValue::Int(c)) => char::from_u32(*c as u32)
+                        .map(String::from)
+                        .unwrap_or_default(),
+                    (_, JvmValue::ObjectRef(o))
+                        if self.is_throwable(&self.heap.get_object(*o)?.class_name) =>
+                    {
+                        self.set_field(id, "cause", detail.clone())?;
+                        self.throwable_string(*o)?
+                    }
+                    _ => super::jvm_value_to_string(detail),
+                };
+                let trace = self.stack_trace()?;
+                self.set_field(id, "detailMessage", JvmValue::StringRef(message))?;
+                self.set_field(id, "stackTrace", trace)?;
+                None
+            }
+            "<init>" => {
+                let mut message = JvmValue::Null;
+                let mut cause = None;
+                for arg in &args[1..] {
+                    match arg {
+                        JvmValue::StringRef(_) => message = arg.clone(),
+                        JvmValue::ObjectRef(_) => cause = Some(arg.clone()),
+                        _ => {}
+                    }
+                }
+                // Throwable(Throwable cause) takes the cause's toString() as
+                // its message.
+                if let (2, Some(JvmValue::ObjectRef(c))) = (args.len(), &cause) {
+                    message = JvmValue::StringRef(self.throwable_string(*c)?);
+                }
+                let trace = self.stack_trace()?;
+                self.set_field(id, "detailMessage", message)?;
+                self.set_field(id, "stackTrace", trace)?;
+                // Left unset until a constructor or initCause sets it, even
+                // to null, so initCause can refuse a second one.
+                if let Some(cause) = cause {
+                    self.set_field(id, "cause", cause)?;
+                }
+                None
+            }
+            "getMessage" | "getLocalizedMessage" => Some(self.field(id, "detailMessage")?),
+            "getCause" => Some(self.field(id, "cause")?),
+            "initCause" => {
+                let cause = args.get(1).cloned().unwrap_or(JvmValue::Null);
+                if self.heap.get_object(id)?.fields.contains_key("cause") {
+                    let message =
+                        format!("Can't overwrite cause of {}", self.throwable_string(id)?);
+                    return Err(self.throw("java/lang/IllegalStateException", &message));
+                }
+                if cause == JvmValue::ObjectRef(id) {
+                    return Err(self.throw(
+                        "java/lang/IllegalArgumentException",
+                        "Self-causation not permitted",
+                    ));
+                }
+                self.set_field(id, "cause", cause)?;
+                Some(JvmValue::ObjectRef(id))
+            }
+            "addSuppressed" => {
+                let suppressed = match args.get(1) {
+                    Some(JvmValue::ObjectRef(s)) if *s == id => {
+                        return Err(self.throw(
+                            "java/lang/IllegalArgumentException",
+                            "Self-suppression not permitted",
+                        ));
+                    }
+                    Some(JvmValue::ObjectRef(s)) => JvmValue::ObjectRef(*s),
+                    _ => return Err(JvmError::NullPointerException),
+                };
+                let mut list = match self.field(id, "suppressedExceptions")? {
+                    JvmValue::ArrayRef(array) => self.heap.get_array(array)?.elements.clone(),
+                    _ => Vec::new(),
+                };
+                list.push(suppressed);
+                let array = self.heap.alloc_array(String::from(THROWABLE), list.len())?;
+                self.heap.get_array_mut(array)?.elements = list;
+                self.set_field(id, "suppressedExceptions", JvmValue::ArrayRef(array))?;
+                None
+            }
+            "getSuppressed" => Some(match self.field(id, "suppressedExceptions")? {
+                JvmValue::Null => {
+                    JvmValue::ArrayRef(self.heap.alloc_array(String::from(THROWABLE), 0)?)
+                }
+                list => list,
+            }),
+            "toString" => Some(JvmValue::StringRef(self.throwable_string(id)?)),
+            "getStackTrace" => Some(match self.field(id, "stackTrace")? {
+                JvmValue::Null => {
+                    JvmValue::ArrayRef(self.heap.alloc_array(String::from(ELEMENT_CLASS), 0)?)
+                }
+                trace => trace,
+            }),
+            "setStackTrace" => {
+                self.set_field(
+                    id,
+                    "stackTrace",
+                    args.get(1).cloned().unwrap_or(JvmValue::Null),
+                )?;
+                None
+            }
+            "fillInStackTrace" => {
+                let trace = self.stack_trace()?;
+                self.set_field(id, "stackTrace", trace)?;
+                Some(JvmValue::ObjectRef(id))
+            }
+            "printStackTrace" => {
+                self.print_stack_trace(id, "", "", 0)?;
+                None
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(result))
+    }
+
+    pub(crate) fn handle_stack_trace_element(
+        &mut self,
+        method_name: &str,
+        args: &[JvmValue],
+    ) -> Result<Option<JvmValue>, JvmError> {
+        let id = args
+            .first()
+            .ok_or(JvmError::NullPointerException)?
+            .as_object_ref()?;
+        Ok(Some(match method_name {
+            "getClassName" => self.field(id, "declaringClass")?,
+            "getMethodName" => self.field(id, "methodName")?,
+            "getFileName" => self.field(id, "fileName")?,
+            "getLineNumber" => self.field(id, "lineNumber")?,
+            "toString" => JvmValue::StringRef(self.element_string(id)?),
+            _ => {
+                return Err(JvmError::MethodNotFound(format!(
+                    "{}::{}",
+                    ELEMENT_CLASS, method_name
+                )));
+            }
+        }))
+    }
+}
@@ -1,6 +1,99 @@
+//! A small `no_std` JVM interpreter.
+//!
+//! Build a [`Vm`] with [`VmBuilder`], hand it a [`NativeBridge`] for the
+//! methods it cannot run itself, and call [`Vm::run`] or [`Vm::run_main`];
+//! [`Vm::run_method`] takes a descriptor to pick between overloads and
+//! checks the arguments against it.
+//! An [`ExecutionObserver`] can be attached to watch execution, and
+//! [`Vm::set_profiling`] collects a [`ProfileReport`] of the hot methods.
+//! [`Vm::set_deterministic`] logs every nondeterministic native call to a
+//! [`NativeLog`] that [`Vm::replay`] can feed back to reproduce a run.
+//! After a failed run, [`Vm::frames`] holds a [`FrameSnapshot`] of each Java
+//! frame that was live, for a stack trace, and [`Vm::fault`] bundles them
+//! with the error as a [`VmFault`] that says what the VM was doing.
+//! [`Vm::set_uncaught_handler`] is shown each [`UncaughtException`] before
+//! the call that let it escape returns, and [`Vm::set_exit_hook`] is told
+//! of each `System.exit`.
+//! [`Vm::snapshot`] captures the loaded classes, statics and heap as a
+//! [`Snapshot`] that [`Vm::restore`] puts back, to skip loading again.
+//! [`Vm::redefine_class`] swaps a loaded class for a newer build of it.
+//! A [`ClassPath`] loads JARs and loose classes in a set order, the first
+//! definition of a class winning.
+//! [`Debugger`] is an observer that serves breakpoints and inspection over
+//! any line-based [`DebugPort`].
+//! Everything reachable from the crate root is the supported surface; the
+//! interpreter internals are private.
+
 #![no_std]
 extern crate alloc;
 
-pub mod heap;
-pub mod interpreter;
-pub mod native;
+mod builder;
+mod classpath;
+mod debugger;
+mod heap;
+mod interpreter;
+mod native;
+mod observer;
+mod profile;
+mod replay;
+mod snapshot;
+mod trace;
+
+pub use builder::{
+    ClassSource,
+    Limits,
+    VmBuilder,
+};
+pub use classpath::{
+    ClassPath,
+    LoadReport,
+    LoadedClass,
+};
+pub use debugger::{
+    DebugPort,
+    Debugger,
+};
+pub use heap::{
+    DumpEntry,
+    Heap,
+    HeapDump,
+    HeapRef,
+    JvmArray,
+    JvmObject,
+};
+pub use interpreter::{
+    Outcome,
+    Vm,
+    jvm_value_to_string,
+};
+pub use native::{
+    NativeArgs,
+    NativeBridge,
+    NoopNatives,
+    Upcall,
+};
+pub use observer::{
+    ExecutionObserver,
+    FrameView,
+    Location,
+    NoObserver,
+};
+pub use profile::{
+    MethodProfile,
+    ProfileReport,
+};
+pub use replay::{
+    LogEntry,
+    NativeLog,
+};
+pub use shared::classfile::ClassFile;
+pub use shared::types::{
+    JvmError,
+    JvmValue,
+};
+pub use snapshot::Snapshot;
+pub use trace::{
+    FrameSnapshot,
+    UncaughtException,
+    VmFault,
+};
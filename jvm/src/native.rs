@@ -1,16 +1,71 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+use shared::classfile;
 use shared::types::{
     JvmError,
     JvmValue,
 };
 
+use crate::heap::Heap;
+use crate::replay::LogEntry;
+
+/// A Java method a native wants run; see [`NativeBridge::take_upcall`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upcall {
+    pub class_name: String,
+    pub method_name: String,
+    /// Picks the overload and checks `args`, as with
+    /// [`Vm::execute_method`](crate::Vm::execute_method).
+    pub descriptor: String,
+    pub args: Vec<JvmValue>,
+}
+
 pub trait NativeBridge {
+    /// Runs a native method. `heap` is the guest's, for reading array and
+    /// object arguments and building results such as a `byte[]`.
     fn call_native(
         &mut self,
+        heap: &mut Heap,
         class_name: &str,
         method_name: &str,
         descriptor: &str,
         args: &[JvmValue],
     ) -> Result<Option<JvmValue>, JvmError>;
+
+    /// A monotonic clock in microseconds, used to time methods while the
+    /// profiler is on. Bridges without a clock keep the default.
+    fn now_micros(&mut self) -> Option<u64> {
+        None
+    }
+
+    /// Whether a native's result can differ between two runs of the same
+    /// guest, as with the clock, random numbers or key input. In
+    /// deterministic mode the VM records these calls and can replay them;
+    /// see [`Vm::set_deterministic`](crate::Vm::set_deterministic).
+    fn nondeterministic(&self, _class_name: &str, _method_name: &str) -> bool {
+        false
+    }
+
+    /// Told about each call deterministic mode logs, recorded or replayed,
+    /// so a bridge can keep the log somewhere that outlives the VM, such as
+    /// a file saved before an OS loader takes over.
+    fn logged(&mut self, _entry: &LogEntry) {}
+
+    /// Java calls the bridge wants made, such as a callback for a timer
+    /// that fired. The VM asks after every native call and runs each one,
+    /// nested in the guest's current call, before the native's caller
+    /// resumes; an exception the callback doesn't catch surfaces from that
+    /// native call. A bridge can't call into the VM directly because the
+    /// VM owns it.
+    fn take_upcall(&mut self) -> Option<Upcall> {
+        None
+    }
+
+    /// What an upcall returned.
+    fn upcall_returned(&mut self, _upcall: &Upcall, _value: Option<JvmValue>) {}
 }
 
 pub struct NoopNatives;
@@ -18,6 +73,7 @@ pub struct NoopNatives;
 impl NativeBridge for NoopNatives {
     fn call_native(
         &mut self,
+        _heap: &mut Heap,
         class_name: &str,
         method_name: &str,
         _descriptor: &str,
@@ -30,3 +86,130 @@ impl NativeBridge for NoopNatives {
         )))
     }
 }
+
+/// A native's arguments with typed accessors, checked against its
+/// descriptor: asking for an `int` where the method declares a `String`,
+/// or getting a value of the wrong kind, is a `TypeError` naming the
+/// method rather than a silent default. Derefs to the raw values.
+pub struct NativeArgs<'a> {
+    method_name: &'a str,
+    descriptor: &'a str,
+    values: &'a [JvmValue],
+}
+
+impl<'a> NativeArgs<'a> {
+    /// An empty `descriptor` skips the declared-type check.
+    pub fn new(method_name: &'a str, descriptor: &'a str, values: &'a [JvmValue]) -> Self {
+        Self {
+            method_name,
+            descriptor,
+            values,
+        }
+    }
+
+    /// An argument of any type, for natives such as `print` that
+    /// `PrintStream`'s overloads all reach; only a missing one is an error.
+    pub fn value(&self, i: usize) -> Result<&'a JvmValue, JvmError> {
+        self.get(i, "BCDFIJLSZ[", "a value")
+    }
+
+    pub fn int(&self, i: usize) -> Result<i32, JvmError> {
+        match self.get(i, "BCISZ", "int")? {
+            JvmValue::Int(v) => Ok(*v),
+            v => Err(self.mismatch(i, "int", v)),
+        }
+    }
+
+    pub fn boolean(&self, i: usize) -> Result<bool, JvmError> {
+        match self.get(i, "Z", "boolean")? {
+            JvmValue::Int(v) => Ok(*v != 0),
+            v => Err(self.mismatch(i, "boolean", v)),
+        }
+    }
+
+    pub fn long(&self, i: usize) -> Result<i64, JvmError> {
+        match self.get(i, "J", "long")? {
+            JvmValue::Long(v) => Ok(*v),
+            v => Err(self.mismatch(i, "long", v)),
+        }
+    }
+
+    pub fn float(&self, i: usize) -> Result<f32, JvmError> {
+        match self.get(i, "F", "float")? {
+            JvmValue::Float(v) => Ok(*v),
+            v => Err(self.mismatch(i, "float", v)),
+        }
+    }
+
+    pub fn double(&self, i: usize) -> Result<f64, JvmError> {
+        match self.get(i, "D", "double")? {
+            JvmValue::Double(v) => Ok(*v),
+            v => Err(self.mismatch(i, "double", v)),
+        }
+    }
+
+    /// A `String` argument; null is an error.
+    pub fn string(&self, i: usize) -> Result<&'a str, JvmError> {
+        self.opt_string(i)?.ok_or_else(|| self.error(i, "is null"))
+    }
+
+    pub fn opt_string(&self, i: usize) -> Result<Option<&'a str>, JvmError> {
+        match self.get(i, "L", "String")? {
+            JvmValue::StringRef(s) => Ok(Some(s)),
+            JvmValue::Null => Ok(None),
+            v => Err(self.mismatch(i, "String", v)),
+        }
+    }
+
+    /// An array argument's heap id, `None` for null.
+    pub fn array(&self, i: usize) -> Result<Option<u32>, JvmError> {
+        match self.get(i, "[", "array")? {
+            JvmValue::ArrayRef(id) => Ok(Some(*id)),
+            JvmValue::Null => Ok(None),
+            v => Err(self.mismatch(i, "array", v)),
+        }
+    }
+
+    /// The contents of a `byte[]` argument; null is an error.
+    pub fn byte_array(&self, heap: &Heap, i: usize) -> Result<Vec<u8>, JvmError> {
+        match self.array(i)? {
+            Some(id) => heap.bytes(id),
+            None => Err(self.error(i, "is null")),
+        }
+    }
+
+    // The value at `i`, once the descriptor (when there is one) agrees that
+    // parameter `i` starts with one of `kinds`.
+    fn get(&self, i: usize, kinds: &str, wanted: &str) -> Result<&'a JvmValue, JvmError> {
+        if !self.descriptor.is_empty() {
+            let params = classfile::descriptor_params(self.descriptor).unwrap_or_default();
+            match params.get(i) {
+                Some(p) if kinds.contains(&p[..1]) => {}
+                Some(p) => return Err(self.error(i, &format!("is declared {}, not {}", p, wanted))),
+                None => return Err(self.error(i, "is not declared")),
+            }
+        }
+        self.values
+            .get(i)
+            .ok_or_else(|| self.error(i, "is missing"))
+    }
+
+    fn mismatch(&self, i: usize, wanted: &str, got: &JvmValue) -> JvmError {
+        self.error(i, &format!("should be {}, got {:?}", wanted, got))
+    }
+
+    fn error(&self, i: usize, what: &str) -> JvmError {
+        JvmError::TypeError(format!(
+            "{}{} argument {} {}",
+            self.method_name, self.descriptor, i, what
+        ))
+    }
+}
+
+impl Deref for NativeArgs<'_> {
+    type Target = [JvmValue];
+
+    fn deref(&self) -> &[JvmValue] {
+        self.values
+    }
+}
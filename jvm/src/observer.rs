@@ -0,0 +1,56 @@
+use shared::types::JvmValue;
+
+use crate::heap::Heap;
+
+/// Where an observed event happened.
+pub struct Location<'a> {
+    pub class_name: &'a str,
+    pub method_name: &'a str,
+    pub descriptor: &'a str,
+    /// Bytecode offset within the method; 0 for invoke and return events.
+    pub pc: usize,
+}
+
+/// The running frame's values, handed to [`ExecutionObserver::on_frame`].
+pub struct FrameView<'a> {
+    pub locals: &'a [JvmValue],
+    /// The operand stack, bottom first.
+    pub stack: &'a [JvmValue],
+    pub heap: &'a Heap,
+}
+
+/// Hooks the interpreter calls while running guest code, for tracing,
+/// coverage or single-stepping tools. Every method defaults to a no-op.
+///
+/// Hooks only fire for methods of loaded classes; calls that go straight to
+/// the [`NativeBridge`](crate::NativeBridge) are not reported.
+pub trait ExecutionObserver {
+    /// When `false` the interpreter skips every hook at compile time, so an
+    /// unobserved VM pays nothing for them.
+    const ENABLED: bool = true;
+
+    /// Called before each instruction runs.
+    fn on_instruction(&mut self, _at: &Location<'_>, _opcode: u8) {}
+
+    /// Called right after [`on_instruction`](Self::on_instruction) with the
+    /// frame's locals, operand stack and the heap. A debugger can block here
+    /// while a user looks around.
+    fn on_frame(&mut self, _at: &Location<'_>, _frame: &FrameView<'_>) {}
+
+    /// Called on entry to a method, with its arguments (receiver first).
+    fn on_invoke(&mut self, _method: &Location<'_>, _args: &[JvmValue]) {}
+
+    /// Called when a method returns normally.
+    fn on_return(&mut self, _method: &Location<'_>, _value: Option<&JvmValue>) {}
+
+    /// Called when an exception of class `exception` is raised at `at`,
+    /// before a handler is looked for.
+    fn on_throw(&mut self, _at: &Location<'_>, _exception: &str) {}
+}
+
+/// The default observer; compiles all hooks away.
+pub struct NoObserver;
+
+impl ExecutionObserver for NoObserver {
+    const ENABLED: bool = false;
+}
@@ -0,0 +1,175 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Counters for one method in a [`ProfileReport`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodProfile {
+    /// `Class.method` with its descriptor; bridge natives have no descriptor.
+    pub name: String,
+    /// The call went to the [`NativeBridge`](crate::NativeBridge).
+    pub native: bool,
+    pub calls: u64,
+    /// Bytecodes run in the method itself, not in its callees.
+    pub bytecodes: u64,
+    /// Time spent in the method and its callees, in microseconds. Recursive
+    /// calls are counted once, at the outermost one, and a method still
+    /// running when the report is taken has no time yet.
+    pub micros: u64,
+}
+
+/// What the profiler saw since it was turned on, hottest method first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileReport {
+    pub methods: Vec<MethodProfile>,
+    /// `false` when the bridge has no clock and every `micros` is 0; the
+    /// methods are then ordered by bytecodes instead.
+    pub timed: bool,
+}
+
+impl ProfileReport {
+    pub fn hottest(&self, n: usize) -> &[MethodProfile] {
+        &self.methods[..n.min(self.methods.len())]
+    }
+
+    /// One line naming the three hottest methods, for an on-screen status.
+    pub fn summary(&self) -> String {
+        let mut s = format!("{} methods", self.methods.len());
+        for m in self.hottest(3) {
+            if self.timed {
+                s.push_str(&format!("; {} {}us", short_name(&m.name), m.micros));
+            } else {
+                s.push_str(&format!("; {} {}bc", short_name(&m.name), m.bytecodes));
+            }
+        }
+        s
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>10} {:>8} {:>10}  method", "us", "calls", "bytecodes")?;
+        for m in &self.methods {
+            let time = if self.timed {
+                format!("{}", m.micros)
+            } else {
+                String::from("-")
+            };
+            let native = if m.native { " [native]" } else { "" };
+            writeln!(
+                f,
+                "{:>10} {:>8} {:>10}  {}{}",
+                time, m.calls, m.bytecodes, m.name, native
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Drops the package and descriptor: `efi/Graphics.drawText` -> `Graphics.drawText`.
+fn short_name(name: &str) -> &str {
+    let name = name.split('(').next().unwrap_or(name);
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+#[derive(Default)]
+struct Counters {
+    calls: u64,
+    bytecodes: u64,
+    micros: u64,
+    // Open activations, so recursion only adds its outermost call's time.
+    active: u32,
+}
+
+impl Counters {
+    fn enter(&mut self) {
+        self.calls += 1;
+        self.active += 1;
+    }
+
+    fn leave(&mut self, elapsed: Option<u64>) {
+        self.active = self.active.saturating_sub(1);
+        if self.active == 0
+            && let Some(us) = elapsed
+        {
+            self.micros += us;
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Profiler {
+    methods: BTreeMap<(usize, usize), Counters>,
+    natives: BTreeMap<String, Counters>,
+    timed: bool,
+}
+
+impl Profiler {
+    pub(crate) fn enter_method(&mut self, class_idx: usize, method_idx: usize) {
+        self.methods
+            .entry((class_idx, method_idx))
+            .or_default()
+            .enter();
+    }
+
+    pub(crate) fn leave_method(
+        &mut self,
+        class_idx: usize,
+        method_idx: usize,
+        elapsed: Option<u64>,
+    ) {
+        self.timed |= elapsed.is_some();
+        if let Some(c) = self.methods.get_mut(&(class_idx, method_idx)) {
+            c.leave(elapsed);
+        }
+    }
+
+    pub(crate) fn add_bytecodes(&mut self, class_idx: usize, method_idx: usize, n: u64) {
+        if let Some(c) = self.methods.get_mut(&(class_idx, method_idx)) {
+            c.bytecodes += n;
+        }
+    }
+
+    pub(crate) fn record_native(&mut self, name: &str, elapsed: Option<u64>) {
+        self.timed |= elapsed.is_some();
+        let c = match self.natives.get_mut(name) {
+            Some(c) => c,
+            None => self.natives.entry(String::from(name)).or_default(),
+        };
+        c.enter();
+        c.leave(elapsed);
+    }
+
+    /// Builds the report, naming guest methods with `name(class, method)`.
+    pub(crate) fn report(&self, name: impl Fn(usize, usize) -> String) -> ProfileReport {
+        let mut methods: Vec<MethodProfile> = self
+            .methods
+            .iter()
+            .map(|(&(ci, mi), c)| MethodProfile {
+                name: name(ci, mi),
+                native: false,
+                calls: c.calls,
+                bytecodes: c.bytecodes,
+                micros: c.micros,
+            })
+            .chain(self.natives.iter().map(|(n, c)| MethodProfile {
+                name: n.clone(),
+                native: true,
+                calls: c.calls,
+                bytecodes: 0,
+                micros: c.micros,
+            }))
+            .collect();
+        if self.timed {
+            methods.sort_by(|a, b| b.micros.cmp(&a.micros).then(b.calls.cmp(&a.calls)));
+        } else {
+            methods.sort_by(|a, b| b.bytecodes.cmp(&a.bytecodes).then(b.calls.cmp(&a.calls)));
+        }
+        ProfileReport {
+            methods,
+            timed: self.timed,
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+
+/// One nondeterministic native call and what it returned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    /// Instructions the VM had executed when the call was made.
+    pub at: u64,
+    pub class_name: String,
+    pub method_name: String,
+    pub value: Option<JvmValue>,
+}
+
+/// The nondeterministic native calls of a deterministic run, in order.
+///
+/// The text form has one call per line,
+/// `<at> <class>.<method> <value>`, where the value is `void`, `null`,
+/// `int 5`, `long 5`, `float <bits>`, `double <bits>` or `string "..."`,
+/// so a log taken on firmware can be replayed under the host runner.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NativeLog {
+    pub entries: Vec<LogEntry>,
+}
+
+impl NativeLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, JvmError> {
+        let mut entries = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad = || JvmError::IoError(format!("replay log line {}: {}", n + 1, line));
+            let mut parts = line.splitn(3, ' ');
+            let at = parts.next().and_then(|s| s.parse().ok()).ok_or_else(bad)?;
+            let (class_name, method_name) = parts
+                .next()
+                .and_then(|s| s.rsplit_once('.'))
+                .ok_or_else(bad)?;
+            let value = parse_value(parts.next().unwrap_or("")).ok_or_else(bad)?;
+            entries.push(LogEntry {
+                at,
+                class_name: String::from(class_name),
+                method_name: String::from(method_name),
+                value,
+            });
+        }
+        Ok(Self { entries })
+    }
+}
+
+/// One line of the log's text form, without the newline.
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}.{} ", self.at, self.class_name, self.method_name)?;
+        match &self.value {
+            None => write!(f, "void"),
+            Some(JvmValue::Null) => write!(f, "null"),
+            Some(JvmValue::Int(v)) => write!(f, "int {}", v),
+            Some(JvmValue::Long(v)) => write!(f, "long {}", v),
+            Some(JvmValue::Float(v)) => write!(f, "float {}", v.to_bits()),
+            Some(JvmValue::Double(v)) => write!(f, "double {}", v.to_bits()),
+            Some(JvmValue::StringRef(s)) => write!(f, "string {:?}", s),
+            // Heap references mean nothing in another run; the natives
+            // classed nondeterministic don't return them.
+            Some(_) => write!(f, "null"),
+        }
+    }
+}
+
+impl fmt::Display for NativeLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for e in &self.entries {
+            writeln!(f, "{}", e)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_value(s: &str) -> Option<Option<JvmValue>> {
+    let (kind, rest) = s.split_once(' ').unwrap_or((s, ""));
+    let value = match kind {
+        "void" => return Some(None),
+        "null" => JvmValue::Null,
+        "int" => JvmValue::Int(rest.parse().ok()?),
+        "long" => JvmValue::Long(rest.parse().ok()?),
+        "float" => JvmValue::Float(f32::from_bits(rest.parse().ok()?)),
+        "double" => JvmValue::Double(f64::from_bits(rest.parse().ok()?)),
+        "string" => JvmValue::StringRef(unquote(rest)?),
+        _ => return None,
+    };
+    Some(Some(value))
+}
+
+// Undoes the `{:?}` quoting the log is written with.
+fn unquote(s: &str) -> Option<String> {
+    let inner = s.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            '0' => out.push('\0'),
+            'u' => {
+                let rest = chars.as_str().strip_prefix('{')?;
+                let (hex, tail) = rest.split_once('}')?;
+                out.push(char::from_u32(u32::from_str_radix(hex, 16).ok()?)?);
+                chars = tail.chars();
+            }
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+/// Where a deterministic VM gets its nondeterministic native results from.
+pub(crate) struct Determinism {
+    pub(crate) log: NativeLog,
+    // Entries still to be injected, the next one last; once they run out
+    // calls go to the bridge again and are recorded.
+    pub(crate) replay: Vec<LogEntry>,
+}
+
+impl Determinism {
+    pub(crate) fn record() -> Self {
+        Self {
+            log: NativeLog::new(),
+            replay: Vec::new(),
+        }
+    }
+
+    pub(crate) fn replay(log: NativeLog) -> Self {
+        let mut replay = log.entries;
+        replay.reverse();
+        Self {
+            log: NativeLog::new(),
+            replay,
+        }
+    }
+
+    /// The logged entry for this call, or an error if the run has drifted
+    /// from the recording. `None` once the log is used up.
+    pub(crate) fn inject(
+        &mut self,
+        at: u64,
+        class_name: &str,
+        method_name: &str,
+    ) -> Result<Option<&LogEntry>, JvmError> {
+        let Some(next) = self.replay.pop() else {
+            return Ok(None);
+        };
+        if next.at != at || next.class_name != class_name || next.method_name != method_name {
+            return Err(JvmError::NativeMethodError(format!(
+                "replay diverged: {}.{} at {} but the log has {}.{} at {}",
+                class_name, method_name, at, next.class_name, next.method_name, next.at
+            )));
+        }
+        self.log.entries.push(next);
+        Ok(self.log.entries.last())
+    }
+
+    pub(crate) fn record_call(
+        &mut self,
+        at: u64,
+        class_name: &str,
+        method_name: &str,
+        value: Option<JvmValue>,
+    ) -> &LogEntry {
+        self.log.entries.push(LogEntry {
+            at,
+            class_name: String::from(class_name),
+            method_name: String::from(method_name),
+            value,
+        });
+        &self.log.entries[self.log.entries.len() - 1]
+    }
+}
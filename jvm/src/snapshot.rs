@@ -0,0 +1,228 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+
+use crate::heap::Heap;
+use crate::interpreter::Vm;
+use crate::native::NativeBridge;
+use crate::observer::ExecutionObserver;
+
+const MAGIC: &[u8; 4] = b"DKVM";
+const VERSION: u8 = 1;
+
+/// Loaded classes, statics and heap of a [`Vm`], as taken by
+/// [`Vm::snapshot`] and put back by [`Vm::restore`].
+///
+/// The bytes are self-contained: they can be kept anywhere (a file, a
+/// variable) and turned back into a snapshot with [`Snapshot::from_bytes`].
+/// Classes are stored in class file form and the rest in a little-endian
+/// layout private to this crate's version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    bytes: Vec<u8>,
+}
+
+impl Snapshot {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl<N: NativeBridge, O: ExecutionObserver> Vm<N, O> {
+    /// Captures the VM between runs. Natives, the observer, limits and the
+    /// profiler aren't part of it.
+    pub fn snapshot(&self) -> Result<Snapshot, JvmError> {
+        let mut w = Writer::default();
+        w.bytes(MAGIC);
+        w.u8(VERSION);
+        w.u32(self.classes.len() as u32);
+        for class in &self.classes {
+            let data = class.to_bytes()?;
+            w.u32(data.len() as u32);
+            w.bytes(&data);
+        }
+        w.u32(self.statics.len() as u32);
+        for (key, value) in &self.statics {
+            w.str(key);
+            w.value(value);
+        }
+        self.heap.write(&mut w);
+        Ok(Snapshot { bytes: w.out })
+    }
+
+    /// Puts back what [`Vm::snapshot`] took, replacing every loaded class,
+    /// static and heap entry. On error the VM is left as it was.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), JvmError> {
+        let mut r = Reader {
+            data: &snapshot.bytes,
+            pos: 0,
+        };
+        if r.take(4)? != MAGIC || r.u8()? != VERSION {
+            return Err(JvmError::IoError(String::from("not a VM snapshot")));
+        }
+        let mut classes = Vec::new();
+        for _ in 0..r.u32()? {
+            let len = r.u32()? as usize;
+            classes.push(self.parse_class(r.take(len)?)?);
+        }
+        let mut statics = BTreeMap::new();
+        for _ in 0..r.u32()? {
+            let key = r.string()?;
+            statics.insert(key, r.value()?);
+        }
+        let heap = Heap::read(&mut r)?;
+
+        self.classes.clear();
+        self.class_map.clear();
+        self.method_index.clear();
+        self.code_cache.clear();
+        // The Class objects were on the heap being replaced.
+        self.class_objects.clear();
+        // parse_class has validated them, so none fails from here on.
+        for class in classes {
+            self.load_class(class)?;
+        }
+        self.statics = statics;
+        self.heap = heap;
+        self.unwound.clear();
+        self.branches = 0;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Writer {
+    out: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn u8(&mut self, v: u8) {
+        self.out.push(v);
+    }
+
+    pub(crate) fn u32(&mut self, v: u32) {
+        self.out.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, v: u64) {
+        self.out.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub(crate) fn bytes(&mut self, v: &[u8]) {
+        self.out.extend_from_slice(v);
+    }
+
+    pub(crate) fn str(&mut self, s: &str) {
+        self.u32(s.len() as u32);
+        self.bytes(s.as_bytes());
+    }
+
+    pub(crate) fn value(&mut self, v: &JvmValue) {
+        match v {
+            JvmValue::Int(i) => {
+                self.u8(0);
+                self.u32(*i as u32);
+            }
+            JvmValue::Long(l) => {
+                self.u8(1);
+                self.u64(*l as u64);
+            }
+            JvmValue::Float(f) => {
+                self.u8(2);
+                self.u32(f.to_bits());
+            }
+            JvmValue::Double(d) => {
+                self.u8(3);
+                self.u64(d.to_bits());
+            }
+            JvmValue::Null => self.u8(4),
+            JvmValue::ObjectRef(id) => {
+                self.u8(5);
+                self.u32(*id);
+            }
+            JvmValue::ArrayRef(id) => {
+                self.u8(6);
+                self.u32(*id);
+            }
+            JvmValue::StringRef(s) => {
+                self.u8(7);
+                self.str(s);
+            }
+            JvmValue::ReturnAddress(pc) => {
+                self.u8(8);
+                self.u64(*pc as u64);
+            }
+        }
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8], JvmError> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&e| e <= self.data.len())
+            .ok_or_else(|| JvmError::IoError(String::from("truncated VM snapshot")))?;
+        let out = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, JvmError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, JvmError> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, JvmError> {
+        let mut b = [0u8; 8];
+        b.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(b))
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String, JvmError> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        core::str::from_utf8(bytes)
+            .map(String::from)
+            .map_err(|_| JvmError::IoError(String::from("bad string in VM snapshot")))
+    }
+
+    pub(crate) fn value(&mut self) -> Result<JvmValue, JvmError> {
+        Ok(match self.u8()? {
+            0 => JvmValue::Int(self.u32()? as i32),
+            1 => JvmValue::Long(self.u64()? as i64),
+            2 => JvmValue::Float(f32::from_bits(self.u32()?)),
+            3 => JvmValue::Double(f64::from_bits(self.u64()?)),
+            4 => JvmValue::Null,
+            5 => JvmValue::ObjectRef(self.u32()?),
+            6 => JvmValue::ArrayRef(self.u32()?),
+            7 => JvmValue::StringRef(self.string()?),
+            8 => JvmValue::ReturnAddress(self.u64()? as usize),
+            tag => {
+                return Err(JvmError::IoError(format!(
+                    "bad value tag {} in VM snapshot",
+                    tag
+                )));
+            }
+        })
+    }
+}
@@ -0,0 +1,162 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use shared::opcodes;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+
+// Operand stack entries a fault shows, from the top.
+const STACK_SHOWN: usize = 4;
+// Characters of a string value a fault shows.
+const STRING_SHOWN: usize = 24;
+
+/// One Java frame as it stood when a run failed; see [`Vm::frames`].
+///
+/// [`Vm::frames`]: crate::Vm::frames
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameSnapshot {
+    pub class_name: String,
+    pub method_name: String,
+    pub descriptor: String,
+    /// Bytecode offset of the instruction that was running: the failing one
+    /// in the innermost frame, the call in the others.
+    pub pc: usize,
+    /// The opcode at `pc`.
+    pub opcode: u8,
+    /// Source position from the class's debug attributes, when present.
+    pub source_file: Option<String>,
+    pub line: Option<u16>,
+    pub locals: Vec<JvmValue>,
+    /// The operand stack, bottom first.
+    pub stack: Vec<JvmValue>,
+}
+
+/// A `Throwable.printStackTrace` style line, such as
+/// `at BootMenu.drawMenu(BootMenu.java:212)`, falling back to the bytecode
+/// offset when the class has no line numbers.
+impl fmt::Display for FrameSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at ")?;
+        for c in self.class_name.chars() {
+            write!(f, "{}", if c == '/' { '.' } else { c })?;
+        }
+        write!(f, ".{}(", self.method_name)?;
+        match (&self.source_file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}", file, line)?,
+            (Some(file), None) => write!(f, "{} pc={}", file, self.pc)?,
+            (None, _) => write!(f, "{} pc={}", self.descriptor, self.pc)?,
+        }
+        write!(f, ")")
+    }
+}
+
+/// An error returned by a call into the VM with the frames it unwound, as
+/// made by [`Vm::fault`].
+///
+/// Displayed as the error, then the innermost frame's method, pc, opcode
+/// and top of the operand stack, then the stack trace:
+///
+/// ```text
+/// TypeError: expected int
+///   in BootMenu.drawMenu(IIIIII)V pc=42 op=iadd stack=[3, null]
+///   at BootMenu.drawMenu(BootMenu.java:212)
+///   at BootMenu.main(BootMenu.java:80)
+/// ```
+///
+/// [`Vm::fault`]: crate::Vm::fault
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmFault {
+    pub error: JvmError,
+    /// Innermost first; empty when the error didn't come from bytecode.
+    pub frames: Vec<FrameSnapshot>,
+}
+
+impl fmt::Display for VmFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        if let Some(top) = self.frames.first() {
+            write!(
+                f,
+                "\n  in {}.{}{} pc={} op={} stack=[",
+                top.class_name,
+                top.method_name,
+                top.descriptor,
+                top.pc,
+                opcodes::name(top.opcode)
+            )?;
+            let skip = top.stack.len().saturating_sub(STACK_SHOWN);
+            if skip > 0 {
+                write!(f, "..., ")?;
+            }
+            for (i, value) in top.stack[skip..].iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write_value(f, value)?;
+            }
+            write!(f, "]")?;
+        }
+        for frame in &self.frames {
+            write!(f, "\n  {}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+/// A Java exception no frame caught, as the handler set with
+/// [`Vm::set_uncaught_handler`] sees it. VM faults Java could have caught,
+/// such as a null dereference, show up as the exception they stand for.
+///
+/// Displayed the way `printStackTrace` prints it, causes included.
+///
+/// [`Vm::set_uncaught_handler`]: crate::Vm::set_uncaught_handler
+#[derive(Debug, Clone, PartialEq)]
+pub struct UncaughtException {
+    /// Dotted, as in `java.lang.IllegalStateException`.
+    pub class_name: String,
+    pub message: Option<String>,
+    /// Innermost first, each like `BootMenu.main(BootMenu.java:80)`.
+    pub trace: Vec<String>,
+    pub cause: Option<Box<UncaughtException>>,
+}
+
+impl fmt::Display for UncaughtException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut exc = Some(self);
+        let mut caption = "";
+        while let Some(e) = exc {
+            write!(f, "{}{}", caption, e.class_name)?;
+            if let Some(message) = &e.message {
+                write!(f, ": {}", message)?;
+            }
+            for line in &e.trace {
+                write!(f, "\n\tat {}", line)?;
+            }
+            exc = e.cause.as_deref();
+            caption = "\nCaused by: ";
+        }
+        Ok(())
+    }
+}
+
+fn write_value(f: &mut fmt::Formatter<'_>, value: &JvmValue) -> fmt::Result {
+    match value {
+        JvmValue::Int(v) => write!(f, "{}", v),
+        JvmValue::Long(v) => write!(f, "{}L", v),
+        JvmValue::Float(v) => write!(f, "{}f", v),
+        JvmValue::Double(v) => write!(f, "{}d", v),
+        JvmValue::Null => write!(f, "null"),
+        JvmValue::ObjectRef(id) => write!(f, "obj#{}", id),
+        JvmValue::ArrayRef(id) => write!(f, "arr#{}", id),
+        JvmValue::StringRef(s) if s.chars().count() > STRING_SHOWN => {
+            let cut: String = s.chars().take(STRING_SHOWN).collect();
+            write!(f, "{:?}...", cut)
+        }
+        JvmValue::StringRef(s) => write!(f, "{:?}", s),
+        JvmValue::ReturnAddress(pc) => write!(f, "ret@{}", pc),
+    }
+}
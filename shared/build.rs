@@ -7,6 +7,10 @@ use std::{
 };
 
 fn main() {
+    // Only the crates that draw text ask for the font.
+    if env::var_os("CARGO_FEATURE_FONT").is_none() {
+        return;
+    }
     let manifest = env::var("CARGO_MANIFEST_DIR").unwrap();
     let out = PathBuf::from(env::var("OUT_DIR").unwrap());
     let dest = out.join("font_data.rs");
@@ -52,15 +56,15 @@ fn parse_bdf(content: &str) -> BdfFont {
     while let Some(line) = lines.next() {
         let line = line.trim();
 
-        if let Some(rest) = line.strip_prefix("FONT_ASCENT ") {
-            if let Ok(v) = rest.trim().parse::<i32>() {
-                font_ascent = v;
-            }
+        if let Some(rest) = line.strip_prefix("FONT_ASCENT ")
+            && let Ok(v) = rest.trim().parse::<i32>()
+        {
+            font_ascent = v;
         }
-        if let Some(rest) = line.strip_prefix("FONT_DESCENT ") {
-            if let Ok(v) = rest.trim().parse::<i32>() {
-                font_descent = v;
-            }
+        if let Some(rest) = line.strip_prefix("FONT_DESCENT ")
+            && let Ok(v) = rest.trim().parse::<i32>()
+        {
+            font_descent = v;
         }
 
         if line.starts_with("STARTCHAR") {
@@ -112,10 +116,10 @@ fn parse_bdf(content: &str) -> BdfFont {
                 }
             }
 
-            if let (Some(enc), Some(dw)) = (encoding, glyph_dw) {
-                if enc == 0x20 {
-                    default_dwidth = dw;
-                }
+            if let (Some(enc), Some(dw)) = (encoding, glyph_dw)
+                && enc == 0x20
+            {
+                default_dwidth = dw;
             }
 
             if let Some(enc) = encoding {
@@ -186,7 +190,12 @@ fn generate_from_bdf(content: &str, dest: &PathBuf) {
     writeln!(f, "pub const GLYPH_W: usize = {};", cell_w).unwrap();
     writeln!(f, "pub const GLYPH_H: usize = {};", cell_h).unwrap();
     writeln!(f).unwrap();
-    writeln!(f, "pub(super) static FONT_DATA: [[u16; {}]; 95] = [", cell_h).unwrap();
+    writeln!(
+        f,
+        "pub(super) static FONT_DATA: [[u16; {}]; 95] = [",
+        cell_h
+    )
+    .unwrap();
 
     for (i, cell) in cells.iter().enumerate() {
         let ch = (0x20 + i) as u8 as char;
@@ -0,0 +1,188 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+/// Where Boot Loader Specification type #1 entries live on the ESP.
+pub const ENTRIES_DIR: &str = "\\loader\\entries";
+
+/// One `\loader\entries\*.conf` file: a kernel with the EFI stub, its
+/// initrds and command line.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoaderEntry {
+    /// The file name less `.conf`, which identifies the entry.
+    pub id: String,
+    pub title: Option<String>,
+    pub version: Option<String>,
+    /// The kernel, as an ESP path with `\` separators.
+    pub linux: Option<String>,
+    pub initrd: Vec<String>,
+    pub options: Option<String>,
+}
+
+impl LoaderEntry {
+    /// Reads the `key value` lines of `text`; unknown keys and comments are
+    /// skipped. Paths are turned into ESP paths and `options` lines are
+    /// joined with spaces, as the specification has it.
+    pub fn parse(id: &str, text: &str) -> LoaderEntry {
+        let mut entry = LoaderEntry {
+            id: String::from(id),
+            ..LoaderEntry::default()
+        };
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.split_once(|c: char| c.is_ascii_whitespace()) {
+                Some((key, value)) => (key, value.trim()),
+                None => (line, ""),
+            };
+            if value.is_empty() {
+                continue;
+            }
+            match key {
+                "title" => entry.title = Some(String::from(value)),
+                "version" => entry.version = Some(String::from(value)),
+                "linux" => entry.linux = Some(esp_path(value)),
+                "initrd" => entry.initrd.push(esp_path(value)),
+                "options" => {
+                    entry.options = Some(match entry.options.take() {
+                        Some(before) => format!("{} {}", before, value),
+                        None => String::from(value),
+                    })
+                }
+                _ => {}
+            }
+        }
+        entry
+    }
+
+    /// What the menu lists it as: the title with the version, else the id.
+    pub fn display_name(&self) -> String {
+        match (&self.title, &self.version) {
+            (Some(title), Some(version)) => format!("{} ({})", title, version),
+            (Some(title), None) => title.clone(),
+            (None, _) => self.id.clone(),
+        }
+    }
+
+    /// The command line the kernel's EFI stub is started with: an
+    /// `initrd=` for each initrd, then the options.
+    pub fn command_line(&self) -> String {
+        let mut parts: Vec<String> = self
+            .initrd
+            .iter()
+            .map(|i| format!("initrd={}", i))
+            .collect();
+        if let Some(options) = &self.options {
+            parts.push(options.clone());
+        }
+        parts.join(" ")
+    }
+}
+
+/// The `+LEFT[-DONE]` boot counter at the end of a counted entry's file
+/// name, as systemd-boot keeps it: tries left before the entry counts as
+/// bad, and boots already tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootCounter {
+    pub left: u32,
+    pub done: u32,
+}
+
+impl BootCounter {
+    /// Splits a file name less `.conf` into the entry id and its counter;
+    /// `fedora+3-1` is `fedora` with 3 left and 1 done.
+    pub fn split(stem: &str) -> (&str, Option<BootCounter>) {
+        let Some((id, counter)) = stem.rsplit_once('+') else {
+            return (stem, None);
+        };
+        let (left, done) = match counter.split_once('-') {
+            Some((left, done)) => (left, done),
+            None => (counter, "0"),
+        };
+        let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        if id.is_empty() || !digits(left) || !digits(done) {
+            return (stem, None);
+        }
+        match (left.parse(), done.parse()) {
+            (Ok(left), Ok(done)) => (id, Some(BootCounter { left, done })),
+            _ => (stem, None),
+        }
+    }
+
+    /// Out of tries without the OS ever marking a boot good.
+    pub fn is_bad(&self) -> bool {
+        self.left == 0
+    }
+
+    /// The counter once one more boot is under way.
+    pub fn after_boot(&self) -> BootCounter {
+        BootCounter {
+            left: self.left.saturating_sub(1),
+            done: self.done.saturating_add(1),
+        }
+    }
+
+    /// The entry's file name with this counter, such as `fedora+2-1.conf`.
+    pub fn file_name(&self, id: &str) -> String {
+        format!("{}+{}-{}.conf", id, self.left, self.done)
+    }
+}
+
+/// Newest first, as systemd-boot lists them: entry ids compared as
+/// versions, so `6.10` comes before `6.9`.
+pub fn compare_ids(a: &str, b: &str) -> Ordering {
+    compare_versions(b, a)
+}
+
+/// Version strings such as kernel releases: runs of digits compare as
+/// numbers, everything else byte by byte.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (na, ra) = split_digits(a);
+                let (nb, rb) = split_digits(b);
+                let na = trim_zeros(na);
+                let nb = trim_zeros(nb);
+                let ord = na.len().cmp(&nb.len()).then_with(|| na.cmp(nb));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                (a, b) = (ra, rb);
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                (a, b) = (&a[1..], &b[1..]);
+            }
+        }
+    }
+}
+
+fn split_digits(s: &[u8]) -> (&[u8], &[u8]) {
+    let n = s.iter().take_while(|b| b.is_ascii_digit()).count();
+    s.split_at(n)
+}
+
+fn trim_zeros(s: &[u8]) -> &[u8] {
+    let n = s.iter().take_while(|&&b| b == b'0').count();
+    &s[n..]
+}
+
+// `/vmlinuz-linux` as `\vmlinuz-linux`.
+fn esp_path(path: &str) -> String {
+    let path = path.replace('/', "\\");
+    if path.starts_with('\\') {
+        path
+    } else {
+        format!("\\{}", path)
+    }
+}
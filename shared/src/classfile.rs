@@ -1,12 +1,25 @@
+use alloc::collections::BTreeSet;
+use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::vec::Vec;
 
-use crate::types::JvmError;
+use crate::signature::{
+    self,
+    ClassSignature,
+    MethodSignature,
+    TypeSig,
+};
+use crate::types::{
+    JvmError,
+    JvmValue,
+};
 
-#[derive(Debug, Clone)]
+pub mod write;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum CpEntry {
     Unused,
-    Utf8(String),
+    Utf8(Rc<str>),
     Integer(i32),
     Float(f32),
     Long(i64),
@@ -44,9 +57,23 @@ pub enum CpEntry {
         bootstrap_method_attr_index: u16,
         name_and_type_index: u16,
     },
+    /// Only in a module descriptor, `module-info.class`.
+    Module {
+        name_index: u16,
+    },
+    /// Only in a module descriptor.
+    Package {
+        name_index: u16,
+    },
+    /// A constant of a kind this parser doesn't model, such as a Dynamic,
+    /// kept by a lenient [`ClassParser`] as its tag and bytes.
+    Opaque {
+        tag: u8,
+        data: Vec<u8>,
+    },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ExceptionTableEntry {
     pub start_pc: u16,
     pub end_pc: u16,
@@ -54,40 +81,228 @@ pub struct ExceptionTableEntry {
     pub catch_type: u16,
 }
 
-#[derive(Debug, Clone)]
+/// An attribute [`parse_class`] doesn't interpret, such as
+/// `StackMapTable`, kept as it was so [`ClassFile::to_bytes`] can write it
+/// back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawAttribute {
+    /// Utf8 cp index of the attribute's name.
+    pub name_index: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineNumberEntry {
+    pub start_pc: u16,
+    pub line: u16,
+}
+
+/// A named local from `LocalVariableTable`, live in slot `index` for the
+/// `length` bytes of code from `start_pc`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalVariableEntry {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name_index: u16,
+    /// The field descriptor, such as `I` or `Ljava/util/List;`.
+    pub descriptor_index: u16,
+    /// The generic signature from `LocalVariableTypeTable`, such as
+    /// `Ljava/util/List<Ljava/lang/String;>;`, for locals that have one.
+    pub signature_index: Option<u16>,
+    pub index: u16,
+}
+
+impl LocalVariableEntry {
+    pub fn covers(&self, slot: u16, pc: usize) -> bool {
+        let start = self.start_pc as usize;
+        self.index == slot && pc >= start && pc < start + self.length as usize
+    }
+}
+
+/// A method's bytecode. Parsed from bytes the class took over, it is a range
+/// of them shared with the class's other methods; otherwise it owns a copy.
+#[derive(Clone)]
+pub struct CodeBytes {
+    data: Rc<Vec<u8>>,
+    start: usize,
+    end: usize,
+}
+
+impl CodeBytes {
+    // `code` as a range of `shared` when it lies there, else a copy.
+    fn within(shared: Option<&Rc<Vec<u8>>>, code: &[u8]) -> Self {
+        if let Some(data) = shared {
+            let start = (code.as_ptr() as usize).wrapping_sub(data.as_ptr() as usize);
+            if start <= data.len() && code.len() <= data.len() - start {
+                return Self {
+                    data: data.clone(),
+                    start,
+                    end: start + code.len(),
+                };
+            }
+        }
+        Self::from(code.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for CodeBytes {
+    fn from(code: Vec<u8>) -> Self {
+        let end = code.len();
+        Self {
+            data: Rc::new(code),
+            start: 0,
+            end,
+        }
+    }
+}
+
+impl core::ops::Deref for CodeBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data[self.start..self.end]
+    }
+}
+
+impl PartialEq for CodeBytes {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl core::fmt::Debug for CodeBytes {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct CodeAttribute {
     pub max_stack: u16,
     pub max_locals: u16,
-    pub code: Vec<u8>,
+    pub code: CodeBytes,
     pub exception_table: Vec<ExceptionTableEntry>,
+    /// From `LineNumberTable`, empty when the class was built without it.
+    pub line_numbers: Vec<LineNumberEntry>,
+    /// From `LocalVariableTable`, empty when the class was built without
+    /// `-g`.
+    pub local_variables: Vec<LocalVariableEntry>,
+    pub attributes: Vec<RawAttribute>,
+}
+
+impl CodeAttribute {
+    /// The source line the bytecode at `pc` came from.
+    pub fn line_at(&self, pc: usize) -> Option<u16> {
+        self.line_numbers
+            .iter()
+            .filter(|e| e.start_pc as usize <= pc)
+            .max_by_key(|e| e.start_pc)
+            .map(|e| e.line)
+    }
+
+    /// The local that occupies `slot` at `pc`, when it has a name there.
+    pub fn local_variable(&self, slot: u16, pc: usize) -> Option<&LocalVariableEntry> {
+        self.local_variables.iter().find(|v| v.covers(slot, pc))
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MethodInfo {
     pub access_flags: u16,
     pub name_index: u16,
     pub descriptor_index: u16,
     pub code: Option<CodeAttribute>,
+    /// The `Exceptions` attribute: a Class cp index per exception the
+    /// method declares it throws.
+    pub exceptions: Vec<u16>,
+    pub annotations: Vec<Annotation>,
+    /// The `Signature` attribute's Utf8 cp index, on a generic method.
+    pub signature: Option<u16>,
+    /// The `AnnotationDefault` attribute, on an element of an annotation
+    /// type that declares a default.
+    pub annotation_default: Option<ElementValue>,
+    pub attributes: Vec<RawAttribute>,
 }
 
-#[derive(Debug, Clone)]
+impl MethodInfo {
+    /// The source line the bytecode at `pc` came from, when the method has
+    /// code and the class was compiled with line numbers.
+    pub fn line_for_pc(&self, pc: usize) -> Option<u16> {
+        self.code.as_ref().and_then(|code| code.line_at(pc))
+    }
+
+    /// See [`CodeAttribute::local_variable`].
+    pub fn local_variable(&self, slot: u16, pc: usize) -> Option<&LocalVariableEntry> {
+        self.code.as_ref()?.local_variable(slot, pc)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct FieldInfo {
     pub access_flags: u16,
     pub name_index: u16,
     pub descriptor_index: u16,
+    pub annotations: Vec<Annotation>,
+    /// The `Signature` attribute's Utf8 cp index, on a field of generic
+    /// type.
+    pub signature: Option<u16>,
+    pub attributes: Vec<RawAttribute>,
 }
 
 pub const ACC_PUBLIC: u16 = 0x0001;
+pub const ACC_PRIVATE: u16 = 0x0002;
 pub const ACC_STATIC: u16 = 0x0008;
 pub const ACC_NATIVE: u16 = 0x0100;
+pub const ACC_MODULE: u16 = 0x8000;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BootstrapMethodEntry {
     pub method_ref: u16,
     pub arguments: Vec<u16>,
 }
 
-#[derive(Debug, Clone)]
+/// One `InnerClasses` entry. The indices are 0 where the attribute leaves
+/// them out: no outer class for local and anonymous classes, no name for
+/// anonymous ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InnerClassEntry {
+    pub inner_class_info_index: u16,
+    pub outer_class_info_index: u16,
+    pub inner_name_index: u16,
+    pub inner_class_access_flags: u16,
+}
+
+/// One entry of a `RuntimeVisibleAnnotations` attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    /// Utf8 cp index of the annotation type's descriptor, such as `LHotkey;`.
+    pub type_index: u16,
+    /// The elements given where the annotation is used, as the Utf8 cp
+    /// index of the element's name and its value. Defaulted elements aren't
+    /// here; they're in the annotation type's `annotation_default`.
+    pub elements: Vec<(u16, ElementValue)>,
+}
+
+/// An annotation element's value (JVMS 4.7.16.1).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElementValue {
+    /// A primitive or String constant: `tag` is one of `BCDFIJSZs` and
+    /// `index` the cp entry holding the value.
+    Const {
+        tag: u8,
+        index: u16,
+    },
+    Enum {
+        type_name_index: u16,
+        const_name_index: u16,
+    },
+    /// A class literal, as the Utf8 cp index of its descriptor.
+    Class(u16),
+    Annotation(Annotation),
+    Array(Vec<ElementValue>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ClassFile {
     pub minor_version: u16,
     pub major_version: u16,
@@ -99,12 +314,26 @@ pub struct ClassFile {
     pub fields: Vec<FieldInfo>,
     pub methods: Vec<MethodInfo>,
     pub bootstrap_methods: Vec<BootstrapMethodEntry>,
+    /// The `SourceFile` attribute's cp index.
+    pub source_file: Option<u16>,
+    pub inner_classes: Vec<InnerClassEntry>,
+    /// The `NestHost` attribute's Class cp index, on a class nested in
+    /// another.
+    pub nest_host: Option<u16>,
+    /// The `NestMembers` attribute's Class cp indices, on the host.
+    pub nest_members: Vec<u16>,
+    pub annotations: Vec<Annotation>,
+    /// The `Signature` attribute's Utf8 cp index, on a generic class or one
+    /// extending a generic type.
+    pub signature: Option<u16>,
+    /// Every class attribute not parsed into a field above.
+    pub attributes: Vec<RawAttribute>,
 }
 
 impl ClassFile {
     pub fn get_utf8(&self, index: u16) -> Result<&str, JvmError> {
         match self.constant_pool.get(index as usize) {
-            Some(CpEntry::Utf8(s)) => Ok(s.as_str()),
+            Some(CpEntry::Utf8(s)) => Ok(s),
             _ => Err(JvmError::ClassFormatError(alloc::format!(
                 "expected Utf8 at cp#{}",
                 index
@@ -122,6 +351,181 @@ impl ClassFile {
         }
     }
 
+    /// The classes `method` declares in its `throws` clause, such as
+    /// `java/io/IOException`.
+    pub fn declared_exceptions(&self, method: &MethodInfo) -> Result<Vec<&str>, JvmError> {
+        method
+            .exceptions
+            .iter()
+            .map(|&i| self.get_class_name(i))
+            .collect()
+    }
+
+    /// The class whose nest this one belongs to: the `NestHost` if it has
+    /// one, itself otherwise.
+    pub fn nest_host_name(&self) -> Result<&str, JvmError> {
+        match self.nest_host {
+            Some(index) => self.get_class_name(index),
+            None => self.class_name(),
+        }
+    }
+
+    /// Whether the `NestMembers` list names `class_name`.
+    pub fn lists_nest_member(&self, class_name: &str) -> bool {
+        self.nest_members
+            .iter()
+            .any(|&i| self.get_class_name(i).ok() == Some(class_name))
+    }
+
+    /// The class an annotation is of, such as `Hotkey`.
+    pub fn annotation_type(&self, annotation: &Annotation) -> Option<&str> {
+        self.get_utf8(annotation.type_index)
+            .ok()?
+            .strip_prefix('L')?
+            .strip_suffix(';')
+    }
+
+    /// The annotation of type `type_name` among `annotations`, which are
+    /// this class's or one of its members'.
+    pub fn find_annotation<'a>(
+        &self,
+        annotations: &'a [Annotation],
+        type_name: &str,
+    ) -> Option<&'a Annotation> {
+        annotations
+            .iter()
+            .find(|a| self.annotation_type(a) == Some(type_name))
+    }
+
+    /// The value `annotation` gives its element `name`, when it gives one.
+    pub fn annotation_element<'a>(
+        &self,
+        annotation: &'a Annotation,
+        name: &str,
+    ) -> Option<&'a ElementValue> {
+        annotation
+            .elements
+            .iter()
+            .find(|(n, _)| self.get_utf8(*n).ok() == Some(name))
+            .map(|(_, v)| v)
+    }
+
+    /// An element value as the VM would see it: constants as their
+    /// primitive or String, an enum constant as its name and a class
+    /// literal as its descriptor. Nested annotations and arrays have no
+    /// such form and give `None`.
+    pub fn element_value(&self, value: &ElementValue) -> Option<JvmValue> {
+        match value {
+            ElementValue::Const { tag, index } => {
+                match (tag, self.constant_pool.get(*index as usize)?) {
+                    (b'J', CpEntry::Long(v)) => Some(JvmValue::Long(*v)),
+                    (b'F', CpEntry::Float(v)) => Some(JvmValue::Float(*v)),
+                    (b'D', CpEntry::Double(v)) => Some(JvmValue::Double(*v)),
+                    (b's', CpEntry::Utf8(s)) => Some(JvmValue::StringRef(String::from(&**s))),
+                    (_, CpEntry::Integer(v)) => Some(JvmValue::Int(*v)),
+                    _ => None,
+                }
+            }
+            ElementValue::Enum {
+                const_name_index, ..
+            } => Some(JvmValue::StringRef(String::from(
+                self.get_utf8(*const_name_index).ok()?,
+            ))),
+            ElementValue::Class(index) => Some(JvmValue::StringRef(String::from(
+                self.get_utf8(*index).ok()?,
+            ))),
+            ElementValue::Annotation(_) | ElementValue::Array(_) => None,
+        }
+    }
+
+    /// The class's generic signature, when it has one.
+    pub fn class_signature(&self) -> Result<Option<ClassSignature>, JvmError> {
+        self.signature
+            .map(|i| signature::parse_class_signature(self.get_utf8(i)?))
+            .transpose()
+    }
+
+    /// `method`'s generic signature, when it has one.
+    pub fn method_signature(
+        &self,
+        method: &MethodInfo,
+    ) -> Result<Option<MethodSignature>, JvmError> {
+        method
+            .signature
+            .map(|i| signature::parse_method_signature(self.get_utf8(i)?))
+            .transpose()
+    }
+
+    /// `field`'s generic type, such as `java.util.List<BootEntry>`, when it
+    /// has one.
+    pub fn field_signature(&self, field: &FieldInfo) -> Result<Option<TypeSig>, JvmError> {
+        field
+            .signature
+            .map(|i| signature::parse_field_signature(self.get_utf8(i)?))
+            .transpose()
+    }
+
+    /// The contents of the attribute called `name` among `attributes`,
+    /// which are this class's or one of its members'.
+    pub fn find_attribute<'a>(
+        &self,
+        attributes: &'a [RawAttribute],
+        name: &str,
+    ) -> Option<&'a [u8]> {
+        attributes
+            .iter()
+            .find(|a| self.get_utf8(a.name_index).ok() == Some(name))
+            .map(|a| a.data.as_slice())
+    }
+
+    /// Whether this is a module descriptor, `module-info.class`, which
+    /// describes a JAR's module rather than declaring a class to run.
+    pub fn is_module(&self) -> bool {
+        self.access_flags & ACC_MODULE != 0
+    }
+
+    /// The module a module descriptor declares, such as `duke.menu`, read
+    /// from its Module attribute.
+    pub fn module_name(&self) -> Option<&str> {
+        let data = self.find_attribute(&self.attributes, "Module")?;
+        let index = u16::from_be_bytes([*data.first()?, *data.get(1)?]);
+        match self.constant_pool.get(index as usize)? {
+            CpEntry::Module { name_index } => self.get_utf8(*name_index).ok(),
+            _ => None,
+        }
+    }
+
+    /// The packages a module descriptor's ModulePackages attribute lists,
+    /// such as `efi/menu`.
+    pub fn module_packages(&self) -> Vec<&str> {
+        let Some(data) = self.find_attribute(&self.attributes, "ModulePackages") else {
+            return Vec::new();
+        };
+        data.get(2..)
+            .unwrap_or(&[])
+            .chunks_exact(2)
+            .filter_map(|c| {
+                match self
+                    .constant_pool
+                    .get(u16::from_be_bytes([c[0], c[1]]) as usize)?
+                {
+                    CpEntry::Package { name_index } => self.get_utf8(*name_index).ok(),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// The file the class was compiled from, such as `BootMenu.java`.
+    pub fn source_file_name(&self) -> Option<&str> {
+        self.get_utf8(self.source_file?).ok()
+    }
+
+    /// [`MethodInfo::line_for_pc`] for `methods[method_idx]`.
+    pub fn line_for_pc(&self, method_idx: usize, pc: usize) -> Option<u16> {
+        self.methods.get(method_idx)?.line_for_pc(pc)
+    }
+
     pub fn class_name(&self) -> Result<&str, JvmError> {
         self.get_class_name(self.this_class)
     }
@@ -164,6 +568,151 @@ impl ClassFile {
     }
 }
 
+impl ClassFile {
+    /// Checks that every index the class holds into its own constant pool,
+    /// from pool entries, the class and member headers and the bootstrap
+    /// methods, lands on an entry of the kind JVMS 4.4 asks for.
+    /// [`parse_class`] runs this before handing a class out; a `ClassFile`
+    /// built or edited by hand can be checked the same way.
+    pub fn validate(&self) -> Result<(), JvmError> {
+        let cp = &self.constant_pool;
+        let bad = |what: &str, index: u16, expected: &str| {
+            Err(JvmError::ClassFormatError(alloc::format!(
+                "{} cp#{} is not {}",
+                what,
+                index,
+                expected
+            )))
+        };
+        let utf8 = |i: u16| matches!(cp.get(i as usize), Some(CpEntry::Utf8(_)));
+        let class = |i: u16| matches!(cp.get(i as usize), Some(CpEntry::Class { .. }));
+        let name_and_type =
+            |i: u16| matches!(cp.get(i as usize), Some(CpEntry::NameAndType { .. }));
+        if !matches!(cp.first(), Some(CpEntry::Unused)) {
+            return Err(JvmError::ClassFormatError(String::from(
+                "constant pool has no unused entry 0",
+            )));
+        }
+        for (i, entry) in cp.iter().enumerate() {
+            let i = i as u16;
+            let ok = match entry {
+                CpEntry::Unused
+                | CpEntry::Utf8(_)
+                | CpEntry::Integer(_)
+                | CpEntry::Float(_)
+                | CpEntry::Long(_)
+                | CpEntry::Double(_)
+                | CpEntry::Opaque { .. } => true,
+                CpEntry::Class { name_index }
+                | CpEntry::Module { name_index }
+                | CpEntry::Package { name_index } => utf8(*name_index),
+                CpEntry::StringRef { string_index } => utf8(*string_index),
+                CpEntry::Fieldref {
+                    class_index,
+                    name_and_type_index,
+                }
+                | CpEntry::Methodref {
+                    class_index,
+                    name_and_type_index,
+                }
+                | CpEntry::InterfaceMethodref {
+                    class_index,
+                    name_and_type_index,
+                } => class(*class_index) && name_and_type(*name_and_type_index),
+                CpEntry::NameAndType {
+                    name_index,
+                    descriptor_index,
+                } => utf8(*name_index) && utf8(*descriptor_index),
+                CpEntry::MethodHandle {
+                    reference_kind,
+                    reference_index,
+                } => {
+                    let target = cp.get(*reference_index as usize);
+                    match reference_kind {
+                        1..=4 => matches!(target, Some(CpEntry::Fieldref { .. })),
+                        5 | 8 => matches!(target, Some(CpEntry::Methodref { .. })),
+                        6 | 7 => matches!(
+                            target,
+                            Some(CpEntry::Methodref { .. } | CpEntry::InterfaceMethodref { .. })
+                        ),
+                        9 => matches!(target, Some(CpEntry::InterfaceMethodref { .. })),
+                        _ => false,
+                    }
+                }
+                CpEntry::MethodType { descriptor_index } => utf8(*descriptor_index),
+                CpEntry::InvokeDynamic {
+                    bootstrap_method_attr_index,
+                    name_and_type_index,
+                } => {
+                    (*bootstrap_method_attr_index as usize) < self.bootstrap_methods.len()
+                        && name_and_type(*name_and_type_index)
+                }
+            };
+            if !ok {
+                return Err(JvmError::ClassFormatError(alloc::format!(
+                    "cp#{} refers to a missing or wrong kind of entry",
+                    i
+                )));
+            }
+        }
+
+        if !class(self.this_class) {
+            return bad("this_class", self.this_class, "a Class");
+        }
+        if self.super_class != 0 && !class(self.super_class) {
+            return bad("super_class", self.super_class, "a Class");
+        }
+        if let Some(&i) = self.interfaces.iter().find(|&&i| !class(i)) {
+            return bad("interface", i, "a Class");
+        }
+        let members = self
+            .fields
+            .iter()
+            .map(|f| (f.name_index, f.descriptor_index))
+            .chain(
+                self.methods
+                    .iter()
+                    .map(|m| (m.name_index, m.descriptor_index)),
+            );
+        for (name, descriptor) in members {
+            if !utf8(name) {
+                return bad("member name", name, "a Utf8");
+            }
+            if !utf8(descriptor) {
+                return bad("member descriptor", descriptor, "a Utf8");
+            }
+        }
+        for b in &self.bootstrap_methods {
+            if !matches!(
+                cp.get(b.method_ref as usize),
+                Some(CpEntry::MethodHandle { .. })
+            ) {
+                return bad("bootstrap method", b.method_ref, "a MethodHandle");
+            }
+            let loadable = |i: u16| {
+                matches!(
+                    cp.get(i as usize),
+                    Some(
+                        CpEntry::Integer(_)
+                            | CpEntry::Float(_)
+                            | CpEntry::Long(_)
+                            | CpEntry::Double(_)
+                            | CpEntry::Class { .. }
+                            | CpEntry::StringRef { .. }
+                            | CpEntry::MethodHandle { .. }
+                            | CpEntry::MethodType { .. }
+                            | CpEntry::Opaque { tag: 17, .. }
+                    )
+                )
+            };
+            if let Some(&i) = b.arguments.iter().find(|&&i| !loadable(i)) {
+                return bad("bootstrap argument", i, "a loadable constant");
+            }
+        }
+        Ok(())
+    }
+}
+
 struct ClassReader<'a> {
     data: &'a [u8],
     pos: usize,
@@ -203,17 +752,390 @@ impl<'a> ClassReader<'a> {
         self.pos += len;
         Ok(slice)
     }
+}
 
-    fn skip(&mut self, n: usize) -> Result<(), JvmError> {
-        if self.pos + n > self.data.len() {
-            return Err(JvmError::ClassFormatError(String::from("unexpected EOF")));
+// The ranges have to lie in the code and the catch type, when there is one,
+// has to name a class (JVMS 4.7.3); whether they land on instruction
+// boundaries is left to the interpreter, which decodes the code anyway.
+fn check_handler(
+    entry: &ExceptionTableEntry,
+    code_len: usize,
+    constant_pool: &[CpEntry],
+) -> Result<(), JvmError> {
+    let (start, end, handler) = (
+        entry.start_pc as usize,
+        entry.end_pc as usize,
+        entry.handler_pc as usize,
+    );
+    if start >= end || end > code_len || handler >= code_len {
+        return Err(JvmError::ClassFormatError(alloc::format!(
+            "exception handler {}..{} -> {} outside code of length {}",
+            start,
+            end,
+            handler,
+            code_len
+        )));
+    }
+    if entry.catch_type != 0
+        && !matches!(
+            constant_pool.get(entry.catch_type as usize),
+            Some(CpEntry::Class { .. })
+        )
+    {
+        return Err(JvmError::ClassFormatError(alloc::format!(
+            "exception handler catch type #{} is not a class",
+            entry.catch_type
+        )));
+    }
+    Ok(())
+}
+
+fn check_attr_len(name: &str, len: usize, expected: usize) -> Result<(), JvmError> {
+    if len == expected {
+        return Ok(());
+    }
+    Err(JvmError::ClassFormatError(alloc::format!(
+        "{} attribute is {} bytes, expected {}",
+        name,
+        len,
+        expected
+    )))
+}
+
+// Element values nest through arrays and annotations, and the parser
+// recurses on them; real annotations stay a few levels deep.
+const MAX_ANNOTATION_DEPTH: usize = 16;
+
+// A RuntimeVisibleAnnotations attribute of `len` bytes. Its entries are
+// read from the attribute's own bytes so a malformed one can't run on
+// into what follows it.
+fn read_annotations(
+    r: &mut ClassReader,
+    len: usize,
+    constant_pool: &[CpEntry],
+) -> Result<Vec<Annotation>, JvmError> {
+    let mut a = ClassReader::new(r.read_bytes(len)?);
+    let count = a.read_u16()?;
+    let mut out = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        out.push(read_annotation(&mut a, constant_pool, 0)?);
+    }
+    check_attr_len("RuntimeVisibleAnnotations", len, a.pos)?;
+    Ok(out)
+}
+
+fn read_annotation(
+    r: &mut ClassReader,
+    constant_pool: &[CpEntry],
+    depth: usize,
+) -> Result<Annotation, JvmError> {
+    let type_index = r.read_u16()?;
+    check_annotation_utf8(constant_pool, type_index)?;
+    let count = r.read_u16()?;
+    let mut elements = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let name = r.read_u16()?;
+        check_annotation_utf8(constant_pool, name)?;
+        elements.push((name, read_element(r, constant_pool, depth)?));
+    }
+    Ok(Annotation {
+        type_index,
+        elements,
+    })
+}
+
+fn read_element(
+    r: &mut ClassReader,
+    constant_pool: &[CpEntry],
+    depth: usize,
+) -> Result<ElementValue, JvmError> {
+    if depth >= MAX_ANNOTATION_DEPTH {
+        return Err(JvmError::ClassFormatError(String::from(
+            "annotation nested too deeply",
+        )));
+    }
+    let tag = r.read_u8()?;
+    Ok(match tag {
+        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' => {
+            let index = r.read_u16()?;
+            let fits = matches!(
+                (tag, constant_pool.get(index as usize)),
+                (b'D', Some(CpEntry::Double(_)))
+                    | (b'F', Some(CpEntry::Float(_)))
+                    | (b'J', Some(CpEntry::Long(_)))
+                    | (b's', Some(CpEntry::Utf8(_)))
+                    | (b'B' | b'C' | b'I' | b'S' | b'Z', Some(CpEntry::Integer(_)))
+            );
+            if !fits {
+                return Err(JvmError::ClassFormatError(alloc::format!(
+                    "annotation constant '{}' at cp#{} has the wrong type",
+                    tag as char,
+                    index
+                )));
+            }
+            ElementValue::Const { tag, index }
         }
-        self.pos += n;
-        Ok(())
+        b'e' => {
+            let type_name_index = r.read_u16()?;
+            let const_name_index = r.read_u16()?;
+            check_annotation_utf8(constant_pool, type_name_index)?;
+            check_annotation_utf8(constant_pool, const_name_index)?;
+            ElementValue::Enum {
+                type_name_index,
+                const_name_index,
+            }
+        }
+        b'c' => {
+            let index = r.read_u16()?;
+            check_annotation_utf8(constant_pool, index)?;
+            ElementValue::Class(index)
+        }
+        b'@' => ElementValue::Annotation(read_annotation(r, constant_pool, depth + 1)?),
+        b'[' => {
+            let count = r.read_u16()?;
+            let mut values = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                values.push(read_element(r, constant_pool, depth + 1)?);
+            }
+            ElementValue::Array(values)
+        }
+        _ => {
+            return Err(JvmError::ClassFormatError(alloc::format!(
+                "bad annotation element tag {:#04x}",
+                tag
+            )));
+        }
+    })
+}
+
+// The grammar is left to the accessors that parse it, as HotSpot leaves it
+// to reflection.
+fn read_signature(
+    r: &mut ClassReader,
+    len: usize,
+    constant_pool: &[CpEntry],
+) -> Result<u16, JvmError> {
+    check_attr_len("Signature", len, 2)?;
+    let index = r.read_u16()?;
+    if !matches!(constant_pool.get(index as usize), Some(CpEntry::Utf8(_))) {
+        return Err(JvmError::ClassFormatError(alloc::format!(
+            "Signature cp#{} is not a Utf8",
+            index
+        )));
+    }
+    Ok(index)
+}
+
+fn check_annotation_utf8(constant_pool: &[CpEntry], index: u16) -> Result<(), JvmError> {
+    if matches!(constant_pool.get(index as usize), Some(CpEntry::Utf8(_))) {
+        return Ok(());
+    }
+    Err(JvmError::ClassFormatError(alloc::format!(
+        "annotation cp#{} is not a Utf8",
+        index
+    )))
+}
+
+// The entries of a LocalVariableTable, or of a LocalVariableTypeTable with
+// the signature read into descriptor_index.
+fn read_locals(
+    r: &mut ClassReader,
+    len: usize,
+    code_len: usize,
+) -> Result<Vec<LocalVariableEntry>, JvmError> {
+    let count = r.read_u16()?;
+    let mut out = Vec::with_capacity(count as usize);
+    if len != 2 + 10 * count as usize {
+        return Err(JvmError::ClassFormatError(alloc::format!(
+            "local variable table of {} bytes holds {} entries",
+            len,
+            count
+        )));
+    }
+    for _ in 0..count {
+        let entry = LocalVariableEntry {
+            start_pc: r.read_u16()?,
+            length: r.read_u16()?,
+            name_index: r.read_u16()?,
+            descriptor_index: r.read_u16()?,
+            signature_index: None,
+            index: r.read_u16()?,
+        };
+        if entry.start_pc as usize + entry.length as usize > code_len {
+            return Err(JvmError::ClassFormatError(alloc::format!(
+                "local in slot {} runs past code of length {}",
+                entry.index,
+                code_len
+            )));
+        }
+        out.push(entry);
+    }
+    Ok(out)
+}
+
+/// Utf8 constants already seen, so that classes parsed against the same
+/// pool share one copy of each string they repeat, such as
+/// `java/lang/Object`, `Code` or `()V`.
+#[derive(Debug, Clone, Default)]
+pub struct StringPool {
+    strings: BTreeSet<Rc<str>>,
+}
+
+impl StringPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, s: &str) -> Rc<str> {
+        if let Some(shared) = self.strings.get(s) {
+            return shared.clone();
+        }
+        let shared: Rc<str> = Rc::from(s);
+        self.strings.insert(shared.clone());
+        shared
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+/// Parses class files with state kept across them: a [`StringPool`] their
+/// Utf8 constants share, and whether to be lenient.
+#[derive(Debug, Clone, Default)]
+pub struct ClassParser {
+    pub strings: StringPool,
+    /// Keeps going where a class has something this parser can't read: a
+    /// known attribute that fails to parse is kept raw, and a constant of a
+    /// kind it doesn't model becomes [`CpEntry::Opaque`]. Each time adds to
+    /// the warnings. A constant of a kind unknown to JVMS, whose size can't
+    /// be told, still fails the class.
+    pub lenient: bool,
+    /// What lenient parsing let through, one line per case, each led by the
+    /// class name.
+    pub warnings: Vec<String>,
+}
+
+impl ClassParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a class from bytes it doesn't own, copying each method's
+    /// code out of them.
+    pub fn parse(&mut self, data: &[u8]) -> Result<ClassFile, JvmError> {
+        self.parse_with(data, None)
+    }
+
+    /// Parses a class that takes its bytes over: each method's code is a
+    /// range of them rather than a copy, and they are freed with the last
+    /// method that refers to them.
+    pub fn parse_owned(&mut self, data: Vec<u8>) -> Result<ClassFile, JvmError> {
+        let data = Rc::new(data);
+        self.parse_with(&data, Some(data.clone()))
+    }
+
+    fn parse_with(
+        &mut self,
+        data: &[u8],
+        shared: Option<Rc<Vec<u8>>>,
+    ) -> Result<ClassFile, JvmError> {
+        let mut ctx = ParseCtx {
+            lenient: self.lenient,
+            warnings: Vec::new(),
+            shared,
+        };
+        let class = parse(data, &mut self.strings, &mut ctx)?;
+        let name = class.class_name().unwrap_or("?");
+        self.warnings.extend(
+            ctx.warnings
+                .into_iter()
+                .map(|w| alloc::format!("{}: {}", name, w)),
+        );
+        Ok(class)
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        core::mem::take(&mut self.warnings)
     }
 }
 
 pub fn parse_class(data: &[u8]) -> Result<ClassFile, JvmError> {
+    ClassParser::new().parse(data)
+}
+
+// Whether parsing keeps going past what it can't read, what it noted each
+// time it did, and the buffer being parsed when Code can share it.
+struct ParseCtx {
+    lenient: bool,
+    warnings: Vec<String>,
+    shared: Option<Rc<Vec<u8>>>,
+}
+
+// Constants JVMS defines that have no CpEntry of their own, by tag, with
+// their size after the tag: Dynamic (JVMS 4.4.10).
+const OPAQUE_SIZES: &[(u8, usize)] = &[(17, 4)];
+
+// An attribute's header, its name looked up.
+struct Attr<'c> {
+    name_index: u16,
+    name: &'c str,
+    len: usize,
+}
+
+impl<'c> Attr<'c> {
+    fn new(name_index: u16, len: usize, constant_pool: &'c [CpEntry]) -> Self {
+        let name = match constant_pool.get(name_index as usize) {
+            Some(CpEntry::Utf8(s)) => s,
+            _ => "",
+        };
+        Self {
+            name_index,
+            name,
+            len,
+        }
+    }
+
+    fn raw(&self, r: &mut ClassReader) -> Result<RawAttribute, JvmError> {
+        Ok(RawAttribute {
+            name_index: self.name_index,
+            data: r.read_bytes(self.len)?.to_vec(),
+        })
+    }
+
+    // Reads an attribute this parser knows with `read`, from the attribute's
+    // own bytes so a malformed one can't run on into what follows. When
+    // lenient, one `read` fails on is kept in `raw` instead and None comes
+    // back.
+    fn read<T>(
+        &self,
+        r: &mut ClassReader,
+        ctx: &mut ParseCtx,
+        raw: &mut Vec<RawAttribute>,
+        read: impl FnOnce(&mut ClassReader, &mut ParseCtx) -> Result<T, JvmError>,
+    ) -> Result<Option<T>, JvmError> {
+        let data = r.read_bytes(self.len)?;
+        match read(&mut ClassReader::new(data), ctx) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if ctx.lenient => {
+                ctx.warnings
+                    .push(alloc::format!("kept {} attribute unread: {}", self.name, e));
+                raw.push(RawAttribute {
+                    name_index: self.name_index,
+                    data: data.to_vec(),
+                });
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn parse(data: &[u8], strings: &mut StringPool, ctx: &mut ParseCtx) -> Result<ClassFile, JvmError> {
     let mut r = ClassReader::new(data);
 
     let magic = r.read_u32()?;
@@ -240,7 +1162,7 @@ pub fn parse_class(data: &[u8]) -> Result<ClassFile, JvmError> {
                 let bytes = r.read_bytes(len)?;
                 let s = core::str::from_utf8(bytes)
                     .map_err(|_| JvmError::ClassFormatError(String::from("invalid utf8 in cp")))?;
-                constant_pool.push(CpEntry::Utf8(String::from(s)));
+                constant_pool.push(CpEntry::Utf8(strings.intern(s)));
             }
             3 => {
                 let val = r.read_u32()? as i32;
@@ -326,12 +1248,30 @@ pub fn parse_class(data: &[u8]) -> Result<ClassFile, JvmError> {
                     name_and_type_index,
                 });
             }
-            _ => {
-                return Err(JvmError::ClassFormatError(alloc::format!(
-                    "unknown cp tag: {}",
-                    tag
-                )));
+            19 => {
+                let name_index = r.read_u16()?;
+                constant_pool.push(CpEntry::Module { name_index });
+            }
+            20 => {
+                let name_index = r.read_u16()?;
+                constant_pool.push(CpEntry::Package { name_index });
             }
+            _ => match OPAQUE_SIZES.iter().find(|&&(t, _)| t == tag) {
+                Some(&(_, size)) if ctx.lenient => {
+                    ctx.warnings
+                        .push(alloc::format!("kept cp#{} (tag {}) opaque", i, tag));
+                    constant_pool.push(CpEntry::Opaque {
+                        tag,
+                        data: r.read_bytes(size)?.to_vec(),
+                    });
+                }
+                _ => {
+                    return Err(JvmError::ClassFormatError(alloc::format!(
+                        "unknown cp tag: {}",
+                        tag
+                    )));
+                }
+            },
         }
         i += 1;
     }
@@ -353,15 +1293,36 @@ pub fn parse_class(data: &[u8]) -> Result<ClassFile, JvmError> {
         let name_index = r.read_u16()?;
         let descriptor_index = r.read_u16()?;
         let attr_count = r.read_u16()?;
+        let mut annotations = Vec::new();
+        let mut signature = None;
+        let mut attributes = Vec::new();
         for _ in 0..attr_count {
-            let _name = r.read_u16()?;
+            let name = r.read_u16()?;
             let len = r.read_u32()? as usize;
-            r.skip(len)?;
+            let attr = Attr::new(name, len, &constant_pool);
+            match attr.name {
+                "RuntimeVisibleAnnotations" => {
+                    annotations = attr
+                        .read(&mut r, ctx, &mut attributes, |a, _| {
+                            read_annotations(a, len, &constant_pool)
+                        })?
+                        .unwrap_or_default();
+                }
+                "Signature" => {
+                    signature = attr.read(&mut r, ctx, &mut attributes, |a, _| {
+                        read_signature(a, len, &constant_pool)
+                    })?;
+                }
+                _ => attributes.push(attr.raw(&mut r)?),
+            }
         }
         fields.push(FieldInfo {
             access_flags,
             name_index,
             descriptor_index,
+            annotations,
+            signature,
+            attributes,
         });
     }
 
@@ -373,48 +1334,50 @@ pub fn parse_class(data: &[u8]) -> Result<ClassFile, JvmError> {
         let descriptor_index = r.read_u16()?;
         let attr_count = r.read_u16()?;
         let mut code = None;
+        let mut exceptions = Vec::new();
+        let mut annotations = Vec::new();
+        let mut annotation_default = None;
+        let mut signature = None;
+        let mut attributes = Vec::new();
 
         for _ in 0..attr_count {
             let attr_name_index = r.read_u16()?;
             let attr_len = r.read_u32()? as usize;
+            let attr = Attr::new(attr_name_index, attr_len, &constant_pool);
+            let raw = &mut attributes;
 
-            let is_code = matches!(
-                constant_pool.get(attr_name_index as usize),
-                Some(CpEntry::Utf8(s)) if s == "Code"
-            );
-
-            if is_code {
-                let max_stack = r.read_u16()?;
-                let max_locals = r.read_u16()?;
-                let code_len = r.read_u32()? as usize;
-                let code_bytes = r.read_bytes(code_len)?;
-
-                let exc_table_len = r.read_u16()?;
-                let mut exception_table = Vec::with_capacity(exc_table_len as usize);
-                for _ in 0..exc_table_len {
-                    exception_table.push(ExceptionTableEntry {
-                        start_pc: r.read_u16()?,
-                        end_pc: r.read_u16()?,
-                        handler_pc: r.read_u16()?,
-                        catch_type: r.read_u16()?,
-                    });
+            match attr.name {
+                "RuntimeVisibleAnnotations" => {
+                    annotations = attr
+                        .read(&mut r, ctx, raw, |a, _| {
+                            read_annotations(a, attr_len, &constant_pool)
+                        })?
+                        .unwrap_or_default();
                 }
-
-                let sub_attr_count = r.read_u16()?;
-                for _ in 0..sub_attr_count {
-                    let _name = r.read_u16()?;
-                    let len = r.read_u32()? as usize;
-                    r.skip(len)?;
+                "Signature" => {
+                    signature = attr.read(&mut r, ctx, raw, |a, _| {
+                        read_signature(a, attr_len, &constant_pool)
+                    })?;
                 }
-
-                code = Some(CodeAttribute {
-                    max_stack,
-                    max_locals,
-                    code: code_bytes.to_vec(),
-                    exception_table,
-                });
-            } else {
-                r.skip(attr_len)?;
+                "AnnotationDefault" => {
+                    annotation_default = attr.read(&mut r, ctx, raw, |a, _| {
+                        let value = read_element(a, &constant_pool, 0)?;
+                        check_attr_len(attr.name, attr_len, a.pos)?;
+                        Ok(value)
+                    })?;
+                }
+                "Exceptions" => {
+                    exceptions = attr
+                        .read(&mut r, ctx, raw, |a, _| {
+                            read_exceptions(a, attr_len, &constant_pool)
+                        })?
+                        .unwrap_or_default();
+                }
+                "Code" => {
+                    code =
+                        attr.read(&mut r, ctx, raw, |a, ctx| read_code(a, ctx, &constant_pool))?;
+                }
+                _ => raw.push(attr.raw(&mut r)?),
             }
         }
 
@@ -423,40 +1386,149 @@ pub fn parse_class(data: &[u8]) -> Result<ClassFile, JvmError> {
             name_index,
             descriptor_index,
             code,
+            exceptions,
+            annotations,
+            signature,
+            annotation_default,
+            attributes,
         });
     }
 
     let attr_count = r.read_u16()?;
     let mut bootstrap_methods = Vec::new();
+    let mut source_file = None;
+    let mut inner_classes = Vec::new();
+    let mut nest_host = None;
+    let mut nest_members = Vec::new();
+    let mut annotations = Vec::new();
+    let mut signature = None;
+    let mut attributes = Vec::new();
+    let is_class = |index: u16| {
+        matches!(
+            constant_pool.get(index as usize),
+            Some(CpEntry::Class { .. })
+        )
+    };
     for _ in 0..attr_count {
         let attr_name_index = r.read_u16()?;
         let attr_len = r.read_u32()? as usize;
+        let attr = Attr::new(attr_name_index, attr_len, &constant_pool);
+        let raw = &mut attributes;
 
-        let is_bootstrap = matches!(
-            constant_pool.get(attr_name_index as usize),
-            Some(CpEntry::Utf8(s)) if s == "BootstrapMethods"
-        );
-
-        if is_bootstrap {
-            let num_methods = r.read_u16()?;
-            for _ in 0..num_methods {
-                let method_ref = r.read_u16()?;
-                let num_args = r.read_u16()?;
-                let mut arguments = Vec::with_capacity(num_args as usize);
-                for _ in 0..num_args {
-                    arguments.push(r.read_u16()?);
-                }
-                bootstrap_methods.push(BootstrapMethodEntry {
-                    method_ref,
-                    arguments,
-                });
+        match attr.name {
+            "RuntimeVisibleAnnotations" => {
+                annotations = attr
+                    .read(&mut r, ctx, raw, |a, _| {
+                        read_annotations(a, attr_len, &constant_pool)
+                    })?
+                    .unwrap_or_default();
             }
-        } else {
-            r.skip(attr_len)?;
+            "Signature" => {
+                signature = attr.read(&mut r, ctx, raw, |a, _| {
+                    read_signature(a, attr_len, &constant_pool)
+                })?;
+            }
+            "SourceFile" => {
+                source_file = attr.read(&mut r, ctx, raw, |a, _| {
+                    let index = a.read_u16()?;
+                    if attr_len != 2
+                        || !matches!(constant_pool.get(index as usize), Some(CpEntry::Utf8(_)))
+                    {
+                        return Err(JvmError::ClassFormatError(alloc::format!(
+                            "bad SourceFile attribute ({} bytes, cp#{})",
+                            attr_len,
+                            index
+                        )));
+                    }
+                    Ok(index)
+                })?;
+            }
+            "BootstrapMethods" => {
+                bootstrap_methods = attr
+                    .read(&mut r, ctx, raw, |a, _| {
+                        let num_methods = a.read_u16()?;
+                        let mut methods = Vec::with_capacity(num_methods as usize);
+                        for _ in 0..num_methods {
+                            let method_ref = a.read_u16()?;
+                            let num_args = a.read_u16()?;
+                            let mut arguments = Vec::with_capacity(num_args as usize);
+                            for _ in 0..num_args {
+                                arguments.push(a.read_u16()?);
+                            }
+                            methods.push(BootstrapMethodEntry {
+                                method_ref,
+                                arguments,
+                            });
+                        }
+                        Ok(methods)
+                    })?
+                    .unwrap_or_default();
+            }
+            "InnerClasses" => {
+                inner_classes = attr
+                    .read(&mut r, ctx, raw, |a, _| {
+                        let count = a.read_u16()?;
+                        check_attr_len(attr.name, attr_len, 2 + 8 * count as usize)?;
+                        let mut entries = Vec::with_capacity(count as usize);
+                        for _ in 0..count {
+                            let entry = InnerClassEntry {
+                                inner_class_info_index: a.read_u16()?,
+                                outer_class_info_index: a.read_u16()?,
+                                inner_name_index: a.read_u16()?,
+                                inner_class_access_flags: a.read_u16()?,
+                            };
+                            if !is_class(entry.inner_class_info_index)
+                                || (entry.outer_class_info_index != 0
+                                    && !is_class(entry.outer_class_info_index))
+                            {
+                                return Err(JvmError::ClassFormatError(String::from(
+                                    "InnerClasses entry doesn't name classes",
+                                )));
+                            }
+                            entries.push(entry);
+                        }
+                        Ok(entries)
+                    })?
+                    .unwrap_or_default();
+            }
+            "NestHost" => {
+                nest_host = attr.read(&mut r, ctx, raw, |a, _| {
+                    check_attr_len(attr.name, attr_len, 2)?;
+                    let index = a.read_u16()?;
+                    if !is_class(index) {
+                        return Err(JvmError::ClassFormatError(alloc::format!(
+                            "NestHost cp#{} is not a class",
+                            index
+                        )));
+                    }
+                    Ok(index)
+                })?;
+            }
+            "NestMembers" => {
+                nest_members = attr
+                    .read(&mut r, ctx, raw, |a, _| {
+                        let count = a.read_u16()?;
+                        check_attr_len(attr.name, attr_len, 2 + 2 * count as usize)?;
+                        let mut members = Vec::with_capacity(count as usize);
+                        for _ in 0..count {
+                            let index = a.read_u16()?;
+                            if !is_class(index) {
+                                return Err(JvmError::ClassFormatError(alloc::format!(
+                                    "NestMembers cp#{} is not a class",
+                                    index
+                                )));
+                            }
+                            members.push(index);
+                        }
+                        Ok(members)
+                    })?
+                    .unwrap_or_default();
+            }
+            _ => raw.push(attr.raw(&mut r)?),
         }
     }
 
-    Ok(ClassFile {
+    let class = ClassFile {
         minor_version,
         major_version,
         constant_pool,
@@ -467,45 +1539,182 @@ pub fn parse_class(data: &[u8]) -> Result<ClassFile, JvmError> {
         fields,
         methods,
         bootstrap_methods,
-    })
+        source_file,
+        inner_classes,
+        nest_host,
+        nest_members,
+        annotations,
+        signature,
+        attributes,
+    };
+    class.validate()?;
+    Ok(class)
 }
 
-pub fn count_descriptor_args(descriptor: &str) -> usize {
-    let mut count = 0;
-    let bytes = descriptor.as_bytes();
-    let mut i = 1;
-    while i < bytes.len() {
-        match bytes[i] {
-            b')' => break,
-            b'L' => {
-                count += 1;
-                while i < bytes.len() && bytes[i] != b';' {
-                    i += 1;
+fn read_exceptions(
+    a: &mut ClassReader,
+    len: usize,
+    constant_pool: &[CpEntry],
+) -> Result<Vec<u16>, JvmError> {
+    let count = a.read_u16()?;
+    if len != 2 + 2 * count as usize {
+        return Err(JvmError::ClassFormatError(alloc::format!(
+            "Exceptions attribute of {} bytes holds {} entries",
+            len,
+            count
+        )));
+    }
+    let mut exceptions = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let index = a.read_u16()?;
+        if !matches!(
+            constant_pool.get(index as usize),
+            Some(CpEntry::Class { .. })
+        ) {
+            return Err(JvmError::ClassFormatError(alloc::format!(
+                "declared exception cp#{} is not a class",
+                index
+            )));
+        }
+        exceptions.push(index);
+    }
+    Ok(exceptions)
+}
+
+fn read_code(
+    r: &mut ClassReader,
+    ctx: &mut ParseCtx,
+    constant_pool: &[CpEntry],
+) -> Result<CodeAttribute, JvmError> {
+    let max_stack = r.read_u16()?;
+    let max_locals = r.read_u16()?;
+    let code_len = r.read_u32()? as usize;
+    let code_bytes = r.read_bytes(code_len)?;
+
+    let exc_table_len = r.read_u16()?;
+    let mut exception_table = Vec::with_capacity(exc_table_len as usize);
+    for _ in 0..exc_table_len {
+        let entry = ExceptionTableEntry {
+            start_pc: r.read_u16()?,
+            end_pc: r.read_u16()?,
+            handler_pc: r.read_u16()?,
+            catch_type: r.read_u16()?,
+        };
+        check_handler(&entry, code_len, constant_pool)?;
+        exception_table.push(entry);
+    }
+
+    let sub_attr_count = r.read_u16()?;
+    let mut line_numbers = Vec::new();
+    let mut local_variables = Vec::new();
+    let mut local_types = Vec::new();
+    let mut raw = Vec::new();
+    for _ in 0..sub_attr_count {
+        let name = r.read_u16()?;
+        let len = r.read_u32()? as usize;
+        let attr = Attr::new(name, len, constant_pool);
+        match attr.name {
+            "LocalVariableTable" => {
+                if let Some(locals) =
+                    attr.read(r, ctx, &mut raw, |a, _| read_locals(a, len, code_len))?
+                {
+                    local_variables.extend(locals);
                 }
-                i += 1;
             }
-            b'[' => {
-                while i < bytes.len() && bytes[i] == b'[' {
-                    i += 1;
-                }
-                if i < bytes.len() && bytes[i] == b'L' {
-                    while i < bytes.len() && bytes[i] != b';' {
-                        i += 1;
-                    }
-                    i += 1;
-                } else {
-                    i += 1;
+            "LocalVariableTypeTable" => {
+                if let Some(locals) =
+                    attr.read(r, ctx, &mut raw, |a, _| read_locals(a, len, code_len))?
+                {
+                    local_types.extend(locals);
                 }
-                count += 1;
             }
-            b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' => {
-                count += 1;
-                i += 1;
+            "LineNumberTable" => {
+                if let Some(lines) =
+                    attr.read(r, ctx, &mut raw, |a, _| read_line_numbers(a, len, code_len))?
+                {
+                    line_numbers.extend(lines);
+                }
             }
-            _ => {
-                i += 1;
+            _ => raw.push(attr.raw(r)?),
+        }
+    }
+
+    // A type table entry adds the signature to the plain entry for the same
+    // variable (JVMS 4.7.14).
+    for t in local_types {
+        let same = |v: &&mut LocalVariableEntry| {
+            v.start_pc == t.start_pc && v.length == t.length && v.index == t.index
+        };
+        if let Some(v) = local_variables.iter_mut().find(same) {
+            v.signature_index = Some(t.descriptor_index);
+        }
+    }
+
+    Ok(CodeAttribute {
+        max_stack,
+        max_locals,
+        code: CodeBytes::within(ctx.shared.as_ref(), code_bytes),
+        exception_table,
+        line_numbers,
+        local_variables,
+        attributes: raw,
+    })
+}
+
+fn read_line_numbers(
+    r: &mut ClassReader,
+    len: usize,
+    code_len: usize,
+) -> Result<Vec<LineNumberEntry>, JvmError> {
+    let count = r.read_u16()?;
+    if len != 2 + 4 * count as usize {
+        return Err(JvmError::ClassFormatError(alloc::format!(
+            "LineNumberTable of {} bytes holds {} entries",
+            len,
+            count
+        )));
+    }
+    let mut out = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let entry = LineNumberEntry {
+            start_pc: r.read_u16()?,
+            line: r.read_u16()?,
+        };
+        if entry.start_pc as usize >= code_len {
+            return Err(JvmError::ClassFormatError(alloc::format!(
+                "line {} starts at {}, outside code of length {}",
+                entry.line,
+                entry.start_pc,
+                code_len
+            )));
+        }
+        out.push(entry);
+    }
+    Ok(out)
+}
+
+/// The parameter types of a method descriptor, one field descriptor each
+/// (`I`, `J`, `Ljava/lang/String;`, `[[B`...), or `None` if it's malformed.
+pub fn descriptor_params(descriptor: &str) -> Option<Vec<&str>> {
+    let inner = descriptor.strip_prefix('(')?;
+    let end = inner.find(')')?;
+    let bytes = &inner.as_bytes()[..end];
+    let mut params = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        while bytes[i] == b'[' {
+            i += 1;
+            if i == bytes.len() {
+                return None;
             }
         }
+        match bytes[i] {
+            b'L' => i += inner[i..end].find(';')? + 1,
+            b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' => i += 1,
+            _ => return None,
+        }
+        params.push(&inner[start..i]);
     }
-    count
+    Some(params)
 }
@@ -0,0 +1,1133 @@
+//! Class file output: [`ClassFile::to_bytes`], and builders for putting a
+//! new class together from Rust, such as a small adapter generated at build
+//! time.
+//!
+//! ```
+//! # use shared::classfile::ACC_PUBLIC;
+//! # use shared::classfile::write::{ClassBuilder, CodeBuilder};
+//! # use shared::opcodes::{ALOAD_0, INVOKESPECIAL, RETURN};
+//! # fn main() -> Result<(), shared::types::JvmError> {
+//! let mut class = ClassBuilder::new("Adapter", "java/lang/Object")?;
+//! let init = class.pool().method_ref("java/lang/Object", "<init>", "()V")?;
+//! let mut code = CodeBuilder::new(1, 1);
+//! code.op(ALOAD_0).op_u16(INVOKESPECIAL, init).op(RETURN);
+//! class.method(ACC_PUBLIC, "<init>", "()V", Some(code.finish()?))?;
+//! let bytes = class.build()?.to_bytes()?;
+//! # assert_eq!(bytes[..4], [0xCA, 0xFE, 0xBA, 0xBE]);
+//! # Ok(())
+//! # }
+//! ```
+
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{
+    ACC_PUBLIC,
+    Annotation,
+    ClassFile,
+    CodeAttribute,
+    CpEntry,
+    ElementValue,
+    ExceptionTableEntry,
+    FieldInfo,
+    LocalVariableEntry,
+    MethodInfo,
+    RawAttribute,
+};
+use crate::opcodes::{
+    GOTO_W,
+    IFEQ,
+    IFNONNULL,
+    IFNULL,
+    JSR,
+    JSR_W,
+};
+use crate::types::JvmError;
+
+impl ClassFile {
+    /// Encodes the class back into class file form. Only what
+    /// [`parse_class`](super::parse_class) keeps is written, so parsing the result gives an
+    /// equal `ClassFile`. Fails when the pool has no room left for an
+    /// attribute's name, or holds a string [`check_utf8`] turns down.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JvmError> {
+        let mut cp = self.constant_pool.clone();
+        if cp.len() > u16::MAX as usize {
+            return Err(pool_full());
+        }
+        let mut attr_name = |name: &str| -> Result<u16, JvmError> {
+            let found = cp
+                .iter()
+                .position(|e| matches!(e, CpEntry::Utf8(s) if &**s == name));
+            if let Some(i) = found {
+                return Ok(i as u16);
+            }
+            if cp.len() == u16::MAX as usize {
+                return Err(pool_full());
+            }
+            cp.push(CpEntry::Utf8(Rc::from(name)));
+            Ok((cp.len() - 1) as u16)
+        };
+        // Names are only added to the pool when an attribute needs one.
+        let codes = || self.methods.iter().filter_map(|m| m.code.as_ref());
+        let code_name = if codes().next().is_some() {
+            attr_name("Code")?
+        } else {
+            0
+        };
+        let exceptions_name = if self.methods.iter().any(|m| !m.exceptions.is_empty()) {
+            attr_name("Exceptions")?
+        } else {
+            0
+        };
+        let lines_name = if codes().any(|c| !c.line_numbers.is_empty()) {
+            attr_name("LineNumberTable")?
+        } else {
+            0
+        };
+        let locals_name = if codes().any(|c| !c.local_variables.is_empty()) {
+            attr_name("LocalVariableTable")?
+        } else {
+            0
+        };
+        let local_types_name = if codes()
+            .flat_map(|c| &c.local_variables)
+            .any(|v| v.signature_index.is_some())
+        {
+            attr_name("LocalVariableTypeTable")?
+        } else {
+            0
+        };
+        let source_name = if self.source_file.is_some() {
+            attr_name("SourceFile")?
+        } else {
+            0
+        };
+        let bootstrap_name = if self.bootstrap_methods.is_empty() {
+            0
+        } else {
+            attr_name("BootstrapMethods")?
+        };
+        let inner_name = if self.inner_classes.is_empty() {
+            0
+        } else {
+            attr_name("InnerClasses")?
+        };
+        let host_name = if self.nest_host.is_some() {
+            attr_name("NestHost")?
+        } else {
+            0
+        };
+        let members_name = if self.nest_members.is_empty() {
+            0
+        } else {
+            attr_name("NestMembers")?
+        };
+        let annotated = !self.annotations.is_empty()
+            || self.fields.iter().any(|f| !f.annotations.is_empty())
+            || self.methods.iter().any(|m| !m.annotations.is_empty());
+        let annotations_name = if annotated {
+            attr_name("RuntimeVisibleAnnotations")?
+        } else {
+            0
+        };
+        let signed = self.signature.is_some()
+            || self.fields.iter().any(|f| f.signature.is_some())
+            || self.methods.iter().any(|m| m.signature.is_some());
+        let signature_name = if signed { attr_name("Signature")? } else { 0 };
+        let default_name = if self.methods.iter().any(|m| m.annotation_default.is_some()) {
+            attr_name("AnnotationDefault")?
+        } else {
+            0
+        };
+
+        let mut out = Vec::new();
+        put_u32(&mut out, 0xCAFEBABE);
+        put_u16(&mut out, self.minor_version);
+        put_u16(&mut out, self.major_version);
+        put_u16(&mut out, cp.len() as u16);
+        for entry in cp.iter().skip(1) {
+            write_cp_entry(&mut out, entry)?;
+        }
+
+        put_u16(&mut out, self.access_flags);
+        put_u16(&mut out, self.this_class);
+        put_u16(&mut out, self.super_class);
+        put_u16(&mut out, self.interfaces.len() as u16);
+        for &i in &self.interfaces {
+            put_u16(&mut out, i);
+        }
+
+        put_u16(&mut out, self.fields.len() as u16);
+        for f in &self.fields {
+            put_u16(&mut out, f.access_flags);
+            put_u16(&mut out, f.name_index);
+            put_u16(&mut out, f.descriptor_index);
+            put_u16(
+                &mut out,
+                !f.annotations.is_empty() as u16
+                    + f.signature.is_some() as u16
+                    + f.attributes.len() as u16,
+            );
+            put_annotations(&mut out, annotations_name, &f.annotations);
+            put_signature(&mut out, signature_name, f.signature);
+            put_raw(&mut out, &f.attributes);
+        }
+
+        put_u16(&mut out, self.methods.len() as u16);
+        for m in &self.methods {
+            put_u16(&mut out, m.access_flags);
+            put_u16(&mut out, m.name_index);
+            put_u16(&mut out, m.descriptor_index);
+            put_u16(
+                &mut out,
+                m.code.is_some() as u16
+                    + !m.exceptions.is_empty() as u16
+                    + !m.annotations.is_empty() as u16
+                    + m.annotation_default.is_some() as u16
+                    + m.signature.is_some() as u16
+                    + m.attributes.len() as u16,
+            );
+            put_annotations(&mut out, annotations_name, &m.annotations);
+            put_signature(&mut out, signature_name, m.signature);
+            put_raw(&mut out, &m.attributes);
+            if let Some(value) = &m.annotation_default {
+                let mut body = Vec::new();
+                put_element(&mut body, value);
+                put_u16(&mut out, default_name);
+                put_u32(&mut out, body.len() as u32);
+                out.extend_from_slice(&body);
+            }
+            if !m.exceptions.is_empty() {
+                put_u16(&mut out, exceptions_name);
+                put_u32(&mut out, 2 + 2 * m.exceptions.len() as u32);
+                put_u16(&mut out, m.exceptions.len() as u16);
+                for &e in &m.exceptions {
+                    put_u16(&mut out, e);
+                }
+            }
+            let Some(code) = &m.code else {
+                continue;
+            };
+            let mut body = Vec::new();
+            put_u16(&mut body, code.max_stack);
+            put_u16(&mut body, code.max_locals);
+            put_u32(&mut body, code.code.len() as u32);
+            body.extend_from_slice(&code.code);
+            put_u16(&mut body, code.exception_table.len() as u16);
+            for e in &code.exception_table {
+                put_u16(&mut body, e.start_pc);
+                put_u16(&mut body, e.end_pc);
+                put_u16(&mut body, e.handler_pc);
+                put_u16(&mut body, e.catch_type);
+            }
+            let typed: Vec<&LocalVariableEntry> = code
+                .local_variables
+                .iter()
+                .filter(|v| v.signature_index.is_some())
+                .collect();
+            let sub_attrs = [
+                !code.line_numbers.is_empty(),
+                !code.local_variables.is_empty(),
+                !typed.is_empty(),
+            ];
+            put_u16(
+                &mut body,
+                (sub_attrs.iter().filter(|&&b| b).count() + code.attributes.len()) as u16,
+            );
+            if !code.line_numbers.is_empty() {
+                put_u16(&mut body, lines_name);
+                put_u32(&mut body, 2 + 4 * code.line_numbers.len() as u32);
+                put_u16(&mut body, code.line_numbers.len() as u16);
+                for l in &code.line_numbers {
+                    put_u16(&mut body, l.start_pc);
+                    put_u16(&mut body, l.line);
+                }
+            }
+            if !code.local_variables.is_empty() {
+                put_u16(&mut body, locals_name);
+                put_u32(&mut body, 2 + 10 * code.local_variables.len() as u32);
+                put_u16(&mut body, code.local_variables.len() as u16);
+                for v in &code.local_variables {
+                    put_local(&mut body, v, v.descriptor_index);
+                }
+            }
+            if !typed.is_empty() {
+                put_u16(&mut body, local_types_name);
+                put_u32(&mut body, 2 + 10 * typed.len() as u32);
+                put_u16(&mut body, typed.len() as u16);
+                for v in typed {
+                    put_local(&mut body, v, v.signature_index.unwrap_or(0));
+                }
+            }
+            put_raw(&mut body, &code.attributes);
+            put_u16(&mut out, code_name);
+            put_u32(&mut out, body.len() as u32);
+            out.extend_from_slice(&body);
+        }
+
+        let mut attrs = 0;
+        let mut tail = Vec::new();
+        if let Some(index) = self.source_file {
+            attrs += 1;
+            put_u16(&mut tail, source_name);
+            put_u32(&mut tail, 2);
+            put_u16(&mut tail, index);
+        }
+        if !self.bootstrap_methods.is_empty() {
+            attrs += 1;
+            let mut body = Vec::new();
+            put_u16(&mut body, self.bootstrap_methods.len() as u16);
+            for b in &self.bootstrap_methods {
+                put_u16(&mut body, b.method_ref);
+                put_u16(&mut body, b.arguments.len() as u16);
+                for &a in &b.arguments {
+                    put_u16(&mut body, a);
+                }
+            }
+            put_u16(&mut tail, bootstrap_name);
+            put_u32(&mut tail, body.len() as u32);
+            tail.extend_from_slice(&body);
+        }
+        if !self.inner_classes.is_empty() {
+            attrs += 1;
+            put_u16(&mut tail, inner_name);
+            put_u32(&mut tail, 2 + 8 * self.inner_classes.len() as u32);
+            put_u16(&mut tail, self.inner_classes.len() as u16);
+            for c in &self.inner_classes {
+                put_u16(&mut tail, c.inner_class_info_index);
+                put_u16(&mut tail, c.outer_class_info_index);
+                put_u16(&mut tail, c.inner_name_index);
+                put_u16(&mut tail, c.inner_class_access_flags);
+            }
+        }
+        if let Some(index) = self.nest_host {
+            attrs += 1;
+            put_u16(&mut tail, host_name);
+            put_u32(&mut tail, 2);
+            put_u16(&mut tail, index);
+        }
+        if !self.nest_members.is_empty() {
+            attrs += 1;
+            put_u16(&mut tail, members_name);
+            put_u32(&mut tail, 2 + 2 * self.nest_members.len() as u32);
+            put_u16(&mut tail, self.nest_members.len() as u16);
+            for &m in &self.nest_members {
+                put_u16(&mut tail, m);
+            }
+        }
+        if !self.annotations.is_empty() {
+            attrs += 1;
+            put_annotations(&mut tail, annotations_name, &self.annotations);
+        }
+        if self.signature.is_some() {
+            attrs += 1;
+            put_signature(&mut tail, signature_name, self.signature);
+        }
+        attrs += self.attributes.len() as u16;
+        put_raw(&mut tail, &self.attributes);
+        put_u16(&mut out, attrs);
+        out.extend_from_slice(&tail);
+        Ok(out)
+    }
+}
+
+fn put_u16(out: &mut Vec<u8>, v: u16) {
+    out.extend_from_slice(&v.to_be_bytes());
+}
+
+// One LocalVariableTable entry, or a LocalVariableTypeTable one when `type_index`
+// is the signature.
+fn put_local(out: &mut Vec<u8>, v: &LocalVariableEntry, type_index: u16) {
+    put_u16(out, v.start_pc);
+    put_u16(out, v.length);
+    put_u16(out, v.name_index);
+    put_u16(out, type_index);
+    put_u16(out, v.index);
+}
+
+fn put_raw(out: &mut Vec<u8>, attributes: &[RawAttribute]) {
+    for a in attributes {
+        put_u16(out, a.name_index);
+        put_u32(out, a.data.len() as u32);
+        out.extend_from_slice(&a.data);
+    }
+}
+
+fn put_signature(out: &mut Vec<u8>, name: u16, signature: Option<u16>) {
+    if let Some(index) = signature {
+        put_u16(out, name);
+        put_u32(out, 2);
+        put_u16(out, index);
+    }
+}
+
+// A whole RuntimeVisibleAnnotations attribute, or nothing for no annotations.
+fn put_annotations(out: &mut Vec<u8>, name: u16, annotations: &[Annotation]) {
+    if annotations.is_empty() {
+        return;
+    }
+    let mut body = Vec::new();
+    put_u16(&mut body, annotations.len() as u16);
+    for a in annotations {
+        put_annotation(&mut body, a);
+    }
+    put_u16(out, name);
+    put_u32(out, body.len() as u32);
+    out.extend_from_slice(&body);
+}
+
+fn put_annotation(out: &mut Vec<u8>, a: &Annotation) {
+    put_u16(out, a.type_index);
+    put_u16(out, a.elements.len() as u16);
+    for (name, value) in &a.elements {
+        put_u16(out, *name);
+        put_element(out, value);
+    }
+}
+
+fn put_element(out: &mut Vec<u8>, value: &ElementValue) {
+    match value {
+        ElementValue::Const { tag, index } => {
+            out.push(*tag);
+            put_u16(out, *index);
+        }
+        ElementValue::Enum {
+            type_name_index,
+            const_name_index,
+        } => {
+            out.push(b'e');
+            put_u16(out, *type_name_index);
+            put_u16(out, *const_name_index);
+        }
+        ElementValue::Class(index) => {
+            out.push(b'c');
+            put_u16(out, *index);
+        }
+        ElementValue::Annotation(a) => {
+            out.push(b'@');
+            put_annotation(out, a);
+        }
+        ElementValue::Array(values) => {
+            out.push(b'[');
+            put_u16(out, values.len() as u16);
+            for v in values {
+                put_element(out, v);
+            }
+        }
+    }
+}
+
+fn pool_full() -> JvmError {
+    JvmError::ClassFormatError(String::from("constant pool over 65535 entries"))
+}
+
+/// Whether `s` can be written as a Utf8 constant. Class files hold Modified
+/// UTF-8 (JVMS 4.4.7), which only matches standard UTF-8 for strings without
+/// NUL or characters past U+FFFF; this writer doesn't encode those, so they
+/// are turned down, as is anything over the 65535 bytes the length allows.
+pub fn check_utf8(s: &str) -> Result<(), JvmError> {
+    if s.len() > u16::MAX as usize {
+        return Err(JvmError::ClassFormatError(alloc::format!(
+            "Utf8 constant of {} bytes is over 65535",
+            s.len()
+        )));
+    }
+    if let Some(c) = s.chars().find(|&c| c == '\0' || c > '\u{FFFF}') {
+        return Err(JvmError::ClassFormatError(alloc::format!(
+            "Utf8 constant has U+{:04X}, which needs Modified UTF-8",
+            c as u32
+        )));
+    }
+    Ok(())
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_be_bytes());
+}
+
+// The slot after a Long or Double is `Unused` and has no bytes of its own.
+fn write_cp_entry(out: &mut Vec<u8>, entry: &CpEntry) -> Result<(), JvmError> {
+    match entry {
+        CpEntry::Unused => {}
+        CpEntry::Utf8(s) => {
+            check_utf8(s)?;
+            out.push(1);
+            put_u16(out, s.len() as u16);
+            out.extend_from_slice(s.as_bytes());
+        }
+        CpEntry::Integer(v) => {
+            out.push(3);
+            put_u32(out, *v as u32);
+        }
+        CpEntry::Float(v) => {
+            out.push(4);
+            put_u32(out, v.to_bits());
+        }
+        CpEntry::Long(v) => {
+            out.push(5);
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+        CpEntry::Double(v) => {
+            out.push(6);
+            out.extend_from_slice(&v.to_bits().to_be_bytes());
+        }
+        CpEntry::Class { name_index } => {
+            out.push(7);
+            put_u16(out, *name_index);
+        }
+        CpEntry::StringRef { string_index } => {
+            out.push(8);
+            put_u16(out, *string_index);
+        }
+        CpEntry::Fieldref {
+            class_index,
+            name_and_type_index,
+        } => {
+            out.push(9);
+            put_u16(out, *class_index);
+            put_u16(out, *name_and_type_index);
+        }
+        CpEntry::Methodref {
+            class_index,
+            name_and_type_index,
+        } => {
+            out.push(10);
+            put_u16(out, *class_index);
+            put_u16(out, *name_and_type_index);
+        }
+        CpEntry::InterfaceMethodref {
+            class_index,
+            name_and_type_index,
+        } => {
+            out.push(11);
+            put_u16(out, *class_index);
+            put_u16(out, *name_and_type_index);
+        }
+        CpEntry::NameAndType {
+            name_index,
+            descriptor_index,
+        } => {
+            out.push(12);
+            put_u16(out, *name_index);
+            put_u16(out, *descriptor_index);
+        }
+        CpEntry::MethodHandle {
+            reference_kind,
+            reference_index,
+        } => {
+            out.push(15);
+            out.push(*reference_kind);
+            put_u16(out, *reference_index);
+        }
+        CpEntry::MethodType { descriptor_index } => {
+            out.push(16);
+            put_u16(out, *descriptor_index);
+        }
+        CpEntry::InvokeDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        } => {
+            out.push(18);
+            put_u16(out, *bootstrap_method_attr_index);
+            put_u16(out, *name_and_type_index);
+        }
+        CpEntry::Module { name_index } => {
+            out.push(19);
+            put_u16(out, *name_index);
+        }
+        CpEntry::Package { name_index } => {
+            out.push(20);
+            put_u16(out, *name_index);
+        }
+        CpEntry::Opaque { tag, data } => {
+            out.push(*tag);
+            out.extend_from_slice(data);
+        }
+    }
+    Ok(())
+}
+
+const ACC_SUPER: u16 = 0x0020;
+
+// Java 5: the newest version whose verifier doesn't ask for StackMapTable
+// frames, which the builders don't compute.
+const DEFAULT_MAJOR_VERSION: u16 = 49;
+
+/// A constant pool under construction. Each method returns the index of an
+/// entry equal to the one asked for, adding it only when there isn't one,
+/// or an error once the pool has no index left to give.
+#[derive(Debug, Clone)]
+pub struct ConstantPool {
+    entries: Vec<CpEntry>,
+}
+
+impl Default for ConstantPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConstantPool {
+    pub fn new() -> Self {
+        Self {
+            entries: alloc::vec![CpEntry::Unused],
+        }
+    }
+
+    fn add(&mut self, entry: CpEntry, same: impl Fn(&CpEntry) -> bool) -> Result<u16, JvmError> {
+        if let Some(i) = self.entries.iter().position(same) {
+            return Ok(i as u16);
+        }
+        let index = self.entries.len();
+        let wide = matches!(entry, CpEntry::Long(_) | CpEntry::Double(_));
+        // The pool's count is a u16 one past the last index.
+        if index + 1 + wide as usize > u16::MAX as usize {
+            return Err(pool_full());
+        }
+        self.entries.push(entry);
+        if wide {
+            self.entries.push(CpEntry::Unused);
+        }
+        Ok(index as u16)
+    }
+
+    /// Fails for a string [`check_utf8`] turns down.
+    pub fn utf8(&mut self, s: &str) -> Result<u16, JvmError> {
+        check_utf8(s)?;
+        self.add(
+            CpEntry::Utf8(Rc::from(s)),
+            |e| matches!(e, CpEntry::Utf8(v) if &**v == s),
+        )
+    }
+
+    pub fn integer(&mut self, v: i32) -> Result<u16, JvmError> {
+        self.add(
+            CpEntry::Integer(v),
+            |e| matches!(e, CpEntry::Integer(x) if *x == v),
+        )
+    }
+
+    /// Floats and doubles are matched by bits, so `-0.0` and each NaN get
+    /// their own entry.
+    pub fn float(&mut self, v: f32) -> Result<u16, JvmError> {
+        self.add(
+            CpEntry::Float(v),
+            |e| matches!(e, CpEntry::Float(x) if x.to_bits() == v.to_bits()),
+        )
+    }
+
+    pub fn long(&mut self, v: i64) -> Result<u16, JvmError> {
+        self.add(
+            CpEntry::Long(v),
+            |e| matches!(e, CpEntry::Long(x) if *x == v),
+        )
+    }
+
+    pub fn double(&mut self, v: f64) -> Result<u16, JvmError> {
+        self.add(
+            CpEntry::Double(v),
+            |e| matches!(e, CpEntry::Double(x) if x.to_bits() == v.to_bits()),
+        )
+    }
+
+    /// A Class entry for an internal name such as `java/lang/Object`.
+    pub fn class(&mut self, name: &str) -> Result<u16, JvmError> {
+        let name_index = self.utf8(name)?;
+        self.add(
+            CpEntry::Class { name_index },
+            |e| matches!(e, CpEntry::Class { name_index: n } if *n == name_index),
+        )
+    }
+
+    pub fn string(&mut self, s: &str) -> Result<u16, JvmError> {
+        let string_index = self.utf8(s)?;
+        self.add(
+            CpEntry::StringRef { string_index },
+            |e| matches!(e, CpEntry::StringRef { string_index: n } if *n == string_index),
+        )
+    }
+
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> Result<u16, JvmError> {
+        let name_index = self.utf8(name)?;
+        let descriptor_index = self.utf8(descriptor)?;
+        self.add(
+            CpEntry::NameAndType {
+                name_index,
+                descriptor_index,
+            },
+            |e| {
+                matches!(e, CpEntry::NameAndType { name_index: n, descriptor_index: d }
+                    if *n == name_index && *d == descriptor_index)
+            },
+        )
+    }
+
+    pub fn field_ref(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<u16, JvmError> {
+        let class_index = self.class(class)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+        self.add(
+            CpEntry::Fieldref {
+                class_index,
+                name_and_type_index,
+            },
+            |e| {
+                matches!(e, CpEntry::Fieldref { class_index: c, name_and_type_index: t }
+                    if *c == class_index && *t == name_and_type_index)
+            },
+        )
+    }
+
+    pub fn method_ref(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<u16, JvmError> {
+        let class_index = self.class(class)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+        self.add(
+            CpEntry::Methodref {
+                class_index,
+                name_and_type_index,
+            },
+            |e| {
+                matches!(e, CpEntry::Methodref { class_index: c, name_and_type_index: t }
+                    if *c == class_index && *t == name_and_type_index)
+            },
+        )
+    }
+
+    pub fn interface_method_ref(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<u16, JvmError> {
+        let class_index = self.class(class)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+        self.add(
+            CpEntry::InterfaceMethodref {
+                class_index,
+                name_and_type_index,
+            },
+            |e| {
+                matches!(e, CpEntry::InterfaceMethodref { class_index: c, name_and_type_index: t }
+                    if *c == class_index && *t == name_and_type_index)
+            },
+        )
+    }
+
+    pub fn into_entries(self) -> Vec<CpEntry> {
+        self.entries
+    }
+}
+
+/// A branch target in a [`CodeBuilder`], made by [`CodeBuilder::label`]
+/// and placed by [`CodeBuilder::bind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+/// Bytecode under construction. Instructions are appended in order;
+/// branches go to labels, which [`CodeBuilder::finish`] turns into offsets.
+#[derive(Debug, Clone)]
+pub struct CodeBuilder {
+    code: Vec<u8>,
+    max_stack: u16,
+    max_locals: u16,
+    labels: Vec<Option<usize>>,
+    // (instruction pc, offset position, label, wide) for each branch.
+    fixups: Vec<(usize, usize, Label, bool)>,
+    handlers: Vec<(Label, Label, Label, u16)>,
+}
+
+impl CodeBuilder {
+    /// The stack and locals sizes aren't worked out from the code; give
+    /// what it needs.
+    pub fn new(max_stack: u16, max_locals: u16) -> Self {
+        Self {
+            code: Vec::new(),
+            max_stack,
+            max_locals,
+            labels: Vec::new(),
+            fixups: Vec::new(),
+            handlers: Vec::new(),
+        }
+    }
+
+    /// The pc the next instruction will be at.
+    pub fn pc(&self) -> usize {
+        self.code.len()
+    }
+
+    /// An instruction without operands, such as `RETURN`.
+    pub fn op(&mut self, op: u8) -> &mut Self {
+        self.code.push(op);
+        self
+    }
+
+    /// An instruction with one byte operand, such as `BIPUSH` or `ILOAD`.
+    pub fn op_u8(&mut self, op: u8, v: u8) -> &mut Self {
+        self.code.extend_from_slice(&[op, v]);
+        self
+    }
+
+    /// An instruction with a two-byte operand, such as `SIPUSH` or a cp
+    /// index for `GETSTATIC` and `INVOKEVIRTUAL`.
+    pub fn op_u16(&mut self, op: u8, v: u16) -> &mut Self {
+        self.code.push(op);
+        self.code.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+
+    /// Any other bytes, such as the operands of `INVOKEINTERFACE`, `IINC`
+    /// or a switch.
+    pub fn raw(&mut self, bytes: &[u8]) -> &mut Self {
+        self.code.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Places `label` at the next instruction.
+    pub fn bind(&mut self, label: Label) -> &mut Self {
+        if let Some(slot) = self.labels.get_mut(label.0) {
+            *slot = Some(self.code.len());
+        }
+        self
+    }
+
+    /// A branch (`IFEQ` through `JSR`, `IFNULL`, `IFNONNULL`, `GOTO_W` or
+    /// `JSR_W`) to `label`.
+    pub fn jump(&mut self, op: u8, label: Label) -> &mut Self {
+        let wide = matches!(op, GOTO_W | JSR_W);
+        let pc = self.code.len();
+        self.code.push(op);
+        self.fixups.push((pc, pc + 1, label, wide));
+        let width = if wide { 4 } else { 2 };
+        self.code.extend_from_slice(&[0; 4][..width]);
+        self
+    }
+
+    /// An exception table entry: code from `start` up to `end` is covered
+    /// by `handler` for `catch_type`, a Class cp index or 0 for any.
+    pub fn handler(
+        &mut self,
+        start: Label,
+        end: Label,
+        handler: Label,
+        catch_type: u16,
+    ) -> &mut Self {
+        self.handlers.push((start, end, handler, catch_type));
+        self
+    }
+
+    /// Patches the branches and returns the code, or an error for a label
+    /// that was never bound, a branch out of reach of a 16-bit offset, a
+    /// branch opcode that isn't one or code too long for a class file.
+    pub fn finish(mut self) -> Result<CodeAttribute, JvmError> {
+        let err = |msg: &str| Err(JvmError::ClassFormatError(String::from(msg)));
+        if self.code.is_empty() || self.code.len() > u16::MAX as usize {
+            return err("code must be 1 to 65535 bytes");
+        }
+        let at = |labels: &[Option<usize>], label: Label| {
+            labels
+                .get(label.0)
+                .copied()
+                .flatten()
+                .ok_or_else(|| JvmError::ClassFormatError(String::from("unbound label")))
+        };
+        for &(pc, pos, label, wide) in &self.fixups {
+            let op = self.code[pc];
+            if !wide && !matches!(op, IFEQ..=JSR | IFNULL | IFNONNULL) {
+                return err("jump with a non-branch opcode");
+            }
+            let offset = at(&self.labels, label)? as i64 - pc as i64;
+            if wide {
+                self.code[pos..pos + 4].copy_from_slice(&(offset as i32).to_be_bytes());
+            } else {
+                let Ok(offset) = i16::try_from(offset) else {
+                    return err("branch out of 16-bit range");
+                };
+                self.code[pos..pos + 2].copy_from_slice(&offset.to_be_bytes());
+            }
+        }
+        let mut exception_table = Vec::with_capacity(self.handlers.len());
+        for &(start, end, handler, catch_type) in &self.handlers {
+            exception_table.push(ExceptionTableEntry {
+                start_pc: at(&self.labels, start)? as u16,
+                end_pc: at(&self.labels, end)? as u16,
+                handler_pc: at(&self.labels, handler)? as u16,
+                catch_type,
+            });
+        }
+        Ok(CodeAttribute {
+            max_stack: self.max_stack,
+            max_locals: self.max_locals,
+            code: self.code.into(),
+            exception_table,
+            line_numbers: Vec::new(),
+            local_variables: Vec::new(),
+            attributes: Vec::new(),
+        })
+    }
+}
+
+/// A class under construction, with its [`ConstantPool`]. Members are
+/// added in the order they're given.
+#[derive(Debug, Clone)]
+pub struct ClassBuilder {
+    pool: ConstantPool,
+    major_version: u16,
+    access_flags: u16,
+    this_class: u16,
+    super_class: u16,
+    interfaces: Vec<u16>,
+    fields: Vec<FieldInfo>,
+    methods: Vec<MethodInfo>,
+}
+
+impl ClassBuilder {
+    /// A public class `name` extending `super_name`, both internal names
+    /// such as `efi/Adapter` and `java/lang/Object`.
+    pub fn new(name: &str, super_name: &str) -> Result<Self, JvmError> {
+        let mut pool = ConstantPool::new();
+        let this_class = pool.class(name)?;
+        let super_class = pool.class(super_name)?;
+        Ok(Self {
+            pool,
+            major_version: DEFAULT_MAJOR_VERSION,
+            access_flags: ACC_PUBLIC | ACC_SUPER,
+            this_class,
+            super_class,
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+        })
+    }
+
+    pub fn pool(&mut self) -> &mut ConstantPool {
+        &mut self.pool
+    }
+
+    pub fn access_flags(&mut self, flags: u16) -> &mut Self {
+        self.access_flags = flags;
+        self
+    }
+
+    pub fn major_version(&mut self, version: u16) -> &mut Self {
+        self.major_version = version;
+        self
+    }
+
+    pub fn interface(&mut self, name: &str) -> Result<&mut Self, JvmError> {
+        let index = self.pool.class(name)?;
+        self.interfaces.push(index);
+        Ok(self)
+    }
+
+    pub fn field(
+        &mut self,
+        access_flags: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<&mut Self, JvmError> {
+        let field = FieldInfo {
+            access_flags,
+            name_index: self.pool.utf8(name)?,
+            descriptor_index: self.pool.utf8(descriptor)?,
+            annotations: Vec::new(),
+            signature: None,
+            attributes: Vec::new(),
+        };
+        self.fields.push(field);
+        Ok(self)
+    }
+
+    /// A method, with `code` from [`CodeBuilder::finish`] unless it's
+    /// native or abstract.
+    pub fn method(
+        &mut self,
+        access_flags: u16,
+        name: &str,
+        descriptor: &str,
+        code: Option<CodeAttribute>,
+    ) -> Result<&mut Self, JvmError> {
+        // The attribute's name, which to_bytes would otherwise add to the
+        // end of the pool, leaving it different from what parses back.
+        if code.is_some() {
+            self.pool.utf8("Code")?;
+        }
+        let method = MethodInfo {
+            access_flags,
+            name_index: self.pool.utf8(name)?,
+            descriptor_index: self.pool.utf8(descriptor)?,
+            code,
+            exceptions: Vec::new(),
+            annotations: Vec::new(),
+            signature: None,
+            annotation_default: None,
+            attributes: Vec::new(),
+        };
+        self.methods.push(method);
+        Ok(self)
+    }
+
+    /// The finished class, after the same [`ClassFile::validate`] the
+    /// parser runs.
+    pub fn build(self) -> Result<ClassFile, JvmError> {
+        let class = ClassFile {
+            minor_version: 0,
+            major_version: self.major_version,
+            constant_pool: self.pool.into_entries(),
+            access_flags: self.access_flags,
+            this_class: self.this_class,
+            super_class: self.super_class,
+            interfaces: self.interfaces,
+            fields: self.fields,
+            methods: self.methods,
+            bootstrap_methods: Vec::new(),
+            source_file: None,
+            inner_classes: Vec::new(),
+            nest_host: None,
+            nest_members: Vec::new(),
+            annotations: Vec::new(),
+            signature: None,
+            attributes: Vec::new(),
+        };
+        class.validate()?;
+        Ok(class)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classfile::{
+        ACC_STATIC,
+        ClassParser,
+        parse_class,
+    };
+    use crate::opcodes::{
+        ASTORE_0,
+        GOTO,
+        ICONST_0,
+        LDC2_W,
+        LRETURN,
+        NOP,
+        POP2,
+        RETURN,
+    };
+
+    fn branch_offset(code: &[u8], pc: usize) -> i16 {
+        i16::from_be_bytes([code[pc + 1], code[pc + 2]])
+    }
+
+    #[test]
+    fn built_class_round_trips() -> Result<(), JvmError> {
+        let mut class = ClassBuilder::new("Sample", "java/lang/Object")?;
+        let long = class.pool().long(0x1234_5678_9abc)?;
+        let double = class.pool().double(-2.5)?;
+        let exception = class.pool().class("java/lang/Exception")?;
+
+        let mut code = CodeBuilder::new(2, 1);
+        let (start, top, done, handler) = (code.label(), code.label(), code.label(), code.label());
+        code.bind(start).op_u16(LDC2_W, double).op(POP2);
+        code.bind(top).op(ICONST_0);
+        let forward = code.pc();
+        code.jump(IFEQ, done);
+        let backward = code.pc();
+        code.jump(GOTO, top);
+        code.bind(done).op_u16(LDC2_W, long).op(LRETURN);
+        code.bind(handler)
+            .op(ASTORE_0)
+            .op_u16(LDC2_W, long)
+            .op(LRETURN);
+        code.handler(start, done, handler, exception);
+        let code = code.finish()?;
+
+        assert_eq!(branch_offset(&code.code, forward), 6);
+        assert_eq!(branch_offset(&code.code, backward), -4);
+        assert_eq!(code.exception_table[0].handler_pc, 15);
+
+        class.field(ACC_STATIC, "count", "J")?.method(
+            ACC_PUBLIC | ACC_STATIC,
+            "sample",
+            "()J",
+            Some(code),
+        )?;
+        let class = class.build()?;
+        assert_eq!(parse_class(&class.to_bytes()?)?, class);
+        assert_eq!(ClassParser::new().parse_owned(class.to_bytes()?)?, class);
+        Ok(())
+    }
+
+    #[test]
+    fn unbound_label_is_an_error() {
+        let mut code = CodeBuilder::new(0, 0);
+        let nowhere = code.label();
+        code.jump(GOTO, nowhere).op(RETURN);
+        assert!(matches!(code.finish(), Err(JvmError::ClassFormatError(_))));
+    }
+
+    #[test]
+    fn branch_past_16_bits_is_an_error() {
+        let mut code = CodeBuilder::new(0, 0);
+        let far = code.label();
+        code.jump(GOTO, far)
+            .raw(&[NOP; 40_000])
+            .bind(far)
+            .op(RETURN);
+        assert!(matches!(code.finish(), Err(JvmError::ClassFormatError(_))));
+    }
+
+    #[test]
+    fn full_constant_pool_is_an_error() {
+        let mut pool = ConstantPool::new();
+        pool.entries.resize(65533, CpEntry::Unused);
+        assert_eq!(pool.long(7), Ok(65533));
+        assert!(pool.integer(8).is_err());
+        assert_eq!(pool.long(7), Ok(65533));
+
+        let mut pool = ConstantPool::new();
+        pool.entries.resize(65534, CpEntry::Unused);
+        assert!(pool.long(7).is_err());
+        assert_eq!(pool.integer(7), Ok(65534));
+        assert!(pool.integer(8).is_err());
+    }
+
+    #[test]
+    fn to_bytes_with_no_room_for_an_attribute_name_is_an_error() -> Result<(), JvmError> {
+        let mut class = ClassBuilder::new("Full", "java/lang/Object")?;
+        class.method(ACC_PUBLIC, "run", "()V", None)?;
+        let mut class = class.build()?;
+        class.methods[0].exceptions.push(class.super_class);
+        class.constant_pool.resize(65535, CpEntry::Unused);
+        assert!(matches!(
+            class.to_bytes(),
+            Err(JvmError::ClassFormatError(_))
+        ));
+        class.methods[0].exceptions.clear();
+        assert!(class.to_bytes().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn utf8_without_a_plain_encoding_is_an_error() {
+        let mut pool = ConstantPool::new();
+        let long = "x".repeat(65536);
+        for s in ["nul\0inside", "\u{1F600}", &long] {
+            assert!(matches!(pool.utf8(s), Err(JvmError::ClassFormatError(_))));
+        }
+        assert!(pool.utf8(&long[..65535]).is_ok());
+        assert!(pool.utf8("caf\u{E9}").is_ok());
+    }
+}
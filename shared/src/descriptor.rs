@@ -0,0 +1,139 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::types::{
+    JvmError,
+    JvmValue,
+};
+
+/// A field type as a descriptor writes it (JVMS 4.3.2), such as `I`,
+/// `Ljava/lang/String;` or `[[B`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+    /// A primitive, by its descriptor letter such as `I`.
+    Base(u8),
+    /// A class by internal name, such as `java/lang/String`.
+    Object(String),
+    Array(Box<FieldType>),
+}
+
+impl FieldType {
+    pub fn parse(s: &str) -> Result<FieldType, JvmError> {
+        let mut p = Parser { s, pos: 0 };
+        let t = p.field_type()?;
+        if p.pos != s.len() {
+            return Err(p.error());
+        }
+        Ok(t)
+    }
+
+    /// Local variable slots a value of this type takes: two for long and
+    /// double, one for everything else.
+    pub fn slots(&self) -> usize {
+        match self {
+            FieldType::Base(b'J' | b'D') => 2,
+            _ => 1,
+        }
+    }
+
+    /// 0 of the right width, or null for references.
+    pub fn default_value(&self) -> JvmValue {
+        match self {
+            FieldType::Base(b'J') => JvmValue::Long(0),
+            FieldType::Base(b'F') => JvmValue::Float(0.0),
+            FieldType::Base(b'D') => JvmValue::Double(0.0),
+            FieldType::Base(_) => JvmValue::Int(0),
+            _ => JvmValue::Null,
+        }
+    }
+}
+
+/// A method descriptor such as `(JLjava/lang/String;)V`, split into its
+/// parameter types and return type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodDescriptor {
+    pub params: Vec<FieldType>,
+    /// `None` for void.
+    pub result: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+    pub fn parse(s: &str) -> Result<MethodDescriptor, JvmError> {
+        let mut p = Parser { s, pos: 0 };
+        p.expect(b'(')?;
+        let mut params = Vec::new();
+        while p.peek() != Some(b')') {
+            params.push(p.field_type()?);
+        }
+        p.pos += 1;
+        let result = if p.peek() == Some(b'V') {
+            p.pos += 1;
+            None
+        } else {
+            Some(p.field_type()?)
+        };
+        if p.pos != s.len() {
+            return Err(p.error());
+        }
+        Ok(MethodDescriptor { params, result })
+    }
+
+    /// Local variable slots the parameters take, not counting a receiver.
+    pub fn param_slots(&self) -> usize {
+        self.params.iter().map(FieldType::slots).sum()
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self) -> JvmError {
+        JvmError::ClassFormatError(format!("bad descriptor {:?} at {}", self.s, self.pos))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.as_bytes().get(self.pos).copied()
+    }
+
+    fn expect(&mut self, b: u8) -> Result<(), JvmError> {
+        if self.peek() != Some(b) {
+            return Err(self.error());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn field_type(&mut self) -> Result<FieldType, JvmError> {
+        // Dimensions loop rather than recurse, like the signature parser.
+        let mut dims = 0;
+        while self.peek() == Some(b'[') {
+            self.pos += 1;
+            dims += 1;
+        }
+        let mut t = match self.peek() {
+            Some(b @ (b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z')) => {
+                self.pos += 1;
+                FieldType::Base(b)
+            }
+            Some(b'L') => {
+                let start = self.pos + 1;
+                let len = self.s[start..].find(';').ok_or_else(|| self.error())?;
+                if len == 0 {
+                    return Err(self.error());
+                }
+                self.pos = start + len + 1;
+                FieldType::Object(String::from(&self.s[start..start + len]))
+            }
+            _ => return Err(self.error()),
+        };
+        for _ in 0..dims {
+            t = FieldType::Array(Box::new(t));
+        }
+        Ok(t)
+    }
+}
@@ -0,0 +1,683 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::classfile::{
+    ClassFile,
+    CodeAttribute,
+    CpEntry,
+    FieldInfo,
+    MethodInfo,
+};
+use crate::opcodes::*;
+
+const CLASS_FLAGS: &[(u16, &str)] = &[
+    (0x0001, "ACC_PUBLIC"),
+    (0x0010, "ACC_FINAL"),
+    (0x0020, "ACC_SUPER"),
+    (0x0200, "ACC_INTERFACE"),
+    (0x0400, "ACC_ABSTRACT"),
+    (0x1000, "ACC_SYNTHETIC"),
+    (0x2000, "ACC_ANNOTATION"),
+    (0x4000, "ACC_ENUM"),
+    (0x8000, "ACC_MODULE"),
+];
+
+const FIELD_FLAGS: &[(u16, &str)] = &[
+    (0x0001, "ACC_PUBLIC"),
+    (0x0002, "ACC_PRIVATE"),
+    (0x0004, "ACC_PROTECTED"),
+    (0x0008, "ACC_STATIC"),
+    (0x0010, "ACC_FINAL"),
+    (0x0040, "ACC_VOLATILE"),
+    (0x0080, "ACC_TRANSIENT"),
+    (0x1000, "ACC_SYNTHETIC"),
+    (0x4000, "ACC_ENUM"),
+];
+
+const METHOD_FLAGS: &[(u16, &str)] = &[
+    (0x0001, "ACC_PUBLIC"),
+    (0x0002, "ACC_PRIVATE"),
+    (0x0004, "ACC_PROTECTED"),
+    (0x0008, "ACC_STATIC"),
+    (0x0010, "ACC_FINAL"),
+    (0x0020, "ACC_SYNCHRONIZED"),
+    (0x0040, "ACC_BRIDGE"),
+    (0x0080, "ACC_VARARGS"),
+    (0x0100, "ACC_NATIVE"),
+    (0x0400, "ACC_ABSTRACT"),
+    (0x0800, "ACC_STRICT"),
+    (0x1000, "ACC_SYNTHETIC"),
+];
+
+// The flags that read as source modifiers, in the order Java writes them.
+const MODIFIERS: &[(u16, &str)] = &[
+    (0x0001, "public"),
+    (0x0002, "private"),
+    (0x0004, "protected"),
+    (0x0400, "abstract"),
+    (0x0008, "static"),
+    (0x0010, "final"),
+    (0x0020, "synchronized"),
+    (0x0040, "volatile"),
+    (0x0080, "transient"),
+    (0x0100, "native"),
+];
+
+/// The whole class in the layout of `javap -c -v`: header, constant pool,
+/// then each field and method with its bytecode and tables.
+///
+/// Bad indices and truncated code are shown as `?` or cut short rather than
+/// failing, so a class that didn't load can be looked at too.
+pub fn disassemble(class: &ClassFile) -> String {
+    let mut out = String::new();
+    let name = class.class_name().unwrap_or("?");
+    let _ = match class.module_name() {
+        Some(module) if class.is_module() => writeln!(out, "module {}", module),
+        _ => writeln!(out, "class {}", dotted(name)),
+    };
+    let _ = writeln!(out, "  minor version: {}", class.minor_version);
+    let _ = writeln!(out, "  major version: {}", class.major_version);
+    let _ = writeln!(
+        out,
+        "  flags: (0x{:04x}) {}",
+        class.access_flags,
+        flags(class.access_flags, CLASS_FLAGS)
+    );
+    let _ = writeln!(out, "  this_class: #{}  // {}", class.this_class, name);
+    let _ = writeln!(
+        out,
+        "  super_class: #{}  // {}",
+        class.super_class,
+        class.super_class_name().unwrap_or("none")
+    );
+    for &i in &class.interfaces {
+        let _ = writeln!(out, "  interface: #{}  // {}", i, cp_text(class, i));
+    }
+
+    out.push_str("Constant pool:\n");
+    for (i, entry) in class.constant_pool.iter().enumerate().skip(1) {
+        let (kind, operands) = match entry {
+            CpEntry::Unused => continue,
+            CpEntry::Utf8(s) => ("Utf8", escape(s)),
+            CpEntry::Integer(v) => ("Integer", format!("{}", v)),
+            CpEntry::Float(v) => ("Float", format!("{}f", v)),
+            CpEntry::Long(v) => ("Long", format!("{}l", v)),
+            CpEntry::Double(v) => ("Double", format!("{}d", v)),
+            CpEntry::Class { name_index } => ("Class", format!("#{}", name_index)),
+            CpEntry::StringRef { string_index } => ("String", format!("#{}", string_index)),
+            CpEntry::Fieldref {
+                class_index,
+                name_and_type_index,
+            } => (
+                "Fieldref",
+                format!("#{}.#{}", class_index, name_and_type_index),
+            ),
+            CpEntry::Methodref {
+                class_index,
+                name_and_type_index,
+            } => (
+                "Methodref",
+                format!("#{}.#{}", class_index, name_and_type_index),
+            ),
+            CpEntry::InterfaceMethodref {
+                class_index,
+                name_and_type_index,
+            } => (
+                "InterfaceMethodref",
+                format!("#{}.#{}", class_index, name_and_type_index),
+            ),
+            CpEntry::NameAndType {
+                name_index,
+                descriptor_index,
+            } => (
+                "NameAndType",
+                format!("#{}:#{}", name_index, descriptor_index),
+            ),
+            CpEntry::MethodHandle {
+                reference_kind,
+                reference_index,
+            } => (
+                "MethodHandle",
+                format!("{}:#{}", reference_kind, reference_index),
+            ),
+            CpEntry::MethodType { descriptor_index } => {
+                ("MethodType", format!("#{}", descriptor_index))
+            }
+            CpEntry::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => (
+                "InvokeDynamic",
+                format!("#{}:#{}", bootstrap_method_attr_index, name_and_type_index),
+            ),
+            CpEntry::Module { name_index } => ("Module", format!("#{}", name_index)),
+            CpEntry::Package { name_index } => ("Package", format!("#{}", name_index)),
+            CpEntry::Opaque { tag, data } => {
+                ("Opaque", format!("tag {}, {} bytes", tag, data.len()))
+            }
+        };
+        let index = format!("#{}", i);
+        let literal = matches!(
+            entry,
+            CpEntry::Utf8(_)
+                | CpEntry::Integer(_)
+                | CpEntry::Float(_)
+                | CpEntry::Long(_)
+                | CpEntry::Double(_)
+                | CpEntry::Opaque { .. }
+        );
+        let _ = if literal {
+            writeln!(out, "{:>6} = {:<18} {}", index, kind, operands)
+        } else {
+            writeln!(
+                out,
+                "{:>6} = {:<18} {:<14} // {}",
+                index,
+                kind,
+                operands,
+                cp_text(class, i as u16)
+            )
+        };
+    }
+
+    out.push_str("{\n");
+    for field in &class.fields {
+        write_field(&mut out, class, field);
+        out.push('\n');
+    }
+    for method in &class.methods {
+        write_method(&mut out, class, method);
+        out.push('\n');
+    }
+    out.push_str("}\n");
+    if let Some(source) = class.source_file_name() {
+        let _ = writeln!(out, "SourceFile: \"{}\"", source);
+    }
+    if let Some(index) = class.signature {
+        let _ = writeln!(out, "Signature: #{}  // {}", index, cp_text(class, index));
+    }
+    if let Some(index) = class.nest_host {
+        let _ = writeln!(out, "NestHost: class {}", cp_text(class, index));
+    }
+    if !class.nest_members.is_empty() {
+        out.push_str("NestMembers:\n");
+        for &i in &class.nest_members {
+            let _ = writeln!(out, "  {}", cp_text(class, i));
+        }
+    }
+    if !class.bootstrap_methods.is_empty() {
+        out.push_str("BootstrapMethods:\n");
+        for (i, b) in class.bootstrap_methods.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "  {}: #{} {}",
+                i,
+                b.method_ref,
+                cp_text(class, b.method_ref)
+            );
+            if !b.arguments.is_empty() {
+                out.push_str("    Method arguments:\n");
+                for &a in &b.arguments {
+                    let _ = writeln!(out, "      #{} {}", a, cp_text(class, a));
+                }
+            }
+        }
+    }
+    for a in &class.attributes {
+        let _ = writeln!(
+            out,
+            "{}: {} bytes",
+            cp_text(class, a.name_index),
+            a.data.len()
+        );
+    }
+    out
+}
+
+/// One method as [`disassemble`] shows it.
+pub fn disassemble_method(class: &ClassFile, method: &MethodInfo) -> String {
+    let mut out = String::new();
+    write_method(&mut out, class, method);
+    out
+}
+
+fn write_field(out: &mut String, class: &ClassFile, field: &FieldInfo) {
+    let descriptor = class.get_utf8(field.descriptor_index).unwrap_or("?");
+    let _ = writeln!(
+        out,
+        "  {}{} {};",
+        modifiers(field.access_flags, false),
+        java_type(descriptor),
+        class.get_utf8(field.name_index).unwrap_or("?")
+    );
+    let _ = writeln!(out, "    descriptor: {}", descriptor);
+    let _ = writeln!(
+        out,
+        "    flags: (0x{:04x}) {}",
+        field.access_flags,
+        flags(field.access_flags, FIELD_FLAGS)
+    );
+    if let Some(index) = field.signature {
+        let _ = writeln!(
+            out,
+            "    Signature: #{}  // {}",
+            index,
+            cp_text(class, index)
+        );
+    }
+}
+
+fn write_method(out: &mut String, class: &ClassFile, method: &MethodInfo) {
+    let name = class.get_utf8(method.name_index).unwrap_or("?");
+    let descriptor = class.get_utf8(method.descriptor_index).unwrap_or("?");
+    let (params, result) = match descriptor.strip_prefix('(').and_then(|d| d.split_once(')')) {
+        Some((_, result)) => (
+            crate::classfile::descriptor_params(descriptor)
+                .unwrap_or_default()
+                .iter()
+                .map(|p| java_type(p))
+                .collect::<Vec<_>>()
+                .join(", "),
+            java_type(result),
+        ),
+        None => (String::new(), String::from("?")),
+    };
+    let _ = match name {
+        "<clinit>" => writeln!(out, "  static {{}};"),
+        "<init>" => writeln!(
+            out,
+            "  {}{}({});",
+            modifiers(method.access_flags, true),
+            dotted(class.class_name().unwrap_or("?")),
+            params
+        ),
+        _ => writeln!(
+            out,
+            "  {}{} {}({});",
+            modifiers(method.access_flags, true),
+            result,
+            name,
+            params
+        ),
+    };
+    let _ = writeln!(out, "    descriptor: {}", descriptor);
+    let _ = writeln!(
+        out,
+        "    flags: (0x{:04x}) {}",
+        method.access_flags,
+        flags(method.access_flags, METHOD_FLAGS)
+    );
+    if let Some(code) = &method.code {
+        write_code(out, class, code);
+    }
+    if !method.exceptions.is_empty() {
+        out.push_str("    Exceptions:\n      throws ");
+        let names: Vec<String> = method
+            .exceptions
+            .iter()
+            .map(|&i| dotted(class.get_class_name(i).unwrap_or("?")))
+            .collect();
+        out.push_str(&names.join(", "));
+        out.push('\n');
+    }
+    if let Some(index) = method.signature {
+        let _ = writeln!(
+            out,
+            "    Signature: #{}  // {}",
+            index,
+            cp_text(class, index)
+        );
+    }
+}
+
+fn write_code(out: &mut String, class: &ClassFile, code: &CodeAttribute) {
+    out.push_str("    Code:\n");
+    let _ = writeln!(
+        out,
+        "      stack={}, locals={}",
+        code.max_stack, code.max_locals
+    );
+    let bytes = &code.code;
+    let mut pc = 0;
+    while pc < bytes.len() {
+        match write_insn(out, class, bytes, pc) {
+            Some(len) => pc += len,
+            None => {
+                let _ = writeln!(out, "      {:>4}: <truncated>", pc);
+                break;
+            }
+        }
+    }
+    if !code.exception_table.is_empty() {
+        out.push_str("      Exception table:\n         from    to  target type\n");
+        for e in &code.exception_table {
+            let kind = match e.catch_type {
+                0 => String::from("any"),
+                i => format!("Class {}", class.get_class_name(i).unwrap_or("?")),
+            };
+            let _ = writeln!(
+                out,
+                "        {:>5} {:>5} {:>5}   {}",
+                e.start_pc, e.end_pc, e.handler_pc, kind
+            );
+        }
+    }
+    if !code.line_numbers.is_empty() {
+        out.push_str("      LineNumberTable:\n");
+        for l in &code.line_numbers {
+            let _ = writeln!(out, "        line {}: {}", l.line, l.start_pc);
+        }
+    }
+    if !code.local_variables.is_empty() {
+        out.push_str("      LocalVariableTable:\n        Start  Length  Slot  Name   Signature\n");
+        for v in &code.local_variables {
+            let _ = writeln!(
+                out,
+                "        {:>5}  {:>6}  {:>4}  {:>5}   {}",
+                v.start_pc,
+                v.length,
+                v.index,
+                class.get_utf8(v.name_index).unwrap_or("?"),
+                class.get_utf8(v.descriptor_index).unwrap_or("?")
+            );
+        }
+    }
+    for a in &code.attributes {
+        let _ = writeln!(
+            out,
+            "      {}: {} bytes",
+            cp_text(class, a.name_index),
+            a.data.len()
+        );
+    }
+}
+
+// Writes the instruction at `pc` and returns its length, or `None` when it
+// runs off the end of the code.
+fn write_insn(out: &mut String, class: &ClassFile, code: &[u8], pc: usize) -> Option<usize> {
+    let op = code[pc];
+    let u8_at = |at: usize| code.get(at).copied();
+    let u16_at = |at: usize| Some(u16::from_be_bytes([*code.get(at)?, *code.get(at + 1)?]));
+    let i32_at = |at: usize| {
+        let b = code.get(at..at + 4)?;
+        Some(i32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let target = |offset: i32| pc as i64 + offset as i64;
+    let (len, operands) = match op {
+        BIPUSH => (2, format!("{}", u8_at(pc + 1)? as i8)),
+        SIPUSH => (3, format!("{}", u16_at(pc + 1)? as i16)),
+        LDC => (2, cp_operand(class, u8_at(pc + 1)? as u16)),
+        LDC_W | LDC2_W | GETSTATIC..=INVOKESTATIC | NEW | ANEWARRAY | CHECKCAST | INSTANCEOF => {
+            (3, cp_operand(class, u16_at(pc + 1)?))
+        }
+        ILOAD..=ALOAD | ISTORE..=ASTORE | RET => (2, format!("{}", u8_at(pc + 1)?)),
+        IINC => (3, format!("{}, {}", u8_at(pc + 1)?, u8_at(pc + 2)? as i8)),
+        IFEQ..=JSR | IFNULL | IFNONNULL => {
+            (3, format!("{}", target(u16_at(pc + 1)? as i16 as i32)))
+        }
+        GOTO_W | JSR_W => (5, format!("{}", target(i32_at(pc + 1)?))),
+        INVOKEINTERFACE => {
+            let index = u16_at(pc + 1)?;
+            (
+                5,
+                format!("{}, {}", cp_operand(class, index), u8_at(pc + 3)?),
+            )
+        }
+        INVOKEDYNAMIC => (5, cp_operand(class, u16_at(pc + 1)?)),
+        MULTIANEWARRAY => {
+            let index = u16_at(pc + 1)?;
+            (
+                4,
+                format!("{}, {}", cp_operand(class, index), u8_at(pc + 3)?),
+            )
+        }
+        NEWARRAY => {
+            let kind = match u8_at(pc + 1)? {
+                4 => "boolean",
+                5 => "char",
+                6 => "float",
+                7 => "double",
+                8 => "byte",
+                9 => "short",
+                10 => "int",
+                11 => "long",
+                _ => "?",
+            };
+            (2, String::from(kind))
+        }
+        WIDE => {
+            let inner = u8_at(pc + 1)?;
+            let slot = u16_at(pc + 2)?;
+            if inner == IINC {
+                (6, format!("iinc {}, {}", slot, u16_at(pc + 4)? as i16))
+            } else {
+                (4, format!("{} {}", name(inner), slot))
+            }
+        }
+        TABLESWITCH => {
+            let base = (pc + 4) & !3;
+            let default = i32_at(base)?;
+            let (low, high) = (i32_at(base + 4)?, i32_at(base + 8)?);
+            let count = (high as i64 - low as i64 + 1).max(0) as usize;
+            if count > code.len() {
+                return None;
+            }
+            let mut s = format!("{{ // {} to {}\n", low, high);
+            for k in 0..count {
+                let t = target(i32_at(base + 12 + 4 * k)?);
+                let _ = writeln!(s, "          {:>12}: {}", low as i64 + k as i64, t);
+            }
+            let _ = write!(
+                s,
+                "          {:>12}: {}\n      }}",
+                "default",
+                target(default)
+            );
+            (base + 12 + 4 * count - pc, s)
+        }
+        LOOKUPSWITCH => {
+            let base = (pc + 4) & !3;
+            let default = i32_at(base)?;
+            let count = i32_at(base + 4)?.max(0) as usize;
+            if count > code.len() {
+                return None;
+            }
+            let mut s = format!("{{ // {}\n", count);
+            for k in 0..count {
+                let key = i32_at(base + 8 + 8 * k)?;
+                let t = target(i32_at(base + 12 + 8 * k)?);
+                let _ = writeln!(s, "          {:>12}: {}", key, t);
+            }
+            let _ = write!(
+                s,
+                "          {:>12}: {}\n      }}",
+                "default",
+                target(default)
+            );
+            (base + 8 + 8 * count - pc, s)
+        }
+        _ => (1, String::new()),
+    };
+    if pc + len > code.len() {
+        return None;
+    }
+    let line = format!("{:<14}{}", name(op), operands);
+    let _ = writeln!(out, "      {:>4}: {}", pc, line.trim_end());
+    Some(len)
+}
+
+// `#index // Kind text`, as javap writes a cp operand.
+fn cp_operand(class: &ClassFile, index: u16) -> String {
+    let kind = match class.constant_pool.get(index as usize) {
+        Some(CpEntry::Fieldref { .. }) => "Field",
+        Some(CpEntry::Methodref { .. }) => "Method",
+        Some(CpEntry::InterfaceMethodref { .. }) => "InterfaceMethod",
+        Some(CpEntry::Class { .. }) => "class",
+        Some(CpEntry::StringRef { .. }) => "String",
+        Some(CpEntry::Integer(_)) => "int",
+        Some(CpEntry::Float(_)) => "float",
+        Some(CpEntry::Long(_)) => "long",
+        Some(CpEntry::Double(_)) => "double",
+        Some(CpEntry::MethodHandle { .. }) => "MethodHandle",
+        Some(CpEntry::MethodType { .. }) => "MethodType",
+        Some(CpEntry::InvokeDynamic { .. }) => "InvokeDynamic",
+        Some(CpEntry::Module { .. }) => "Module",
+        Some(CpEntry::Package { .. }) => "Package",
+        _ => "?",
+    };
+    let operand = format!("#{}", index);
+    format!("{:<18} // {} {}", operand, kind, cp_text(class, index))
+}
+
+// What a cp entry stands for, with the indices inside it followed.
+fn cp_text(class: &ClassFile, index: u16) -> String {
+    let utf8 = |i: u16| class.get_utf8(i).unwrap_or("?");
+    let nat = |i: u16| match class.constant_pool.get(i as usize) {
+        Some(CpEntry::NameAndType {
+            name_index,
+            descriptor_index,
+        }) => {
+            let name = utf8(*name_index);
+            let name = if name.starts_with('<') {
+                format!("\"{}\"", name)
+            } else {
+                String::from(name)
+            };
+            format!("{}:{}", name, utf8(*descriptor_index))
+        }
+        _ => String::from("?"),
+    };
+    match class.constant_pool.get(index as usize) {
+        Some(CpEntry::Utf8(s)) => escape(s),
+        Some(CpEntry::Integer(v)) => format!("{}", v),
+        Some(CpEntry::Float(v)) => format!("{}f", v),
+        Some(CpEntry::Long(v)) => format!("{}l", v),
+        Some(CpEntry::Double(v)) => format!("{}d", v),
+        Some(
+            CpEntry::Class { name_index }
+            | CpEntry::Module { name_index }
+            | CpEntry::Package { name_index },
+        ) => String::from(utf8(*name_index)),
+        Some(CpEntry::StringRef { string_index }) => escape(utf8(*string_index)),
+        Some(
+            CpEntry::Fieldref {
+                class_index,
+                name_and_type_index,
+            }
+            | CpEntry::Methodref {
+                class_index,
+                name_and_type_index,
+            }
+            | CpEntry::InterfaceMethodref {
+                class_index,
+                name_and_type_index,
+            },
+        ) => format!(
+            "{}.{}",
+            class.get_class_name(*class_index).unwrap_or("?"),
+            nat(*name_and_type_index)
+        ),
+        Some(CpEntry::NameAndType { .. }) => nat(index),
+        Some(CpEntry::MethodHandle {
+            reference_kind,
+            reference_index,
+        }) => format!(
+            "REF_{} {}",
+            match reference_kind {
+                1 => "getField",
+                2 => "getStatic",
+                3 => "putField",
+                4 => "putStatic",
+                5 => "invokeVirtual",
+                6 => "invokeStatic",
+                7 => "invokeSpecial",
+                8 => "newInvokeSpecial",
+                9 => "invokeInterface",
+                _ => "?",
+            },
+            cp_text(class, *reference_index)
+        ),
+        Some(CpEntry::MethodType { descriptor_index }) => String::from(utf8(*descriptor_index)),
+        Some(CpEntry::InvokeDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        }) => format!(
+            "#{}:{}",
+            bootstrap_method_attr_index,
+            nat(*name_and_type_index)
+        ),
+        _ => String::from("?"),
+    }
+}
+
+fn flags(access: u16, table: &[(u16, &str)]) -> String {
+    let names: Vec<&str> = table
+        .iter()
+        .filter(|(bit, _)| access & bit != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    names.join(", ")
+}
+
+// `public static ` and so on, with the trailing space. Methods use the
+// volatile and transient bits for bridge and varargs, which aren't
+// modifiers.
+fn modifiers(access: u16, method: bool) -> String {
+    let mut out = String::new();
+    for (bit, word) in MODIFIERS {
+        if method && matches!(bit, 0x0040 | 0x0080) {
+            continue;
+        }
+        if access & bit != 0 {
+            out.push_str(word);
+            out.push(' ');
+        }
+    }
+    out
+}
+
+// Control characters, such as the \u0001 holes in string concatenation
+// recipes, as javap writes them.
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        if c.is_control() {
+            let _ = write!(out, "\\u{:04x}", c as u32);
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn dotted(name: &str) -> String {
+    name.replace('/', ".")
+}
+
+// A field descriptor as Java source writes the type, such as
+// `java.lang.String[]` for `[Ljava/lang/String;`.
+fn java_type(descriptor: &str) -> String {
+    let dims = descriptor.bytes().take_while(|&b| b == b'[').count();
+    let base = match &descriptor[dims..] {
+        "B" => String::from("byte"),
+        "C" => String::from("char"),
+        "D" => String::from("double"),
+        "F" => String::from("float"),
+        "I" => String::from("int"),
+        "J" => String::from("long"),
+        "S" => String::from("short"),
+        "Z" => String::from("boolean"),
+        "V" => String::from("void"),
+        other => match other.strip_prefix('L').and_then(|s| s.strip_suffix(';')) {
+            Some(name) => dotted(name),
+            None => String::from(other),
+        },
+    };
+    let mut out = base;
+    for _ in 0..dims {
+        out.push_str("[]");
+    }
+    out
+}
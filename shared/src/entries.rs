@@ -0,0 +1,142 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// How the menu orders entries before pins and grouping apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// As discovery found them.
+    Discovery,
+    /// By name, without case.
+    Alphabetical,
+    /// The entry booted last, then by how often each was booted.
+    LastBooted,
+}
+
+impl Order {
+    pub fn parse(s: &str) -> Option<Order> {
+        match s {
+            "discovery" => Some(Order::Discovery),
+            "alphabetical" => Some(Order::Alphabetical),
+            "last-booted" => Some(Order::LastBooted),
+            _ => None,
+        }
+    }
+}
+
+/// What [`EntryPolicy::arrange`] looks at in an entry.
+pub struct EntryFacts<'a> {
+    pub name: &'a str,
+    pub path: &'a str,
+    /// The icon key, such as `fedora`, which groups an OS's entries.
+    pub family: Option<&'a str>,
+    pub boots: u32,
+    pub last: bool,
+}
+
+/// The `entry_*` settings: how entries are ordered and grouped, and which
+/// are hidden or renamed. Patterns match an entry's name or path without
+/// case, with `*` for any run of characters; lists are separated by `;`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryPolicy {
+    pub order: Order,
+    /// Entries to list first, in this order.
+    pub pinned: Vec<String>,
+    /// Keeps each OS family's entries together, families in the order their
+    /// first entry comes.
+    pub group: bool,
+    pub hide: Vec<String>,
+    /// Pattern and new name.
+    pub rename: Vec<(String, String)>,
+}
+
+impl EntryPolicy {
+    /// `entry_order` (discovery, alphabetical or last-booted), `entry_pin`,
+    /// `entry_group = 1`, `entry_hide`, and `entry_rename` as
+    /// `pattern=New name` pairs.
+    pub fn from_settings<'a>(get: impl Fn(&str) -> Option<&'a str>) -> Self {
+        let list = |key: &str| -> Vec<String> {
+            get(key)
+                .map(|v| {
+                    v.split(';')
+                        .map(str::trim)
+                        .filter(|p| !p.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        Self {
+            order: get("entry_order")
+                .and_then(Order::parse)
+                .unwrap_or(Order::Discovery),
+            pinned: list("entry_pin"),
+            group: get("entry_group") == Some("1"),
+            hide: list("entry_hide"),
+            rename: list("entry_rename")
+                .iter()
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(from, to)| (String::from(from.trim()), String::from(to.trim())))
+                .collect(),
+        }
+    }
+
+    /// The name the first matching rename rule gives the entry.
+    pub fn rename(&self, name: &str, path: &str) -> Option<&str> {
+        self.rename
+            .iter()
+            .find(|(pattern, _)| matches(pattern, name, path))
+            .map(|(_, to)| to.as_str())
+    }
+
+    /// The entries to list, as indices into `entries` in menu order: the
+    /// hidden ones left out, the rest sorted by `order`, grouped, and the
+    /// pinned ones moved to the front. Every step keeps ties in the order
+    /// they came.
+    pub fn arrange(&self, entries: &[EntryFacts]) -> Vec<usize> {
+        let mut idx: Vec<usize> = (0..entries.len())
+            .filter(|&i| {
+                let e = &entries[i];
+                !self.hide.iter().any(|p| matches(p, e.name, e.path))
+            })
+            .collect();
+        match self.order {
+            Order::Discovery => {}
+            Order::Alphabetical => idx.sort_by_cached_key(|&i| entries[i].name.to_lowercase()),
+            Order::LastBooted => {
+                idx.sort_by_key(|&i| (!entries[i].last, u32::MAX - entries[i].boots))
+            }
+        }
+        if self.group {
+            let mut families: Vec<Option<&str>> = Vec::new();
+            for &i in &idx {
+                if !families.contains(&entries[i].family) {
+                    families.push(entries[i].family);
+                }
+            }
+            idx.sort_by_key(|&i| families.iter().position(|f| *f == entries[i].family));
+        }
+        idx.sort_by_key(|&i| {
+            let e = &entries[i];
+            self.pinned
+                .iter()
+                .position(|p| matches(p, e.name, e.path))
+                .unwrap_or(usize::MAX)
+        });
+        idx
+    }
+}
+
+fn matches(pattern: &str, name: &str, path: &str) -> bool {
+    wildcard(pattern.as_bytes(), name.as_bytes()) || wildcard(pattern.as_bytes(), path.as_bytes())
+}
+
+// `*` matches any run, everything else one byte without case.
+fn wildcard(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|i| wildcard(rest, &text[i..])),
+        Some((p, rest)) => text
+            .split_first()
+            .is_some_and(|(t, text)| p.eq_ignore_ascii_case(t) && wildcard(rest, text)),
+    }
+}
@@ -0,0 +1,125 @@
+use alloc::format;
+use core::fmt;
+
+use crate::preview::Crc32;
+use crate::types::JvmError;
+
+/// Where a fatal error came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+    /// Reading classes off the ESP.
+    Loader,
+    /// The interpreter itself.
+    Vm,
+    /// A native method or the firmware behind it.
+    Native,
+    /// Falling back to the default entry after the menu failed.
+    Boot,
+}
+
+impl Subsystem {
+    pub fn id(self) -> u8 {
+        match self {
+            Subsystem::Loader => 1,
+            Subsystem::Vm => 2,
+            Subsystem::Native => 3,
+            Subsystem::Boot => 4,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Subsystem::Loader),
+            2 => Some(Subsystem::Vm),
+            3 => Some(Subsystem::Native),
+            4 => Some(Subsystem::Boot),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Subsystem::Loader => "loader",
+            Subsystem::Vm => "vm",
+            Subsystem::Native => "native",
+            Subsystem::Boot => "boot",
+        }
+    }
+
+    /// The subsystem a run-time error belongs to.
+    pub fn of(err: &JvmError) -> Self {
+        match err {
+            JvmError::NativeMethodError(_) | JvmError::IoError(_) | JvmError::Interrupted(_) => {
+                Subsystem::Native
+            }
+            _ => Subsystem::Vm,
+        }
+    }
+}
+
+/// The breadcrumb a fatal error leaves for fleet monitoring.
+///
+/// Stored as [`FaultRecord::LEN`] little-endian bytes: format version (1),
+/// subsystem id, error code ([`JvmError::code`]), CRC-32 of the error
+/// message and the boot counter. The message itself is left out so the
+/// record stays small and free of paths or user data; equal hashes mean
+/// the same failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultRecord {
+    pub subsystem: Subsystem,
+    pub code: u16,
+    pub message_hash: u32,
+    pub boot: u32,
+}
+
+impl FaultRecord {
+    pub const VERSION: u8 = 1;
+    pub const LEN: usize = 12;
+
+    pub fn new(subsystem: Subsystem, err: &JvmError, boot: u32) -> Self {
+        let mut crc = Crc32::new();
+        crc.update(format!("{}", err).as_bytes());
+        Self {
+            subsystem,
+            code: err.code(),
+            message_hash: crc.finish(),
+            boot,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut b = [0u8; Self::LEN];
+        b[0] = Self::VERSION;
+        b[1] = self.subsystem.id();
+        b[2..4].copy_from_slice(&self.code.to_le_bytes());
+        b[4..8].copy_from_slice(&self.message_hash.to_le_bytes());
+        b[8..12].copy_from_slice(&self.boot.to_le_bytes());
+        b
+    }
+
+    pub fn parse(b: &[u8]) -> Option<Self> {
+        if b.len() < Self::LEN || b[0] != Self::VERSION {
+            return None;
+        }
+        Some(Self {
+            subsystem: Subsystem::from_id(b[1])?,
+            code: u16::from_le_bytes([b[2], b[3]]),
+            message_hash: u32::from_le_bytes([b[4], b[5], b[6], b[7]]),
+            boot: u32::from_le_bytes([b[8], b[9], b[10], b[11]]),
+        })
+    }
+}
+
+/// `vm/10 hash=1a2b3c4d boot=42`
+impl fmt::Display for FaultRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} hash={:08x} boot={}",
+            self.subsystem.name(),
+            self.code,
+            self.message_hash,
+            self.boot
+        )
+    }
+}
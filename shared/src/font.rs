@@ -1,7 +1,9 @@
+// Cozette's printable ASCII as build.rs lays it out, shared by the firmware
+// and the desktop runner so text metrics match.
 include!(concat!(env!("OUT_DIR"), "/font_data.rs"));
 
 pub fn glyph(ch: u8) -> &'static [u16] {
-    if ch >= 0x20 && ch <= 0x7E {
+    if (0x20..=0x7E).contains(&ch) {
         &FONT_DATA[(ch - 0x20) as usize]
     } else {
         &FALLBACK
@@ -0,0 +1,208 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A `menuentry` or `submenu` from a grub.cfg.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuItem {
+    pub title: String,
+    /// The `--id` it was given, if any.
+    pub id: Option<String>,
+    /// `None` for a menuentry; a submenu's entries otherwise.
+    pub submenu: Option<Vec<MenuItem>>,
+}
+
+/// The menu a grub.cfg builds, as far as reading it without running it
+/// goes: the entries and submenus in order, and the `default` it sets.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GrubMenu {
+    pub items: Vec<MenuItem>,
+    pub default: Option<String>,
+}
+
+impl GrubMenu {
+    /// Reads the `menuentry` and `submenu` blocks and top-level
+    /// `set default=` lines; scripting is skipped, so entries made in loops
+    /// or sourced files are missed.
+    pub fn parse(text: &str) -> GrubMenu {
+        let mut menu = GrubMenu::default();
+        // The open blocks: the submenus' items, and None for other blocks
+        // such as a menuentry's or a function's.
+        let mut stack: Vec<Option<(MenuItem, Vec<MenuItem>)>> = Vec::new();
+        for line in text.lines() {
+            let words = split_words(line);
+            let Some(first) = words.first() else {
+                continue;
+            };
+            let opens = words.last().is_some_and(|w| w == "{");
+            match first.as_str() {
+                "menuentry" | "submenu" if words.len() > 1 => {
+                    let item = MenuItem {
+                        title: words[1].clone(),
+                        id: words
+                            .iter()
+                            .position(|w| w == "--id" || w == "$menuentry_id_option")
+                            .and_then(|i| words.get(i + 1))
+                            .filter(|w| w.as_str() != "{")
+                            .cloned(),
+                        submenu: None,
+                    };
+                    if first == "submenu" {
+                        stack.push(Some((item, Vec::new())));
+                        continue;
+                    }
+                    push_item(&mut menu.items, &mut stack, item);
+                    if opens {
+                        stack.push(None);
+                    }
+                }
+                "}" => close(&mut menu.items, &mut stack),
+                "set" if stack.is_empty() => {
+                    if let Some(value) = words.get(1).and_then(|w| w.strip_prefix("default=")) {
+                        menu.default = Some(String::from(value));
+                    }
+                }
+                _ => {
+                    if opens {
+                        stack.push(None);
+                    } else if words.last().is_some_and(|w| w == "}") {
+                        close(&mut menu.items, &mut stack);
+                    }
+                }
+            }
+        }
+        while !stack.is_empty() {
+            close(&mut menu.items, &mut stack);
+        }
+        menu
+    }
+
+    /// The entry GRUB boots by default: `default` as an index, a `>` path
+    /// of indices, a title or an id, and the first entry when it's unset or
+    /// names a saved entry this can't read.
+    pub fn default_entry(&self) -> Option<&MenuItem> {
+        let default = self.default.as_deref().unwrap_or("0");
+        let mut items = &self.items;
+        let mut found = None;
+        for step in default.split('>') {
+            let item = match step.parse::<usize>() {
+                Ok(i) => items.get(i),
+                Err(_) => items
+                    .iter()
+                    .find(|it| it.title == step || it.id.as_deref() == Some(step)),
+            };
+            let Some(item) = item else {
+                found = None;
+                break;
+            };
+            found = Some(item);
+            if let Some(children) = &item.submenu {
+                items = children;
+            }
+        }
+        let first = || self.items.iter().find(|it| it.submenu.is_none());
+        match found {
+            Some(item) if item.submenu.is_none() => Some(item),
+            _ => first(),
+        }
+    }
+
+    /// The entries' titles, top level first; with `submenus`, then each
+    /// submenu's as `Submenu > Entry`.
+    pub fn titles(&self, submenus: bool) -> Vec<String> {
+        let mut out: Vec<String> = self
+            .items
+            .iter()
+            .filter(|it| it.submenu.is_none())
+            .map(|it| it.title.clone())
+            .collect();
+        if submenus {
+            for item in &self.items {
+                if let Some(children) = &item.submenu {
+                    collect_titles(&item.title, children, &mut out);
+                }
+            }
+        }
+        out
+    }
+}
+
+fn collect_titles(prefix: &str, items: &[MenuItem], out: &mut Vec<String>) {
+    for item in items {
+        let title = format!("{} > {}", prefix, item.title);
+        match &item.submenu {
+            Some(children) => collect_titles(&title, children, out),
+            None => out.push(title),
+        }
+    }
+}
+
+fn push_item(
+    top: &mut Vec<MenuItem>,
+    stack: &mut [Option<(MenuItem, Vec<MenuItem>)>],
+    item: MenuItem,
+) {
+    match stack.iter_mut().rev().find_map(|b| b.as_mut()) {
+        Some((_, items)) => items.push(item),
+        None => top.push(item),
+    }
+}
+
+fn close(top: &mut Vec<MenuItem>, stack: &mut Vec<Option<(MenuItem, Vec<MenuItem>)>>) {
+    if let Some(Some((mut submenu, items))) = stack.pop() {
+        submenu.submenu = Some(items);
+        push_item(top, stack, submenu);
+    }
+}
+
+// Shell-style words: quotes group and are dropped, `#` starts a comment,
+// and `{`, `}` and `;` stand alone.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                for q in chars.by_ref() {
+                    if q == '\'' {
+                        break;
+                    }
+                    word.push(q);
+                }
+            }
+            '"' => {
+                in_word = true;
+                while let Some(q) = chars.next() {
+                    match q {
+                        '"' => break,
+                        '\\' => word.extend(chars.next()),
+                        _ => word.push(q),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                word.extend(chars.next());
+            }
+            '#' if !in_word => break,
+            '{' | '}' | ';' if !in_word => words.push(String::from(c)),
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(core::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            _ => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
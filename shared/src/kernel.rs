@@ -0,0 +1,43 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Microcode goes first, ahead of the initrd it patches the CPU for.
+const MICROCODE: &[&str] = &["intel-ucode.img", "amd-ucode.img"];
+
+// Longest first, so `initrd.img-6.1` isn't read as `initrd` and `.img-6.1`.
+const INITRD_PREFIXES: &[&str] = &["initrd.img", "initramfs", "initrd"];
+
+/// Whether `name` is named like a kernel: `vmlinuz-linux`, `bzImage`,
+/// `kernel-6.9.1` and the like. Its header still has to say so.
+pub fn is_kernel_name(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    ["vmlinuz", "vmlinux", "bzimage", "kernel"]
+        .iter()
+        .any(|p| lower.starts_with(p))
+        && !lower.ends_with(".sig")
+}
+
+/// The initrds in `files` that go with `kernel`, microcode first: the one
+/// whose name carries the same suffix, such as `initramfs-linux.img` for
+/// `vmlinuz-linux` or `initrd.img-6.1.0-18-amd64` for
+/// `vmlinuz-6.1.0-18-amd64`.
+pub fn matching_initrds(kernel: &str, files: &[String]) -> Vec<String> {
+    let suffix = match kernel.split_once('-') {
+        Some((_, suffix)) => format!("-{}", suffix),
+        None => String::new(),
+    };
+    let mut out: Vec<String> = files
+        .iter()
+        .filter(|f| MICROCODE.iter().any(|m| f.eq_ignore_ascii_case(m)))
+        .cloned()
+        .collect();
+    let initrd = files.iter().find(|f| {
+        INITRD_PREFIXES
+            .iter()
+            .find_map(|p| f.strip_prefix(p))
+            .is_some_and(|rest| rest.strip_suffix(".img").unwrap_or(rest) == suffix)
+    });
+    out.extend(initrd.cloned());
+    out
+}
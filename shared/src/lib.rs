@@ -1,7 +1,24 @@
 #![no_std]
 extern crate alloc;
 
+pub mod bls;
 pub mod classfile;
+pub mod descriptor;
+pub mod disasm;
+pub mod entries;
+pub mod fault;
+#[cfg(feature = "font")]
+pub mod font;
+pub mod grub;
+pub mod kernel;
+pub mod loadopt;
 pub mod opcodes;
+pub mod osrelease;
+pub mod pe;
+pub mod preview;
+pub mod protocols;
+pub mod signature;
+pub mod term;
+pub mod theme;
 pub mod types;
 pub mod zip;
@@ -0,0 +1,172 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const LOAD_OPTION_ACTIVE: u32 = 0x1;
+const LOAD_OPTION_HIDDEN: u32 = 0x8;
+const LOAD_OPTION_CATEGORY_APP: u32 = 0x100;
+
+/// A `Boot####` variable: an EFI_LOAD_OPTION (UEFI 3.1.3).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadOption {
+    pub attributes: u32,
+    pub description: String,
+    /// The device path list, end node included, as the firmware stored it.
+    pub file_path: Vec<u8>,
+    pub optional_data: Vec<u8>,
+}
+
+impl LoadOption {
+    pub fn parse(data: &[u8]) -> Option<LoadOption> {
+        let attributes = u32::from_le_bytes(data.get(0..4)?.try_into().ok()?);
+        let path_len = u16::from_le_bytes(data.get(4..6)?.try_into().ok()?) as usize;
+        let mut units = Vec::new();
+        let mut pos = 6;
+        loop {
+            let unit = u16::from_le_bytes(data.get(pos..pos + 2)?.try_into().ok()?);
+            pos += 2;
+            if unit == 0 {
+                break;
+            }
+            units.push(unit);
+        }
+        let file_path = data.get(pos..pos + path_len)?.to_vec();
+        nodes(&file_path)?;
+        Some(LoadOption {
+            attributes,
+            description: String::from_utf16_lossy(&units),
+            file_path,
+            optional_data: data[pos + path_len..].to_vec(),
+        })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.attributes & LOAD_OPTION_ACTIVE != 0
+    }
+
+    /// Left out of the firmware's own boot menu.
+    pub fn is_hidden(&self) -> bool {
+        self.attributes & LOAD_OPTION_HIDDEN != 0
+    }
+
+    /// A firmware application, such as the setup utility, rather than
+    /// something that boots.
+    pub fn is_app(&self) -> bool {
+        self.attributes & LOAD_OPTION_CATEGORY_APP != 0
+    }
+
+    /// The file its device path ends in, such as
+    /// `\EFI\fedora\shimx64.efi`, if it names one.
+    pub fn file(&self) -> Option<String> {
+        let nodes = nodes(&self.file_path)?;
+        nodes
+            .iter()
+            .rev()
+            .find(|n| n.kind == 4 && n.sub == 4)
+            .map(|n| utf16z(n.data))
+    }
+
+    /// The device path in the firmware's text form, for nodes it knows,
+    /// such as `PciRoot(0x0)/Pci(0x1d,0x0)/NVMe(0x1)/HD(1)/\EFI\BOOT\BOOTX64.EFI`.
+    pub fn path_text(&self) -> String {
+        device_path_text(&self.file_path)
+    }
+}
+
+/// The `BootOrder` variable: option numbers, first to try first.
+pub fn boot_order(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .collect()
+}
+
+/// The variable that holds option `number`, such as `Boot000A`.
+pub fn boot_variable(number: u16) -> String {
+    format!("Boot{:04X}", number)
+}
+
+struct Node<'a> {
+    kind: u8,
+    sub: u8,
+    data: &'a [u8],
+}
+
+// The first instance's nodes, up to its end node; None when a length runs
+// off the buffer or no end node comes.
+fn nodes(path: &[u8]) -> Option<Vec<Node<'_>>> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    loop {
+        let header = path.get(pos..pos + 4)?;
+        let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+        if len < 4 {
+            return None;
+        }
+        let data = path.get(pos + 4..pos + len)?;
+        if header[0] == 0x7f {
+            return Some(out);
+        }
+        out.push(Node {
+            kind: header[0],
+            sub: header[1],
+            data,
+        });
+        pos += len;
+    }
+}
+
+/// `path` as text; nodes it doesn't know come out as `Path(type,subtype)`.
+pub fn device_path_text(path: &[u8]) -> String {
+    let Some(nodes) = nodes(path) else {
+        return String::from("(bad device path)");
+    };
+    let parts: Vec<String> = nodes.iter().map(node_text).collect();
+    parts.join("/")
+}
+
+fn node_text(node: &Node) -> String {
+    let d = node.data;
+    let u8_at = |i: usize| d.get(i).copied().unwrap_or(0);
+    let u16_at = |i: usize| {
+        d.get(i..i + 2)
+            .map_or(0, |b| u16::from_le_bytes([b[0], b[1]]))
+    };
+    let u32_at = |i: usize| {
+        d.get(i..i + 4)
+            .map_or(0, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    match (node.kind, node.sub) {
+        (1, 1) => format!("Pci({:#x},{:#x})", u8_at(1), u8_at(0)),
+        (2, 1) => format!("PciRoot({:#x})", u32_at(4)),
+        (3, 1) => format!("Ata({})", u8_at(0)),
+        (3, 2) => format!("Scsi({},{})", u16_at(0), u16_at(2)),
+        (3, 5) => format!("USB({:#x},{:#x})", u8_at(0), u8_at(1)),
+        (3, 11) => {
+            let mac: Vec<String> = d.iter().take(6).map(|b| format!("{:02x}", b)).collect();
+            format!("MAC({})", mac.concat())
+        }
+        (3, 12) => String::from("IPv4()"),
+        (3, 13) => String::from("IPv6()"),
+        (3, 18) => format!("Sata({:#x},{:#x},{:#x})", u16_at(0), u16_at(2), u16_at(4)),
+        (3, 23) => format!("NVMe({:#x})", u32_at(0)),
+        (3, 24) => format!("Uri({})", String::from_utf8_lossy(d)),
+        (4, 1) => format!("HD({})", u32_at(0)),
+        (4, 2) => format!("CDROM({})", u32_at(0)),
+        (4, 4) => utf16z(d),
+        (4, 6) | (4, 7) => String::from("FvFile()"),
+        (5, 1) => format!(
+            "BBS({})",
+            String::from_utf8_lossy(d.get(4..).unwrap_or(&[])).trim_end_matches('\0')
+        ),
+        (kind, sub) => format!("Path({},{})", kind, sub),
+    }
+}
+
+fn utf16z(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .take_while(|&u| u != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
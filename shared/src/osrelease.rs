@@ -0,0 +1,123 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Where a root filesystem keeps its os-release, in the order systemd
+/// reads them.
+pub const PATHS: &[&str] = &["\\etc\\os-release", "\\usr\\lib\\os-release"];
+
+// Icon keys by os-release ID, for distributions and the ones derived from
+// them through ID_LIKE.
+const ICONS: &[(&str, &str)] = &[
+    ("almalinux", "alma"),
+    ("alpine", "alpine"),
+    ("arch", "arch"),
+    ("centos", "centos"),
+    ("debian", "debian"),
+    ("endeavouros", "endeavouros"),
+    ("fedora", "fedora"),
+    ("gentoo", "gentoo"),
+    ("kali", "kali"),
+    ("linuxmint", "mint"),
+    ("manjaro", "manjaro"),
+    ("microsoft", "windows"),
+    ("nixos", "nixos"),
+    ("opensuse", "opensuse"),
+    ("opensuse-leap", "opensuse"),
+    ("opensuse-tumbleweed", "opensuse"),
+    ("pop", "pop"),
+    ("rhel", "redhat"),
+    ("rocky", "rocky"),
+    ("suse", "opensuse"),
+    ("ubuntu", "ubuntu"),
+    ("void", "void"),
+    ("windows", "windows"),
+];
+
+/// The fields of an os-release file the menu uses.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsRelease {
+    pub name: Option<String>,
+    pub pretty_name: Option<String>,
+    /// Lowercase, such as `fedora`.
+    pub id: Option<String>,
+    pub id_like: Vec<String>,
+    pub version_id: Option<String>,
+}
+
+impl OsRelease {
+    /// Reads `KEY=value` lines, with the shell quoting os-release allows.
+    pub fn parse(text: &str) -> OsRelease {
+        let mut release = OsRelease::default();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = unquote(value.trim());
+            if value.is_empty() {
+                continue;
+            }
+            match key.trim() {
+                "NAME" => release.name = Some(value),
+                "PRETTY_NAME" => release.pretty_name = Some(value),
+                "ID" => release.id = Some(value),
+                "ID_LIKE" => {
+                    release.id_like = value.split_ascii_whitespace().map(String::from).collect()
+                }
+                "VERSION_ID" => release.version_id = Some(value),
+                _ => {}
+            }
+        }
+        release
+    }
+
+    /// PRETTY_NAME, else NAME and VERSION_ID, such as `Fedora Linux 41`.
+    pub fn label(&self) -> Option<String> {
+        if let Some(pretty) = &self.pretty_name {
+            return Some(pretty.clone());
+        }
+        let name = self.name.as_ref()?;
+        Some(match &self.version_id {
+            Some(version) => format!("{} {}", name, version),
+            None => name.clone(),
+        })
+    }
+
+    /// The icon for ID, else the first ID_LIKE with one, else `linux`.
+    pub fn icon(&self) -> &'static str {
+        self.id
+            .iter()
+            .chain(&self.id_like)
+            .find_map(|id| icon_key(id))
+            .unwrap_or("linux")
+    }
+}
+
+/// The icon for an os-release ID or an ESP vendor directory, compared
+/// without case.
+pub fn icon_key(id: &str) -> Option<&'static str> {
+    ICONS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(id))
+        .map(|&(_, icon)| icon)
+}
+
+// One shell word: quotes dropped and backslash escapes applied.
+fn unquote(value: &str) -> String {
+    let mut out = String::new();
+    let mut quote = None;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (q, Some(open)) if q == open => quote = None,
+            ('\\', q) if q != Some('\'') => out.extend(chars.next()),
+            _ => out.push(c),
+        }
+    }
+    out
+}
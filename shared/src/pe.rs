@@ -0,0 +1,197 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::osrelease::OsRelease;
+
+const HEADER_READ: usize = 64 * 1024;
+const SECTION_READ_MAX: usize = 1024 * 1024;
+
+// VS_FIXEDFILEINFO.dwSignature, little-endian.
+const VERSION_SIGNATURE: [u8; 4] = [0xBD, 0x04, 0xEF, 0xFE];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+    /// A unified kernel image: stub, kernel, initrd and os-release in one PE.
+    Uki,
+    /// A kernel with the EFI stub, booted directly.
+    Linux,
+    Efi,
+}
+
+impl ImageKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            ImageKind::Uki => "Unified kernel image",
+            ImageKind::Linux => "Linux kernel",
+            ImageKind::Efi => "EFI application",
+        }
+    }
+}
+
+/// What [`inspect`] could learn about an EFI image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageInfo {
+    pub kind: ImageKind,
+    pub machine: &'static str,
+    /// The kernel release, or the file version from a version resource.
+    pub version: Option<String>,
+    /// A UKI's embedded os-release.
+    pub os_release: Option<OsRelease>,
+    /// A UKI's built-in kernel command line.
+    pub cmdline: Option<String>,
+}
+
+struct Section {
+    name: [u8; 8],
+    offset: u64,
+    size: usize,
+}
+
+impl Section {
+    fn is(&self, name: &str) -> bool {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(8);
+        &self.name[..len] == name.as_bytes()
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    let b = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    let b = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Reads a PE image's headers and the few sections that carry version
+/// information. `read(offset, len)` returns up to `len` bytes from the file,
+/// so a multi-megabyte kernel is never loaded whole.
+pub fn inspect(read: &mut dyn FnMut(u64, usize) -> Option<Vec<u8>>) -> Option<ImageInfo> {
+    let head = read(0, HEADER_READ)?;
+    if head.get(0..2)? != b"MZ" {
+        return None;
+    }
+    let pe = u32_at(&head, 0x3C)? as usize;
+    if head.get(pe..pe + 4)? != b"PE\0\0" {
+        return None;
+    }
+    let machine = match u16_at(&head, pe + 4)? {
+        0x8664 => "x64",
+        0x014C => "ia32",
+        0xAA64 => "aa64",
+        0x5064 => "riscv64",
+        _ => "unknown",
+    };
+    let count = u16_at(&head, pe + 6)? as usize;
+    let table = pe + 24 + u16_at(&head, pe + 20)? as usize;
+
+    let mut sections = Vec::with_capacity(count);
+    for i in 0..count {
+        let at = table + i * 40;
+        let Some(raw) = head.get(at..at + 40) else {
+            break;
+        };
+        let mut name = [0u8; 8];
+        name.copy_from_slice(&raw[..8]);
+        let virtual_size = u32_at(raw, 8)? as usize;
+        let raw_size = u32_at(raw, 16)? as usize;
+        // Raw data is padded to the file alignment; the virtual size is the
+        // real length when it is set.
+        let size = if virtual_size > 0 {
+            virtual_size.min(raw_size)
+        } else {
+            raw_size
+        };
+        sections.push(Section {
+            name,
+            offset: u32_at(raw, 20)? as u64,
+            size,
+        });
+    }
+
+    let mut read_section = |name: &str| -> Option<Vec<u8>> {
+        let s = sections.iter().find(|s| s.is(name))?;
+        read(s.offset, s.size.min(SECTION_READ_MAX))
+    };
+
+    let os_release = read_section(".osrel").map(|data| OsRelease::parse(&trim_text(&data)));
+    let uname = read_section(".uname").map(|data| trim_text(&data));
+    let cmdline = read_section(".cmdline")
+        .map(|data| trim_text(&data))
+        .filter(|c| !c.is_empty());
+    let is_uki = sections.iter().any(|s| s.is(".linux"));
+
+    let mut info = ImageInfo {
+        kind: ImageKind::Efi,
+        machine,
+        version: None,
+        os_release,
+        cmdline,
+    };
+    if is_uki {
+        info.kind = ImageKind::Uki;
+        info.version = uname.filter(|v| !v.is_empty());
+    } else if let Some(release) = linux_version(&head) {
+        info.kind = ImageKind::Linux;
+        info.version = Some(release);
+    } else if head.get(0x38..0x3C) == Some(b"ARM\x64") {
+        // An arm64 Image, whose header carries no version.
+        info.kind = ImageKind::Linux;
+    } else {
+        info.version = read_section(".rsrc").and_then(|data| file_version(&data));
+    }
+    Some(info)
+}
+
+/// The Windows release a Windows Boot Manager's file version, such as
+/// `10.0.22621.2506`, comes from; bootmgfw.efi is serviced with the OS.
+pub fn windows_release(file_version: &str) -> Option<&'static str> {
+    let mut parts = file_version.split('.').map(|p| p.parse::<u32>().ok());
+    let (major, minor, build) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(match (major, minor) {
+        (10, 0) if build >= 22000 => "Windows 11",
+        (10, 0) => "Windows 10",
+        (6, 3) => "Windows 8.1",
+        (6, 2) => "Windows 8",
+        (6, 1) => "Windows 7",
+        (6, 0) => "Windows Vista",
+        _ => return None,
+    })
+}
+
+// The x86 boot protocol puts a pointer to the version banner in the setup
+// header; the release is its first word.
+fn linux_version(head: &[u8]) -> Option<String> {
+    if head.get(0x202..0x206)? != b"HdrS" {
+        return None;
+    }
+    let ptr = u16_at(head, 0x20E)? as usize;
+    if ptr == 0 {
+        return None;
+    }
+    let banner = head.get(ptr + 0x200..)?;
+    let end = banner.iter().position(|&b| b == 0 || b == b' ')?;
+    core::str::from_utf8(&banner[..end]).ok().map(String::from)
+}
+
+fn file_version(rsrc: &[u8]) -> Option<String> {
+    let at = (0..rsrc.len().saturating_sub(16))
+        .step_by(4)
+        .find(|&i| rsrc[i..i + 4] == VERSION_SIGNATURE)?;
+    let ms = u32_at(rsrc, at + 8)?;
+    let ls = u32_at(rsrc, at + 12)?;
+    Some(format!(
+        "{}.{}.{}.{}",
+        ms >> 16,
+        ms & 0xFFFF,
+        ls >> 16,
+        ls & 0xFFFF
+    ))
+}
+
+fn trim_text(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from(String::from_utf8_lossy(&data[..end]).trim())
+}
@@ -0,0 +1,150 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::osrelease::OsRelease;
+use crate::pe::ImageInfo;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl Timestamp {
+    /// As a number that orders the same way the times do, such as
+    /// `202405171230` for 2024-05-17 12:30.
+    pub fn sortable(&self) -> i64 {
+        let date = self.year as i64 * 10_000 + self.month as i64 * 100 + self.day as i64;
+        date * 10_000 + self.hour as i64 * 100 + self.minute as i64
+    }
+}
+
+/// What the menu's preview pane shows for a boot entry. Each side gathers
+/// the facts its own way; [`EntryDetails::render`] keeps the text the same.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntryDetails {
+    pub path: String,
+    pub size: Option<u64>,
+    pub modified: Option<Timestamp>,
+    pub image: Option<ImageInfo>,
+    pub crc32: Option<u32>,
+    /// What a loader entry starts its kernel with, or a UKI's own.
+    pub command_line: Option<String>,
+    /// The entries of the GRUB menu it starts, as
+    /// [`GrubMenu::titles`](crate::grub::GrubMenu::titles) lists them.
+    pub menu: Vec<String>,
+    /// The firmware `Boot####` option it boots, for one found in NVRAM.
+    pub boot_option: Option<u16>,
+}
+
+impl EntryDetails {
+    /// `Label: value` lines, one per known fact, joined with `\n`.
+    pub fn render(&self) -> String {
+        let mut out = format!("Path: {}", self.path);
+        if let Some(size) = self.size {
+            out.push_str(&format!("\nSize: {}", format_size(size)));
+        }
+        if let Some(t) = self.modified {
+            out.push_str(&format!(
+                "\nModified: {:04}-{:02}-{:02} {:02}:{:02}",
+                t.year, t.month, t.day, t.hour, t.minute
+            ));
+        }
+        match &self.image {
+            Some(image) => {
+                out.push_str(&format!(
+                    "\nType: {} ({})",
+                    image.kind.label(),
+                    image.machine
+                ));
+                if let Some(os) = image.os_release.as_ref().and_then(OsRelease::label) {
+                    out.push_str(&format!("\nOS: {}", os));
+                }
+                if let Some(version) = &image.version {
+                    out.push_str(&format!("\nVersion: {}", version));
+                }
+            }
+            None if let Some(number) = self.boot_option => out.push_str(&format!(
+                "\nType: firmware boot option (Boot{:04X})",
+                number
+            )),
+            None if self.size.is_some() => out.push_str("\nType: not a PE image"),
+            None => out.push_str("\nType: unreadable"),
+        }
+        if let Some(crc) = self.crc32 {
+            out.push_str(&format!("\nCRC32: {:08x}", crc));
+        }
+        if let Some(command_line) = &self.command_line {
+            out.push_str(&format!("\nCommand line: {}", command_line));
+        }
+        for title in &self.menu {
+            out.push_str(&format!("\nMenu entry: {}", title));
+        }
+        out
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const KIB: u64 = 1024;
+    const MIB: u64 = KIB * 1024;
+    if bytes >= MIB {
+        format!("{}.{} MiB", bytes / MIB, bytes % MIB * 10 / MIB)
+    } else if bytes >= KIB {
+        format!("{} KiB", bytes / KIB)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+static CRC_TABLE: [u32; 256] = crc_table();
+
+/// Streaming CRC-32 (IEEE), the short hash shown in the preview pane.
+pub struct Crc32(u32);
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self(0xFFFF_FFFF)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let mut c = self.0;
+        for &b in data {
+            c = CRC_TABLE[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8);
+        }
+        self.0 = c;
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,90 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Protocols worth naming in a handle listing, by lowercase GUID.
+pub const KNOWN: &[(&str, &str)] = &[
+    ("5b1b31a1-9562-11d2-8e3f-00a0c969723b", "LoadedImage"),
+    (
+        "bc62157e-3e33-4fec-9920-2d3b36d750df",
+        "LoadedImageDevicePath",
+    ),
+    ("09576e91-6d3f-11d2-8e39-00a0c969723b", "DevicePath"),
+    ("8b843e20-8132-4852-90cc-551a4e4a7f1c", "DevicePathToText"),
+    ("964e5b22-6459-11d2-8e39-00a0c969723b", "SimpleFileSystem"),
+    ("56ec3091-954c-11d2-8e3f-00a0c969723b", "LoadFile"),
+    ("4006c0c1-fcb3-403e-996d-4a6c8724e06d", "LoadFile2"),
+    ("964e5b21-6459-11d2-8e39-00a0c969723b", "BlockIo"),
+    ("a77b2472-e282-4e9f-a245-c2c0e27bbcc1", "BlockIo2"),
+    ("ce345171-ba0b-11d2-8e4f-00a0c969723b", "DiskIo"),
+    ("151c8eae-7f2c-472c-9e54-9828194f6a88", "DiskIo2"),
+    ("8cf2f62c-bc9b-4821-808d-ec9ec421a1a0", "PartitionInfo"),
+    ("52c78312-8edc-4233-98f2-1a1aa5e388a5", "NvmExpressPassThru"),
+    ("1d3de7f0-0807-424f-aa69-11a54e19a46f", "AtaPassThru"),
+    ("143b7632-b81b-4cb7-abd3-b625a5b9bffe", "ExtScsiPassThru"),
+    ("932f47e6-2362-4002-803e-3cd54b138f85", "ScsiIo"),
+    ("387477c1-69c7-11d2-8e39-00a0c969723b", "SimpleTextInput"),
+    ("dd9e7534-7762-4698-8c14-f58517a625aa", "SimpleTextInputEx"),
+    ("387477c2-69c7-11d2-8e39-00a0c969723b", "SimpleTextOutput"),
+    ("31878c87-0b75-11d5-9a4f-0090273fc14d", "SimplePointer"),
+    ("8d59d32b-c655-4ae9-9b15-f25904992a43", "AbsolutePointer"),
+    ("9042a9de-23dc-4a38-96fb-7aded080516a", "GraphicsOutput"),
+    ("bd8c1056-9f36-44ec-92a8-a6337f817986", "EdidActive"),
+    ("1c0c34f6-d380-41fa-a049-8ad06c1a66aa", "EdidDiscovered"),
+    ("bb25cf6f-f1d4-11d2-9a0c-0090273fc1fd", "SerialIo"),
+    ("4cf5b200-68b8-4ca5-9eec-b23e3f50029a", "PciIo"),
+    ("2f707ebb-4a1a-11d4-9a38-0090273fc14d", "PciRootBridgeIo"),
+    ("2b2f68d6-0cd2-44cf-8e8b-bba20b1b5b75", "UsbIo"),
+    ("3e745226-9818-45b6-a2ac-d7cd0e8ba2bc", "Usb2Hc"),
+    ("a19832b9-ac25-11d3-9a2d-0090273fc14d", "SimpleNetwork"),
+    ("e18541cd-f755-4f73-928d-643c8a79b229", "ManagedNetwork"),
+    ("03c4e603-ac28-11d3-9a2d-0090273fc14d", "PxeBaseCode"),
+    ("00720665-67eb-4a99-baf7-d3c33a1c7cc9", "Tcp4ServiceBinding"),
+    ("ec20eb79-6c1a-4664-9a0d-d2e4cc16d664", "Tcp6ServiceBinding"),
+    ("83f01464-99bd-45e5-b383-af6305d8e9e6", "Udp4ServiceBinding"),
+    ("66ed4721-3c98-4d3e-81e3-d03dd39a7254", "Udp6ServiceBinding"),
+    (
+        "9d9a39d8-bd42-4a73-a4d5-8ee94be11380",
+        "Dhcp4ServiceBinding",
+    ),
+    (
+        "9fb9a8a1-2f4a-43a6-889c-d0f7b6c47ad5",
+        "Dhcp6ServiceBinding",
+    ),
+    ("bdc8e6af-d9bc-4379-a72a-e0c4e75dae1c", "HttpServiceBinding"),
+    ("18a031ab-b443-4d1a-a5c0-0c09261e9f71", "DriverBinding"),
+    ("107a772c-d5e1-11d4-9a46-0090273fc14d", "ComponentName"),
+    ("6a7a5cff-e8d9-4f70-bada-75ab3025ce14", "ComponentName2"),
+    ("3152bca5-eade-433d-862e-c01cdc291f44", "Rng"),
+    ("607f766c-7455-42be-930b-e4d76db2720f", "Tcg2"),
+    ("f541796d-a62e-4954-a775-9584f61b9cdd", "Tcg"),
+    ("9e23d768-d2f3-4366-9fc3-3a7aba864374", "Vlan"),
+];
+
+/// The friendly name of a protocol GUID, if it's one we know.
+pub fn name(guid: &str) -> Option<&'static str> {
+    KNOWN
+        .iter()
+        .find(|(g, _)| g.eq_ignore_ascii_case(guid))
+        .map(|(_, n)| *n)
+}
+
+/// The GUID of a known protocol, by friendly name.
+pub fn guid(name: &str) -> Option<&'static str> {
+    KNOWN.iter().find(|(_, n)| *n == name).map(|(g, _)| *g)
+}
+
+/// One line per protocol, `Name  guid` for known ones first and bare GUIDs
+/// after, in the order the handle reported them.
+pub fn describe<'a>(guids: impl IntoIterator<Item = &'a str>) -> String {
+    let (known, unknown): (Vec<&str>, Vec<&str>) =
+        guids.into_iter().partition(|g| name(g).is_some());
+    let mut lines = Vec::new();
+    for g in known {
+        let mut line = String::from(name(g).unwrap_or_default());
+        line.push_str("  ");
+        line.push_str(g);
+        lines.push(line);
+    }
+    lines.extend(unknown.into_iter().map(String::from));
+    lines.join("\n")
+}
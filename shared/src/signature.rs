@@ -0,0 +1,410 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::types::JvmError;
+
+// Type arguments nest, and the parser recurses on them; source code stays a
+// few levels deep.
+const MAX_DEPTH: usize = 32;
+
+/// A type as a `Signature` attribute writes it (JVMS 4.7.9.1).
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeSig {
+    /// A primitive, by its descriptor letter such as `I`.
+    Base(u8),
+    Class(ClassTypeSig),
+    /// A type variable such as `T`.
+    Var(String),
+    Array(Box<TypeSig>),
+}
+
+/// A class type: its outermost part carries the package, such as
+/// `java/util/Map`, and each further part is a nested class, such as
+/// `Entry`, each with its own type arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassTypeSig {
+    pub parts: Vec<(String, Vec<TypeArg>)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeArg {
+    /// `?`
+    Any,
+    Exact(TypeSig),
+    /// `? extends T`
+    Extends(TypeSig),
+    /// `? super T`
+    Super(TypeSig),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeParam {
+    pub name: String,
+    pub class_bound: Option<TypeSig>,
+    pub interface_bounds: Vec<TypeSig>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassSignature {
+    pub type_params: Vec<TypeParam>,
+    pub superclass: ClassTypeSig,
+    pub interfaces: Vec<ClassTypeSig>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodSignature {
+    pub type_params: Vec<TypeParam>,
+    pub params: Vec<TypeSig>,
+    /// `None` for void.
+    pub result: Option<TypeSig>,
+    pub throws: Vec<TypeSig>,
+}
+
+impl MethodSignature {
+    /// The method as Java source declares it, such as
+    /// `<T> T first(java.util.List<T>) throws E`.
+    pub fn declaration(&self, name: &str) -> String {
+        let mut out = String::new();
+        if !self.type_params.is_empty() {
+            out = format!("<{}> ", List(&self.type_params));
+        }
+        match &self.result {
+            Some(t) => out.push_str(&format!("{}", t)),
+            None => out.push_str("void"),
+        }
+        out.push_str(&format!(" {}({})", name, List(&self.params)));
+        if !self.throws.is_empty() {
+            out.push_str(&format!(" throws {}", List(&self.throws)));
+        }
+        out
+    }
+}
+
+pub fn parse_class_signature(s: &str) -> Result<ClassSignature, JvmError> {
+    let mut p = Parser::new(s);
+    let type_params = p.type_params()?;
+    let superclass = p.class_type(0)?;
+    let mut interfaces = Vec::new();
+    while !p.done() {
+        interfaces.push(p.class_type(0)?);
+    }
+    Ok(ClassSignature {
+        type_params,
+        superclass,
+        interfaces,
+    })
+}
+
+pub fn parse_method_signature(s: &str) -> Result<MethodSignature, JvmError> {
+    let mut p = Parser::new(s);
+    let type_params = p.type_params()?;
+    p.expect(b'(')?;
+    let mut params = Vec::new();
+    while p.peek() != Some(b')') {
+        params.push(p.java_type(0)?);
+    }
+    p.expect(b')')?;
+    let result = if p.peek() == Some(b'V') {
+        p.pos += 1;
+        None
+    } else {
+        Some(p.java_type(0)?)
+    };
+    let mut throws = Vec::new();
+    while !p.done() {
+        p.expect(b'^')?;
+        throws.push(p.reference_type(0)?);
+    }
+    Ok(MethodSignature {
+        type_params,
+        params,
+        result,
+        throws,
+    })
+}
+
+/// A field's signature, which is always a reference type.
+pub fn parse_field_signature(s: &str) -> Result<TypeSig, JvmError> {
+    let mut p = Parser::new(s);
+    let sig = p.reference_type(0)?;
+    if !p.done() {
+        return Err(p.error());
+    }
+    Ok(sig)
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Self {
+        Self { s, pos: 0 }
+    }
+
+    fn error(&self) -> JvmError {
+        JvmError::ClassFormatError(format!("bad signature {:?} at {}", self.s, self.pos))
+    }
+
+    fn done(&self) -> bool {
+        self.pos == self.s.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.as_bytes().get(self.pos).copied()
+    }
+
+    fn expect(&mut self, b: u8) -> Result<(), JvmError> {
+        if self.peek() != Some(b) {
+            return Err(self.error());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // Runs up to one of `.;[/<>:`, which identifiers can't hold.
+    fn identifier(&mut self) -> Result<String, JvmError> {
+        let start = self.pos;
+        while let Some(b) = self.peek()
+            && !b".;[/<>:".contains(&b)
+        {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(self.error());
+        }
+        Ok(String::from(&self.s[start..self.pos]))
+    }
+
+    fn type_params(&mut self) -> Result<Vec<TypeParam>, JvmError> {
+        let mut params = Vec::new();
+        if self.peek() != Some(b'<') {
+            return Ok(params);
+        }
+        self.pos += 1;
+        while self.peek() != Some(b'>') {
+            let name = self.identifier()?;
+            self.expect(b':')?;
+            // The class bound is left out when only interfaces bound it.
+            let class_bound = match self.peek() {
+                Some(b':') => None,
+                _ => Some(self.reference_type(0)?),
+            };
+            let mut interface_bounds = Vec::new();
+            while self.peek() == Some(b':') {
+                self.pos += 1;
+                interface_bounds.push(self.reference_type(0)?);
+            }
+            params.push(TypeParam {
+                name,
+                class_bound,
+                interface_bounds,
+            });
+        }
+        self.pos += 1;
+        if params.is_empty() {
+            return Err(self.error());
+        }
+        Ok(params)
+    }
+
+    fn java_type(&mut self, depth: usize) -> Result<TypeSig, JvmError> {
+        match self.peek() {
+            Some(b @ (b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z')) => {
+                self.pos += 1;
+                Ok(TypeSig::Base(b))
+            }
+            _ => self.reference_type(depth),
+        }
+    }
+
+    fn reference_type(&mut self, depth: usize) -> Result<TypeSig, JvmError> {
+        if depth >= MAX_DEPTH {
+            return Err(self.error());
+        }
+        match self.peek() {
+            Some(b'L') => Ok(TypeSig::Class(self.class_type(depth)?)),
+            Some(b'T') => {
+                self.pos += 1;
+                let name = self.identifier()?;
+                self.expect(b';')?;
+                Ok(TypeSig::Var(name))
+            }
+            Some(b'[') => {
+                // Dimensions loop rather than recurse; a class file may
+                // stack 255 of them.
+                let mut dims = 0;
+                while self.peek() == Some(b'[') {
+                    self.pos += 1;
+                    dims += 1;
+                }
+                let mut sig = self.java_type(depth)?;
+                for _ in 0..dims {
+                    sig = TypeSig::Array(Box::new(sig));
+                }
+                Ok(sig)
+            }
+            _ => Err(self.error()),
+        }
+    }
+
+    fn class_type(&mut self, depth: usize) -> Result<ClassTypeSig, JvmError> {
+        self.expect(b'L')?;
+        let mut name = self.identifier()?;
+        while self.peek() == Some(b'/') {
+            self.pos += 1;
+            name.push('/');
+            name.push_str(&self.identifier()?);
+        }
+        let mut parts = Vec::new();
+        loop {
+            let args = self.type_args(depth)?;
+            parts.push((name, args));
+            match self.peek() {
+                Some(b'.') => {
+                    self.pos += 1;
+                    name = self.identifier()?;
+                }
+                _ => break,
+            }
+        }
+        self.expect(b';')?;
+        Ok(ClassTypeSig { parts })
+    }
+
+    fn type_args(&mut self, depth: usize) -> Result<Vec<TypeArg>, JvmError> {
+        let mut args = Vec::new();
+        if self.peek() != Some(b'<') {
+            return Ok(args);
+        }
+        self.pos += 1;
+        while self.peek() != Some(b'>') {
+            args.push(match self.peek() {
+                Some(b'*') => {
+                    self.pos += 1;
+                    TypeArg::Any
+                }
+                Some(b'+') => {
+                    self.pos += 1;
+                    TypeArg::Extends(self.reference_type(depth + 1)?)
+                }
+                Some(b'-') => {
+                    self.pos += 1;
+                    TypeArg::Super(self.reference_type(depth + 1)?)
+                }
+                _ => TypeArg::Exact(self.reference_type(depth + 1)?),
+            });
+        }
+        self.pos += 1;
+        if args.is_empty() {
+            return Err(self.error());
+        }
+        Ok(args)
+    }
+}
+
+// The Display impls write types the way Java source does, such as
+// `java.util.List<BootEntry>`.
+
+impl fmt::Display for TypeSig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeSig::Base(b) => f.write_str(match b {
+                b'B' => "byte",
+                b'C' => "char",
+                b'D' => "double",
+                b'F' => "float",
+                b'I' => "int",
+                b'J' => "long",
+                b'S' => "short",
+                _ => "boolean",
+            }),
+            TypeSig::Class(c) => write!(f, "{}", c),
+            TypeSig::Var(name) => f.write_str(name),
+            TypeSig::Array(elem) => write!(f, "{}[]", elem),
+        }
+    }
+}
+
+impl fmt::Display for ClassTypeSig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (name, args)) in self.parts.iter().enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            f.write_str(&name.replace('/', "."))?;
+            if !args.is_empty() {
+                write!(f, "<{}>", List(args))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TypeArg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeArg::Any => f.write_str("?"),
+            TypeArg::Exact(t) => write!(f, "{}", t),
+            TypeArg::Extends(t) => write!(f, "? extends {}", t),
+            TypeArg::Super(t) => write!(f, "? super {}", t),
+        }
+    }
+}
+
+impl fmt::Display for TypeParam {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)?;
+        let bounds: Vec<&TypeSig> = self
+            .class_bound
+            .iter()
+            .chain(&self.interface_bounds)
+            .filter(|b| !is_object(b))
+            .collect();
+        for (i, bound) in bounds.iter().enumerate() {
+            f.write_str(if i == 0 { " extends " } else { " & " })?;
+            write!(f, "{}", bound)?;
+        }
+        Ok(())
+    }
+}
+
+/// `<T> extends Super implements A, B`, less the `extends` for Object.
+impl fmt::Display for ClassSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.type_params.is_empty() {
+            write!(f, "<{}>", List(&self.type_params))?;
+        }
+        let superclass = TypeSig::Class(self.superclass.clone());
+        if !is_object(&superclass) {
+            write!(f, " extends {}", superclass)?;
+        }
+        if !self.interfaces.is_empty() {
+            write!(f, " implements {}", List(&self.interfaces))?;
+        }
+        Ok(())
+    }
+}
+
+fn is_object(t: &TypeSig) -> bool {
+    matches!(t, TypeSig::Class(c) if c.parts.len() == 1 && c.parts[0].0 == "java/lang/Object" && c.parts[0].1.is_empty())
+}
+
+// Items joined with ", ".
+struct List<'a, T>(&'a [T]);
+
+impl<T: fmt::Display> fmt::Display for List<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, item) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", item)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,358 @@
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Palette index meaning "the window's own colour" rather than an ANSI one.
+pub const DEFAULT: u8 = 0xFF;
+
+pub const BOLD: u8 = 1;
+pub const UNDERLINE: u8 = 2;
+pub const INVERSE: u8 = 4;
+
+/// The 16 ANSI colours as 0xRRGGBB, normal then bright.
+pub const PALETTE: [u32; 16] = [
+    0x000000, 0xCD3131, 0x0DBC79, 0xE5E510, 0x2472C8, 0xBC3FBC, 0x11A8CD, 0xE5E5E5, 0x666666,
+    0xF14C4C, 0x23D18B, 0xF5F543, 0x3B8EEA, 0xD670D6, 0x29B8DB, 0xFFFFFF,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: u8,
+    pub fg: u8,
+    pub bg: u8,
+    pub attrs: u8,
+}
+
+impl Cell {
+    pub const BLANK: Cell = Cell {
+        ch: b' ',
+        fg: DEFAULT,
+        bg: DEFAULT,
+        attrs: 0,
+    };
+
+    /// The cell's colours as 0xRRGGBB, with bold brightening the eight
+    /// normal colours and inverse swapping the pair.
+    pub fn colors(&self, default_fg: u32, default_bg: u32) -> (u32, u32) {
+        let fg = match self.fg {
+            DEFAULT => default_fg,
+            c if c < 8 && self.attrs & BOLD != 0 => PALETTE[c as usize + 8],
+            c => PALETTE[(c & 15) as usize],
+        };
+        let bg = match self.bg {
+            DEFAULT => default_bg,
+            c => PALETTE[(c & 15) as usize],
+        };
+        if self.attrs & INVERSE != 0 {
+            (bg, fg)
+        } else {
+            (fg, bg)
+        }
+    }
+}
+
+/// A monospaced bitmap font: `glyph(ch)` gives one `u16` per pixel row,
+/// leftmost pixel in the top bit.
+pub struct Font {
+    pub width: usize,
+    pub height: usize,
+    pub glyph: fn(u8) -> &'static [u16],
+}
+
+enum Parse {
+    Ground,
+    Escape,
+    Csi,
+}
+
+/// A fixed grid of character cells fed with console text. Understands
+/// CR, LF, tab, backspace and the common CSI sequences (SGR colours and
+/// attributes, cursor moves, erase); lines scrolled off the top are kept in
+/// a bounded history.
+pub struct Terminal {
+    cols: usize,
+    rows: usize,
+    screen: Vec<Vec<Cell>>,
+    history: VecDeque<Vec<Cell>>,
+    history_limit: usize,
+    cx: usize,
+    cy: usize,
+    pen: Cell,
+    state: Parse,
+    params: Vec<u16>,
+}
+
+impl Terminal {
+    pub fn new(cols: usize, rows: usize, history_limit: usize) -> Self {
+        let cols = cols.max(1);
+        let rows = rows.max(1);
+        Self {
+            cols,
+            rows,
+            screen: vec![vec![Cell::BLANK; cols]; rows],
+            history: VecDeque::new(),
+            history_limit,
+            cx: 0,
+            cy: 0,
+            pen: Cell::BLANK,
+            state: Parse::Ground,
+            params: Vec::new(),
+        }
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Changes the grid size, keeping the cursor on screen. Rows below the
+    /// cursor are dropped first; rows above it that no longer fit go to the
+    /// history.
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        let cols = cols.max(1);
+        let rows = rows.max(1);
+        for line in &mut self.screen {
+            line.resize(cols, Cell::BLANK);
+        }
+        while self.screen.len() > rows {
+            if self.cy + 1 < self.screen.len() {
+                self.screen.pop();
+            } else {
+                let line = self.screen.remove(0);
+                self.push_history(line);
+                self.cy -= 1;
+            }
+        }
+        while self.screen.len() < rows {
+            self.screen.push(vec![Cell::BLANK; cols]);
+        }
+        self.cols = cols;
+        self.rows = rows;
+        self.cx = self.cx.min(cols - 1);
+    }
+
+    /// Blanks the screen and drops the history.
+    pub fn clear(&mut self) {
+        for line in &mut self.screen {
+            line.fill(Cell::BLANK);
+        }
+        self.history.clear();
+        self.cx = 0;
+        self.cy = 0;
+    }
+
+    pub fn write(&mut self, text: &str) {
+        for ch in text.chars() {
+            self.feed(ch);
+        }
+    }
+
+    /// Row `y` of the view scrolled `back` lines into the history; 0 is the
+    /// live screen. History rows may be narrower than the grid after a
+    /// resize.
+    pub fn view_row(&self, back: usize, y: usize) -> &[Cell] {
+        let h = self.history.len();
+        let idx = h - back.min(h) + y;
+        if idx < h {
+            &self.history[idx]
+        } else {
+            self.screen.get(idx - h).map(Vec::as_slice).unwrap_or(&[])
+        }
+    }
+
+    /// Draws the view `back` lines into the history into `out`, a buffer of
+    /// 0xRRGGBB pixels `width` wide. Cells that don't fit are clipped.
+    pub fn render(
+        &self,
+        back: usize,
+        default_fg: u32,
+        default_bg: u32,
+        font: &Font,
+        out: &mut [u32],
+        width: usize,
+    ) {
+        out.fill(default_bg);
+        let height = out.len() / width.max(1);
+        for y in 0..self.rows {
+            let py = y * font.height;
+            if py + font.height > height {
+                break;
+            }
+            for (x, cell) in self.view_row(back, y).iter().enumerate() {
+                let px = x * font.width;
+                if px + font.width > width {
+                    break;
+                }
+                let (fg, bg) = cell.colors(default_fg, default_bg);
+                let glyph = (font.glyph)(cell.ch);
+                for row in 0..font.height {
+                    let bits = match glyph.get(row) {
+                        Some(_) if cell.attrs & UNDERLINE != 0 && row == font.height - 1 => {
+                            u16::MAX
+                        }
+                        Some(&bits) => bits,
+                        None => 0,
+                    };
+                    let line = &mut out[(py + row) * width + px..][..font.width];
+                    for (col, pixel) in line.iter_mut().enumerate() {
+                        *pixel = if bits & (0x8000 >> col) != 0 { fg } else { bg };
+                    }
+                }
+            }
+        }
+    }
+
+    fn feed(&mut self, ch: char) {
+        match self.state {
+            Parse::Ground => self.ground(ch),
+            Parse::Escape => {
+                self.state = if ch == '[' {
+                    self.params.clear();
+                    Parse::Csi
+                } else {
+                    Parse::Ground
+                };
+            }
+            Parse::Csi => match ch {
+                '0'..='9' => {
+                    let digit = ch as u16 - '0' as u16;
+                    match self.params.last_mut() {
+                        Some(p) => *p = p.saturating_mul(10).saturating_add(digit),
+                        None => self.params.push(digit),
+                    }
+                }
+                ';' => {
+                    if self.params.is_empty() {
+                        self.params.push(0);
+                    }
+                    self.params.push(0);
+                }
+                '\x40'..='\x7E' => {
+                    self.csi(ch);
+                    self.state = Parse::Ground;
+                }
+                _ => {}
+            },
+        }
+    }
+
+    fn ground(&mut self, ch: char) {
+        match ch {
+            '\x1B' => self.state = Parse::Escape,
+            '\n' => {
+                self.cx = 0;
+                self.line_feed();
+            }
+            '\r' => self.cx = 0,
+            '\t' => {
+                let next = (self.cx / 8 + 1) * 8;
+                while self.cx < next.min(self.cols) {
+                    self.put(b' ');
+                }
+            }
+            '\x08' => self.cx = self.cx.saturating_sub(1),
+            c if (c as u32) < 0x20 => {}
+            c => self.put(if c.is_ascii() { c as u8 } else { b'?' }),
+        }
+    }
+
+    fn put(&mut self, ch: u8) {
+        if self.cx >= self.cols {
+            self.cx = 0;
+            self.line_feed();
+        }
+        self.screen[self.cy][self.cx] = Cell { ch, ..self.pen };
+        self.cx += 1;
+    }
+
+    fn line_feed(&mut self) {
+        if self.cy + 1 < self.rows {
+            self.cy += 1;
+            return;
+        }
+        let line = self.screen.remove(0);
+        self.push_history(line);
+        self.screen.push(vec![Cell::BLANK; self.cols]);
+    }
+
+    fn push_history(&mut self, line: Vec<Cell>) {
+        if self.history_limit == 0 {
+            return;
+        }
+        if self.history.len() == self.history_limit {
+            self.history.pop_front();
+        }
+        self.history.push_back(line);
+    }
+
+    fn param(&self, i: usize, default: u16) -> usize {
+        match self.params.get(i) {
+            Some(&0) | None => default as usize,
+            Some(&p) => p as usize,
+        }
+    }
+
+    fn csi(&mut self, cmd: char) {
+        match cmd {
+            'm' => self.sgr(),
+            'A' => self.cy = self.cy.saturating_sub(self.param(0, 1)),
+            'B' => self.cy = (self.cy + self.param(0, 1)).min(self.rows - 1),
+            'C' => self.cx = (self.cx + self.param(0, 1)).min(self.cols - 1),
+            'D' => self.cx = self.cx.saturating_sub(self.param(0, 1)),
+            'H' | 'f' => {
+                self.cy = (self.param(0, 1) - 1).min(self.rows - 1);
+                self.cx = (self.param(1, 1) - 1).min(self.cols - 1);
+            }
+            // Only "to the end" and "everything" are told apart.
+            'J' => {
+                let start = match self.params.first() {
+                    Some(2) | Some(3) => 0,
+                    _ => {
+                        let cx = self.cx.min(self.cols);
+                        self.screen[self.cy][cx..].fill(Cell::BLANK);
+                        self.cy + 1
+                    }
+                };
+                for line in &mut self.screen[start..] {
+                    line.fill(Cell::BLANK);
+                }
+            }
+            'K' => {
+                let cx = self.cx.min(self.cols);
+                self.screen[self.cy][cx..].fill(Cell::BLANK);
+            }
+            _ => {}
+        }
+    }
+
+    fn sgr(&mut self) {
+        if self.params.is_empty() {
+            self.params.push(0);
+        }
+        for &p in &self.params {
+            match p {
+                0 => self.pen = Cell::BLANK,
+                1 => self.pen.attrs |= BOLD,
+                4 => self.pen.attrs |= UNDERLINE,
+                7 => self.pen.attrs |= INVERSE,
+                22 => self.pen.attrs &= !BOLD,
+                24 => self.pen.attrs &= !UNDERLINE,
+                27 => self.pen.attrs &= !INVERSE,
+                30..=37 => self.pen.fg = (p - 30) as u8,
+                39 => self.pen.fg = DEFAULT,
+                40..=47 => self.pen.bg = (p - 40) as u8,
+                49 => self.pen.bg = DEFAULT,
+                90..=97 => self.pen.fg = (p - 90 + 8) as u8,
+                100..=107 => self.pen.bg = (p - 100 + 8) as u8,
+                _ => {}
+            }
+        }
+    }
+}
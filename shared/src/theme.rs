@@ -0,0 +1,192 @@
+use alloc::string::String;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Dark,
+    Light,
+}
+
+impl Variant {
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Dark => "dark",
+            Variant::Light => "light",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "dark" => Some(Variant::Dark),
+            "light" => Some(Variant::Light),
+            _ => None,
+        }
+    }
+
+    pub fn other(self) -> Self {
+        match self {
+            Variant::Dark => Variant::Light,
+            Variant::Light => Variant::Dark,
+        }
+    }
+}
+
+/// The colour roles a theme assigns, in [`Palette`] order.
+pub const ROLES: [&str; 9] = [
+    "background",
+    "surface",
+    "card",
+    "border",
+    "text",
+    "text_dim",
+    "text_faint",
+    "accent",
+    "error",
+];
+
+/// One variant's colours as 0xRRGGBB, indexed like [`ROLES`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette(pub [u32; ROLES.len()]);
+
+impl Palette {
+    pub const DARK: Palette = Palette([
+        0x0F0F12, 0x17171C, 0x212126, 0x2E2E36, 0xE0E0E6, 0x7A7A8A, 0x4D4D59, 0x8C8FA6, 0xE06C6C,
+    ]);
+
+    pub const LIGHT: Palette = Palette([
+        0xF4F4F6, 0xEAEAEE, 0xDCDCE2, 0xC8C8D0, 0x1E1E24, 0x5E5E6E, 0x9A9AA6, 0x5A5E7A, 0xC03C3C,
+    ]);
+
+    pub fn get(&self, role: &str) -> Option<u32> {
+        let i = ROLES.iter().position(|r| *r == role)?;
+        Some(self.0[i])
+    }
+
+    fn set(&mut self, role: &str, color: u32) {
+        if let Some(i) = ROLES.iter().position(|r| *r == role) {
+            self.0[i] = color;
+        }
+    }
+}
+
+/// A named pair of palettes, one per [`Variant`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub name: String,
+    pub dark: Palette,
+    pub light: Palette,
+}
+
+impl Theme {
+    pub fn builtin() -> Self {
+        Self {
+            name: String::from("default"),
+            dark: Palette::DARK,
+            light: Palette::LIGHT,
+        }
+    }
+
+    /// Reads a theme file: `[dark]` and `[light]` sections of
+    /// `role = RRGGBB` lines (`#RRGGBB` and `0xRRGGBB` work too). Roles a
+    /// section leaves out keep the built-in colours, and lines before the
+    /// first section apply to both variants.
+    pub fn parse(name: &str, text: &str) -> Self {
+        let mut theme = Self::builtin();
+        theme.name = String::from(name);
+        let mut section = None;
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(s) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                section = Variant::parse(s.trim());
+                continue;
+            }
+            let Some((role, value)) = line.split_once('=') else {
+                continue;
+            };
+            let Some(color) = parse_color(value.trim()) else {
+                continue;
+            };
+            let role = role.trim();
+            match section {
+                Some(Variant::Dark) => theme.dark.set(role, color),
+                Some(Variant::Light) => theme.light.set(role, color),
+                None => {
+                    theme.dark.set(role, color);
+                    theme.light.set(role, color);
+                }
+            }
+        }
+        theme
+    }
+
+    pub fn palette(&self, variant: Variant) -> &Palette {
+        match variant {
+            Variant::Dark => &self.dark,
+            Variant::Light => &self.light,
+        }
+    }
+}
+
+fn parse_color(s: &str) -> Option<u32> {
+    let hex = s
+        .strip_prefix('#')
+        .or_else(|| s.strip_prefix("0x"))
+        .unwrap_or(s);
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
+
+/// Which variant a theme shows: pinned by config or the hotkey, otherwise
+/// light from `light_from` until `dark_from` (hours, local time) and dark
+/// for the rest of the day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub light_from: u8,
+    pub dark_from: u8,
+    pub pinned: Option<Variant>,
+}
+
+impl Selection {
+    /// Settings `theme_variant` (`auto`, `dark` or `light`), `theme_light`
+    /// and `theme_dark` (hours, default 7 and 19), looked up with `get`.
+    pub fn from_settings<'a>(get: impl Fn(&str) -> Option<&'a str>) -> Self {
+        let hour = |key: &str, default: u8| {
+            get(key)
+                .and_then(|v| v.parse::<u8>().ok())
+                .filter(|&h| h < 24)
+                .unwrap_or(default)
+        };
+        Self {
+            light_from: hour("theme_light", 7),
+            dark_from: hour("theme_dark", 19),
+            pinned: get("theme_variant").and_then(Variant::parse),
+        }
+    }
+
+    /// The variant for `hour`; without a clock the theme stays dark.
+    pub fn active(&self, hour: Option<u8>) -> Variant {
+        if let Some(v) = self.pinned {
+            return v;
+        }
+        let Some(h) = hour else {
+            return Variant::Dark;
+        };
+        let light = if self.light_from <= self.dark_from {
+            h >= self.light_from && h < self.dark_from
+        } else {
+            h >= self.light_from || h < self.dark_from
+        };
+        if light { Variant::Light } else { Variant::Dark }
+    }
+
+    /// Pins the variant that isn't showing at `hour` and returns it.
+    pub fn toggle(&mut self, hour: Option<u8>) -> Variant {
+        let v = self.active(hour).other();
+        self.pinned = Some(v);
+        v
+    }
+}
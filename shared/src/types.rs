@@ -39,6 +39,7 @@ impl JvmValue {
     pub fn as_object_ref(&self) -> Result<u32, JvmError> {
         match self {
             JvmValue::ObjectRef(id) => Ok(*id),
+            JvmValue::Null => Err(JvmError::NullPointerException),
             _ => Err(JvmError::TypeError(String::from("expected object ref"))),
         }
     }
@@ -46,6 +47,7 @@ impl JvmValue {
     pub fn as_array_ref(&self) -> Result<u32, JvmError> {
         match self {
             JvmValue::ArrayRef(id) => Ok(*id),
+            JvmValue::Null => Err(JvmError::NullPointerException),
             _ => Err(JvmError::TypeError(String::from("expected array ref"))),
         }
     }
@@ -53,9 +55,21 @@ impl JvmValue {
     pub fn is_null(&self) -> bool {
         matches!(self, JvmValue::Null)
     }
+
+    /// What a field of type `descriptor` holds before anything is stored in
+    /// it: 0 of the right width, or null for references.
+    pub fn default_for(descriptor: &str) -> JvmValue {
+        match descriptor.as_bytes().first() {
+            Some(b'B' | b'C' | b'I' | b'S' | b'Z') => JvmValue::Int(0),
+            Some(b'J') => JvmValue::Long(0),
+            Some(b'F') => JvmValue::Float(0.0),
+            Some(b'D') => JvmValue::Double(0.0),
+            _ => JvmValue::Null,
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum JvmError {
     ClassFormatError(String),
     StackOverflow,
@@ -69,8 +83,47 @@ pub enum JvmError {
     NullPointerException,
     OutOfMemory,
     DivisionByZero,
+    ArithmeticOverflow(String),
     IoError(String),
+    Interrupted(String),
     SystemExit(i32),
+    BudgetExceeded(u64),
+    NegativeArraySize(i32),
+    ClassCast(String),
+    /// A Java exception on its way up the stack: its `toString()` and the
+    /// heap id of the throwable.
+    Thrown(String, u32),
+    IllegalAccess(String),
+}
+
+impl JvmError {
+    /// A stable number for the kind of error, for records that outlive the
+    /// message (see [`crate::fault`]). New variants get new numbers.
+    pub fn code(&self) -> u16 {
+        match self {
+            JvmError::ClassFormatError(_) => 1,
+            JvmError::StackOverflow => 2,
+            JvmError::StackUnderflow => 3,
+            JvmError::TypeError(_) => 4,
+            JvmError::UnsupportedOpcode(_) => 5,
+            JvmError::MethodNotFound(_) => 6,
+            JvmError::ClassNotFound(_) => 7,
+            JvmError::NativeMethodError(_) => 8,
+            JvmError::ArrayIndexOutOfBounds(..) => 9,
+            JvmError::NullPointerException => 10,
+            JvmError::OutOfMemory => 11,
+            JvmError::DivisionByZero => 12,
+            JvmError::ArithmeticOverflow(_) => 13,
+            JvmError::IoError(_) => 14,
+            JvmError::Interrupted(_) => 15,
+            JvmError::SystemExit(_) => 16,
+            JvmError::BudgetExceeded(_) => 17,
+            JvmError::NegativeArraySize(_) => 18,
+            JvmError::ClassCast(_) => 19,
+            JvmError::Thrown(..) => 20,
+            JvmError::IllegalAccess(_) => 21,
+        }
+    }
 }
 
 impl fmt::Display for JvmError {
@@ -90,8 +143,17 @@ impl fmt::Display for JvmError {
             JvmError::NullPointerException => write!(f, "NullPointerException"),
             JvmError::OutOfMemory => write!(f, "OutOfMemory"),
             JvmError::DivisionByZero => write!(f, "ArithmeticException: / by zero"),
+            JvmError::ArithmeticOverflow(msg) => write!(f, "ArithmeticException: {}", msg),
             JvmError::IoError(msg) => write!(f, "IoError: {}", msg),
+            JvmError::Interrupted(msg) => write!(f, "InterruptedException: {}", msg),
             JvmError::SystemExit(code) => write!(f, "SystemExit: {}", code),
+            JvmError::BudgetExceeded(n) => {
+                write!(f, "BudgetExceeded: {} backward branches", n)
+            }
+            JvmError::NegativeArraySize(n) => write!(f, "NegativeArraySizeException: {}", n),
+            JvmError::ClassCast(msg) => write!(f, "ClassCastException: {}", msg),
+            JvmError::IllegalAccess(msg) => write!(f, "IllegalAccessError: {}", msg),
+            JvmError::Thrown(description, _) => write!(f, "Uncaught {}", description),
         }
     }
 }
@@ -1,23 +1,55 @@
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::vec::Vec;
 
 use crate::types::JvmError;
 
+mod write;
+
+pub use write::ZipWriter;
+
 const EOCD_SIGNATURE: u32 = 0x06054b50;
 const CD_SIGNATURE: u32 = 0x02014b50;
 const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
 
+// General purpose flag bits for encryption (APPNOTE 4.4.4), and the method
+// WinZip's AES entries give.
+const FLAG_ENCRYPTED: u16 = 0x0001;
+const FLAG_STRONG_ENCRYPTION: u16 = 0x0040;
+const METHOD_AES: u16 = 99;
+
+const MANIFEST: &str = "META-INF/MANIFEST.MF";
+const VERSIONS: &str = "META-INF/versions/";
+const MODULE_INFO: &str = "module-info.class";
+
+/// The Java release a multi-release JAR is read as when the caller doesn't
+/// pick one.
+pub const DEFAULT_RELEASE: u16 = 17;
+
+/// How much [`ZipArchive::stream_entry`] inflates at a time.
+pub const STREAM_CHUNK: usize = 32 * 1024;
+
 pub struct ZipEntry {
     pub name: String,
     pub compression_method: u16,
+    /// The general purpose bit flags.
+    pub flags: u16,
+    /// CRC-32 of the uncompressed contents, as the central directory gives it.
+    pub crc32: u32,
     pub compressed_size: u32,
     pub uncompressed_size: u32,
     pub local_header_offset: u32,
 }
 
+/// A ZIP read in place, or from its own buffer when it was nested
+/// compressed inside another; see [`ZipArchive::open_nested`].
 pub struct ZipArchive<'a> {
-    data: &'a [u8],
+    data: Cow<'a, [u8]>,
     entries: Vec<ZipEntry>,
+    // Indexes into `entries`, ordered by name, for lookups by name.
+    by_name: Vec<usize>,
+    verify_crc: bool,
 }
 
 fn read_u16_le(data: &[u8], offset: usize) -> u16 {
@@ -31,13 +63,64 @@ fn read_u32_le(data: &[u8], offset: usize) -> u32 {
         | ((data[offset + 3] as u32) << 24)
 }
 
+/// A [`ZipArchive`] that owns its bytes, for keeping in a long-lived
+/// struct without the buffer it was read from.
+pub type ZipArchiveOwned = ZipArchive<'static>;
+
+impl ZipArchiveOwned {
+    pub fn from_vec(data: Vec<u8>) -> Result<Self, JvmError> {
+        Self::open(Cow::Owned(data))
+    }
+}
+
 impl<'a> ZipArchive<'a> {
     pub fn new(data: &'a [u8]) -> Result<Self, JvmError> {
+        Self::open(Cow::Borrowed(data))
+    }
+
+    /// Copies the bytes out of a borrowed archive so it can outlive them.
+    pub fn into_owned(self) -> ZipArchiveOwned {
+        ZipArchive {
+            data: Cow::Owned(self.data.into_owned()),
+            entries: self.entries,
+            by_name: self.by_name,
+            verify_crc: self.verify_crc,
+        }
+    }
+
+    fn open(bytes: Cow<'a, [u8]>) -> Result<Self, JvmError> {
+        let data = &bytes[..];
         let eocd_offset = Self::find_eocd(data)?;
 
         let cd_offset = read_u32_le(data, eocd_offset + 16) as usize;
         let cd_entry_count = read_u16_le(data, eocd_offset + 10) as usize;
 
+        // The end record counts disks from 0; a spanned or split archive
+        // has its directory on a later one, or entries spread across them.
+        // ZIP64 leaves placeholders in these fields, so it is told first.
+        let disk = read_u16_le(data, eocd_offset + 4);
+        let cd_disk = read_u16_le(data, eocd_offset + 6);
+        let on_this_disk = read_u16_le(data, eocd_offset + 8);
+        let counts = [disk, cd_disk, on_this_disk, cd_entry_count as u16];
+        if counts.contains(&0xffff) || cd_offset == 0xffff_ffff {
+            return Err(JvmError::IoError(String::from(
+                "archive is ZIP64 — Duke reads JARs under 4 GiB and 65535 entries only",
+            )));
+        }
+        if disk != 0 || cd_disk != 0 {
+            return Err(JvmError::IoError(alloc::format!(
+                "archive is split across {} disks — Duke reads single-file JARs only",
+                disk.max(cd_disk) as u32 + 1
+            )));
+        }
+        if on_this_disk as usize != cd_entry_count {
+            return Err(JvmError::IoError(alloc::format!(
+                "central directory counts disagree: {} entries on this disk, {} in all",
+                on_this_disk,
+                cd_entry_count
+            )));
+        }
+
         let mut entries = Vec::with_capacity(cd_entry_count);
         let mut pos = cd_offset;
 
@@ -50,7 +133,9 @@ impl<'a> ZipArchive<'a> {
                 break;
             }
 
+            let flags = read_u16_le(data, pos + 8);
             let compression_method = read_u16_le(data, pos + 10);
+            let crc32 = read_u32_le(data, pos + 16);
             let compressed_size = read_u32_le(data, pos + 20);
             let uncompressed_size = read_u32_le(data, pos + 24);
             let name_len = read_u16_le(data, pos + 28) as usize;
@@ -69,6 +154,8 @@ impl<'a> ZipArchive<'a> {
             entries.push(ZipEntry {
                 name,
                 compression_method,
+                flags,
+                crc32,
                 compressed_size,
                 uncompressed_size,
                 local_header_offset,
@@ -77,7 +164,14 @@ impl<'a> ZipArchive<'a> {
             pos += 46 + name_len + extra_len + comment_len;
         }
 
-        Ok(Self { data, entries })
+        let mut by_name: Vec<usize> = (0..entries.len()).collect();
+        by_name.sort_by(|&a, &b| entries[a].name.cmp(&entries[b].name));
+        Ok(Self {
+            data: bytes,
+            by_name,
+            entries,
+            verify_crc: true,
+        })
     }
 
     fn find_eocd(data: &[u8]) -> Result<usize, JvmError> {
@@ -111,43 +205,193 @@ impl<'a> ZipArchive<'a> {
         &self.entries
     }
 
-    pub fn read_entry(&self, entry: &ZipEntry) -> Result<Vec<u8>, JvmError> {
+    /// The entry called `name`, such as `icons/boot.png`.
+    pub fn find(&self, name: &str) -> Option<&ZipEntry> {
+        let i = self
+            .by_name
+            .binary_search_by(|&i| self.entries[i].name.as_str().cmp(name))
+            .ok()?;
+        Some(&self.entries[self.by_name[i]])
+    }
+
+    /// The entries whose names start with `prefix`, such as `icons/`, in
+    /// name order.
+    pub fn entries_under<'s>(&'s self, prefix: &str) -> impl Iterator<Item = &'s ZipEntry> {
+        let start = self
+            .by_name
+            .partition_point(|&i| self.entries[i].name.as_str() < prefix);
+        let end = start
+            + self.by_name[start..].partition_point(|&i| self.entries[i].name.starts_with(prefix));
+        self.by_name[start..end].iter().map(|&i| &self.entries[i])
+    }
+
+    /// The entries whose names match `pattern`, in name order; see
+    /// [`glob_match`].
+    pub fn matching<'s>(&'s self, pattern: &'s str) -> impl Iterator<Item = &'s ZipEntry> {
+        // Everything before the first wildcard is a prefix to narrow by.
+        let literal = pattern.find(['*', '?']).unwrap_or(pattern.len());
+        self.entries_under(&pattern[..literal])
+            .filter(move |e| glob_match(pattern, &e.name))
+    }
+
+    /// Whether [`ZipArchive::read_entry`] checks each entry against its
+    /// stored CRC-32, which it does unless turned off here.
+    pub fn set_verify_crc(&mut self, on: bool) {
+        self.verify_crc = on;
+    }
+
+    /// An entry's contents: borrowed from the archive when it's stored, and
+    /// inflated into a new buffer when it's compressed.
+    pub fn read_entry(&self, entry: &ZipEntry) -> Result<Cow<'_, [u8]>, JvmError> {
+        let compressed = self.entry_data(entry)?;
+        let contents = match entry.compression_method {
+            0 => Cow::Borrowed(compressed),
+            8 => Cow::Owned(self.inflate(compressed)?),
+            m => return Err(unsupported_method(m)),
+        };
+        self.check_crc(entry, crc32(&contents))?;
+        Ok(contents)
+    }
+
+    /// Hands an entry's contents to `sink` a chunk at a time, inflating into
+    /// a buffer of [`STREAM_CHUNK`] bytes, so a large entry such as a
+    /// background image never sits whole in memory. The CRC-32 is checked
+    /// after the last chunk, so `sink` may see data that then fails it.
+    pub fn stream_entry(
+        &self,
+        entry: &ZipEntry,
+        mut sink: impl FnMut(&[u8]) -> Result<(), JvmError>,
+    ) -> Result<(), JvmError> {
+        let compressed = self.entry_data(entry)?;
+        let mut crc = !0u32;
+        let mut feed = |chunk: &[u8]| {
+            crc = crc32_update(crc, chunk);
+            sink(chunk)
+        };
+        match entry.compression_method {
+            0 => feed(compressed)?,
+            8 => self.inflate_chunks(compressed, &mut feed)?,
+            m => return Err(unsupported_method(m)),
+        }
+        self.check_crc(entry, !crc)
+    }
+
+    // The entry's bytes as stored, after its local header.
+    fn entry_data(&self, entry: &ZipEntry) -> Result<&[u8], JvmError> {
+        if entry.flags & (FLAG_ENCRYPTED | FLAG_STRONG_ENCRYPTION) != 0
+            || entry.compression_method == METHOD_AES
+        {
+            return Err(JvmError::IoError(alloc::format!(
+                "entry {} is encrypted — Duke cannot read password-protected JARs",
+                entry.name
+            )));
+        }
+
         let offset = entry.local_header_offset as usize;
 
         if offset + 30 > self.data.len() {
-            return Err(JvmError::IoError(String::from(
-                "invalid local header offset",
+            return Err(JvmError::IoError(alloc::format!(
+                "{}: invalid local header offset",
+                entry.name
             )));
         }
 
-        let sig = read_u32_le(self.data, offset);
+        let sig = read_u32_le(&self.data, offset);
         if sig != LOCAL_HEADER_SIGNATURE {
-            return Err(JvmError::IoError(String::from(
-                "bad local header signature",
+            return Err(JvmError::IoError(alloc::format!(
+                "{}: bad local header signature",
+                entry.name
             )));
         }
 
-        let name_len = read_u16_le(self.data, offset + 26) as usize;
-        let extra_len = read_u16_le(self.data, offset + 28) as usize;
+        let name_len = read_u16_le(&self.data, offset + 26) as usize;
+        let extra_len = read_u16_le(&self.data, offset + 28) as usize;
         let data_start = offset + 30 + name_len + extra_len;
         let data_end = data_start + entry.compressed_size as usize;
 
         if data_end > self.data.len() {
-            return Err(JvmError::IoError(String::from(
-                "entry data beyond end of file",
+            return Err(JvmError::IoError(alloc::format!(
+                "{}: entry data beyond end of file",
+                entry.name
             )));
         }
 
-        let compressed = &self.data[data_start..data_end];
+        Ok(&self.data[data_start..data_end])
+    }
 
-        match entry.compression_method {
-            0 => Ok(compressed.to_vec()),
-            8 => self.inflate(compressed),
-            m => Err(JvmError::IoError(alloc::format!(
-                "unsupported ZIP compression method: {}",
-                m
-            ))),
+    fn check_crc(&self, entry: &ZipEntry, crc: u32) -> Result<(), JvmError> {
+        if self.verify_crc && crc != entry.crc32 {
+            return Err(JvmError::IoError(alloc::format!(
+                "{}: CRC-32 is {:08x} but the JAR says {:08x}; the file may be truncated or corrupt",
+                entry.name,
+                crc,
+                entry.crc32
+            )));
         }
+        Ok(())
+    }
+
+    fn inflate_chunks(
+        &self,
+        compressed: &[u8],
+        sink: &mut dyn FnMut(&[u8]) -> Result<(), JvmError>,
+    ) -> Result<(), JvmError> {
+        #[cfg(feature = "deflate")]
+        {
+            use miniz_oxide::inflate::stream::{
+                InflateState,
+                inflate,
+            };
+            use miniz_oxide::{
+                DataFormat,
+                MZFlush,
+                MZStatus,
+            };
+
+            let mut state = InflateState::new_boxed(DataFormat::Raw);
+            let mut out = alloc::vec![0u8; STREAM_CHUNK];
+            let mut input = compressed;
+            loop {
+                let r = inflate(&mut state, input, &mut out, MZFlush::None);
+                input = &input[r.bytes_consumed..];
+                if r.bytes_written > 0 {
+                    sink(&out[..r.bytes_written])?;
+                }
+                match r.status {
+                    Ok(MZStatus::StreamEnd) => return Ok(()),
+                    Ok(_) if r.bytes_consumed > 0 || r.bytes_written > 0 => {}
+                    Ok(_) | Err(_) => {
+                        return Err(JvmError::IoError(alloc::format!(
+                            "deflate error: {:?}",
+                            r.status
+                        )));
+                    }
+                }
+            }
+        }
+        #[cfg(not(feature = "deflate"))]
+        {
+            let _ = (compressed, sink);
+            Err(JvmError::IoError(String::from(
+                "DEFLATE not supported — rebuild with 'deflate' feature",
+            )))
+        }
+    }
+
+    /// The JARs stored inside this one, such as a fat JAR's
+    /// `BOOT-INF/lib/*.jar`, for [`ZipArchive::open_nested`].
+    pub fn nested_jars(&self) -> impl Iterator<Item = &ZipEntry> {
+        self.matching("**.jar")
+    }
+
+    /// Opens an entry as an archive of its own. A stored entry is read in
+    /// place; a compressed one is inflated into a buffer the new archive
+    /// keeps.
+    pub fn open_nested(&self, entry: &ZipEntry) -> Result<ZipArchive<'_>, JvmError> {
+        let mut nested = ZipArchive::open(self.read_entry(entry)?)
+            .map_err(|e| JvmError::IoError(alloc::format!("{}: {}", entry.name, e)))?;
+        nested.verify_crc = self.verify_crc;
+        Ok(nested)
     }
 
     fn inflate(&self, compressed: &[u8]) -> Result<Vec<u8>, JvmError> {
@@ -165,9 +409,217 @@ impl<'a> ZipArchive<'a> {
         }
     }
 
+    /// The classes to load, as [`DEFAULT_RELEASE`] would see them; see
+    /// [`ZipArchive::class_entries_for`].
     pub fn class_entries(&self) -> impl Iterator<Item = &ZipEntry> {
-        self.entries
+        self.class_entries_for(DEFAULT_RELEASE).into_iter()
+    }
+
+    /// The classes to load on Java `release`, in archive order. In a
+    /// multi-release JAR a class under `META-INF/versions/N/` replaces the
+    /// one at the root for releases from N up, and the highest such N wins.
+    /// Elsewhere, and in JARs whose manifest doesn't say `Multi-Release:
+    /// true`, `META-INF` is skipped.
+    pub fn class_entries_for(&self, release: u16) -> Vec<&ZipEntry> {
+        let multi = self.is_multi_release();
+        // Path within the release -> (version, entry), 8 for the root.
+        let mut chosen: BTreeMap<&str, (u16, usize)> = BTreeMap::new();
+        for (i, e) in self.entries.iter().enumerate() {
+            if !e.name.ends_with(".class") {
+                continue;
+            }
+            let Some((version, path)) = class_path(&e.name, multi) else {
+                continue;
+            };
+            if version > release {
+                continue;
+            }
+            match chosen.get(path) {
+                Some(&(v, _)) if v >= version => {}
+                _ => {
+                    chosen.insert(path, (version, i));
+                }
+            }
+        }
+        let mut picked: Vec<usize> = chosen.into_values().map(|(_, i)| i).collect();
+        picked.sort_unstable();
+        picked.into_iter().map(|i| &self.entries[i]).collect()
+    }
+
+    /// The main section of `META-INF/MANIFEST.MF`, if the archive has one
+    /// that can be read.
+    pub fn manifest(&self) -> Option<Manifest> {
+        let entry = self.find(MANIFEST)?;
+        let data = self.read_entry(entry).ok()?;
+        Some(Manifest::parse(&String::from_utf8_lossy(&data)))
+    }
+
+    /// Whether the manifest has `Multi-Release: true`, without which the
+    /// versioned directories are ignored.
+    pub fn is_multi_release(&self) -> bool {
+        self.manifest()
+            .and_then(|m| {
+                m.get("Multi-Release")
+                    .map(|v| v.eq_ignore_ascii_case("true"))
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// The main attributes of a JAR manifest, such as `Main-Class`. Per-entry
+/// sections after the first blank line are left out.
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    attributes: Vec<(String, String)>,
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Manifest {
+        let mut attributes: Vec<(String, String)> = Vec::new();
+        for line in text.lines() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if line.is_empty() {
+                break;
+            }
+            // Lines wrap at 72 bytes; a leading space continues the last.
+            if let Some(more) = line.strip_prefix(' ') {
+                if let Some((_, value)) = attributes.last_mut() {
+                    value.push_str(more);
+                }
+                continue;
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                attributes.push((String::from(key.trim()), String::from(value.trim())));
+            }
+        }
+        Manifest { attributes }
+    }
+
+    /// An attribute's value; names are matched ignoring case.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.attributes
             .iter()
-            .filter(|e| e.name.ends_with(".class") && !e.name.contains("META-INF"))
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
     }
+
+    /// `Main-Class` as an internal name, such as `efi/menu/Main`.
+    pub fn main_class(&self) -> Option<String> {
+        self.get("Main-Class")
+            .filter(|name| !name.is_empty())
+            .map(|name| name.replace('.', "/"))
+    }
+
+    /// The `Class-Path` entries, relative URLs separated by spaces.
+    pub fn class_path(&self) -> impl Iterator<Item = &str> {
+        self.get("Class-Path")
+            .unwrap_or("")
+            .split_ascii_whitespace()
+    }
+}
+
+/// Resolves a `Class-Path` entry against `jar`, the path of the JAR naming
+/// it, both with `/` separators and relative to the directory classes load
+/// from. None for an absolute path or URL, or one that climbs out of that
+/// directory.
+pub fn resolve_class_path(jar: &str, entry: &str) -> Option<String> {
+    if entry.starts_with('/') || entry.contains(':') {
+        return None;
+    }
+    let mut parts: Vec<&str> = jar.split('/').collect();
+    parts.pop();
+    for part in entry.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            _ => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+// A class entry's release and path within it: 8 and the name itself at the
+// root, or N and the rest under META-INF/versions/N/ (N from 9) when
+// `multi`. None for anything else in META-INF, and for module descriptors,
+// which declare no class.
+fn class_path(name: &str, multi: bool) -> Option<(u16, &str)> {
+    let (version, path) = match name.strip_prefix(VERSIONS) {
+        Some(rest) if multi => {
+            let (version, path) = rest.split_once('/')?;
+            (version.parse().ok().filter(|&v: &u16| v >= 9)?, path)
+        }
+        Some(_) => return None,
+        None => (8, name),
+    };
+    (!path.starts_with("META-INF/") && path != MODULE_INFO).then_some((version, path))
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xedb88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+/// The CRC-32 ZIP stores for each entry (the IEEE polynomial, reflected).
+pub fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(!0, data)
+}
+
+// Carries a CRC across chunks, before the final inversion.
+fn crc32_update(mut c: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        c = CRC_TABLE[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8);
+    }
+    c
+}
+
+/// Whether an entry name matches a glob: `*` stands for any run of
+/// characters within one directory, `**` for any run across them, and `?`
+/// for one character other than `/`. `*.properties` matches only at the
+/// root; `**.properties` matches anywhere.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (p, n) = (pattern.as_bytes(), name.as_bytes());
+    let (mut pi, mut ni) = (0, 0);
+    // Where to pick up after the last star: the pattern past it, the name
+    // position it has consumed up to, and whether it may cross '/'.
+    let mut star: Option<(usize, usize, bool)> = None;
+    while ni < n.len() {
+        if pi < p.len() && p[pi] == b'*' {
+            let deep = p.get(pi + 1) == Some(&b'*');
+            pi += if deep { 2 } else { 1 };
+            star = Some((pi, ni, deep));
+        } else if pi < p.len() && (p[pi] == n[ni] || p[pi] == b'?' && n[ni] != b'/') {
+            pi += 1;
+            ni += 1;
+        } else if let Some((sp, sn, deep)) = star
+            && (deep || n[sn] != b'/')
+        {
+            pi = sp;
+            ni = sn + 1;
+            star = Some((sp, sn + 1, deep));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == b'*')
+}
+
+fn unsupported_method(m: u16) -> JvmError {
+    JvmError::IoError(alloc::format!("unsupported ZIP compression method: {}", m))
 }
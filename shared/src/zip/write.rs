@@ -0,0 +1,173 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{
+    CD_SIGNATURE,
+    EOCD_SIGNATURE,
+    LOCAL_HEADER_SIGNATURE,
+    ZipArchive,
+    crc32,
+    read_u16_le,
+    read_u32_le,
+};
+use crate::types::JvmError;
+
+// Version 2.0, the first with DEFLATE, is all these entries need.
+const VERSION: u16 = 20;
+// Bit 11: the name is UTF-8.
+const FLAG_UTF8: u16 = 0x0800;
+// 1980-01-01 00:00, the earliest time a ZIP can give; the firmware has no
+// clock worth trusting.
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Builds a ZIP archive, such as a bundle of screenshots and logs for the
+/// ESP, either from nothing or on the end of an existing one.
+pub struct ZipWriter {
+    out: Vec<u8>,
+    // Central directory records, written after the last entry.
+    central: Vec<u8>,
+    count: usize,
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        ZipWriter {
+            out: Vec::new(),
+            central: Vec::new(),
+            count: 0,
+        }
+    }
+
+    /// Keeps an archive's entries as they are, and their central directory
+    /// records byte for byte, so new entries go on after them.
+    pub fn append(mut data: Vec<u8>) -> Result<Self, JvmError> {
+        let eocd = ZipArchive::find_eocd(&data)?;
+        let count = read_u16_le(&data, eocd + 10) as usize;
+        let cd_size = read_u32_le(&data, eocd + 12) as usize;
+        let cd_offset = read_u32_le(&data, eocd + 16) as usize;
+        if cd_offset + cd_size > eocd {
+            return Err(JvmError::IoError(String::from(
+                "central directory overlaps its end record",
+            )));
+        }
+        let central = data[cd_offset..cd_offset + cd_size].to_vec();
+        data.truncate(cd_offset);
+        Ok(ZipWriter {
+            out: data,
+            central,
+            count,
+        })
+    }
+
+    /// Adds an entry as it is.
+    pub fn add_stored(&mut self, name: &str, data: &[u8]) -> Result<(), JvmError> {
+        self.add(name, 0, data, data)
+    }
+
+    /// Adds an entry compressed with DEFLATE, or stored when compressing
+    /// doesn't make it smaller.
+    pub fn add_deflated(&mut self, name: &str, data: &[u8]) -> Result<(), JvmError> {
+        #[cfg(feature = "deflate")]
+        {
+            let compressed = miniz_oxide::deflate::compress_to_vec(data, 6);
+            if compressed.len() < data.len() {
+                return self.add(name, 8, &compressed, data);
+            }
+            self.add_stored(name, data)
+        }
+        #[cfg(not(feature = "deflate"))]
+        {
+            let _ = (name, data);
+            Err(JvmError::IoError(String::from(
+                "DEFLATE not supported — rebuild with 'deflate' feature",
+            )))
+        }
+    }
+
+    fn add(&mut self, name: &str, method: u16, stored: &[u8], data: &[u8]) -> Result<(), JvmError> {
+        // No ZIP64: counts and offsets must fit the classic fields.
+        let too_big =
+            |what: &str| JvmError::IoError(format!("{}: {} too large for ZIP", name, what));
+        if self.count >= u16::MAX as usize {
+            return Err(too_big("entry count"));
+        }
+        let name_len = u16::try_from(name.len()).map_err(|_| too_big("name"))?;
+        let size = u32::try_from(data.len()).map_err(|_| too_big("entry"))?;
+        let stored_size = u32::try_from(stored.len()).map_err(|_| too_big("entry"))?;
+        let offset = u32::try_from(self.out.len()).map_err(|_| too_big("archive"))?;
+        let crc = crc32(data);
+
+        let out = &mut self.out;
+        put_u32(out, LOCAL_HEADER_SIGNATURE);
+        put_u16(out, VERSION);
+        put_u16(out, FLAG_UTF8);
+        put_u16(out, method);
+        put_u16(out, DOS_TIME);
+        put_u16(out, DOS_DATE);
+        put_u32(out, crc);
+        put_u32(out, stored_size);
+        put_u32(out, size);
+        put_u16(out, name_len);
+        put_u16(out, 0);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(stored);
+
+        let cd = &mut self.central;
+        put_u32(cd, CD_SIGNATURE);
+        put_u16(cd, VERSION);
+        put_u16(cd, VERSION);
+        put_u16(cd, FLAG_UTF8);
+        put_u16(cd, method);
+        put_u16(cd, DOS_TIME);
+        put_u16(cd, DOS_DATE);
+        put_u32(cd, crc);
+        put_u32(cd, stored_size);
+        put_u32(cd, size);
+        put_u16(cd, name_len);
+        // Extra field, comment, disk, internal and external attributes.
+        put_u16(cd, 0);
+        put_u16(cd, 0);
+        put_u16(cd, 0);
+        put_u16(cd, 0);
+        put_u32(cd, 0);
+        put_u32(cd, offset);
+        cd.extend_from_slice(name.as_bytes());
+
+        self.count += 1;
+        Ok(())
+    }
+
+    /// The finished archive, with its central directory and end record.
+    pub fn finish(mut self) -> Result<Vec<u8>, JvmError> {
+        let too_big = || JvmError::IoError(String::from("archive too large for ZIP"));
+        let cd_offset = u32::try_from(self.out.len()).map_err(|_| too_big())?;
+        let cd_size = u32::try_from(self.central.len()).map_err(|_| too_big())?;
+        self.out.extend_from_slice(&self.central);
+        let out = &mut self.out;
+        put_u32(out, EOCD_SIGNATURE);
+        put_u16(out, 0);
+        put_u16(out, 0);
+        put_u16(out, self.count as u16);
+        put_u16(out, self.count as u16);
+        put_u32(out, cd_size);
+        put_u32(out, cd_offset);
+        put_u16(out, 0);
+        Ok(self.out)
+    }
+}
+
+impl Default for ZipWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn put_u16(out: &mut Vec<u8>, v: u16) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
@@ -0,0 +1,102 @@
+use uefi::table::cfg::ConfigTableEntry;
+
+const SDT_HEADER_LEN: usize = 36;
+
+pub struct Bgrt {
+    pub x: usize,
+    pub y: usize,
+    pub image: &'static [u8],
+}
+
+// Firmware tables live at identity-mapped physical addresses while boot
+// services are active, so they can be borrowed for the rest of the run.
+unsafe fn phys(addr: u64, len: usize) -> &'static [u8] {
+    unsafe { core::slice::from_raw_parts(addr as *const u8, len) }
+}
+
+fn read_u32(data: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes([data[off], data[off + 1], data[off + 2], data[off + 3]])
+}
+
+fn read_u64(data: &[u8], off: usize) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&data[off..off + 8]);
+    u64::from_le_bytes(b)
+}
+
+fn sdt(addr: u64) -> Option<&'static [u8]> {
+    if addr == 0 {
+        return None;
+    }
+    let header = unsafe { phys(addr, SDT_HEADER_LEN) };
+    let len = read_u32(header, 4) as usize;
+    if len < SDT_HEADER_LEN {
+        return None;
+    }
+    Some(unsafe { phys(addr, len) })
+}
+
+pub fn find_table(signature: &[u8; 4]) -> Option<&'static [u8]> {
+    let (root, entry_size) = uefi::system::with_config_table(|entries| {
+        let entry = entries
+            .iter()
+            .find(|e| e.guid == ConfigTableEntry::ACPI2_GUID)
+            .or_else(|| {
+                entries
+                    .iter()
+                    .find(|e| e.guid == ConfigTableEntry::ACPI_GUID)
+            })?;
+        let rsdp = unsafe { phys(entry.address as u64, 36) };
+        if &rsdp[0..8] != b"RSD PTR " {
+            return None;
+        }
+        // Revision 2+ carries the 64-bit XSDT; older firmware only has the RSDT.
+        if rsdp[15] >= 2 && read_u64(rsdp, 24) != 0 {
+            Some((read_u64(rsdp, 24), 8))
+        } else {
+            Some((read_u32(rsdp, 16) as u64, 4))
+        }
+    })?;
+
+    let root = sdt(root)?;
+    let mut off = SDT_HEADER_LEN;
+    while off + entry_size <= root.len() {
+        let addr = if entry_size == 8 {
+            read_u64(root, off)
+        } else {
+            read_u32(root, off) as u64
+        };
+        if let Some(table) = sdt(addr)
+            && &table[0..4] == signature
+        {
+            return Some(table);
+        }
+        off += entry_size;
+    }
+    None
+}
+
+pub fn bgrt() -> Option<Bgrt> {
+    let table = find_table(b"BGRT")?;
+    if table.len() < 56 {
+        return None;
+    }
+    // Image type 0 is the only one defined: a BMP.
+    if table[39] != 0 {
+        return None;
+    }
+    let image_addr = read_u64(table, 40);
+    if image_addr == 0 {
+        return None;
+    }
+    let header = unsafe { phys(image_addr, 6) };
+    if &header[0..2] != b"BM" {
+        return None;
+    }
+    let size = read_u32(header, 2) as usize;
+    Some(Bgrt {
+        x: read_u32(table, 48) as usize,
+        y: read_u32(table, 52) as usize,
+        image: unsafe { phys(image_addr, size) },
+    })
+}
@@ -35,7 +35,7 @@ pub fn parse(data: &[u8]) -> Result<Bitmap, &'static str> {
     let abs_h = height.unsigned_abs() as usize;
     let bottom_up = height > 0;
     let bytes_per_px = bpp / 8;
-    let row_stride = ((abs_w * bytes_per_px + 3) / 4) * 4;
+    let row_stride = (abs_w * bytes_per_px).div_ceil(4) * 4;
 
     let mut pixels = Vec::with_capacity(abs_w * abs_h);
 
@@ -62,6 +62,34 @@ pub fn parse(data: &[u8]) -> Result<Bitmap, &'static str> {
     })
 }
 
+// Writes a bottom-up 24-bit BMP, the format `parse` reads back.
+pub fn encode(width: usize, height: usize, pixels: &[BltPixel]) -> Vec<u8> {
+    let row_stride = (width * 3).div_ceil(4) * 4;
+    let pixel_offset = 54;
+    let file_size = pixel_offset + row_stride * height;
+
+    let mut out = Vec::with_capacity(file_size);
+    out.extend_from_slice(b"BM");
+    out.extend_from_slice(&(file_size as u32).to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&(pixel_offset as u32).to_le_bytes());
+    out.extend_from_slice(&40u32.to_le_bytes());
+    out.extend_from_slice(&(width as i32).to_le_bytes());
+    out.extend_from_slice(&(height as i32).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&24u16.to_le_bytes());
+    out.extend_from_slice(&[0; 24]);
+
+    for row in (0..height).rev() {
+        let start = out.len();
+        for px in &pixels[row * width..(row + 1) * width] {
+            out.extend_from_slice(&[px.blue, px.green, px.red]);
+        }
+        out.resize(start + row_stride, 0);
+    }
+    out
+}
+
 fn read_u16(data: &[u8], off: usize) -> u16 {
     u16::from_le_bytes([data[off], data[off + 1]])
 }
@@ -0,0 +1,1088 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use jvm::NativeArgs;
+use log::info;
+use shared::bls::{
+    self,
+    BootCounter,
+    LoaderEntry,
+};
+use shared::entries::{
+    EntryFacts,
+    EntryPolicy,
+};
+use shared::grub::GrubMenu;
+use shared::kernel;
+use shared::loadopt::{
+    self,
+    LoadOption,
+};
+use shared::osrelease::{
+    self,
+    OsRelease,
+};
+use shared::pe::{
+    self,
+    ImageKind,
+};
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::boot::SearchType;
+use uefi::fs::FileSystem;
+use uefi::proto::BootPolicy;
+use uefi::proto::device_path::build::{
+    self as dp_build,
+    DevicePathBuilder,
+};
+use uefi::proto::device_path::{
+    DevicePath,
+    FfiDevicePath,
+};
+use uefi::proto::loaded_image::LoadedImage;
+use uefi::proto::media::fs::SimpleFileSystem;
+use uefi::runtime::{
+    self,
+    ResetType,
+    VariableAttributes,
+    VariableVendor,
+};
+use uefi::{
+    CString16,
+    Handle,
+    Status,
+    boot,
+    cstr16,
+};
+
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+use crate::proto::open_shared;
+use crate::shutdown::{
+    self,
+    ShutdownReason,
+};
+use crate::{
+    linux,
+    power,
+    preview,
+    secureboot,
+    timeout,
+    tpm,
+};
+
+const CLASS: &str = "efi/BootServices";
+
+// What the Firmware Setup entry's path reads as, for entry_hide and the
+// like; there is no file behind it.
+const FIRMWARE_SETUP_PATH: &str = "firmware-setup";
+
+#[derive(Clone)]
+pub struct BootEntry {
+    pub name: String,
+    pub path: String,
+    pub device: Handle,
+    // The command line a loader entry starts its kernel with.
+    pub options: Option<String>,
+    // Initrds served to the kernel's EFI stub over LoadFile2.
+    pub initrds: Vec<String>,
+    // The menu a GRUB install's grub.cfg sets up.
+    pub grub: Option<GrubMenu>,
+    // A firmware Boot#### option, which boots from its own device path
+    // rather than `device` and `path`.
+    pub boot_option: Option<FirmwareOption>,
+    // The built-in Firmware Setup entry, which reboots into the firmware's
+    // own setup screens.
+    pub setup: bool,
+    // A counted loader entry's file name and boot counter, which goes down
+    // by one each time it is started.
+    pub tries: Option<(String, BootCounter)>,
+    // What the menu draws beside it, such as `fedora`.
+    pub icon: Option<&'static str>,
+}
+
+#[derive(Clone)]
+pub struct FirmwareOption {
+    pub number: u16,
+    pub device_path: Vec<u8>,
+}
+
+pub struct EntryStore {
+    entries: Vec<BootEntry>,
+    // Command lines edited in the menu, by entry, for this boot only.
+    edited: Vec<(i32, String)>,
+}
+
+impl EntryStore {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            edited: Vec::new(),
+        }
+    }
+
+    pub fn replace(&mut self, entries: Vec<BootEntry>) -> i32 {
+        self.entries = entries;
+        self.edited.clear();
+        self.entries.len() as i32
+    }
+
+    pub fn get(&self, idx: i32) -> Option<&BootEntry> {
+        self.entries.get(idx as usize)
+    }
+
+    pub fn edited(&self, idx: i32) -> Option<&str> {
+        self.edited
+            .iter()
+            .find(|(i, _)| *i == idx)
+            .map(|(_, options)| options.as_str())
+    }
+
+    // None drops the edit, back to the entry's own command line.
+    pub fn set_edited(&mut self, idx: i32, options: Option<String>) {
+        self.edited.retain(|(i, _)| *i != idx);
+        if let Some(options) = options {
+            self.edited.push((idx, options));
+        }
+    }
+
+    pub fn position(&self, path: &str) -> Option<i32> {
+        self.entries
+            .iter()
+            .position(|e| e.path == path)
+            .map(|i| i as i32)
+    }
+}
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "chainload", chainload);
+    reg.register(CLASS, "chainloadEntry", chainload_entry);
+    reg.register(CLASS, "chainloadWithOptions", chainload_with_options);
+    reg.register(CLASS, "stall", stall);
+    reg.register(CLASS, "discoverEntries", discover_entries);
+    reg.register(CLASS, "entryName", entry_name);
+    reg.register(CLASS, "entryPath", entry_path);
+    reg.register(CLASS, "entryBootCount", entry_boot_count);
+    reg.register(CLASS, "entryIcon", entry_icon);
+    reg.register(CLASS, "setBootNext", set_boot_next);
+    reg.register(CLASS, "entryOptions", entry_options);
+    reg.register(CLASS, "setEntryOptions", set_entry_options);
+    reg.register(CLASS, "entryTriesLeft", entry_tries_left);
+}
+
+fn chainload(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    // chainload(int) starts an entry, chainload(String) a path.
+    match args.first() {
+        Some(JvmValue::Int(_)) => start_entry(ctx, args.int(0)?)?,
+        _ => chainload_path(ctx, args.string(0)?, None)?,
+    }
+    Ok(None)
+}
+
+// A path on Duke's own ESP, started with `options` as its command line,
+// such as a kernel's or memtest's.
+fn chainload_with_options(
+    ctx: &mut NativeContext,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let path = args.string(0)?;
+    let options = args.string(1)?;
+    chainload_path(ctx, path, Some(options).filter(|o| !o.is_empty()))?;
+    Ok(None)
+}
+
+fn chainload_path(
+    ctx: &mut NativeContext,
+    path: &str,
+    options: Option<&str>,
+) -> Result<(), JvmError> {
+    let loaded_image = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle())
+        .map_err(|e| JvmError::IoError(format!("LoadedImage: {:?}", e)))?;
+    let device_handle = loaded_image
+        .device()
+        .ok_or_else(|| JvmError::IoError(String::from("no device handle")))?;
+    drop(loaded_image);
+    ctx.timeout.record_boot(path);
+    tpm::measure_boot(&ctx.config, Some(device_handle), path, options);
+    shutdown::teardown(ctx, ShutdownReason::Chainload);
+    let result = do_chainload(device_handle, path, options);
+    ctx.timeout.record_return();
+    shutdown::reenter(ctx);
+    result
+}
+
+fn chainload_entry(
+    ctx: &mut NativeContext,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    start_entry(ctx, args.int(0)?)?;
+    Ok(None)
+}
+
+// Boots a firmware option once, as the setup menu's one-time boot does:
+// BootOrder and Duke's boot stats stay as they were. False for an entry
+// that isn't a Boot#### option.
+fn set_boot_next(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let Some(number) = ctx
+        .entries
+        .get(args.int(0)?)
+        .and_then(|e| e.boot_option.as_ref())
+        .map(|option| option.number)
+    else {
+        return Ok(Some(JvmValue::Int(0)));
+    };
+    shutdown::teardown(ctx, ShutdownReason::Exit);
+    boot_next(number)
+}
+
+fn start_entry(ctx: &mut NativeContext, idx: i32) -> Result<(), JvmError> {
+    let Some(mut entry) = ctx.entries.get(idx).cloned() else {
+        return Ok(());
+    };
+    // Not counted as a boot, so last-booted ordering never lands on it.
+    if entry.setup {
+        return power::reboot_to_firmware(ctx).map(|_| ());
+    }
+    if let Some(edited) = ctx.entries.edited(idx) {
+        entry.options = Some(String::from(edited));
+    }
+    ctx.timeout.record_boot(&entry.path);
+    if let Some((file, counter)) = &entry.tries {
+        count_boot(entry.device, file, counter);
+    }
+    let device = entry.boot_option.is_none().then_some(entry.device);
+    tpm::measure_boot(&ctx.config, device, &entry.path, entry.options.as_deref());
+    shutdown::teardown(ctx, ShutdownReason::Chainload);
+    let result = match &entry.boot_option {
+        Some(option) => boot_firmware_option(option),
+        None if !entry.initrds.is_empty() => linux::boot(
+            entry.device,
+            &entry.path,
+            &entry.initrds,
+            entry.options.as_deref(),
+        ),
+        None => do_chainload(entry.device, &entry.path, entry.options.as_deref()),
+    };
+    ctx.timeout.record_return();
+    shutdown::reenter(ctx);
+    result
+}
+
+// Used when the menu itself can't run: boots whatever it would have
+// preselected, discovering entries first if Java never got that far.
+pub fn boot_default(ctx: &mut NativeContext) -> Result<(), JvmError> {
+    if ctx.entries.get(0).is_none() {
+        let options = ctx.config.get("kernel_options").map(String::from);
+        let entries = discover_efi_entries(options.as_deref());
+        let entries = arrange(ctx, entries);
+        ctx.entries.replace(entries);
+    }
+    let idx = timeout::default_index(ctx);
+    chainload_entry(
+        ctx,
+        &NativeArgs::new("chainloadEntry", "(I)V", &[JvmValue::Int(idx)]),
+    )?;
+    Ok(())
+}
+
+fn stall(_ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    boot::stall(Duration::from_millis(args.int(0)?.max(0) as u64));
+    Ok(None)
+}
+
+fn discover_entries(
+    ctx: &mut NativeContext,
+    _args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let options = ctx.config.get("kernel_options").map(String::from);
+    let entries = discover_efi_entries(options.as_deref());
+    let entries = arrange(ctx, entries);
+    let count = ctx.entries.replace(entries);
+    Ok(Some(JvmValue::Int(count)))
+}
+
+// duke.cfg's entry_* rules, applied before Java sees the list.
+fn arrange(ctx: &NativeContext, mut entries: Vec<BootEntry>) -> Vec<BootEntry> {
+    let policy = EntryPolicy::from_settings(|key| ctx.config.get(key));
+    for entry in &mut entries {
+        if let Some(name) = policy.rename(&entry.name, &entry.path) {
+            entry.name = String::from(name);
+        }
+    }
+    let stats = &ctx.timeout.stats;
+    let facts: Vec<EntryFacts> = entries
+        .iter()
+        .map(|e| EntryFacts {
+            name: &e.name,
+            path: &e.path,
+            family: e.icon,
+            boots: stats.count(&e.path),
+            last: stats.last_entry.as_deref() == Some(e.path.as_str()),
+        })
+        .collect();
+    let order = policy.arrange(&facts);
+    let mut slots: Vec<Option<BootEntry>> = entries.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
+fn entry_name(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let name = ctx
+        .entries
+        .get(args.int(0)?)
+        .map(|e| e.name.clone())
+        .unwrap_or_else(|| String::from("?"));
+    Ok(Some(JvmValue::StringRef(name)))
+}
+
+fn entry_path(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let path = ctx
+        .entries
+        .get(args.int(0)?)
+        .map(|e| e.path.clone())
+        .unwrap_or_default();
+    Ok(Some(JvmValue::StringRef(path)))
+}
+
+fn entry_boot_count(
+    ctx: &mut NativeContext,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let count = ctx
+        .entries
+        .get(args.int(0)?)
+        .map(|e| ctx.timeout.stats.count(&e.path))
+        .unwrap_or(0);
+    Ok(Some(JvmValue::Int(count as i32)))
+}
+
+fn entry_icon(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(
+        match ctx.entries.get(args.int(0)?).and_then(|e| e.icon) {
+            Some(icon) => JvmValue::StringRef(String::from(icon)),
+            None => JvmValue::Null,
+        },
+    ))
+}
+
+// Boots a counted loader entry has left, or -1 for one that isn't counted.
+fn entry_tries_left(
+    ctx: &mut NativeContext,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let left = ctx
+        .entries
+        .get(args.int(0)?)
+        .and_then(|e| e.tries.as_ref())
+        .map_or(-1, |(_, counter)| counter.left as i32);
+    Ok(Some(JvmValue::Int(left)))
+}
+
+// The command line the entry would boot with: an edit, else its own, else
+// the one built into a UKI. Null for a firmware option, which has none to
+// give.
+fn entry_options(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let idx = args.int(0)?;
+    let Some(entry) = ctx.entries.get(idx) else {
+        return Ok(Some(JvmValue::Null));
+    };
+    if entry.boot_option.is_some() {
+        return Ok(Some(JvmValue::Null));
+    }
+    let options = match ctx.entries.edited(idx) {
+        Some(edited) => Some(String::from(edited)),
+        None => entry.options.clone().or_else(|| {
+            preview::inspect(entry.device, &entry.path).and_then(|image| image.cmdline)
+        }),
+    };
+    Ok(Some(JvmValue::StringRef(options.unwrap_or_default())))
+}
+
+// Replaces the entry's command line until the next discovery; null puts
+// its own back. False for a firmware option.
+fn set_entry_options(
+    ctx: &mut NativeContext,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let idx = args.int(0)?;
+    if ctx.entries.get(idx).is_none_or(|e| e.boot_option.is_some()) {
+        return Ok(Some(JvmValue::Int(0)));
+    }
+    let options = args.opt_string(1)?.map(String::from);
+    ctx.entries.set_edited(idx, options);
+    Ok(Some(JvmValue::Int(1)))
+}
+
+// Compares bytes: a name's last four needn't start on a char boundary.
+fn has_efi_extension(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    bytes.len() >= 5 && bytes[bytes.len() - 4..].eq_ignore_ascii_case(b".efi")
+}
+
+fn capitalize(s: &str) -> String {
+    let mut bytes = Vec::from(s.as_bytes());
+    if let Some(first) = bytes.first_mut() {
+        first.make_ascii_uppercase();
+    }
+    String::from_utf8(bytes).unwrap_or_else(|_| String::from(s))
+}
+
+fn is_utility_efi(name: &str) -> bool {
+    const SKIP: &[&str] = &[
+        "mmx64.efi",
+        "mmia32.efi",
+        "mmaa64.efi",
+        "fwupx64.efi",
+        "fwupia32.efi",
+        "fwupaa64.efi",
+        "fbx64.efi",
+        "fbia32.efi",
+        "fbaa64.efi",
+        "memtest86.efi",
+        "memtest86plus.efi",
+        "duke.efi",
+    ];
+    SKIP.iter().any(|s| name.eq_ignore_ascii_case(s))
+}
+
+// `kernel_options` is the command line for kernels found loose on the ESP,
+// which have no entry file to give one. Not cancellable: it runs at startup,
+// where an Esc would only end the menu before it has anything to show.
+fn discover_efi_entries(kernel_options: Option<&str>) -> Vec<BootEntry> {
+    const KNOWN_LOADERS: &[&str] = &[
+        "shimx64.efi",
+        "shimia32.efi",
+        "shimaa64.efi",
+        "grubx64.efi",
+        "grubia32.efi",
+        "grubaa64.efi",
+        "systemd-bootx64.efi",
+        "systemd-bootia32.efi",
+        "systemd-bootaa64.efi",
+        "refind_x64.efi",
+        "refind_ia32.efi",
+        "refind_aa64.efi",
+        "vmlinuz.efi",
+        "bootmgfw.efi",
+        "bootx64.efi",
+        "bootia32.efi",
+        "bootaa64.efi",
+    ];
+
+    let mut entries = Vec::new();
+    // os-release of each root filesystem the firmware can read, as with an
+    // ext4 or btrfs driver loaded.
+    let mut roots = Vec::new();
+    let mut kernels = Vec::new();
+
+    let handles: Vec<Handle> =
+        match boot::locate_handle_buffer(SearchType::from_proto::<SimpleFileSystem>()) {
+            Ok(buf) => buf.to_vec(),
+            Err(_) => return entries,
+        };
+
+    for handle in handles {
+        let Ok(sfs) = boot::open_protocol_exclusive::<SimpleFileSystem>(handle) else {
+            continue;
+        };
+        let mut fs = FileSystem::new(sfs);
+
+        if let Some(release) = read_os_release(&mut fs) {
+            roots.push(release);
+            continue;
+        }
+
+        scan_loader_entries(&mut fs, handle, &mut entries);
+
+        let vendor_dirs: Vec<String> = match fs.read_dir(uefi::cstr16!("\\EFI")) {
+            Ok(iter) => iter
+                .filter_map(|r| r.ok())
+                .filter(|info| info.is_directory())
+                .map(|info| format!("{}", info.file_name()))
+                .collect(),
+            Err(_) => continue,
+        };
+
+        scan_esp(&mut fs, handle, &vendor_dirs, KNOWN_LOADERS, &mut entries);
+        scan_loose_kernels(&mut fs, handle, &mut kernels);
+    }
+
+    entries.extend(loose_kernel_entries(&kernels, kernel_options));
+    label_entries(&mut entries, &roots);
+    let firmware = firmware_entries(&entries);
+    entries.extend(firmware);
+
+    let mut seen = Vec::new();
+    entries.retain(|e| {
+        let key = e.name.clone();
+        if seen.contains(&key) {
+            false
+        } else {
+            seen.push(key);
+            true
+        }
+    });
+
+    if power::firmware_setup_supported() {
+        entries.push(BootEntry {
+            name: String::from("Firmware Setup"),
+            path: String::from(FIRMWARE_SETUP_PATH),
+            device: boot::image_handle(),
+            options: None,
+            initrds: Vec::new(),
+            grub: None,
+            boot_option: None,
+            setup: true,
+            tries: None,
+            icon: None,
+        });
+    }
+
+    entries
+}
+
+fn scan_esp(
+    fs: &mut FileSystem,
+    device: Handle,
+    vendor_dirs: &[String],
+    known_loaders: &[&str],
+    entries: &mut Vec<BootEntry>,
+) {
+    for vendor in vendor_dirs {
+        if vendor == "." || vendor == ".." {
+            continue;
+        }
+        if vendor.eq_ignore_ascii_case("duke") {
+            continue;
+        }
+
+        let is_boot_dir = vendor.eq_ignore_ascii_case("boot");
+        let is_linux_dir = vendor.eq_ignore_ascii_case("linux");
+
+        let dir_str = format!("\\EFI\\{}", vendor);
+        let Ok(dir_path) = CString16::try_from(dir_str.as_str()) else {
+            continue;
+        };
+
+        let contents: Vec<(String, bool)> = match fs.read_dir(&*dir_path) {
+            Ok(iter) => iter
+                .filter_map(|r| r.ok())
+                .map(|info| (format!("{}", info.file_name()), info.is_directory()))
+                .collect(),
+            Err(_) => continue,
+        };
+
+        if is_linux_dir {
+            for (fname, is_dir) in &contents {
+                if fname == "." || fname == ".." || *is_dir {
+                    continue;
+                }
+                if has_efi_extension(fname) && !is_utility_efi(fname) {
+                    let entry_path = format!("\\EFI\\{}\\{}", vendor, fname);
+                    entries.push(BootEntry {
+                        // Until label_entries reads its .osrel and .uname.
+                        name: format!("Linux ({})", fname),
+                        path: entry_path,
+                        device,
+                        options: None,
+                        initrds: Vec::new(),
+                        grub: None,
+                        boot_option: None,
+                        setup: false,
+                        tries: None,
+                        icon: None,
+                    });
+                }
+            }
+            continue;
+        }
+
+        let mut all_efi: Vec<(String, String)> = Vec::new();
+
+        for (fname, is_dir) in &contents {
+            if fname == "." || fname == ".." {
+                continue;
+            }
+            if !is_dir && has_efi_extension(fname) && !is_utility_efi(fname) {
+                all_efi.push((fname.clone(), format!("\\EFI\\{}\\{}", vendor, fname)));
+            } else if *is_dir {
+                let sub_str = format!("\\EFI\\{}\\{}", vendor, fname);
+                let Ok(sub_path) = CString16::try_from(sub_str.as_str()) else {
+                    continue;
+                };
+                let sub_files: Vec<String> = match fs.read_dir(&*sub_path) {
+                    Ok(iter) => iter
+                        .filter_map(|r| r.ok())
+                        .filter(|info| !info.is_directory())
+                        .map(|info| format!("{}", info.file_name()))
+                        .collect(),
+                    Err(_) => continue,
+                };
+                for sub_fname in &sub_files {
+                    if has_efi_extension(sub_fname) && !is_utility_efi(sub_fname) {
+                        all_efi.push((
+                            sub_fname.clone(),
+                            format!("\\EFI\\{}\\{}\\{}", vendor, fname, sub_fname),
+                        ));
+                    }
+                }
+            }
+        }
+
+        if is_boot_dir {
+            if all_efi
+                .iter()
+                .any(|(f, _)| f.eq_ignore_ascii_case("duke.efi"))
+            {
+                continue;
+            }
+            if let Some(best) = pick_best_loader(&all_efi, known_loaders) {
+                entries.push(BootEntry {
+                    name: String::from("UEFI Default"),
+                    path: best,
+                    device,
+                    options: None,
+                    initrds: Vec::new(),
+                    grub: None,
+                    boot_option: None,
+                    setup: false,
+                    tries: None,
+                    icon: None,
+                });
+            }
+            continue;
+        }
+
+        if let Some(best) = pick_best_loader(&all_efi, known_loaders) {
+            let grub = read_grub_menu(fs, vendor);
+            let name = grub
+                .as_ref()
+                .and_then(|menu| menu.default_entry())
+                .map(|item| item.title.clone())
+                .unwrap_or_else(|| capitalize(vendor));
+            entries.push(BootEntry {
+                name,
+                path: best,
+                device,
+                options: None,
+                initrds: Vec::new(),
+                grub,
+                boot_option: None,
+                setup: false,
+                tries: None,
+                icon: None,
+            });
+        }
+    }
+}
+
+// Boot Loader Specification entries, newest first. One without a `linux`
+// line, such as an `efi` entry, is left to the vendor scan.
+fn scan_loader_entries(fs: &mut FileSystem, device: Handle, entries: &mut Vec<BootEntry>) {
+    let Ok(dir) = CString16::try_from(bls::ENTRIES_DIR) else {
+        return;
+    };
+    let mut files: Vec<String> = match fs.read_dir(&*dir) {
+        Ok(iter) => iter
+            .filter_map(|r| r.ok())
+            .filter(|info| !info.is_directory())
+            .map(|info| format!("{}", info.file_name()))
+            .filter(|name| conf_stem(name).is_some())
+            .collect(),
+        Err(_) => return,
+    };
+    // Entries out of tries go last, so a slot with tries left is preferred.
+    files.sort_by(|a, b| {
+        let (a, a_counter) = BootCounter::split(conf_stem(a).unwrap_or(a));
+        let (b, b_counter) = BootCounter::split(conf_stem(b).unwrap_or(b));
+        let a_bad = a_counter.is_some_and(|c| c.is_bad());
+        let b_bad = b_counter.is_some_and(|c| c.is_bad());
+        a_bad.cmp(&b_bad).then_with(|| bls::compare_ids(a, b))
+    });
+
+    for fname in files {
+        let conf = format!("{}\\{}", bls::ENTRIES_DIR, fname);
+        let Ok(conf_path) = CString16::try_from(conf.as_str()) else {
+            continue;
+        };
+        let Ok(data) = fs.read(&*conf_path) else {
+            continue;
+        };
+        let (id, counter) = BootCounter::split(conf_stem(&fname).unwrap_or(&fname));
+        let entry = LoaderEntry::parse(id, &String::from_utf8_lossy(&data));
+        let Some(linux) = entry.linux.clone() else {
+            continue;
+        };
+        entries.push(BootEntry {
+            name: entry.display_name(),
+            path: linux,
+            device,
+            options: Some(entry.command_line()),
+            initrds: entry.initrd.clone(),
+            grub: None,
+            boot_option: None,
+            setup: false,
+            tries: counter.map(|counter| (fname.clone(), counter)),
+            icon: None,
+        });
+    }
+}
+
+// Renames the entry's file to take one try off, before the kernel starts,
+// as systemd-boot does; the OS renames it once more to drop the counter
+// after a good boot. An entry out of tries is left as it is.
+fn count_boot(device: Handle, file: &str, counter: &BootCounter) {
+    if counter.is_bad() {
+        return;
+    }
+    let (id, _) = BootCounter::split(conf_stem(file).unwrap_or(file));
+    let Some(sfs) = open_shared::<SimpleFileSystem>(device) else {
+        return;
+    };
+    let from = format!("{}\\{}", bls::ENTRIES_DIR, file);
+    let to = format!(
+        "{}\\{}",
+        bls::ENTRIES_DIR,
+        counter.after_boot().file_name(id)
+    );
+    let (Ok(from_path), Ok(to_path)) = (
+        CString16::try_from(from.as_str()),
+        CString16::try_from(to.as_str()),
+    ) else {
+        return;
+    };
+    if let Err(e) = FileSystem::new(sfs).rename(&*from_path, &*to_path) {
+        info!("Boot counting: {}: {:?}", from, e);
+    }
+}
+
+// The name without its `.conf`, in any case, or None for other files. The
+// suffix is compared as bytes, since a name's last five needn't start on a
+// char boundary.
+fn conf_stem(name: &str) -> Option<&str> {
+    let split = name.len().checked_sub(5).filter(|&at| at > 0)?;
+    if !name.as_bytes()[split..].eq_ignore_ascii_case(b".conf") {
+        return None;
+    }
+    name.get(..split)
+}
+
+// Most distributions leave only a stub on the ESP that loads the real
+// grub.cfg from /boot; that has no entries and the vendor name stays.
+fn read_grub_menu(fs: &mut FileSystem, vendor: &str) -> Option<GrubMenu> {
+    let path = CString16::try_from(format!("\\EFI\\{}\\grub.cfg", vendor).as_str()).ok()?;
+    let data = fs.read(&*path).ok()?;
+    let menu = GrubMenu::parse(&String::from_utf8_lossy(&data));
+    (!menu.items.is_empty()).then_some(menu)
+}
+
+// A kernel found outside \EFI by its name, to be checked by its header
+// once the ESP isn't held open exclusively.
+struct LooseKernel {
+    device: Handle,
+    path: String,
+    initrds: Vec<String>,
+}
+
+fn scan_loose_kernels(fs: &mut FileSystem, device: Handle, kernels: &mut Vec<LooseKernel>) {
+    for dir in ["", "\\boot"] {
+        let Ok(dir_path) = CString16::try_from(if dir.is_empty() { "\\" } else { dir }) else {
+            continue;
+        };
+        let files: Vec<String> = match fs.read_dir(&*dir_path) {
+            Ok(iter) => iter
+                .filter_map(|r| r.ok())
+                .filter(|info| !info.is_directory())
+                .map(|info| format!("{}", info.file_name()))
+                .collect(),
+            Err(_) => continue,
+        };
+        for fname in files.iter().filter(|f| kernel::is_kernel_name(f)) {
+            kernels.push(LooseKernel {
+                device,
+                path: format!("{}\\{}", dir, fname),
+                initrds: kernel::matching_initrds(fname, &files)
+                    .into_iter()
+                    .map(|initrd| format!("{}\\{}", dir, initrd))
+                    .collect(),
+            });
+        }
+    }
+}
+
+// The kernels whose header says Linux, booted directly through their EFI
+// stub.
+fn loose_kernel_entries(kernels: &[LooseKernel], options: Option<&str>) -> Vec<BootEntry> {
+    let mut entries = Vec::new();
+    for k in kernels {
+        let Some(image) = preview::inspect(k.device, &k.path) else {
+            continue;
+        };
+        if image.kind != ImageKind::Linux {
+            continue;
+        }
+        let fname = k.path.rsplit('\\').next().unwrap_or(&k.path);
+        let mut cmdline: Vec<String> = k.initrds.iter().map(|i| format!("initrd={}", i)).collect();
+        cmdline.extend(options.map(String::from));
+        entries.push(BootEntry {
+            name: match image.version {
+                Some(version) => format!("Linux {}", version),
+                None => format!("Linux ({})", fname),
+            },
+            path: k.path.clone(),
+            device: k.device,
+            options: Some(cmdline.join(" ")),
+            initrds: k.initrds.clone(),
+            grub: None,
+            boot_option: None,
+            setup: false,
+            tries: None,
+            icon: Some("linux"),
+        });
+    }
+    entries
+}
+
+fn is_windows_boot_manager(path: &str) -> bool {
+    path.rsplit('\\')
+        .next()
+        .is_some_and(|f| f.eq_ignore_ascii_case("bootmgfw.efi"))
+}
+
+fn read_os_release(fs: &mut FileSystem) -> Option<OsRelease> {
+    osrelease::PATHS.iter().find_map(|path| {
+        let path = CString16::try_from(*path).ok()?;
+        let data = fs.read(&*path).ok()?;
+        Some(OsRelease::parse(&String::from_utf8_lossy(&data)))
+    })
+}
+
+// Names and icons from os-release: a UKI's own, with its kernel release to
+// tell one UKI from the next, else that of a root filesystem whose ID is the
+// vendor directory's name. Windows Boot Manager is named for the release
+// its file version belongs to, which needs no BCD parsing. Loader entries
+// keep the title they were given.
+// UKIs end up newest kernel first, in the places they were found in.
+fn label_entries(entries: &mut [BootEntry], roots: &[OsRelease]) {
+    let mut ukis: Vec<(usize, Option<String>)> = Vec::new();
+    for (idx, entry) in entries.iter_mut().enumerate() {
+        if entry.options.is_some() {
+            entry.icon = Some("linux");
+            continue;
+        }
+        let mut parts = entry.path.split('\\').filter(|p| !p.is_empty());
+        let vendor = match (parts.next(), parts.next()) {
+            (Some(efi), Some(vendor)) if efi.eq_ignore_ascii_case("EFI") => vendor,
+            _ => continue,
+        };
+        if vendor.eq_ignore_ascii_case("linux") {
+            let image = preview::inspect(entry.device, &entry.path);
+            let release = image.as_ref().and_then(|i| i.os_release.as_ref());
+            let version = image.as_ref().and_then(|i| i.version.clone());
+            entry.icon = Some(release.map_or("linux", OsRelease::icon));
+            let label = release.and_then(OsRelease::label);
+            match (label, &version) {
+                (Some(label), Some(version)) => entry.name = format!("{} ({})", label, version),
+                (Some(label), None) => entry.name = label,
+                (None, Some(version)) => entry.name = format!("Linux {}", version),
+                (None, None) => {}
+            }
+            ukis.push((idx, version));
+            continue;
+        }
+        entry.icon = osrelease::icon_key(vendor);
+        if is_windows_boot_manager(&entry.path) {
+            let version = preview::inspect(entry.device, &entry.path).and_then(|i| i.version);
+            let release = version.as_deref().and_then(pe::windows_release);
+            entry.name = String::from(release.unwrap_or("Windows"));
+            continue;
+        }
+        let root = roots.iter().find(|r| {
+            r.id.as_deref()
+                .is_some_and(|id| id.eq_ignore_ascii_case(vendor))
+        });
+        if let Some(release) = root {
+            entry.icon = Some(release.icon());
+            if let Some(label) = release.label() {
+                entry.name = label;
+            }
+        }
+    }
+
+    let mut sorted = ukis.clone();
+    sorted.sort_by(|(_, a), (_, b)| {
+        bls::compare_versions(b.as_deref().unwrap_or(""), a.as_deref().unwrap_or(""))
+    });
+    let moved: Vec<BootEntry> = sorted.iter().map(|&(i, _)| entries[i].clone()).collect();
+    for ((slot, _), entry) in ukis.iter().zip(moved) {
+        entries[*slot] = entry;
+    }
+}
+
+// The firmware's active Boot#### options in BootOrder, less the one that
+// started Duke and those whose file the ESP scan already found. Network
+// boot, USB targets and other disks come in this way.
+fn firmware_entries(found: &[BootEntry]) -> Vec<BootEntry> {
+    let global = &VariableVendor::GLOBAL_VARIABLE;
+    let Ok((order, _)) = runtime::get_variable_boxed(cstr16!("BootOrder"), global) else {
+        return Vec::new();
+    };
+    let current = runtime::get_variable_boxed(cstr16!("BootCurrent"), global)
+        .ok()
+        .and_then(|(data, _)| loadopt::boot_order(&data).first().copied());
+
+    let mut entries = Vec::new();
+    for number in loadopt::boot_order(&order) {
+        if Some(number) == current {
+            continue;
+        }
+        let Ok(name) = CString16::try_from(loadopt::boot_variable(number).as_str()) else {
+            continue;
+        };
+        let Some(option) = runtime::get_variable_boxed(&name, global)
+            .ok()
+            .and_then(|(data, _)| LoadOption::parse(&data))
+        else {
+            continue;
+        };
+        if !option.is_active() || option.is_hidden() || option.is_app() {
+            continue;
+        }
+        if let Some(file) = option.file() {
+            let known = found.iter().any(|e| e.path.eq_ignore_ascii_case(&file));
+            let duke = file.to_ascii_lowercase().ends_with("duke.efi");
+            if known || duke {
+                continue;
+            }
+        }
+        entries.push(BootEntry {
+            name: option.description.clone(),
+            path: option.path_text(),
+            device: boot::image_handle(),
+            options: None,
+            initrds: Vec::new(),
+            grub: None,
+            boot_option: Some(FirmwareOption {
+                number,
+                device_path: option.file_path,
+            }),
+            setup: false,
+            tries: None,
+            icon: None,
+        });
+    }
+    entries
+}
+
+fn pick_best_loader(candidates: &[(String, String)], known: &[&str]) -> Option<String> {
+    for loader in known {
+        for (fname, full_path) in candidates {
+            if fname.eq_ignore_ascii_case(loader) {
+                return Some(full_path.clone());
+            }
+        }
+    }
+    candidates.first().map(|(_, p)| p.clone())
+}
+
+// A full device path loads directly. The short forms firmware stores for
+// disks and network boot only its own boot manager expands, so those are
+// handed to it through BootNext and a reset.
+fn boot_firmware_option(option: &FirmwareOption) -> Result<(), JvmError> {
+    // LoadOption::parse checked that the path ends in an end node.
+    let device_path =
+        unsafe { DevicePath::from_ffi_ptr(option.device_path.as_ptr().cast::<FfiDevicePath>()) };
+    let loaded = boot::load_image(
+        boot::image_handle(),
+        boot::LoadImageSource::FromDevicePath {
+            device_path,
+            boot_policy: BootPolicy::BootSelection,
+        },
+    );
+    match loaded {
+        Ok(handle) => boot::start_image(handle)
+            .map_err(|e| JvmError::IoError(format!("start_image: {:?}", e))),
+        Err(_) => boot_next(option.number).map(|_| ()),
+    }
+}
+
+// Returns only if BootNext couldn't be written.
+fn boot_next(number: u16) -> Result<Option<JvmValue>, JvmError> {
+    let attrs = VariableAttributes::NON_VOLATILE
+        | VariableAttributes::BOOTSERVICE_ACCESS
+        | VariableAttributes::RUNTIME_ACCESS;
+    runtime::set_variable(
+        cstr16!("BootNext"),
+        &VariableVendor::GLOBAL_VARIABLE,
+        attrs,
+        &number.to_le_bytes(),
+    )
+    .map_err(|e| JvmError::IoError(format!("BootNext: {:?}", e)))?;
+    runtime::reset(ResetType::COLD, Status::SUCCESS, None)
+}
+
+pub fn do_chainload(
+    device_handle: Handle,
+    path_str: &str,
+    options: Option<&str>,
+) -> Result<(), JvmError> {
+    let mut buf = Vec::new();
+    let full_path = file_device_path(device_handle, path_str, &mut buf)?;
+
+    let handle = secureboot::load(device_handle, path_str, full_path)?;
+
+    // The image reads its load options while it runs, so they have to
+    // outlive start_image.
+    let options_wide = match options {
+        Some(options) => Some(
+            CString16::try_from(options)
+                .map_err(|_| JvmError::IoError(String::from("invalid options encoding")))?,
+        ),
+        None => None,
+    };
+    if let Some(wide) = &options_wide {
+        let mut image = boot::open_protocol_exclusive::<LoadedImage>(handle)
+            .map_err(|e| JvmError::IoError(format!("LoadedImage: {:?}", e)))?;
+        unsafe { image.set_load_options(wide.as_ptr().cast(), wide.num_bytes() as u32) };
+    }
+
+    boot::start_image(handle).map_err(|e| JvmError::IoError(format!("start_image: {:?}", e)))?;
+
+    Ok(())
+}
+
+// `path_str` on `device_handle` as one device path, built in `buf`.
+pub fn file_device_path<'a>(
+    device_handle: Handle,
+    path_str: &str,
+    buf: &'a mut Vec<u8>,
+) -> Result<&'a DevicePath, JvmError> {
+    let path_wide = CString16::try_from(path_str)
+        .map_err(|_| JvmError::IoError(String::from("invalid path encoding")))?;
+
+    let device_path = boot::open_protocol_exclusive::<DevicePath>(device_handle)
+        .map_err(|e| JvmError::IoError(format!("DevicePath: {:?}", e)))?;
+
+    let mut builder = DevicePathBuilder::with_vec(buf);
+    for node in device_path.node_iter() {
+        builder = builder
+            .push(&node)
+            .map_err(|e| JvmError::IoError(format!("path build: {:?}", e)))?;
+    }
+    builder = builder
+        .push(&dp_build::media::FilePath {
+            path_name: &path_wide,
+        })
+        .map_err(|e| JvmError::IoError(format!("path build: {:?}", e)))?;
+    let full_path = builder
+        .finalize()
+        .map_err(|e| JvmError::IoError(format!("path finalize: {:?}", e)))?;
+
+    drop(device_path);
+    Ok(full_path)
+}
@@ -0,0 +1,26 @@
+use alloc::string::String;
+
+use shared::types::JvmError;
+
+pub struct CancelToken {
+    cancelled: bool,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self { cancelled: false }
+    }
+
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    // Clears the request so the next operation starts fresh.
+    pub fn take(&mut self) -> Result<(), JvmError> {
+        if core::mem::replace(&mut self.cancelled, false) {
+            Err(JvmError::Interrupted(String::from("operation cancelled")))
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use alloc::{
+    format,
+    vec,
+};
+
+use log::info;
+use uefi::boot;
+use uefi::proto::console::gop::{
+    BltOp,
+    BltPixel,
+    BltRegion,
+    GraphicsOutput,
+};
+
+use crate::bmp;
+use crate::config::Config;
+use crate::fsnat::{
+    create_esp_directory,
+    list_esp_directory,
+    write_esp_file,
+};
+use crate::natives::NativeContext;
+
+const CAPTURE_DIR: &str = "\\EFI\\duke\\capture";
+
+// Screen capture for demos. Toggled from the keyboard; every `every`-th
+// endFrame is written out as a numbered BMP until the frame or byte budget
+// runs out, so a forgotten capture can't fill the ESP.
+pub struct Capture {
+    enabled: bool,
+    every: u32,
+    max_frames: u32,
+    max_bytes: usize,
+    frame: u32,
+    next_index: Option<u32>,
+    saved: u32,
+    bytes: usize,
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            every: 1,
+            max_frames: 0,
+            max_bytes: 0,
+            frame: 0,
+            next_index: None,
+            saved: 0,
+            bytes: 0,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        let get = |key: &str, default: u32| {
+            config
+                .get(key)
+                .and_then(|v| v.parse::<u32>().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            every: get("capture_every", 1).max(1),
+            max_frames: get("capture_max_frames", 300),
+            max_bytes: get("capture_max_mb", 64) as usize * 1024 * 1024,
+            ..Self::new()
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.frame = 0;
+        info!(
+            "Capture {}",
+            if self.enabled { "started" } else { "stopped" }
+        );
+    }
+
+    fn exhausted(&self) -> bool {
+        self.saved >= self.max_frames || self.bytes >= self.max_bytes
+    }
+}
+
+pub fn end_frame(ctx: &mut NativeContext) {
+    let cap = &mut ctx.capture;
+    if !cap.enabled {
+        return;
+    }
+    cap.frame += 1;
+    if !cap.frame.is_multiple_of(cap.every) {
+        return;
+    }
+    if cap.exhausted() {
+        info!("Capture budget used up, stopping");
+        cap.enabled = false;
+        return;
+    }
+
+    let (w, h) = (ctx.gfx.screen_w, ctx.gfx.screen_h);
+    let Some(handle) = ctx.gfx.gop_handle else {
+        return;
+    };
+    let Ok(mut gop) = boot::open_protocol_exclusive::<GraphicsOutput>(handle) else {
+        return;
+    };
+    let mut pixels = vec![BltPixel::new(0, 0, 0); w * h];
+    let grabbed = gop.blt(BltOp::VideoToBltBuffer {
+        buffer: &mut pixels,
+        src: (0, 0),
+        dest: BltRegion::Full,
+        dims: (w, h),
+    });
+    drop(gop);
+    if grabbed.is_err() {
+        return;
+    }
+
+    // Number past whatever an earlier run left behind instead of overwriting it.
+    let index = match cap.next_index {
+        Some(i) => i,
+        None => {
+            let _ = create_esp_directory(CAPTURE_DIR);
+            list_esp_directory(CAPTURE_DIR)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|name| frame_number(name))
+                .max()
+                .map_or(0, |last| last.saturating_add(1))
+        }
+    };
+    let data = bmp::encode(w, h, &pixels);
+    let path = format!("{}\\frame{:05}.bmp", CAPTURE_DIR, index);
+    match write_esp_file(&path, &data) {
+        Ok(()) => {
+            cap.next_index = Some(index + 1);
+            cap.saved += 1;
+            cap.bytes += data.len();
+        }
+        Err(e) => {
+            info!("Capture write failed, stopping: {}", e);
+            cap.enabled = false;
+        }
+    }
+}
+
+// The number in a `frameNNNNN.bmp` name; FAT may hand the name back in
+// another case.
+fn frame_number(name: &str) -> Option<u32> {
+    let lower = name.to_ascii_lowercase();
+    let digits = lower.strip_prefix("frame")?.strip_suffix(".bmp")?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
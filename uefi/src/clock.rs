@@ -0,0 +1,40 @@
+use core::time::Duration;
+
+use log::info;
+use uefi::boot;
+
+const CALIBRATE_MS: u64 = 10;
+
+// UEFI has no monotonic clock with sub-second resolution, so the profiler
+// reads the TSC and converts with a rate measured against Stall at start-up.
+pub struct Clock {
+    ticks_per_us: u64,
+}
+
+impl Clock {
+    pub fn calibrate() -> Option<Self> {
+        let start = read_tsc()?;
+        boot::stall(Duration::from_millis(CALIBRATE_MS));
+        let ticks = read_tsc()?.wrapping_sub(start);
+        let ticks_per_us = ticks / (CALIBRATE_MS * 1000);
+        if ticks_per_us == 0 {
+            return None;
+        }
+        info!("Clock: TSC at {} MHz", ticks_per_us);
+        Some(Self { ticks_per_us })
+    }
+
+    pub fn now_micros(&self) -> Option<u64> {
+        Some(read_tsc()? / self.ticks_per_us)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn read_tsc() -> Option<u64> {
+    Some(unsafe { core::arch::x86_64::_rdtsc() })
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn read_tsc() -> Option<u64> {
+    None
+}
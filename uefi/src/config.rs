@@ -0,0 +1,158 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+
+use jvm::NativeArgs;
+use log::info;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+
+use crate::fsnat::read_esp_file;
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+use crate::smbios::{
+    self,
+    MachineIdentity,
+};
+
+const CLASS: &str = "efi/Config";
+
+const BASE_PATH: &str = "\\EFI\\duke\\duke.cfg";
+const OVERLAY_DIR: &str = "\\EFI\\duke\\duke.cfg.d";
+
+pub struct Config {
+    values: BTreeMap<String, String>,
+    identity: MachineIdentity,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self {
+            values: BTreeMap::new(),
+            identity: MachineIdentity::unknown(),
+        }
+    }
+
+    // Overlays apply from least to most specific, so a per-UUID file beats a
+    // per-model one and both beat duke.cfg.
+    pub fn load() -> Self {
+        let mut config = Self::new();
+        config.identity = smbios::read_identity();
+        info!(
+            "Machine: {} {} ({})",
+            config.identity.manufacturer.as_deref().unwrap_or("?"),
+            config.identity.model.as_deref().unwrap_or("?"),
+            config.identity.uuid.as_deref().unwrap_or("no uuid"),
+        );
+        config.apply_file(BASE_PATH);
+        if let Some(model) = &config.identity.model {
+            let path = format!("{}\\{}.cfg", OVERLAY_DIR, sanitize(model));
+            config.apply_file(&path);
+        }
+        if let Some(uuid) = &config.identity.uuid {
+            let path = format!("{}\\{}.cfg", OVERLAY_DIR, uuid);
+            config.apply_file(&path);
+        }
+        config
+    }
+
+    fn apply_file(&mut self, path: &str) {
+        let Ok(data) = read_esp_file(path) else {
+            return;
+        };
+        let text = String::from_utf8_lossy(&data);
+        let count = self.merge(&text);
+        info!("Config: {} ({} keys)", path, count);
+    }
+
+    fn merge(&mut self, text: &str) -> usize {
+        let mut count = 0;
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+            if key.is_empty() {
+                continue;
+            }
+            self.values
+                .insert(String::from(key), String::from(value.trim()));
+            count += 1;
+        }
+        count
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    // Every setting as `key=value` lines in key order, overlays applied, so
+    // the same settings always read the same however the files lay them out.
+    pub fn canonical(&self) -> String {
+        self.values
+            .iter()
+            .map(|(key, value)| format!("{}={}\n", key, value))
+            .collect()
+    }
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "get", get);
+    reg.register(CLASS, "getInt", get_int);
+    reg.register(CLASS, "machineId", machine_id);
+    reg.register(CLASS, "machineModel", machine_model);
+}
+
+fn get(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(match ctx.config.get(args.string(0)?) {
+        Some(v) => JvmValue::StringRef(String::from(v)),
+        None => JvmValue::Null,
+    }))
+}
+
+fn get_int(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let (key, default) = (args.string(0)?, args.int(1)?);
+    let value = ctx
+        .config
+        .get(key)
+        .and_then(|v| v.parse::<i32>().ok())
+        .unwrap_or(default);
+    Ok(Some(JvmValue::Int(value)))
+}
+
+fn machine_id(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(match &ctx.config.identity.uuid {
+        Some(uuid) => JvmValue::StringRef(uuid.clone()),
+        None => JvmValue::Null,
+    }))
+}
+
+fn machine_model(
+    ctx: &mut NativeContext,
+    _args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(match &ctx.config.identity.model {
+        Some(model) => JvmValue::StringRef(model.clone()),
+        None => JvmValue::Null,
+    }))
+}
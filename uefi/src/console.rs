@@ -0,0 +1,145 @@
+use alloc::collections::VecDeque;
+use core::time::Duration;
+
+use jvm::{
+    NativeArgs,
+    jvm_value_to_string,
+};
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::boot;
+use uefi::proto::console::text::{
+    Key,
+    ScanCode,
+};
+
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+
+const CLASS: &str = "efi/Console";
+
+pub const KEY_ABORT: i32 = -3;
+pub const KEY_CAPTURE: i32 = -8;
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "print", print);
+    reg.register(CLASS, "println", println);
+    reg.register(CLASS, "readKey", read_key);
+    reg.register(CLASS, "pollKey", poll_key);
+}
+
+fn print(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let text = jvm_value_to_string(args.value(0)?);
+    if ctx.terminal.redirected() {
+        ctx.terminal.write(&text);
+    } else {
+        uefi::print!("{}", text);
+    }
+    Ok(None)
+}
+
+fn println(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let text = args.first().map(jvm_value_to_string).unwrap_or_default();
+    if ctx.terminal.redirected() {
+        ctx.terminal.write(&text);
+        ctx.terminal.write("\n");
+    } else {
+        uefi::println!("{}", text);
+    }
+    Ok(None)
+}
+
+fn read_key(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    loop {
+        if let Some(code) = take_key(ctx) {
+            return Ok(Some(JvmValue::Int(code)));
+        }
+        boot::stall(Duration::from_millis(50));
+    }
+}
+
+// Returns 0 when no key is waiting.
+fn poll_key(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(JvmValue::Int(take_key(ctx).unwrap_or(0))))
+}
+
+// The capture hotkey is handled here and never reaches Java.
+fn take_key(ctx: &mut NativeContext) -> Option<i32> {
+    while let Some(code) = ctx.input.next_key() {
+        if code == KEY_CAPTURE {
+            ctx.capture.toggle();
+        } else {
+            return Some(code);
+        }
+    }
+    None
+}
+
+pub struct InputState {
+    pending: VecDeque<i32>,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self {
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    fn next_key(&mut self) -> Option<i32> {
+        self.pending.pop_front().or_else(poll_console)
+    }
+
+    // Drains the keyboard without blocking. The abort key is swallowed and
+    // reported; anything else is queued for the next readKey.
+    pub fn poll_abort(&mut self) -> bool {
+        let mut abort = false;
+        while let Some(code) = poll_console() {
+            if code == KEY_ABORT {
+                abort = true;
+            } else {
+                self.pending.push_back(code);
+            }
+        }
+        abort
+    }
+}
+
+fn poll_console() -> Option<i32> {
+    loop {
+        match uefi::system::with_stdin(|stdin| stdin.read_key()) {
+            Ok(Some(Key::Printable(c))) => return Some(u16::from(c) as i32),
+            Ok(Some(Key::Special(scan))) => {
+                let code = if scan == ScanCode::UP {
+                    -1
+                } else if scan == ScanCode::DOWN {
+                    -2
+                } else if scan == ScanCode::ESCAPE {
+                    -3
+                } else if scan == ScanCode::HOME {
+                    -4
+                } else if scan == ScanCode::END {
+                    -5
+                } else if scan == ScanCode::RIGHT {
+                    -6
+                } else if scan == ScanCode::LEFT {
+                    -7
+                } else if scan == ScanCode::FUNCTION_12 {
+                    KEY_CAPTURE
+                } else {
+                    continue;
+                };
+                return Some(code);
+            }
+            _ => return None,
+        }
+    }
+}
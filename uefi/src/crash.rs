@@ -0,0 +1,60 @@
+use alloc::format;
+use core::time::Duration;
+
+use jvm::UncaughtException;
+use uefi::boot;
+
+// Trace lines that fit under the heading on an 80x25 console.
+const MAX_LINES: usize = 18;
+const WAIT_SECS: u64 = 30;
+const POLL_MS: u64 = 50;
+
+// Replaces the menu with what BootMenu threw and where. Returns whether a
+// key was pressed in time; see screen.
+pub fn show(exception: &UncaughtException) -> bool {
+    screen(
+        "The boot menu stopped on an uncaught exception:",
+        &format!("{}", exception),
+    )
+}
+
+// For a BootMenu that gave up with System.exit(status), status nonzero.
+pub fn exited(status: i32) -> bool {
+    screen(&format!("The boot menu exited with status {}.", status), "")
+}
+
+// Clears the console for `heading` and `body`, then gives the user WAIT_SECS
+// to press a key. Returns whether they did, in which case the caller boots
+// the default entry rather than dropping back to the firmware.
+fn screen(heading: &str, body: &str) -> bool {
+    uefi::system::with_stdout(|out| {
+        let _ = out.clear();
+    });
+    uefi::println!();
+    uefi::println!("  {}", heading);
+    uefi::println!();
+    let mut lines = body.lines();
+    for line in lines.by_ref().take(MAX_LINES) {
+        uefi::println!("  {}", line.replace('\t', "    "));
+    }
+    let rest = lines.count();
+    if rest > 0 {
+        uefi::println!("      ... {} more", rest);
+    }
+    if !body.is_empty() {
+        uefi::println!();
+    }
+    uefi::println!("  Press any key to boot the default entry ({}s)", WAIT_SECS);
+
+    // Keys pressed before the crash don't count as an answer.
+    uefi::system::with_stdin(|stdin| {
+        let _ = stdin.reset(false);
+    });
+    for _ in 0..WAIT_SECS * 1000 / POLL_MS {
+        if let Ok(Some(_)) = uefi::system::with_stdin(|stdin| stdin.read_key()) {
+            return true;
+        }
+        boot::stall(Duration::from_millis(POLL_MS));
+    }
+    false
+}
@@ -0,0 +1,62 @@
+use alloc::string::String;
+
+use jvm::NativeArgs;
+use log::info;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+
+use crate::fsnat::write_esp_file;
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+
+const CLASS: &str = "efi/Debug";
+const HEAP_DUMP_PATH: &str = "\\EFI\\duke\\heap.dump";
+const PROFILE_PATH: &str = "\\EFI\\duke\\profile.txt";
+
+// Debug.dumpHeap and Debug.writeProfile are intercepted by the VM, which
+// renders the heap or profile and hands the text to these natives to persist.
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "writeHeapDump", write_heap_dump);
+    reg.register(CLASS, "writeProfile", write_profile);
+    reg.register(CLASS, "reload", reload);
+}
+
+// `dev = 1` lets the menu reload itself: this unwinds it like System.exit,
+// and run_menu then redefines the classes from \EFI\duke and starts it again.
+fn reload(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    if ctx.config.get("dev") != Some("1") {
+        return Err(JvmError::NativeMethodError(String::from(
+            "Debug.reload needs dev = 1",
+        )));
+    }
+    info!("Reloading classes");
+    ctx.reload = true;
+    Err(JvmError::SystemExit(0))
+}
+
+fn write_heap_dump(
+    _ctx: &mut NativeContext,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let text = args.string(0)?;
+    write_esp_file(HEAP_DUMP_PATH, text.as_bytes())?;
+    info!(
+        "Heap dump written to {} ({} bytes)",
+        HEAP_DUMP_PATH,
+        text.len()
+    );
+    Ok(None)
+}
+
+fn write_profile(
+    _ctx: &mut NativeContext,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    write_esp_file(PROFILE_PATH, args.string(0)?.as_bytes())?;
+    info!("Profile written to {}", PROFILE_PATH);
+    Ok(None)
+}
@@ -0,0 +1,418 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use jvm::NativeArgs;
+use log::info;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::Handle;
+use uefi::boot::{
+    self,
+    SearchType,
+};
+use uefi::proto::ProtocolPointer;
+use uefi::proto::ata::AtaRequestBuilder;
+use uefi::proto::ata::pass_thru::AtaPassThru;
+use uefi::proto::media::block::BlockIO;
+use uefi::proto::media::partition::PartitionInfo;
+use uefi::proto::nvme::pass_thru::NvmePassThru;
+use uefi::proto::nvme::{
+    NvmeQueueType,
+    NvmeRequestBuilder,
+};
+
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+use crate::proto::open_shared;
+
+const CLASS: &str = "efi/DiskHealth";
+
+const NVME_GET_LOG_PAGE: u8 = 0x02;
+const NVME_IDENTIFY: u8 = 0x06;
+const NVME_LOG_SMART: u32 = 0x02;
+const ATA_IDENTIFY: u8 = 0xEC;
+const ATA_SMART: u8 = 0xB0;
+const ATA_SMART_READ_DATA: u8 = 0xD0;
+const ATA_ATTR_REALLOCATED: u8 = 5;
+const ATA_ATTR_TEMPERATURE: u8 = 194;
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+const SCAN_CHUNK: usize = 4 * 1024 * 1024;
+
+// What SMART said about one drive. Fields a transport doesn't report stay
+// `None` rather than pretending to be zero.
+pub struct DiskReport {
+    name: String,
+    temperature: Option<i32>,
+    reallocated: Option<u64>,
+    percent_used: Option<u32>,
+    media_errors: Option<u64>,
+    critical: bool,
+}
+
+impl DiskReport {
+    fn warning(&self) -> bool {
+        self.critical
+            || self.reallocated.is_some_and(|n| n > 0)
+            || self.media_errors.is_some_and(|n| n > 0)
+            || self.percent_used.is_some_and(|p| p >= 100)
+    }
+
+    fn summary(&self) -> String {
+        let mut s = self.name.clone();
+        if let Some(t) = self.temperature {
+            s.push_str(&format!("  {}C", t));
+        }
+        if let Some(p) = self.percent_used {
+            s.push_str(&format!("  {}% used", p));
+        }
+        if let Some(n) = self.reallocated {
+            s.push_str(&format!("  {} reallocated", n));
+        }
+        if let Some(n) = self.media_errors {
+            s.push_str(&format!("  {} media errors", n));
+        }
+        if self.critical {
+            s.push_str("  CRITICAL WARNING");
+        }
+        s
+    }
+}
+
+struct Partition {
+    handle: Handle,
+    name: String,
+}
+
+// Sequential read of one partition, a chunk per step.
+struct Scan {
+    handle: Handle,
+    name: String,
+    next_lba: u64,
+    last_lba: u64,
+    errors: u64,
+}
+
+pub struct DiskHealth {
+    reports: Vec<DiskReport>,
+    partitions: Vec<Partition>,
+    scan: Option<Scan>,
+}
+
+impl DiskHealth {
+    pub fn new() -> Self {
+        Self {
+            reports: Vec::new(),
+            partitions: Vec::new(),
+            scan: None,
+        }
+    }
+}
+
+fn handles_for<P: ProtocolPointer + ?Sized>() -> Vec<Handle> {
+    boot::locate_handle_buffer(SearchType::from_proto::<P>())
+        .map(|h| h.to_vec())
+        .unwrap_or_default()
+}
+
+fn trimmed_ascii(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_matches(|c: char| c == ' ' || c == '\0')
+        .into()
+}
+
+fn read_u64(data: &[u8], off: usize) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&data[off..off + 8]);
+    u64::from_le_bytes(b)
+}
+
+fn probe_nvme(index: usize, handle: Handle) -> Option<DiskReport> {
+    let pt = open_shared::<NvmePassThru>(handle)?;
+    let align = pt.io_align();
+    let mut ctrl = pt.controller();
+
+    let model = NvmeRequestBuilder::new(align, NVME_IDENTIFY, NvmeQueueType::ADMIN)
+        .with_timeout(COMMAND_TIMEOUT)
+        .with_cdw10(1)
+        .with_transfer_buffer(4096)
+        .ok()
+        .and_then(|req| ctrl.execute_command(req.build()).ok())
+        .and_then(|resp| resp.transfer_buffer().map(|b| trimmed_ascii(&b[24..64])))
+        .filter(|m| !m.is_empty())
+        .unwrap_or_else(|| format!("NVMe {}", index));
+
+    // NUMDL is the dword count minus one; 512 bytes is the whole SMART page.
+    let req = NvmeRequestBuilder::new(align, NVME_GET_LOG_PAGE, NvmeQueueType::ADMIN)
+        .with_timeout(COMMAND_TIMEOUT)
+        .with_cdw10(NVME_LOG_SMART | ((512 / 4 - 1) << 16))
+        .with_transfer_buffer(512)
+        .ok()?
+        .build();
+    let resp = ctrl.execute_command(req).ok()?;
+    let log = resp.transfer_buffer()?;
+
+    let kelvin = u16::from_le_bytes([log[1], log[2]]) as i32;
+    Some(DiskReport {
+        name: model,
+        temperature: (kelvin > 0).then_some(kelvin - 273),
+        reallocated: None,
+        percent_used: Some(log[5] as u32),
+        media_errors: Some(read_u64(log, 160)),
+        critical: log[0] != 0,
+    })
+}
+
+fn probe_ata(handle: Handle, out: &mut Vec<DiskReport>) {
+    let Some(pt) = open_shared::<AtaPassThru>(handle) else {
+        return;
+    };
+    let align = pt.io_align();
+    for mut dev in pt.iter_devices() {
+        let port = dev.port();
+
+        // IDENTIFY doubles as the presence probe for the port.
+        let Some(model) = AtaRequestBuilder::read_pio(align, ATA_IDENTIFY)
+            .and_then(|b| b.with_timeout(COMMAND_TIMEOUT).with_read_buffer(512))
+            .ok()
+            .and_then(|b| dev.execute_command(b.build()).ok())
+            .and_then(|resp| {
+                // ATA strings are stored as big-endian words.
+                let id = resp.read_buffer()?;
+                let mut model = [0u8; 40];
+                for (i, pair) in id[54..94].chunks(2).enumerate() {
+                    model[i * 2] = pair[1];
+                    model[i * 2 + 1] = pair[0];
+                }
+                Some(trimmed_ascii(&model))
+            })
+        else {
+            continue;
+        };
+
+        let smart = AtaRequestBuilder::read_pio(align, ATA_SMART)
+            .and_then(|b| {
+                b.with_timeout(COMMAND_TIMEOUT)
+                    .with_features(ATA_SMART_READ_DATA)
+                    .with_cylinder(0x4F, 0xC2)
+                    .with_sector_count(1)
+                    .with_read_buffer(512)
+            })
+            .ok()
+            .and_then(|b| dev.execute_command(b.build()).ok())
+            .and_then(|resp| resp.read_buffer().map(|b| b.to_vec()));
+
+        let mut report = DiskReport {
+            name: if model.is_empty() {
+                format!("ATA port {}", port)
+            } else {
+                model
+            },
+            temperature: None,
+            reallocated: None,
+            percent_used: None,
+            media_errors: None,
+            critical: false,
+        };
+        // 30 attribute slots of 12 bytes: id, flags, value, worst, raw[6].
+        if let Some(data) = smart {
+            for attr in data[2..362].chunks(12) {
+                let raw = u64::from_le_bytes([
+                    attr[5], attr[6], attr[7], attr[8], attr[9], attr[10], 0, 0,
+                ]);
+                match attr[0] {
+                    ATA_ATTR_REALLOCATED => report.reallocated = Some(raw),
+                    ATA_ATTR_TEMPERATURE => report.temperature = Some((raw & 0xFF) as i32),
+                    _ => {}
+                }
+            }
+        }
+        out.push(report);
+    }
+}
+
+fn probe_all() -> Vec<DiskReport> {
+    let mut reports = Vec::new();
+    for (i, handle) in handles_for::<NvmePassThru>().into_iter().enumerate() {
+        if let Some(r) = probe_nvme(i, handle) {
+            reports.push(r);
+        }
+    }
+    for handle in handles_for::<AtaPassThru>() {
+        probe_ata(handle, &mut reports);
+    }
+    reports
+}
+
+fn list_partitions() -> Vec<Partition> {
+    let mut parts = Vec::new();
+    for handle in handles_for::<BlockIO>() {
+        let Some(bio) = open_shared::<BlockIO>(handle) else {
+            continue;
+        };
+        let media = bio.media();
+        if !media.is_logical_partition() || !media.is_media_present() {
+            continue;
+        }
+        let mb = (media.last_block() + 1) * media.block_size() as u64 / (1024 * 1024);
+        let label = open_shared::<PartitionInfo>(handle)
+            .and_then(|info| {
+                let entry = info.gpt_partition_entry()?;
+                let name = entry.partition_name;
+                let units: Vec<u16> = name
+                    .iter()
+                    .map(|c| u16::from(*c))
+                    .take_while(|&c| c != 0)
+                    .collect();
+                Some(String::from_utf16_lossy(&units))
+            })
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| format!("Partition {}", parts.len() + 1));
+        parts.push(Partition {
+            handle,
+            name: format!("{} ({} MiB)", label, mb),
+        });
+    }
+    parts
+}
+
+impl Scan {
+    // Returns the percentage done after this step.
+    fn step(&mut self) -> i32 {
+        let Some(bio) = open_shared::<BlockIO>(self.handle) else {
+            self.errors += 1;
+            self.next_lba = self.last_lba + 1;
+            return 100;
+        };
+        let media = bio.media();
+        let block = media.block_size() as usize;
+        let blocks = ((SCAN_CHUNK / block) as u64).min(self.last_lba + 1 - self.next_lba);
+        let mut buf = alloc::vec![0u8; blocks as usize * block];
+        if bio
+            .read_blocks(media.media_id(), self.next_lba, &mut buf)
+            .is_err()
+        {
+            self.errors += 1;
+        }
+        self.next_lba += blocks;
+        (self.next_lba * 100 / (self.last_lba + 1)) as i32
+    }
+
+    fn summary(&self) -> String {
+        let scope = if self.next_lba <= self.last_lba {
+            format!(
+                "{}: stopped at {}%",
+                self.name,
+                self.next_lba * 100 / (self.last_lba + 1)
+            )
+        } else {
+            format!("{}: read fully", self.name)
+        };
+        match self.errors {
+            0 => format!("{}, no read errors", scope),
+            n => format!("{}, {} failed reads", scope, n),
+        }
+    }
+}
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "probe", probe);
+    reg.register(CLASS, "summary", summary);
+    reg.register(CLASS, "warning", warning);
+    reg.register(CLASS, "partitions", partitions);
+    reg.register(CLASS, "partitionName", partition_name);
+    reg.register(CLASS, "scanStart", scan_start);
+    reg.register(CLASS, "scanStep", scan_step);
+    reg.register(CLASS, "scanErrors", scan_errors);
+    reg.register(CLASS, "scanFinish", scan_finish);
+}
+
+fn probe(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let reports = probe_all();
+    for r in &reports {
+        info!("Disk health: {}", r.summary());
+    }
+    ctx.disks.reports = reports;
+    Ok(Some(JvmValue::Int(ctx.disks.reports.len() as i32)))
+}
+
+fn summary(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(match ctx.disks.reports.get(args.int(0)? as usize) {
+        Some(r) => JvmValue::StringRef(r.summary()),
+        None => JvmValue::Null,
+    }))
+}
+
+fn warning(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let warn = ctx
+        .disks
+        .reports
+        .get(args.int(0)? as usize)
+        .is_some_and(|r| r.warning());
+    Ok(Some(JvmValue::Int(warn as i32)))
+}
+
+fn partitions(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    ctx.disks.partitions = list_partitions();
+    Ok(Some(JvmValue::Int(ctx.disks.partitions.len() as i32)))
+}
+
+fn partition_name(
+    ctx: &mut NativeContext,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(
+        match ctx.disks.partitions.get(args.int(0)? as usize) {
+            Some(p) => JvmValue::StringRef(p.name.clone()),
+            None => JvmValue::Null,
+        },
+    ))
+}
+
+fn scan_start(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let part = ctx
+        .disks
+        .partitions
+        .get(args.int(0)? as usize)
+        .ok_or_else(|| JvmError::IoError(String::from("no such partition")))?;
+    let bio = open_shared::<BlockIO>(part.handle)
+        .ok_or_else(|| JvmError::IoError(String::from("partition has no BlockIO")))?;
+    let media = bio.media();
+    let mb = (media.last_block() + 1) * media.block_size() as u64 / (1024 * 1024);
+    ctx.disks.scan = Some(Scan {
+        handle: part.handle,
+        name: part.name.clone(),
+        next_lba: 0,
+        last_lba: media.last_block(),
+        errors: 0,
+    });
+    Ok(Some(JvmValue::Int(mb.min(i32::MAX as u64) as i32)))
+}
+
+fn scan_step(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    ctx.check_cancelled()?;
+    let done = ctx.disks.scan.as_mut().map_or(100, |s| s.step());
+    Ok(Some(JvmValue::Int(done)))
+}
+
+fn scan_errors(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let n = ctx.disks.scan.as_ref().map_or(0, |s| s.errors);
+    Ok(Some(JvmValue::Int(n.min(i32::MAX as u64) as i32)))
+}
+
+fn scan_finish(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(match ctx.disks.scan.take() {
+        Some(scan) => {
+            let summary = scan.summary();
+            info!("Surface scan: {}", summary);
+            JvmValue::StringRef(summary)
+        }
+        None => JvmValue::Null,
+    }))
+}
@@ -0,0 +1,49 @@
+use log::info;
+use shared::fault::{
+    FaultRecord,
+    Subsystem,
+};
+use shared::types::JvmError;
+use uefi::runtime::{
+    self,
+    VariableAttributes,
+};
+use uefi::{
+    CStr16,
+    cstr16,
+};
+
+use crate::config::Config;
+use crate::stats::VENDOR;
+
+// The last fatal error as a FaultRecord, for an OS-side agent to collect
+// (efivarfs: DukeFault-d0e3c7a2-5b1f-4c9e-8a6d-2f4b7e19c3a5). A newer fault
+// overwrites it; otherwise it stays until whoever reads it deletes it.
+const VAR_NAME: &CStr16 = cstr16!("DukeFault");
+
+pub fn record(subsystem: Subsystem, err: &JvmError, boot: u32) {
+    let record = FaultRecord::new(subsystem, err, boot);
+    let attrs = VariableAttributes::NON_VOLATILE
+        | VariableAttributes::BOOTSERVICE_ACCESS
+        | VariableAttributes::RUNTIME_ACCESS;
+    match runtime::set_variable(VAR_NAME, &VENDOR, attrs, &record.to_bytes()) {
+        Ok(()) => info!("Fault recorded: {}", record),
+        Err(e) => crate::warn!("Fault not recorded: {:?}", e.status()),
+    }
+}
+
+// Logs the fault a previous run left behind. Machines with nothing on the OS
+// side to collect it can set `fault_clear = 1` to have Duke delete it once
+// it's been logged.
+pub fn check_previous(config: &Config) {
+    let Ok((data, _)) = runtime::get_variable_boxed(VAR_NAME, &VENDOR) else {
+        return;
+    };
+    match FaultRecord::parse(&data) {
+        Some(record) => crate::warn!("Previous run failed: {}", record),
+        None => crate::warn!("Unreadable fault record ({} bytes)", data.len()),
+    }
+    if config.get("fault_clear") == Some("1") {
+        let _ = runtime::delete_variable(VAR_NAME, &VENDOR);
+    }
+}
@@ -0,0 +1,443 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{
+    format,
+    vec,
+};
+
+use jvm::{
+    Heap,
+    NativeArgs,
+};
+use shared::preview::Timestamp;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::boot::ScopedProtocol;
+use uefi::data_types::Align;
+use uefi::fs::FileSystem;
+use uefi::proto::media::file::{
+    File,
+    FileAttribute,
+    FileInfo,
+    FileMode,
+    RegularFile,
+};
+use uefi::proto::media::fs::SimpleFileSystem;
+use uefi::{
+    CStr16,
+    CString16,
+    boot,
+};
+
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+
+const CLASS: &str = "efi/FileSystem";
+
+pub struct FsCache {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl FsCache {
+    pub fn new() -> Self {
+        Self {
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn read(&mut self, path: &str) -> Result<&[u8], JvmError> {
+        if !self.files.contains_key(path) {
+            let data = read_esp_file(path)?;
+            self.files.insert(String::from(path), data);
+        }
+        Ok(self.files[path].as_slice())
+    }
+
+    // Called for every path a native changes, so images drawn from it are
+    // read again; for a directory, everything cached beneath it goes too.
+    // FAT names ignore case, so writing `\efi\duke\a.bmp` also drops a
+    // cached `\EFI\Duke\A.BMP`.
+    pub fn forget(&mut self, path: &str) {
+        let path = path.trim_matches('\\');
+        self.files.retain(|cached, _| {
+            let cached = cached.trim_start_matches('\\');
+            let under = cached
+                .get(..path.len())
+                .is_some_and(|head| head.eq_ignore_ascii_case(path));
+            !(under && matches!(cached.as_bytes().get(path.len()), None | Some(b'\\')))
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.files.clear();
+    }
+}
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register_heap(CLASS, "readFile", read_file);
+    reg.register_heap(CLASS, "listDirectory", list_directory);
+    reg.register_heap(CLASS, "listEntries", list_entries);
+    reg.register(CLASS, "fileSize", file_size);
+    reg.register_heap(CLASS, "fileInfo", file_info);
+    reg.register_heap(CLASS, "writeFile", write_file);
+    reg.register_heap(CLASS, "appendFile", append_file);
+    reg.register(CLASS, "delete", delete);
+    reg.register(CLASS, "rename", rename);
+    reg.register(CLASS, "mkdir", mkdir);
+    reg.register(CLASS, "exists", exists);
+    reg.register(CLASS, "isDirectory", is_directory);
+}
+
+// Both return null rather than throw when the path can't be read, as a
+// missing file is the usual case for optional config and assets.
+fn read_file(
+    _ctx: &mut NativeContext,
+    heap: &mut Heap,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    match read_esp_file(args.string(0)?) {
+        Ok(data) => Ok(Some(JvmValue::ArrayRef(heap.alloc_bytes(&data)?))),
+        Err(_) => Ok(Some(JvmValue::Null)),
+    }
+}
+
+fn list_directory(
+    _ctx: &mut NativeContext,
+    heap: &mut Heap,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    match list_esp_directory(args.string(0)?) {
+        Ok(names) => Ok(Some(JvmValue::ArrayRef(heap.alloc_strings(&names)?))),
+        Err(_) => Ok(Some(JvmValue::Null)),
+    }
+}
+
+// Unlike reading, a failed write throws: the caller asked for the data to be
+// kept. Missing directories on the way are created.
+fn write_file(
+    ctx: &mut NativeContext,
+    heap: &mut Heap,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let path = args.string(0)?;
+    let data = args.byte_array(heap, 1)?;
+    ctx.fs.forget(path);
+    create_parent(path)?;
+    write_esp_file(path, &data).map_err(|e| in_file(path, e))?;
+    Ok(None)
+}
+
+fn append_file(
+    ctx: &mut NativeContext,
+    heap: &mut Heap,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let path = args.string(0)?;
+    let data = args.byte_array(heap, 1)?;
+    ctx.fs.forget(path);
+    create_parent(path)?;
+    append_esp_file(path, &data).map_err(|e| in_file(path, e))?;
+    Ok(None)
+}
+
+// A file, or a directory once it is empty.
+fn delete(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let path = args.string(0)?;
+    ctx.fs.forget(path);
+    let mut fs = esp_file_system()?;
+    let wide = wide_path(path).map_err(|e| in_file(path, e))?;
+    let info = fs
+        .metadata(&*wide)
+        .map_err(|e| in_file(path, JvmError::IoError(format!("metadata: {:?}", e))))?;
+    let removed = if info.is_directory() {
+        fs.remove_dir(&*wide)
+    } else {
+        fs.remove_file(&*wide)
+    };
+    removed.map_err(|e| in_file(path, JvmError::IoError(format!("delete: {:?}", e))))?;
+    Ok(None)
+}
+
+// Renames a file or directory, replacing a file already at `to`. Within
+// one directory this is the file protocol's own rename, a SetInfo with the
+// new name. uefi::fs can only move by copying and deleting, so a file going
+// to another directory is copied; a directory is refused there, as it is
+// on the host.
+fn rename(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let (from, to) = (args.string(0)?, args.string(1)?);
+    ctx.fs.forget(from);
+    ctx.fs.forget(to);
+    let failed = |e: JvmError| {
+        in_file(
+            from,
+            match e {
+                JvmError::IoError(msg) => JvmError::IoError(format!("rename to {}: {}", to, msg)),
+                e => e,
+            },
+        )
+    };
+    let (wide_from, wide_to) = (wide_path(from)?, wide_path(to)?);
+    // The file system is opened exclusively, so each step opens its own.
+    let (directory, target) = {
+        let mut fs = esp_file_system()?;
+        let directory = fs
+            .metadata(&*wide_from)
+            .map_err(|e| failed(JvmError::IoError(format!("metadata: {:?}", e))))?
+            .is_directory();
+        (directory, fs.metadata(&*wide_to).ok())
+    };
+    if target.as_ref().is_some_and(|info| info.is_directory()) {
+        return Err(failed(JvmError::IoError(String::from("is a directory"))));
+    }
+    let (from_dir, from_name) = split_parent(from);
+    let (to_dir, to_name) = split_parent(to);
+    if from_dir.eq_ignore_ascii_case(to_dir) {
+        // A case-only rename finds `from` as the target; it must stay.
+        if target.is_some() && !from_name.eq_ignore_ascii_case(to_name) {
+            esp_file_system()?
+                .remove_file(&*wide_to)
+                .map_err(|e| failed(JvmError::IoError(format!("delete: {:?}", e))))?;
+        }
+        return rename_in_place(&wide_from, to_name)
+            .map(|()| None)
+            .map_err(failed);
+    }
+    if directory {
+        return Err(failed(JvmError::IoError(String::from(
+            "a directory can only be renamed within its parent",
+        ))));
+    }
+    create_parent(to)?;
+    esp_file_system()?
+        .rename(&*wide_from, &*wide_to)
+        .map_err(|e| failed(JvmError::IoError(format!("{:?}", e))))?;
+    Ok(None)
+}
+
+// `(parent, name)` with the separators around them trimmed; the root's
+// parent is "".
+fn split_parent(path: &str) -> (&str, &str) {
+    let path = path.trim_matches('\\');
+    match path.rsplit_once('\\') {
+        Some((parent, name)) => (parent, name),
+        None => ("", path),
+    }
+}
+
+fn rename_in_place(path: &CStr16, name: &str) -> Result<(), JvmError> {
+    let mut file = esp_volume()?
+        .open_volume()
+        .and_then(|mut root| root.open(path, FileMode::ReadWrite, FileAttribute::empty()))
+        .map_err(|e| JvmError::IoError(format!("open: {:?}", e.status())))?;
+    let info = file
+        .get_boxed_info::<FileInfo>()
+        .map_err(|e| JvmError::IoError(format!("get_info: {:?}", e.status())))?;
+    let name = wide_path(name)?;
+    // FileInfo's fixed part, the name and its terminator, and room to align.
+    let mut storage = vec![0u8; 80 + 2 * (name.num_chars() + 1) + FileInfo::alignment()];
+    let storage = FileInfo::align_buf(&mut storage)
+        .ok_or_else(|| JvmError::IoError(String::from("unaligned buffer")))?;
+    let renamed = FileInfo::new(
+        storage,
+        info.file_size(),
+        info.physical_size(),
+        *info.create_time(),
+        *info.last_access_time(),
+        *info.modification_time(),
+        info.attribute(),
+        &name,
+    )
+    .map_err(|e| JvmError::IoError(format!("{:?}", e)))?;
+    file.set_info(&*renamed)
+        .map_err(|e| JvmError::IoError(format!("set_info: {:?}", e.status())))
+}
+
+// With any parents missing; an existing directory is fine.
+fn mkdir(_ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let path = args.string(0)?;
+    create_esp_directory(path).map_err(|e| in_file(path, e))?;
+    Ok(None)
+}
+
+fn exists(_ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let found = esp_file_system()?
+        .try_exists(&*wide_path(args.string(0)?)?)
+        .unwrap_or(false);
+    Ok(Some(JvmValue::Int(found as i32)))
+}
+
+fn is_directory(_ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let directory = esp_file_system()?
+        .metadata(&*wide_path(args.string(0)?)?)
+        .is_ok_and(|info| info.is_directory());
+    Ok(Some(JvmValue::Int(directory as i32)))
+}
+
+// The volume Duke was loaded from. It is opened exclusively, so only one
+// can be held at a time.
+fn esp_volume() -> Result<ScopedProtocol<SimpleFileSystem>, JvmError> {
+    boot::get_image_file_system(boot::image_handle())
+        .map_err(|e| JvmError::IoError(format!("get_image_file_system: {:?}", e)))
+}
+
+fn esp_file_system() -> Result<FileSystem, JvmError> {
+    Ok(FileSystem::new(esp_volume()?))
+}
+
+fn wide_path(path: &str) -> Result<CString16, JvmError> {
+    CString16::try_from(path).map_err(|_| JvmError::IoError(String::from("invalid path encoding")))
+}
+
+fn create_parent(path: &str) -> Result<(), JvmError> {
+    match path.rsplit_once('\\') {
+        Some((parent, _)) if !parent.is_empty() => {
+            create_esp_directory(parent).map_err(|e| in_file(parent, e))
+        }
+        _ => Ok(()),
+    }
+}
+
+// `path: what went wrong`, so the Java side can tell which file it was.
+fn in_file(path: &str, e: JvmError) -> JvmError {
+    match e {
+        JvmError::IoError(msg) => JvmError::IoError(format!("{}: {}", path, msg)),
+        e => e,
+    }
+}
+
+// Bytes in the file, from its directory entry so nothing is read; -1 when
+// it can't be found, like readFile's null.
+fn file_size(_ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let size = match esp_file_size(args.string(0)?) {
+        Ok(size) => size as i64,
+        Err(_) => -1,
+    };
+    Ok(Some(JvmValue::Long(size)))
+}
+
+// EFI_FILE_INFO as {size, modified, attributes}: the time as
+// Timestamp::sortable gives it, 0 when the firmware left it unset, and the
+// EFI_FILE_* attribute bits. Null when the path can't be found.
+fn file_info(
+    _ctx: &mut NativeContext,
+    heap: &mut Heap,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let Ok(info) = esp_file_system()?.metadata(&*wide_path(args.string(0)?)?) else {
+        return Ok(Some(JvmValue::Null));
+    };
+    let t = info.modification_time();
+    let modified = match t.year() {
+        0 => 0,
+        year => Timestamp {
+            year,
+            month: t.month(),
+            day: t.day(),
+            hour: t.hour(),
+            minute: t.minute(),
+        }
+        .sortable(),
+    };
+    let values = [
+        info.file_size() as i64,
+        modified,
+        info.attribute().bits() as i64,
+    ];
+    Ok(Some(JvmValue::ArrayRef(heap.alloc_longs(&values)?)))
+}
+
+fn esp_file_size(path: &str) -> Result<u64, JvmError> {
+    let info = esp_file_system()?
+        .metadata(&*wide_path(path)?)
+        .map_err(|e| JvmError::IoError(format!("metadata: {:?}", e)))?;
+    if info.is_directory() {
+        return Err(JvmError::IoError(format!("{}: is a directory", path)));
+    }
+    Ok(info.file_size())
+}
+
+// listDirectory for a file browser: directories first, each ending in `\`,
+// then files, both by name without case.
+fn list_entries(
+    _ctx: &mut NativeContext,
+    heap: &mut Heap,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let Ok(wide) = wide_path(args.string(0)?) else {
+        return Ok(Some(JvmValue::Null));
+    };
+    let Ok(iter) = esp_file_system()?.read_dir(&*wide) else {
+        return Ok(Some(JvmValue::Null));
+    };
+    let mut names: Vec<(bool, String)> = iter
+        .filter_map(|r| r.ok())
+        .map(|info| (info.is_directory(), format!("{}", info.file_name())))
+        .filter(|(_, n)| n != "." && n != "..")
+        .collect();
+    names.sort_by_cached_key(|(dir, name)| (!dir, name.to_lowercase()));
+    let names: Vec<String> = names
+        .into_iter()
+        .map(|(dir, name)| if dir { format!("{}\\", name) } else { name })
+        .collect();
+    Ok(Some(JvmValue::ArrayRef(heap.alloc_strings(&names)?)))
+}
+
+#[allow(dead_code)]
+pub fn load_file_from_esp(path: &CStr16) -> Result<Vec<u8>, JvmError> {
+    esp_file_system()?
+        .read(path)
+        .map_err(|e| JvmError::IoError(format!("read: {:?}", e)))
+}
+
+pub fn read_esp_file(path: &str) -> Result<Vec<u8>, JvmError> {
+    esp_file_system()?
+        .read(&*wide_path(path)?)
+        .map_err(|e| JvmError::IoError(format!("read: {:?}", e)))
+}
+
+pub fn write_esp_file(path: &str, data: &[u8]) -> Result<(), JvmError> {
+    esp_file_system()?
+        .write(&*wide_path(path)?, data)
+        .map_err(|e| JvmError::IoError(format!("write: {:?}", e)))
+}
+
+// Creates the file if it isn't there yet.
+pub fn append_esp_file(path: &str, data: &[u8]) -> Result<(), JvmError> {
+    let wide = wide_path(path)?;
+    let mut root = esp_volume()?
+        .open_volume()
+        .map_err(|e| JvmError::IoError(format!("open_volume: {:?}", e.status())))?;
+    let mut file = root
+        .open(&wide, FileMode::CreateReadWrite, FileAttribute::empty())
+        .map_err(|e| JvmError::IoError(format!("open: {:?}", e.status())))?
+        .into_regular_file()
+        .ok_or_else(|| JvmError::IoError(String::from("is a directory")))?;
+    file.set_position(RegularFile::END_OF_FILE)
+        .map_err(|e| JvmError::IoError(format!("seek: {:?}", e.status())))?;
+    file.write(data)
+        .map_err(|e| JvmError::IoError(format!("write: {:?}", e.status())))?;
+    file.flush()
+        .map_err(|e| JvmError::IoError(format!("flush: {:?}", e.status())))
+}
+
+pub fn create_esp_directory(path: &str) -> Result<(), JvmError> {
+    esp_file_system()?
+        .create_dir_all(&*wide_path(path)?)
+        .map_err(|e| JvmError::IoError(format!("create_dir: {:?}", e)))
+}
+
+pub fn list_esp_directory(path: &str) -> Result<Vec<String>, JvmError> {
+    match esp_file_system()?.read_dir(&*wide_path(path)?) {
+        Ok(iter) => Ok(iter
+            .filter_map(|r| r.ok())
+            .map(|info| format!("{}", info.file_name()))
+            .filter(|n| n != "." && n != "..")
+            .collect()),
+        Err(e) => Err(JvmError::IoError(format!("read_dir: {:?}", e))),
+    }
+}
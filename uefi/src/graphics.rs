@@ -0,0 +1,290 @@
+use alloc::format;
+use alloc::string::String;
+
+use jvm::NativeArgs;
+use shared::font;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::boot::SearchType;
+use uefi::proto::console::gop::{
+    BltOp,
+    BltPixel,
+    BltRegion,
+    GraphicsOutput,
+};
+use uefi::{
+    Handle,
+    boot,
+};
+
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+use crate::{
+    acpi,
+    bmp,
+    capture,
+};
+
+const CLASS: &str = "efi/Graphics";
+
+pub struct GraphicsState {
+    pub gop_handle: Option<Handle>,
+    pub screen_w: usize,
+    pub screen_h: usize,
+}
+
+impl GraphicsState {
+    pub fn new() -> Self {
+        Self {
+            gop_handle: None,
+            screen_w: 0,
+            screen_h: 0,
+        }
+    }
+}
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "initGraphics", init_graphics);
+    reg.register(CLASS, "screenWidth", screen_width);
+    reg.register(CLASS, "screenHeight", screen_height);
+    reg.register(CLASS, "fontWidth", font_width);
+    reg.register(CLASS, "fontHeight", font_height);
+    reg.register(CLASS, "clearScreen", clear_screen);
+    reg.register(CLASS, "fillRect", fill_rect);
+    reg.register(CLASS, "drawText", draw_text);
+    reg.register(CLASS, "drawImage", draw_image);
+    reg.register(CLASS, "imageWidth", image_width);
+    reg.register(CLASS, "imageHeight", image_height);
+    reg.register(CLASS, "drawBgrt", draw_bgrt);
+    reg.register(CLASS, "endFrame", end_frame);
+}
+
+fn init_graphics(
+    ctx: &mut NativeContext,
+    _args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let handles = boot::locate_handle_buffer(SearchType::from_proto::<GraphicsOutput>())
+        .map_err(|e| JvmError::IoError(format!("GOP locate: {:?}", e)));
+
+    match handles {
+        Ok(buf) => {
+            let h = buf[0];
+            match boot::open_protocol_exclusive::<GraphicsOutput>(h) {
+                Ok(gop) => {
+                    let (w, h_res) = gop.current_mode_info().resolution();
+                    ctx.gfx.screen_w = w;
+                    ctx.gfx.screen_h = h_res;
+                    ctx.gfx.gop_handle = Some(h);
+                    drop(gop);
+                    Ok(Some(JvmValue::Int(1)))
+                }
+                Err(_) => Ok(Some(JvmValue::Int(0))),
+            }
+        }
+        Err(_) => Ok(Some(JvmValue::Int(0))),
+    }
+}
+
+fn screen_width(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(JvmValue::Int(ctx.gfx.screen_w as i32)))
+}
+
+fn screen_height(
+    ctx: &mut NativeContext,
+    _args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(JvmValue::Int(ctx.gfx.screen_h as i32)))
+}
+
+fn font_width(_ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(JvmValue::Int(font::GLYPH_W as i32)))
+}
+
+fn font_height(_ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(JvmValue::Int(font::GLYPH_H as i32)))
+}
+
+fn clear_screen(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let color = args.int(0)?;
+    if let Some(h) = ctx.gfx.gop_handle
+        && let Ok(mut gop) = boot::open_protocol_exclusive::<GraphicsOutput>(h)
+    {
+        let (r, g, b) = unpack_rgb(color);
+        let _ = gop.blt(BltOp::VideoFill {
+            color: BltPixel::new(r, g, b),
+            dest: (0, 0),
+            dims: (ctx.gfx.screen_w, ctx.gfx.screen_h),
+        });
+    }
+    Ok(None)
+}
+
+fn fill_rect(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let [x, y, w, h, color] = [
+        args.int(0)?,
+        args.int(1)?,
+        args.int(2)?,
+        args.int(3)?,
+        args.int(4)?,
+    ];
+    if let Some(gh) = ctx.gfx.gop_handle
+        && let Ok(mut gop) = boot::open_protocol_exclusive::<GraphicsOutput>(gh)
+    {
+        let (cr, cg, cb) = unpack_rgb(color);
+        let _ = gop.blt(BltOp::VideoFill {
+            color: BltPixel::new(cr, cg, cb),
+            dest: (x as usize, y as usize),
+            dims: (w as usize, h as usize),
+        });
+    }
+    Ok(None)
+}
+
+fn draw_text(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let text = args.string(0)?;
+    let [x, y, fg, scale] = [args.int(1)?, args.int(2)?, args.int(3)?, args.int(4)?];
+    let (fr, fga, fb) = unpack_rgb(fg);
+    draw_text_gop(
+        ctx.gfx.gop_handle,
+        text,
+        x as usize,
+        y as usize,
+        BltPixel::new(fr, fga, fb),
+        scale as usize,
+    )?;
+    Ok(None)
+}
+
+fn draw_image(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let (path, x, y) = (args.string(0)?, args.int(1)?, args.int(2)?);
+    if let Ok(data) = ctx.fs.read(path)
+        && let Ok(bitmap) = bmp::parse(data)
+        && let Some(h) = ctx.gfx.gop_handle
+        && let Ok(mut gop) = boot::open_protocol_exclusive::<GraphicsOutput>(h)
+    {
+        let _ = gop.blt(BltOp::BufferToVideo {
+            buffer: &bitmap.pixels,
+            src: BltRegion::Full,
+            dest: (x as usize, y as usize),
+            dims: (bitmap.width, bitmap.height),
+        });
+    }
+    Ok(None)
+}
+
+// Redraws the firmware boot logo where the firmware put it. Returns 0 when
+// there is no usable BGRT or the logo would not fit the current mode.
+fn draw_bgrt(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    if let Some(logo) = acpi::bgrt()
+        && let Ok(bitmap) = bmp::parse(logo.image)
+        && logo.x + bitmap.width <= ctx.gfx.screen_w
+        && logo.y + bitmap.height <= ctx.gfx.screen_h
+        && let Some(h) = ctx.gfx.gop_handle
+        && let Ok(mut gop) = boot::open_protocol_exclusive::<GraphicsOutput>(h)
+    {
+        let drawn = gop
+            .blt(BltOp::BufferToVideo {
+                buffer: &bitmap.pixels,
+                src: BltRegion::Full,
+                dest: (logo.x, logo.y),
+                dims: (bitmap.width, bitmap.height),
+            })
+            .is_ok();
+        return Ok(Some(JvmValue::Int(drawn as i32)));
+    }
+    Ok(Some(JvmValue::Int(0)))
+}
+
+fn end_frame(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    capture::end_frame(ctx);
+    Ok(None)
+}
+
+fn image_width(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let w = image_dims(ctx, args.string(0)?)
+        .map(|(w, _)| w)
+        .unwrap_or(0);
+    Ok(Some(JvmValue::Int(w as i32)))
+}
+
+fn image_height(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let h = image_dims(ctx, args.string(0)?)
+        .map(|(_, h)| h)
+        .unwrap_or(0);
+    Ok(Some(JvmValue::Int(h as i32)))
+}
+
+fn image_dims(ctx: &mut NativeContext, path: &str) -> Option<(usize, usize)> {
+    let bm = bmp::parse(ctx.fs.read(path).ok()?).ok()?;
+    Some((bm.width, bm.height))
+}
+
+pub fn unpack_rgb(color: i32) -> (u8, u8, u8) {
+    let r = ((color >> 16) & 0xFF) as u8;
+    let g = ((color >> 8) & 0xFF) as u8;
+    let b = (color & 0xFF) as u8;
+    (r, g, b)
+}
+
+fn draw_text_gop(
+    gop_handle: Option<Handle>,
+    text: &str,
+    x: usize,
+    y: usize,
+    fg: BltPixel,
+    scale: usize,
+) -> Result<(), JvmError> {
+    let h =
+        gop_handle.ok_or_else(|| JvmError::IoError(String::from("Graphics not initialized")))?;
+    let mut gop = boot::open_protocol_exclusive::<GraphicsOutput>(h)
+        .map_err(|e| JvmError::IoError(format!("GOP: {:?}", e)))?;
+
+    let char_w = font::GLYPH_W * scale;
+    let char_h = font::GLYPH_H * scale;
+    let total_w = text.len() * char_w;
+    let total_h = char_h;
+
+    if total_w == 0 || total_h == 0 {
+        return Ok(());
+    }
+
+    let mut buf = alloc::vec![BltPixel::new(0, 0, 0); total_w * total_h];
+    let _ = gop.blt(BltOp::VideoToBltBuffer {
+        buffer: &mut buf,
+        src: (x, y),
+        dest: BltRegion::Full,
+        dims: (total_w, total_h),
+    });
+
+    for (ci, ch) in text.bytes().enumerate() {
+        let gly = font::glyph(ch);
+        for (row, bits) in gly.iter().enumerate().take(font::GLYPH_H) {
+            for col in 0..font::GLYPH_W {
+                if bits & (0x8000 >> col) != 0 {
+                    for sy in 0..scale {
+                        for sx in 0..scale {
+                            let px = ci * char_w + col * scale + sx;
+                            let py = row * scale + sy;
+                            if px < total_w && py < total_h {
+                                buf[py * total_w + px] = fg;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let _ = gop.blt(BltOp::BufferToVideo {
+        buffer: &buf,
+        src: BltRegion::Full,
+        dest: (x, y),
+        dims: (total_w, total_h),
+    });
+
+    Ok(())
+}
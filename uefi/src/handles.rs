@@ -0,0 +1,110 @@
+use alloc::format;
+use alloc::string::{
+    String,
+    ToString,
+};
+use alloc::vec::Vec;
+
+use jvm::NativeArgs;
+use log::info;
+use shared::protocols;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::Handle;
+use uefi::boot::{
+    self,
+    SearchType,
+};
+use uefi::proto::device_path::DevicePath;
+use uefi::proto::device_path::text::{
+    AllowShortcuts,
+    DisplayOnly,
+};
+
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+use crate::proto::open_shared;
+
+const CLASS: &str = "efi/Debug";
+
+struct HandleInfo {
+    name: String,
+    protocols: String,
+}
+
+// Every handle in the system and the protocols on it, as of the last
+// Debug.handles(). Rendered when taken, since drivers can uninstall
+// protocols (and free the GUIDs) while the browser is open.
+pub struct HandleList {
+    handles: Vec<HandleInfo>,
+}
+
+impl HandleList {
+    pub fn new() -> Self {
+        Self {
+            handles: Vec::new(),
+        }
+    }
+}
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "handles", handles);
+    reg.register(CLASS, "handleName", handle_name);
+    reg.register(CLASS, "handleProtocols", handle_protocols);
+}
+
+fn describe(index: usize, handle: Handle) -> HandleInfo {
+    let guids: Vec<String> = boot::protocols_per_handle(handle)
+        .map(|p| p.iter().map(|g| g.to_string()).collect())
+        .unwrap_or_default();
+    // The device path says where a handle is; handles without one (drivers,
+    // service bindings) go by their best-known protocol instead.
+    let name = open_shared::<DevicePath>(handle)
+        .and_then(|dp| dp.to_string(DisplayOnly(true), AllowShortcuts(true)).ok())
+        .map(|s| s.to_string())
+        .filter(|s| !s.is_empty())
+        .or_else(|| {
+            guids
+                .iter()
+                .find_map(|g| protocols::name(g))
+                .map(String::from)
+        })
+        .unwrap_or_else(|| format!("Handle {}", index));
+    HandleInfo {
+        name,
+        protocols: protocols::describe(guids.iter().map(String::as_str)),
+    }
+}
+
+fn handles(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let all = boot::locate_handle_buffer(SearchType::AllHandles)
+        .map_err(|e| JvmError::IoError(format!("LocateHandleBuffer: {:?}", e)))?;
+    ctx.handles.handles = all
+        .iter()
+        .enumerate()
+        .map(|(i, &h)| describe(i, h))
+        .collect();
+    info!("Handles: {}", ctx.handles.handles.len());
+    Ok(Some(JvmValue::Int(ctx.handles.handles.len() as i32)))
+}
+
+fn handle_name(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(match ctx.handles.handles.get(args.int(0)? as usize) {
+        Some(h) => JvmValue::StringRef(h.name.clone()),
+        None => JvmValue::Null,
+    }))
+}
+
+fn handle_protocols(
+    ctx: &mut NativeContext,
+    args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(match ctx.handles.handles.get(args.int(0)? as usize) {
+        Some(h) => JvmValue::StringRef(h.protocols.clone()),
+        None => JvmValue::Null,
+    }))
+}
@@ -0,0 +1,136 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+
+use shared::types::JvmError;
+use uefi::fs::FileSystem;
+use uefi::proto::device_path::{
+    DevicePath,
+    FfiDevicePath,
+};
+use uefi::proto::media::fs::SimpleFileSystem;
+use uefi::proto::media::load_file::LoadFile2;
+use uefi::{
+    CString16,
+    Guid,
+    Handle,
+    Identify,
+    Status,
+    boot,
+    guid,
+};
+
+use crate::bootnat;
+use crate::proto::open_shared;
+
+// The vendor media node a kernel's EFI stub (Linux 5.8 and later) looks up
+// to find a LoadFile2 that hands it the initrd.
+const INITRD_MEDIA_GUID: Guid = guid!("5568e427-68fc-4f3d-ac74-ca555231cc68");
+
+// LoadFile2 with the initrd it serves behind it, so the handler finds the
+// data through `this`.
+#[repr(C)]
+struct InitrdLoader {
+    load_file: unsafe extern "efiapi" fn(
+        this: *mut InitrdLoader,
+        file_path: *const FfiDevicePath,
+        boot_policy: u8,
+        buffer_size: *mut usize,
+        buffer: *mut c_void,
+    ) -> Status,
+    data: *const u8,
+    len: usize,
+}
+
+// The stub asks for the size with no buffer first, then for the data.
+unsafe extern "efiapi" fn load_initrd(
+    this: *mut InitrdLoader,
+    _file_path: *const FfiDevicePath,
+    boot_policy: u8,
+    buffer_size: *mut usize,
+    buffer: *mut c_void,
+) -> Status {
+    if this.is_null() || buffer_size.is_null() {
+        return Status::INVALID_PARAMETER;
+    }
+    if boot_policy != 0 {
+        return Status::UNSUPPORTED;
+    }
+    unsafe {
+        let loader = &*this;
+        if buffer.is_null() || *buffer_size < loader.len {
+            *buffer_size = loader.len;
+            return Status::BUFFER_TOO_SMALL;
+        }
+        core::ptr::copy_nonoverlapping(loader.data, buffer.cast::<u8>(), loader.len);
+        *buffer_size = loader.len;
+    }
+    Status::SUCCESS
+}
+
+// The vendor media node with the initrd GUID, then the end node.
+fn initrd_device_path() -> [u8; 24] {
+    let mut path = [0u8; 24];
+    path[..4].copy_from_slice(&[0x04, 0x03, 20, 0]);
+    path[4..20].copy_from_slice(&INITRD_MEDIA_GUID.to_bytes());
+    path[20..].copy_from_slice(&[0x7f, 0xff, 4, 0]);
+    path
+}
+
+// Every initrd on `device`, one after another, as the stub would load them
+// from `initrd=` options.
+fn read_initrds(device: Handle, initrds: &[String]) -> Result<Vec<u8>, JvmError> {
+    let sfs = open_shared::<SimpleFileSystem>(device)
+        .ok_or_else(|| JvmError::IoError(String::from("initrd: no filesystem")))?;
+    let mut fs = FileSystem::new(sfs);
+    let mut data = Vec::new();
+    for initrd in initrds {
+        let path = CString16::try_from(initrd.as_str())
+            .map_err(|_| JvmError::IoError(format!("{}: invalid path", initrd)))?;
+        let bytes = fs
+            .read(&*path)
+            .map_err(|e| JvmError::IoError(format!("{}: {:?}", initrd, e)))?;
+        data.extend_from_slice(&bytes);
+    }
+    Ok(data)
+}
+
+// Starts a kernel's EFI stub with `initrds` served over LoadFile2 for as long
+// as it runs. The `initrd=` options stay on the command line for kernels too
+// old to look for the LoadFile2; newer ones try it first.
+pub fn boot(
+    device: Handle,
+    path: &str,
+    initrds: &[String],
+    options: Option<&str>,
+) -> Result<(), JvmError> {
+    let data = read_initrds(device, initrds)?;
+    let loader = Box::new(InitrdLoader {
+        load_file: load_initrd,
+        data: data.as_ptr(),
+        len: data.len(),
+    });
+    let device_path = initrd_device_path();
+    let loader_ptr = (&*loader as *const InitrdLoader).cast::<c_void>();
+    let path_ptr = device_path.as_ptr().cast::<c_void>();
+
+    let handle = unsafe { boot::install_protocol_interface(None, &DevicePath::GUID, path_ptr) }
+        .map_err(|e| JvmError::IoError(format!("initrd device path: {:?}", e)))?;
+    let installed =
+        unsafe { boot::install_protocol_interface(Some(handle), &LoadFile2::GUID, loader_ptr) };
+    let result = match &installed {
+        Ok(_) => bootnat::do_chainload(device, path, options),
+        Err(e) => Err(JvmError::IoError(format!("initrd LoadFile2: {:?}", e))),
+    };
+
+    // Reached only when the kernel failed to start or returned.
+    unsafe {
+        if installed.is_ok() {
+            let _ = boot::uninstall_protocol_interface(handle, &LoadFile2::GUID, loader_ptr);
+        }
+        let _ = boot::uninstall_protocol_interface(handle, &DevicePath::GUID, path_ptr);
+    }
+    result
+}
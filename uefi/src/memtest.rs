@@ -0,0 +1,199 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ptr::NonNull;
+
+use jvm::NativeArgs;
+use log::info;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::boot::{
+    self,
+    AllocateType,
+    MemoryType,
+};
+use uefi::mem::memory_map::MemoryMap;
+
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+
+const CLASS: &str = "efi/MemTest";
+
+const CHUNK_PAGES: usize = 1024;
+const CHUNK_BYTES: usize = CHUNK_PAGES * boot::PAGE_SIZE;
+const MIB: usize = 1024 * 1024;
+
+// Left free when testing all memory, so the firmware and the menu itself
+// can still allocate while the test runs.
+const HEADROOM_CHUNKS: usize = 16;
+
+// `None` writes each word's own address, which catches shorted or aliased
+// address lines that fixed patterns miss.
+const PATTERNS: [Option<u64>; 5] = [
+    Some(0),
+    Some(u64::MAX),
+    Some(0xAAAA_AAAA_AAAA_AAAA),
+    Some(0x5555_5555_5555_5555),
+    None,
+];
+
+// How many whole chunks fit in the conventional memory the firmware has
+// free; a chunk has to be contiguous, so each region counts on its own.
+fn free_chunks() -> usize {
+    let Ok(map) = boot::memory_map(MemoryType::LOADER_DATA) else {
+        return 0;
+    };
+    map.entries()
+        .filter(|d| d.ty == MemoryType::CONVENTIONAL)
+        .map(|d| d.page_count as usize / CHUNK_PAGES)
+        .sum()
+}
+
+// Pattern write/read-back over free pages. Work is split into one step per
+// chunk and pattern so Java can draw progress and Esc can stop it.
+pub struct MemTest {
+    chunks: Vec<NonNull<u8>>,
+    next: usize,
+    errors: u64,
+    first_error: Option<u64>,
+}
+
+impl MemTest {
+    pub fn new() -> Self {
+        Self {
+            chunks: Vec::new(),
+            next: 0,
+            errors: 0,
+            first_error: None,
+        }
+    }
+
+    // `max_mb` of 0 takes all the free memory the map shows, minus headroom.
+    fn start(&mut self, max_mb: usize) -> usize {
+        self.release();
+        self.next = 0;
+        self.errors = 0;
+        self.first_error = None;
+
+        let available = free_chunks().saturating_sub(HEADROOM_CHUNKS);
+        let limit = match max_mb {
+            0 => available,
+            mb => (mb * MIB / CHUNK_BYTES).max(1).min(available),
+        };
+        // Reserved before any pages are taken, so pushing a chunk never has
+        // to allocate.
+        self.chunks.reserve_exact(limit);
+        while self.chunks.len() < limit {
+            match boot::allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_DATA, CHUNK_PAGES)
+            {
+                Ok(chunk) => self.chunks.push(chunk),
+                Err(_) => break,
+            }
+        }
+        self.tested_mb()
+    }
+
+    fn tested_mb(&self) -> usize {
+        self.chunks.len() * CHUNK_BYTES / MIB
+    }
+
+    fn total_steps(&self) -> usize {
+        self.chunks.len() * PATTERNS.len()
+    }
+
+    // Returns the percentage done after this step.
+    fn step(&mut self) -> i32 {
+        let total = self.total_steps();
+        if self.next >= total {
+            return 100;
+        }
+        let chunk = self.chunks[self.next % self.chunks.len()];
+        let pattern = PATTERNS[self.next / self.chunks.len()];
+        let words = chunk.as_ptr() as *mut u64;
+        let count = CHUNK_BYTES / 8;
+
+        // The pages belong to us until `release`, so the raw accesses are in
+        // bounds; volatile keeps the compiler from eliding the read-back.
+        for i in 0..count {
+            unsafe {
+                let p = words.add(i);
+                p.write_volatile(pattern.unwrap_or(p as u64));
+            }
+        }
+        for i in 0..count {
+            unsafe {
+                let p = words.add(i);
+                if p.read_volatile() != pattern.unwrap_or(p as u64) {
+                    self.errors += 1;
+                    self.first_error.get_or_insert(p as u64);
+                }
+            }
+        }
+
+        self.next += 1;
+        (self.next * 100 / total) as i32
+    }
+
+    fn summary(&self) -> String {
+        let total = self.total_steps();
+        let scope = if self.next < total {
+            format!(
+                "Stopped after {} of {} passes over {} MiB",
+                self.next,
+                total,
+                self.tested_mb()
+            )
+        } else {
+            format!(
+                "Tested {} MiB with {} patterns",
+                self.tested_mb(),
+                PATTERNS.len()
+            )
+        };
+        match self.first_error {
+            None => format!("{}: no errors", scope),
+            Some(addr) => format!("{}: {} errors, first at {:#x}", scope, self.errors, addr),
+        }
+    }
+
+    pub fn release(&mut self) {
+        for chunk in self.chunks.drain(..) {
+            let _ = unsafe { boot::free_pages(chunk, CHUNK_PAGES) };
+        }
+    }
+}
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "start", start);
+    reg.register(CLASS, "step", step);
+    reg.register(CLASS, "errors", errors);
+    reg.register(CLASS, "finish", finish);
+}
+
+fn start(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let max_mb = args.int(0)?.max(0) as usize;
+    let mb = ctx.memtest.start(max_mb);
+    info!("Memory test started over {} MiB", mb);
+    Ok(Some(JvmValue::Int(mb as i32)))
+}
+
+fn step(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    ctx.check_cancelled()?;
+    Ok(Some(JvmValue::Int(ctx.memtest.step())))
+}
+
+fn errors(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let n = ctx.memtest.errors.min(i32::MAX as u64);
+    Ok(Some(JvmValue::Int(n as i32)))
+}
+
+fn finish(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let summary = ctx.memtest.summary();
+    info!("Memory test: {}", summary);
+    ctx.memtest.release();
+    Ok(Some(JvmValue::StringRef(summary)))
+}
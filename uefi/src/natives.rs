@@ -0,0 +1,373 @@
+use alloc::collections::{
+    BTreeMap,
+    VecDeque,
+};
+
+use jvm::{
+    Heap,
+    Limits,
+    LogEntry,
+    NativeArgs,
+    NativeBridge,
+    NativeLog,
+    UncaughtException,
+    Upcall,
+};
+use log::info;
+use shared::fault::Subsystem;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use shared::zip::DEFAULT_RELEASE;
+
+use crate::bootnat::EntryStore;
+use crate::cancel::CancelToken;
+use crate::capture::Capture;
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::console::InputState;
+use crate::diskhealth::DiskHealth;
+use crate::fsnat::FsCache;
+use crate::graphics::GraphicsState;
+use crate::handles::HandleList;
+use crate::memtest::MemTest;
+use crate::preview::PreviewCache;
+use crate::replay::ReplayLog;
+use crate::shutdown::ShutdownReason;
+use crate::terminal::TermState;
+use crate::theme::ThemeState;
+use crate::timeout::Timeout;
+use crate::timer::Timer;
+use crate::{
+    bootnat,
+    config,
+    console,
+    debug,
+    diskhealth,
+    fault,
+    fsnat,
+    graphics,
+    handles,
+    memtest,
+    power,
+    preview,
+    replay,
+    secureboot,
+    shutdown,
+    sysinfo,
+    terminal,
+    theme,
+    timeout,
+    timer,
+    tpm,
+};
+
+pub type NativeFn = fn(&mut NativeContext, &NativeArgs) -> Result<Option<JvmValue>, JvmError>;
+// For the natives that take or return arrays and objects.
+pub type HeapNativeFn =
+    fn(&mut NativeContext, &mut Heap, &NativeArgs) -> Result<Option<JvmValue>, JvmError>;
+
+#[derive(Clone, Copy)]
+enum Native {
+    Plain(NativeFn),
+    Heap(HeapNativeFn),
+}
+
+pub struct NativeContext {
+    pub gfx: GraphicsState,
+    pub fs: FsCache,
+    pub entries: EntryStore,
+    pub input: InputState,
+    pub cancel: CancelToken,
+    pub config: Config,
+    pub timeout: Timeout,
+    pub capture: Capture,
+    pub memtest: MemTest,
+    pub disks: DiskHealth,
+    pub handles: HandleList,
+    pub previews: PreviewCache,
+    pub terminal: TermState,
+    pub replay: ReplayLog,
+    pub theme: ThemeState,
+    pub timer: Timer,
+    // Java calls natives have asked for; the VM drains it via take_upcall.
+    pub upcalls: VecDeque<Upcall>,
+    // Set by Debug.reload for run_menu to pick up.
+    pub reload: bool,
+}
+
+impl NativeContext {
+    fn new() -> Self {
+        Self {
+            gfx: GraphicsState::new(),
+            fs: FsCache::new(),
+            entries: EntryStore::new(),
+            input: InputState::new(),
+            cancel: CancelToken::new(),
+            config: Config::new(),
+            timeout: Timeout::new(),
+            capture: Capture::new(),
+            memtest: MemTest::new(),
+            disks: DiskHealth::new(),
+            handles: HandleList::new(),
+            previews: PreviewCache::new(),
+            terminal: TermState::new(),
+            replay: ReplayLog::new(),
+            theme: ThemeState::new(),
+            timer: Timer::new(),
+            upcalls: VecDeque::new(),
+            reload: false,
+        }
+    }
+
+    // Long-running natives call this between units of work so the abort key
+    // can stop them; it surfaces as InterruptedException in Java.
+    pub fn check_cancelled(&mut self) -> Result<(), JvmError> {
+        if self.input.poll_abort() {
+            self.cancel.cancel();
+        }
+        self.cancel.take()
+    }
+}
+
+pub struct NativeRegistry {
+    table: BTreeMap<(&'static str, &'static str), Native>,
+}
+
+impl NativeRegistry {
+    fn new() -> Self {
+        Self {
+            table: BTreeMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, class_name: &'static str, method_name: &'static str, f: NativeFn) {
+        self.table
+            .insert((class_name, method_name), Native::Plain(f));
+    }
+
+    pub fn register_heap(
+        &mut self,
+        class_name: &'static str,
+        method_name: &'static str,
+        f: HeapNativeFn,
+    ) {
+        self.table
+            .insert((class_name, method_name), Native::Heap(f));
+    }
+
+    fn lookup(&self, class_name: &str, method_name: &str) -> Option<Native> {
+        self.table.get(&(class_name, method_name)).copied()
+    }
+}
+
+pub struct UefiNatives {
+    registry: NativeRegistry,
+    ctx: NativeContext,
+    clock: Option<Clock>,
+    // The last exception BootMenu let escape, for the crash screen.
+    uncaught: Option<UncaughtException>,
+}
+
+impl UefiNatives {
+    pub fn new() -> Self {
+        let mut registry = NativeRegistry::new();
+        console::register(&mut registry);
+        fsnat::register(&mut registry);
+        graphics::register(&mut registry);
+        terminal::register(&mut registry);
+        theme::register(&mut registry);
+        bootnat::register(&mut registry);
+        preview::register(&mut registry);
+        sysinfo::register(&mut registry);
+        config::register(&mut registry);
+        timeout::register(&mut registry);
+        timer::register(&mut registry);
+        memtest::register(&mut registry);
+        diskhealth::register(&mut registry);
+        debug::register(&mut registry);
+        handles::register(&mut registry);
+        secureboot::register(&mut registry);
+        tpm::register(&mut registry);
+        power::register(&mut registry);
+        Self {
+            registry,
+            ctx: NativeContext::new(),
+            clock: None,
+            uncaught: None,
+        }
+    }
+}
+
+impl UefiNatives {
+    pub fn load_settings(&mut self) {
+        self.ctx.config = Config::load();
+        self.ctx.timeout = Timeout::load(&self.ctx.config);
+        self.ctx.capture = Capture::from_config(&self.ctx.config);
+        self.ctx.replay = ReplayLog::from_config(&self.ctx.config);
+        self.ctx.theme = ThemeState::load(&self.ctx.config);
+        fault::check_previous(&self.ctx.config);
+        if self.profiling() {
+            self.clock = Clock::calibrate();
+        }
+    }
+
+    // `profile = 1` turns on the VM's method profiler; Debug.writeProfile
+    // saves the report and the run's end logs the hottest methods.
+    pub fn profiling(&self) -> bool {
+        self.ctx.config.get("profile") == Some("1")
+    }
+
+    // `assertions = 1` checks `assert` statements, as `java -ea` would.
+    pub fn assertions(&self) -> bool {
+        self.ctx.config.get("assertions") == Some("1")
+    }
+
+    // `release = N` reads multi-release JARs as Java N would.
+    pub fn release(&self) -> u16 {
+        self.ctx
+            .config
+            .get("release")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_RELEASE)
+    }
+
+    // `lenient = 1` loads classes past constants and attributes the parser
+    // can't read, logging what it let through.
+    pub fn lenient(&self) -> bool {
+        self.ctx.config.get("lenient") == Some("1")
+    }
+
+    // `classpath = menu.jar lib.jar` puts those JARs first, in that order.
+    pub fn class_path_order(&self) -> &str {
+        self.ctx.config.get("classpath").unwrap_or("")
+    }
+
+    // `verify_jars = 0` skips the CRC-32 check on JAR entries, for speed.
+    pub fn verify_jars(&self) -> bool {
+        self.ctx.config.get("verify_jars") != Some("0")
+    }
+
+    pub fn deterministic(&self) -> bool {
+        self.ctx.replay.recording()
+    }
+
+    pub fn replay_log(&self) -> Option<NativeLog> {
+        ReplayLog::load(&self.ctx.config)
+    }
+
+    pub fn debugging(&self) -> bool {
+        self.ctx.config.get("debug") == Some("serial")
+    }
+
+    // `budget` caps the menu's backward branches so a looping BootMenu falls
+    // through to the default entry instead of hanging the machine.
+    pub fn limits(&self) -> Limits {
+        let budget = self
+            .ctx
+            .config
+            .get("budget")
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|&n| n > 0);
+        let depth = self
+            .ctx
+            .config
+            .get("max_depth")
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&n| n > 0);
+        // Firmware only promises 128 KiB of stack; keep a margin for the
+        // natives and boot services the deepest frame may still call.
+        Limits {
+            max_backward_branches: budget,
+            max_call_depth: depth.unwrap_or(Limits::default().max_call_depth),
+            max_stack_bytes: Some(96 * 1024),
+            ..Limits::default()
+        }
+    }
+
+    pub fn boot_default(&mut self) -> Result<(), JvmError> {
+        bootnat::boot_default(&mut self.ctx)
+    }
+
+    pub fn record_fault(&self, subsystem: Subsystem, err: &JvmError) {
+        fault::record(subsystem, err, self.ctx.timeout.stats.runs);
+    }
+
+    pub fn set_uncaught(&mut self, exception: UncaughtException) {
+        self.uncaught = Some(exception);
+    }
+
+    pub fn take_uncaught(&mut self) -> Option<UncaughtException> {
+        self.uncaught.take()
+    }
+
+    // Java is on its way out through System.exit; stop the timer so no
+    // callback lands during the exit screen, and get the log out.
+    pub fn on_exit(&mut self, status: i32) {
+        info!("System.exit({})", status);
+        self.ctx.timer.cancel();
+        log::logger().flush();
+    }
+
+    pub fn exit_boots_default(&self) -> bool {
+        self.ctx.config.get("exit_action") == Some("boot")
+    }
+
+    pub fn take_reload(&mut self) -> bool {
+        core::mem::take(&mut self.ctx.reload)
+    }
+
+    pub fn reenter(&mut self) {
+        shutdown::reenter(&mut self.ctx);
+    }
+
+    pub fn shutdown(&mut self, reason: ShutdownReason) {
+        shutdown::teardown(&mut self.ctx, reason);
+    }
+}
+
+impl NativeBridge for UefiNatives {
+    fn call_native(
+        &mut self,
+        heap: &mut Heap,
+        class_name: &str,
+        method_name: &str,
+        descriptor: &str,
+        args: &[JvmValue],
+    ) -> Result<Option<JvmValue>, JvmError> {
+        let native_args = NativeArgs::new(method_name, descriptor, args);
+        match self.registry.lookup(class_name, method_name) {
+            Some(Native::Plain(f)) => f(&mut self.ctx, &native_args),
+            Some(Native::Heap(f)) => f(&mut self.ctx, heap, &native_args),
+            None => {
+                crate::warn!(
+                    "unhandled native: {}::{}{}",
+                    class_name,
+                    method_name,
+                    descriptor,
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    fn now_micros(&mut self) -> Option<u64> {
+        self.clock.as_ref()?.now_micros()
+    }
+
+    fn nondeterministic(&self, class_name: &str, method_name: &str) -> bool {
+        replay::nondeterministic(class_name, method_name)
+    }
+
+    fn logged(&mut self, entry: &LogEntry) {
+        self.ctx.replay.push(entry);
+    }
+
+    fn take_upcall(&mut self) -> Option<Upcall> {
+        if self.ctx.upcalls.is_empty() {
+            self.ctx.timer.poll(&mut self.ctx.upcalls);
+        }
+        self.ctx.upcalls.pop_front()
+    }
+}
@@ -0,0 +1,102 @@
+use alloc::format;
+
+use jvm::NativeArgs;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::runtime::{
+    self,
+    ResetType,
+    VariableAttributes,
+    VariableVendor,
+};
+use uefi::{
+    CStr16,
+    Status,
+    cstr16,
+};
+
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+use crate::shutdown::{
+    self,
+    ShutdownReason,
+};
+
+const CLASS: &str = "efi/Power";
+
+// efi/Power's constants. Zero, what an unset int holds, is none of them.
+const WARM_REBOOT: i32 = 1;
+const COLD_REBOOT: i32 = 2;
+const POWER_OFF: i32 = 3;
+
+// EFI_OS_INDICATIONS_BOOT_TO_FW_UI (UEFI 8.5.4).
+const BOOT_TO_FW_UI: u64 = 0x1;
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "reset", reset);
+    reg.register(CLASS, "rebootToFirmware", reboot_to_firmware_native);
+}
+
+// Never returns for a known kind; anything else is refused before Duke
+// tears anything down.
+fn reset(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let kind = match args.int(0)? {
+        WARM_REBOOT => ResetType::WARM,
+        COLD_REBOOT => ResetType::COLD,
+        POWER_OFF => ResetType::SHUTDOWN,
+        other => {
+            return Err(JvmError::NativeMethodError(format!(
+                "reset: unknown kind {}",
+                other
+            )));
+        }
+    };
+    shutdown::teardown(ctx, ShutdownReason::Exit);
+    runtime::reset(kind, Status::SUCCESS, None)
+}
+
+fn os_indications(name: &CStr16) -> Option<u64> {
+    let (data, _) = runtime::get_variable_boxed(name, &VariableVendor::GLOBAL_VARIABLE).ok()?;
+    Some(u64::from_le_bytes(data.get(..8)?.try_into().ok()?))
+}
+
+// The firmware says it can stop in its setup screens on the next boot.
+pub fn firmware_setup_supported() -> bool {
+    os_indications(cstr16!("OsIndicationsSupported")).is_some_and(|v| v & BOOT_TO_FW_UI != 0)
+}
+
+fn reboot_to_firmware_native(
+    ctx: &mut NativeContext,
+    _args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    reboot_to_firmware(ctx)
+}
+
+// Asks the firmware to open its setup on the next boot and resets; false,
+// with nothing torn down, when it can't.
+pub fn reboot_to_firmware(ctx: &mut NativeContext) -> Result<Option<JvmValue>, JvmError> {
+    if !firmware_setup_supported() {
+        return Ok(Some(JvmValue::Int(0)));
+    }
+    // Other bits the OS set, such as a capsule update, stay set.
+    let current = os_indications(cstr16!("OsIndications")).unwrap_or(0);
+    let attrs = VariableAttributes::NON_VOLATILE
+        | VariableAttributes::BOOTSERVICE_ACCESS
+        | VariableAttributes::RUNTIME_ACCESS;
+    if runtime::set_variable(
+        cstr16!("OsIndications"),
+        &VariableVendor::GLOBAL_VARIABLE,
+        attrs,
+        &(current | BOOT_TO_FW_UI).to_le_bytes(),
+    )
+    .is_err()
+    {
+        return Ok(Some(JvmValue::Int(0)));
+    }
+    shutdown::teardown(ctx, ShutdownReason::Exit);
+    runtime::reset(ResetType::COLD, Status::SUCCESS, None)
+}
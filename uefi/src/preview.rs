@@ -0,0 +1,183 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{
+    format,
+    vec,
+};
+
+use jvm::NativeArgs;
+use shared::pe::{
+    self,
+    ImageInfo,
+};
+use shared::preview::{
+    Crc32,
+    EntryDetails,
+    Timestamp,
+};
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::proto::media::file::{
+    File,
+    FileAttribute,
+    FileInfo,
+    FileMode,
+    RegularFile,
+};
+use uefi::proto::media::fs::SimpleFileSystem;
+use uefi::{
+    CString16,
+    Handle,
+};
+
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+use crate::proto::open_shared;
+
+const CLASS: &str = "efi/BootServices";
+const HASH_CHUNK: usize = 1024 * 1024;
+
+// Details are gathered the first time an entry is highlighted and kept for
+// the session; the files don't change while the menu is up.
+pub struct PreviewCache {
+    details: BTreeMap<String, String>,
+}
+
+impl PreviewCache {
+    pub fn new() -> Self {
+        Self {
+            details: BTreeMap::new(),
+        }
+    }
+}
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "entryDetails", entry_details);
+}
+
+// `preview_hash = 1` adds a CRC32 of the whole file, which means reading
+// it; off by default since a UKI can be tens of megabytes.
+fn entry_details(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let Some(entry) = ctx.entries.get(args.int(0)?) else {
+        return Ok(Some(JvmValue::Null));
+    };
+    let (device, path, options) = (entry.device, entry.path.clone(), entry.options.clone());
+    let submenus = ctx.config.get("grub_submenus") == Some("1");
+    let menu = entry.grub.as_ref().map(|grub| grub.titles(submenus));
+    let boot_option = entry.boot_option.as_ref().map(|option| option.number);
+    // Loader entries can share a kernel, so the command line is part of the
+    // key.
+    let key = match &options {
+        Some(options) => format!("{} {}", path, options),
+        None => path.clone(),
+    };
+    if let Some(text) = ctx.previews.details.get(&key) {
+        return Ok(Some(JvmValue::StringRef(text.clone())));
+    }
+
+    // A firmware option's path is its device path, with no file on the ESP
+    // to read.
+    let hash = ctx.config.get("preview_hash") == Some("1");
+    let mut details = match boot_option {
+        Some(_) => EntryDetails {
+            path: path.clone(),
+            boot_option,
+            ..EntryDetails::default()
+        },
+        None => gather(device, &path, hash, &mut || ctx.check_cancelled())?,
+    };
+    if options.is_some() {
+        details.command_line = options;
+    }
+    details.menu = menu.unwrap_or_default();
+    let text = details.render();
+    ctx.previews.details.insert(key, text.clone());
+    Ok(Some(JvmValue::StringRef(text)))
+}
+
+fn gather(
+    device: Handle,
+    path: &str,
+    hash: bool,
+    check_cancelled: &mut dyn FnMut() -> Result<(), JvmError>,
+) -> Result<EntryDetails, JvmError> {
+    let mut details = EntryDetails {
+        path: String::from(path),
+        ..EntryDetails::default()
+    };
+    let Some(mut file) = open_file(device, path) else {
+        return Ok(details);
+    };
+
+    if let Ok(info) = file.get_boxed_info::<FileInfo>() {
+        details.size = Some(info.file_size());
+        let t = info.modification_time();
+        if t.year() != 0 {
+            details.modified = Some(Timestamp {
+                year: t.year(),
+                month: t.month(),
+                day: t.day(),
+                hour: t.hour(),
+                minute: t.minute(),
+            });
+        }
+    }
+
+    details.image = pe::inspect(&mut |offset, len| read_at(&mut file, offset, len));
+    details.command_line = details.image.as_ref().and_then(|i| i.cmdline.clone());
+
+    if hash {
+        let mut crc = Crc32::new();
+        let mut buf = vec![0u8; HASH_CHUNK];
+        file.set_position(0)
+            .map_err(|e| JvmError::IoError(format!("{}: {:?}", path, e)))?;
+        loop {
+            check_cancelled()?;
+            let n = file
+                .read(&mut buf)
+                .map_err(|e| JvmError::IoError(format!("{}: {:?}", path, e)))?;
+            if n == 0 {
+                break;
+            }
+            crc.update(&buf[..n]);
+        }
+        details.crc32 = Some(crc.finish());
+    }
+    Ok(details)
+}
+
+// Just the PE facts, for naming entries during discovery.
+pub fn inspect(device: Handle, path: &str) -> Option<ImageInfo> {
+    let mut file = open_file(device, path)?;
+    pe::inspect(&mut |offset, len| read_at(&mut file, offset, len))
+}
+
+// Opened non-exclusively so the ESP Duke itself runs from stays usable.
+fn open_file(device: Handle, path: &str) -> Option<RegularFile> {
+    let wide = CString16::try_from(path).ok()?;
+    let mut sfs = open_shared::<SimpleFileSystem>(device)?;
+    let mut root = sfs.open_volume().ok()?;
+    root.open(&wide, FileMode::Read, FileAttribute::empty())
+        .ok()?
+        .into_regular_file()
+}
+
+fn read_at(file: &mut RegularFile, offset: u64, len: usize) -> Option<Vec<u8>> {
+    file.set_position(offset).ok()?;
+    let mut buf = vec![0u8; len];
+    let mut filled = 0;
+    while filled < len {
+        let n = file.read(&mut buf[filled..]).ok()?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    buf.truncate(filled);
+    Some(buf)
+}
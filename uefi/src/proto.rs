@@ -0,0 +1,25 @@
+use uefi::Handle;
+use uefi::boot::{
+    self,
+    OpenProtocolAttributes,
+    OpenProtocolParams,
+    ScopedProtocol,
+};
+use uefi::proto::ProtocolPointer;
+
+// Pass-through, block and filesystem protocols are shared with the
+// firmware's own drivers; an exclusive open would disconnect them (the ESP
+// included), so these are only ever opened with GetProtocol.
+pub fn open_shared<P: ProtocolPointer + ?Sized>(handle: Handle) -> Option<ScopedProtocol<P>> {
+    unsafe {
+        boot::open_protocol::<P>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()
+}
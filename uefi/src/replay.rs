@@ -0,0 +1,107 @@
+use alloc::format;
+use alloc::string::String;
+
+use jvm::{
+    LogEntry,
+    NativeLog,
+};
+use log::info;
+
+use crate::config::Config;
+use crate::fsnat::{
+    read_esp_file,
+    write_esp_file,
+};
+
+const LOG_PATH: &str = "\\EFI\\duke\\replay.log";
+
+// Natives whose results can change between two boots of the same machine.
+// The host runner classes its natives the same way, so a log taken here
+// replays there.
+const NONDETERMINISTIC: &[(&str, &str)] = &[
+    ("efi/Console", "readKey"),
+    ("efi/Console", "pollKey"),
+    ("efi/Timeout", "seconds"),
+    ("efi/Timeout", "defaultEntry"),
+    ("efi/Timeout", "fallbackEntry"),
+    ("efi/BootServices", "entryBootCount"),
+    // The theme's variant follows the RTC.
+    ("efi/Theme", "activeVariant"),
+    ("efi/Theme", "color"),
+    ("efi/Theme", "toggle"),
+];
+
+pub fn nondeterministic(class_name: &str, method_name: &str) -> bool {
+    NONDETERMINISTIC.contains(&(class_name, method_name))
+}
+
+// `deterministic = 1` logs those natives to \EFI\duke\replay.log;
+// `replay = 1` feeds the log back in and writes the replayed run over it.
+// The log is kept as text and saved on teardown, so it survives a
+// chainload that never returns to the VM.
+pub struct ReplayLog {
+    text: Option<String>,
+    calls: usize,
+}
+
+impl ReplayLog {
+    pub fn new() -> Self {
+        Self {
+            text: None,
+            calls: 0,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        let on = config.get("deterministic") == Some("1") || config.get("replay") == Some("1");
+        Self {
+            text: on.then(String::new),
+            calls: 0,
+        }
+    }
+
+    pub fn recording(&self) -> bool {
+        self.text.is_some()
+    }
+
+    // The log to play back, when `replay = 1` and one was saved.
+    pub fn load(config: &Config) -> Option<NativeLog> {
+        if config.get("replay") != Some("1") {
+            return None;
+        }
+        let data = match read_esp_file(LOG_PATH) {
+            Ok(data) => data,
+            Err(e) => {
+                crate::warn!("No replay log at {}: {}", LOG_PATH, e);
+                return None;
+            }
+        };
+        match NativeLog::parse(&String::from_utf8_lossy(&data)) {
+            Ok(log) => {
+                info!("Replaying {} calls from {}", log.entries.len(), LOG_PATH);
+                Some(log)
+            }
+            Err(e) => {
+                crate::warn!("Bad replay log: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn push(&mut self, entry: &LogEntry) {
+        if let Some(text) = &mut self.text {
+            text.push_str(&format!("{}\n", entry));
+            self.calls += 1;
+        }
+    }
+
+    pub fn save(&self) {
+        let Some(text) = &self.text else {
+            return;
+        };
+        match write_esp_file(LOG_PATH, text.as_bytes()) {
+            Ok(()) => info!("Replay log: {} calls written to {}", self.calls, LOG_PATH),
+            Err(e) => crate::warn!("Replay log not saved: {}", e),
+        }
+    }
+}
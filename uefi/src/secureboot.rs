@@ -0,0 +1,255 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::{
+    mem,
+    ptr,
+};
+
+use jvm::NativeArgs;
+use log::info;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::boot::ScopedProtocol;
+use uefi::fs::FileSystem;
+use uefi::proto::device_path::{
+    DevicePath,
+    FfiDevicePath,
+};
+use uefi::proto::media::fs::SimpleFileSystem;
+use uefi::proto::shim::ShimLock;
+use uefi::proto::{
+    ProtocolPointer,
+    unsafe_protocol,
+};
+use uefi::runtime::{
+    self,
+    VariableVendor,
+};
+use uefi::{
+    CStr16,
+    CString16,
+    Handle,
+    Status,
+    boot,
+    cstr16,
+};
+
+use crate::bootnat;
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+use crate::proto::open_shared;
+
+const CLASS: &str = "efi/SecureBoot";
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "enabled", enabled);
+    reg.register(CLASS, "setupMode", setup_mode);
+    reg.register(CLASS, "entryBlocked", entry_blocked);
+    reg.register(CLASS, "shimPresent", shim_present);
+}
+
+// A one-byte global flag; missing on firmware without Secure Boot.
+fn flag(name: &CStr16) -> bool {
+    runtime::get_variable_boxed(name, &VariableVendor::GLOBAL_VARIABLE)
+        .is_ok_and(|(data, _)| data.first() == Some(&1))
+}
+
+fn enabled(_ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(JvmValue::Int(flag(cstr16!("SecureBoot")) as i32)))
+}
+
+// No platform key enrolled, so nothing is enforced yet.
+fn setup_mode(_ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(JvmValue::Int(flag(cstr16!("SetupMode")) as i32)))
+}
+
+// Whether the entry would be refused: it is loaded, as chainloading would,
+// and unloaded again without running; shim gets its say on what the
+// firmware refuses. Only checked with Secure Boot on, and never for a
+// firmware Boot#### option, whose short-form paths only the firmware's boot
+// manager can load. Loading reads the whole image, so the menu should ask
+// once per entry.
+fn entry_blocked(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let Some(entry) = ctx.entries.get(args.int(0)?) else {
+        return Ok(Some(JvmValue::Int(0)));
+    };
+    if entry.boot_option.is_some() || entry.setup || !flag(cstr16!("SecureBoot")) {
+        return Ok(Some(JvmValue::Int(0)));
+    }
+    let mut buf = Vec::new();
+    let path = bootnat::file_device_path(entry.device, &entry.path, &mut buf)?;
+    let blocked = match load_image(path) {
+        Ok(handle) => {
+            let _ = boot::unload_image(handle);
+            false
+        }
+        Err(Status::SECURITY_VIOLATION | Status::ACCESS_DENIED) => {
+            !has_shim() || verify_with_shim(&read_image(entry.device, &entry.path)?).is_err()
+        }
+        Err(status) => return Err(JvmError::IoError(format!("load_image: {:?}", status))),
+    };
+    Ok(Some(JvmValue::Int(blocked as i32)))
+}
+
+fn shim_present(
+    _ctx: &mut NativeContext,
+    _args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(JvmValue::Int(has_shim() as i32)))
+}
+
+// Loads the image `full_path` names, `path` on `device`. When the firmware
+// refuses it and Duke was started through shim, shim checks it against its
+// own keys and MOK instead, which is how locally signed kernels boot.
+pub fn load(device: Handle, path: &str, full_path: &DevicePath) -> Result<Handle, JvmError> {
+    match load_image(full_path) {
+        Ok(handle) => {
+            info!("{}: loaded by the firmware", path);
+            Ok(handle)
+        }
+        Err(Status::SECURITY_VIOLATION | Status::ACCESS_DENIED) if has_shim() => {
+            let image = read_image(device, path)?;
+            verify_with_shim(&image)?;
+            let handle = load_verified(&image, full_path)?;
+            info!("{}: refused by the firmware, verified by shim", path);
+            Ok(handle)
+        }
+        Err(status) => Err(JvmError::IoError(format!("load_image: {:?}", status))),
+    }
+}
+
+// LoadImage through the raw table: on SECURITY_VIOLATION the firmware still
+// loads the image and hands back a handle, which `boot::load_image` drops,
+// leaving the image in memory. Here it is unloaded.
+fn load_image(path: &DevicePath) -> Result<Handle, Status> {
+    let st = uefi::table::system_table_raw().ok_or(Status::NOT_READY)?;
+    let bt = unsafe { st.as_ref().boot_services };
+    let mut image = ptr::null_mut();
+    let status = unsafe {
+        ((*bt).load_image)(
+            false.into(),
+            boot::image_handle().as_ptr(),
+            path.as_ffi_ptr().cast(),
+            ptr::null(),
+            0,
+            &mut image,
+        )
+    };
+    let handle = unsafe { Handle::from_ptr(image) };
+    match (status, handle) {
+        (Status::SUCCESS, Some(handle)) => Ok(handle),
+        (status, handle) => {
+            if let Some(handle) = handle {
+                let _ = boot::unload_image(handle);
+            }
+            Err(status)
+        }
+    }
+}
+
+fn has_shim() -> bool {
+    boot::get_handle_for_protocol::<ShimLock>().is_ok()
+}
+
+fn verify_with_shim(image: &[u8]) -> Result<(), JvmError> {
+    let shim = boot::get_handle_for_protocol::<ShimLock>()
+        .ok()
+        .and_then(open_shared::<ShimLock>)
+        .ok_or_else(|| JvmError::IoError(String::from("no shim")))?;
+    shim.verify(image)
+        .map_err(|e| JvmError::IoError(format!("shim verify: {:?}", e.status())))
+}
+
+pub fn read_image(device: Handle, path: &str) -> Result<Vec<u8>, JvmError> {
+    let sfs = open_shared::<SimpleFileSystem>(device)
+        .ok_or_else(|| JvmError::IoError(String::from("no filesystem")))?;
+    let wide = CString16::try_from(path)
+        .map_err(|_| JvmError::IoError(String::from("invalid path encoding")))?;
+    FileSystem::new(sfs)
+        .read(&*wide)
+        .map_err(|e| JvmError::IoError(format!("{}: {:?}", path, e)))
+}
+
+// The security arch protocols the firmware's LoadImage consults under
+// Secure Boot (PI spec, volume 2, 12.8 and 12.9).
+#[repr(C)]
+#[unsafe_protocol("a46423e3-4617-49f1-b9ff-d1bfa9115839")]
+struct SecurityArch {
+    file_authentication_state: unsafe extern "efiapi" fn(
+        this: *const SecurityArch,
+        authentication_status: u32,
+        file: *const FfiDevicePath,
+    ) -> Status,
+}
+
+#[repr(C)]
+#[unsafe_protocol("94ab2f58-1438-4ef1-9152-18941a3a0e68")]
+struct Security2Arch {
+    file_authentication: unsafe extern "efiapi" fn(
+        this: *const Security2Arch,
+        file: *const FfiDevicePath,
+        buffer: *mut c_void,
+        size: usize,
+        boot_policy: u8,
+    ) -> Status,
+}
+
+unsafe extern "efiapi" fn allow_state(
+    _this: *const SecurityArch,
+    _authentication_status: u32,
+    _file: *const FfiDevicePath,
+) -> Status {
+    Status::SUCCESS
+}
+
+unsafe extern "efiapi" fn allow(
+    _this: *const Security2Arch,
+    _file: *const FfiDevicePath,
+    _buffer: *mut c_void,
+    _size: usize,
+    _boot_policy: u8,
+) -> Status {
+    Status::SUCCESS
+}
+
+fn arch<P: ProtocolPointer + ?Sized>() -> Option<ScopedProtocol<P>> {
+    boot::get_handle_for_protocol::<P>()
+        .ok()
+        .and_then(open_shared::<P>)
+}
+
+// Loads an image shim has already passed, with the firmware's own checks
+// answering yes for the one LoadImage call, as shim does for the loader it
+// starts.
+fn load_verified(image: &[u8], full_path: &DevicePath) -> Result<Handle, JvmError> {
+    let mut security = arch::<SecurityArch>();
+    let mut security2 = arch::<Security2Arch>();
+    let saved = security
+        .as_mut()
+        .map(|p| mem::replace(&mut p.file_authentication_state, allow_state));
+    let saved2 = security2
+        .as_mut()
+        .map(|p| mem::replace(&mut p.file_authentication, allow));
+
+    let loaded = boot::load_image(
+        boot::image_handle(),
+        boot::LoadImageSource::FromBuffer {
+            buffer: image,
+            file_path: Some(full_path),
+        },
+    );
+
+    if let (Some(p), Some(f)) = (security.as_mut(), saved) {
+        p.file_authentication_state = f;
+    }
+    if let (Some(p), Some(f)) = (security2.as_mut(), saved2) {
+        p.file_authentication = f;
+    }
+    loaded.map_err(|e| JvmError::IoError(format!("load_image: {:?}", e)))
+}
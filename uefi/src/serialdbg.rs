@@ -0,0 +1,86 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use jvm::DebugPort;
+use log::info;
+use uefi::boot::{
+    self,
+    ScopedProtocol,
+};
+use uefi::proto::console::serial::{
+    ControlBits,
+    Serial,
+};
+
+// The debugger's end of the first serial port. Reads time out on most
+// firmware, so a blocking read just retries until a line is complete.
+pub struct SerialPort {
+    serial: ScopedProtocol<Serial>,
+    pending: Vec<u8>,
+}
+
+impl SerialPort {
+    pub fn open() -> Option<Self> {
+        let handle = boot::get_handle_for_protocol::<Serial>().ok()?;
+        let serial = boot::open_protocol_exclusive::<Serial>(handle).ok()?;
+        info!("Debugger listening on serial");
+        Some(Self {
+            serial,
+            pending: Vec::new(),
+        })
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        let mut byte = [0u8];
+        self.serial.read(&mut byte).ok()?;
+        Some(byte[0])
+    }
+
+    // Takes a finished line off the buffer, if there is one.
+    fn take_line(&mut self) -> Option<String> {
+        let end = self
+            .pending
+            .iter()
+            .position(|&b| b == b'\n' || b == b'\r')?;
+        let line: Vec<u8> = self.pending.drain(..=end).collect();
+        Some(String::from_utf8_lossy(&line[..end]).into_owned())
+    }
+
+    fn input_waiting(&self) -> bool {
+        self.serial
+            .get_control_bits()
+            .is_ok_and(|bits| !bits.contains(ControlBits::INPUT_BUFFER_EMPTY))
+    }
+}
+
+impl DebugPort for SerialPort {
+    fn read_line(&mut self) -> Option<String> {
+        loop {
+            // A CR LF pair ends one line and leaves an empty one behind.
+            if let Some(line) = self.take_line() {
+                if line.is_empty() {
+                    continue;
+                }
+                return Some(line);
+            }
+            if let Some(b) = self.read_byte() {
+                self.pending.push(b);
+            }
+        }
+    }
+
+    fn poll_line(&mut self) -> Option<String> {
+        while self.input_waiting() {
+            match self.read_byte() {
+                Some(b) => self.pending.push(b),
+                None => break,
+            }
+        }
+        self.take_line().filter(|line| !line.is_empty())
+    }
+
+    fn write_line(&mut self, line: &str) {
+        let _ = self.serial.write(line.as_bytes());
+        let _ = self.serial.write(b"\r\n");
+    }
+}
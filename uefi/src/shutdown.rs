@@ -0,0 +1,49 @@
+use uefi::boot;
+
+use crate::graphics::GraphicsState;
+use crate::natives::NativeContext;
+
+// Timeout the boot manager arms before starting an image (UEFI 2.x, 3.1.2).
+const DEFAULT_WATCHDOG_SECS: usize = 300;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownReason {
+    Exit,
+    Chainload,
+    Crash,
+}
+
+// Releases everything Duke holds so the next owner of the machine (the
+// firmware boot manager or a chainloaded loader) starts from a clean slate.
+pub fn teardown(ctx: &mut NativeContext, reason: ShutdownReason) {
+    ctx.fs.clear();
+    ctx.input.clear();
+    ctx.memtest.release();
+    ctx.timer.cancel();
+    ctx.replay.save();
+    if reason != ShutdownReason::Chainload {
+        ctx.gfx = GraphicsState::new();
+    }
+
+    log::logger().flush();
+
+    uefi::system::with_stdout(|out| {
+        let _ = out.reset(false);
+        let _ = out.enable_cursor(true);
+    });
+
+    let watchdog = match reason {
+        ShutdownReason::Chainload => DEFAULT_WATCHDOG_SECS,
+        ShutdownReason::Exit | ShutdownReason::Crash => 0,
+    };
+    let _ = boot::set_watchdog_timer(watchdog, 0x10000, None);
+}
+
+// Undoes what a chainload teardown left behind that would trip up the menu
+// once it has control again, whether the image failed to load, returned or
+// the menu was restarted: the boot manager's watchdog and the cancelled
+// timer callback's stale upcalls.
+pub fn reenter(ctx: &mut NativeContext) {
+    ctx.upcalls.clear();
+    let _ = boot::set_watchdog_timer(0, 0x10000, None);
+}
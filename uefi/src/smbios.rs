@@ -0,0 +1,144 @@
+use alloc::format;
+use alloc::string::String;
+
+use uefi::table::cfg::ConfigTableEntry;
+
+const TYPE_SYSTEM_INFO: u8 = 1;
+const TYPE_END: u8 = 127;
+
+pub struct MachineIdentity {
+    pub uuid: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+}
+
+impl MachineIdentity {
+    pub fn unknown() -> Self {
+        Self {
+            uuid: None,
+            manufacturer: None,
+            model: None,
+        }
+    }
+}
+
+pub fn read_identity() -> MachineIdentity {
+    let Some(table) = structure_table() else {
+        return MachineIdentity::unknown();
+    };
+    find_system_info(table).unwrap_or_else(MachineIdentity::unknown)
+}
+
+// Firmware hands out physical addresses, which are identity-mapped while
+// boot services are active.
+fn structure_table() -> Option<&'static [u8]> {
+    let (addr, len) = uefi::system::with_config_table(|entries| {
+        let smbios3 = entries
+            .iter()
+            .find(|e| e.guid == ConfigTableEntry::SMBIOS3_GUID);
+        if let Some(entry) = smbios3 {
+            let ep = unsafe { core::slice::from_raw_parts(entry.address as *const u8, 0x18) };
+            if &ep[0..5] == b"_SM3_" {
+                let len = u32::from_le_bytes([ep[0x0C], ep[0x0D], ep[0x0E], ep[0x0F]]);
+                let addr = u64::from_le_bytes(ep[0x10..0x18].try_into().ok()?);
+                return Some((addr, len as usize));
+            }
+        }
+        let smbios = entries
+            .iter()
+            .find(|e| e.guid == ConfigTableEntry::SMBIOS_GUID)?;
+        let ep = unsafe { core::slice::from_raw_parts(smbios.address as *const u8, 0x1F) };
+        if &ep[0..4] != b"_SM_" {
+            return None;
+        }
+        let len = u16::from_le_bytes([ep[0x16], ep[0x17]]);
+        let addr = u32::from_le_bytes([ep[0x18], ep[0x19], ep[0x1A], ep[0x1B]]);
+        Some((addr as u64, len as usize))
+    })?;
+    if addr == 0 || len == 0 {
+        return None;
+    }
+    Some(unsafe { core::slice::from_raw_parts(addr as *const u8, len) })
+}
+
+fn find_system_info(table: &[u8]) -> Option<MachineIdentity> {
+    let mut pos = 0;
+    while pos + 4 <= table.len() {
+        let kind = table[pos];
+        let len = table[pos + 1] as usize;
+        if len < 4 || pos + len > table.len() {
+            return None;
+        }
+        let formatted = &table[pos..pos + len];
+        let strings_start = pos + len;
+        let strings_end = strings_end(table, strings_start)?;
+        let strings = &table[strings_start..strings_end];
+
+        if kind == TYPE_SYSTEM_INFO {
+            let field = |off: usize| formatted.get(off).copied().unwrap_or(0);
+            return Some(MachineIdentity {
+                uuid: formatted.get(0x08..0x18).and_then(format_uuid),
+                manufacturer: smbios_string(strings, field(0x04)),
+                model: smbios_string(strings, field(0x05)),
+            });
+        }
+        if kind == TYPE_END {
+            return None;
+        }
+        pos = strings_end + 2;
+    }
+    None
+}
+
+// The string set ends with a double NUL; returns the index of that pair.
+fn strings_end(table: &[u8], start: usize) -> Option<usize> {
+    let mut i = start;
+    while i + 1 < table.len() {
+        if table[i] == 0 && table[i + 1] == 0 {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+fn smbios_string(strings: &[u8], index: u8) -> Option<String> {
+    if index == 0 {
+        return None;
+    }
+    let raw = strings.split(|&b| b == 0).nth(index as usize - 1)?;
+    let s = String::from_utf8_lossy(raw);
+    let s = s.trim();
+    if s.is_empty() {
+        None
+    } else {
+        Some(String::from(s))
+    }
+}
+
+// SMBIOS 2.6+ stores the first three UUID fields little-endian. All-zero and
+// all-ones mean the vendor never set one.
+fn format_uuid(b: &[u8]) -> Option<String> {
+    if b.iter().all(|&x| x == 0) || b.iter().all(|&x| x == 0xFF) {
+        return None;
+    }
+    Some(format!(
+        "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        b[3],
+        b[2],
+        b[1],
+        b[0],
+        b[5],
+        b[4],
+        b[7],
+        b[6],
+        b[8],
+        b[9],
+        b[10],
+        b[11],
+        b[12],
+        b[13],
+        b[14],
+        b[15]
+    ))
+}
@@ -0,0 +1,154 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+
+use log::info;
+use uefi::runtime::{
+    self,
+    VariableAttributes,
+    VariableVendor,
+};
+use uefi::{
+    cstr16,
+    guid,
+};
+
+const VAR_NAME: &uefi::CStr16 = cstr16!("DukeBootStats");
+// Created by a tool in the booted OS, with any contents, once it has come
+// up: the pending entry booted fine. Duke deletes it when it reads it.
+const ACK_NAME: &uefi::CStr16 = cstr16!("DukeBootOk");
+pub const VENDOR: VariableVendor = VariableVendor(guid!("d0e3c7a2-5b1f-4c9e-8a6d-2f4b7e19c3a5"));
+
+// Persisted in an NV variable as `key=value` lines so it survives reboots
+// and stays readable from the firmware shell.
+pub struct BootStats {
+    // Duke starts, counted whether or not anything got booted.
+    pub runs: u32,
+    pub last_entry: Option<String>,
+    pub streak: u32,
+    // The entry last acknowledged as booting fine.
+    pub good: Option<String>,
+    // The entry last started, until the OS acknowledges it, with how many
+    // times in a row it was and whether it came back to Duke.
+    pub pending: Option<String>,
+    pub tries: u32,
+    pub returned: bool,
+    counts: BTreeMap<String, u32>,
+}
+
+impl BootStats {
+    pub fn new() -> Self {
+        Self {
+            runs: 0,
+            last_entry: None,
+            streak: 0,
+            good: None,
+            pending: None,
+            tries: 0,
+            returned: false,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    pub fn load() -> Self {
+        let mut stats = Self::new();
+        let Ok((data, _)) = runtime::get_variable_boxed(VAR_NAME, &VENDOR) else {
+            return stats;
+        };
+        let text = String::from_utf8_lossy(&data);
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key {
+                "runs" => stats.runs = value.parse().unwrap_or(0),
+                "last" => stats.last_entry = Some(String::from(value)),
+                "streak" => stats.streak = value.parse().unwrap_or(0),
+                "good" => stats.good = Some(String::from(value)),
+                "pending" => stats.pending = Some(String::from(value)),
+                "tries" => stats.tries = value.parse().unwrap_or(0),
+                "returned" => stats.returned = value == "1",
+                _ => {
+                    if let Some(path) = key.strip_prefix("count:") {
+                        stats
+                            .counts
+                            .insert(String::from(path), value.parse().unwrap_or(0));
+                    }
+                }
+            }
+        }
+        if runtime::get_variable_boxed(ACK_NAME, &VENDOR).is_ok() {
+            stats.acknowledge();
+            let _ = runtime::delete_variable(ACK_NAME, &VENDOR);
+        }
+        stats
+    }
+
+    pub fn save(&self) {
+        let mut text = format!("runs={}\n", self.runs);
+        if let Some(last) = &self.last_entry {
+            text.push_str(&format!("last={}\n", last));
+        }
+        text.push_str(&format!("streak={}\n", self.streak));
+        if let Some(good) = &self.good {
+            text.push_str(&format!("good={}\n", good));
+        }
+        if let Some(pending) = &self.pending {
+            text.push_str(&format!("pending={}\n", pending));
+            text.push_str(&format!("tries={}\n", self.tries));
+            text.push_str(&format!("returned={}\n", self.returned as u8));
+        }
+        for (path, count) in &self.counts {
+            text.push_str(&format!("count:{}={}\n", path, count));
+        }
+        let attrs = VariableAttributes::NON_VOLATILE
+            | VariableAttributes::BOOTSERVICE_ACCESS
+            | VariableAttributes::RUNTIME_ACCESS;
+        if let Err(e) = runtime::set_variable(VAR_NAME, &VENDOR, attrs, text.as_bytes()) {
+            info!("Failed to save boot stats: {:?}", e.status());
+        }
+    }
+
+    // A boot extends the streak only when it repeats the previous entry
+    // without the user having touched the menu.
+    pub fn record(&mut self, path: &str, touched: bool) {
+        let repeat = self.last_entry.as_deref() == Some(path);
+        self.streak = match (touched, repeat) {
+            (true, _) => 0,
+            (false, true) => self.streak.saturating_add(1),
+            (false, false) => 1,
+        };
+        self.last_entry = Some(String::from(path));
+        self.tries = if self.pending.as_deref() == Some(path) {
+            self.tries.saturating_add(1)
+        } else {
+            1
+        };
+        self.pending = Some(String::from(path));
+        self.returned = false;
+        let count = self.counts.entry(String::from(path)).or_insert(0);
+        *count = count.saturating_add(1);
+    }
+
+    pub fn count(&self, path: &str) -> u32 {
+        self.counts.get(path).copied().unwrap_or(0)
+    }
+
+    // The pending entry booted fine and becomes the one to fall back to.
+    pub fn acknowledge(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            self.good = Some(pending);
+        }
+        self.tries = 0;
+        self.returned = false;
+    }
+
+    // The entry to boot instead of the pending one, once it came back to
+    // Duke or was started `max_tries` times without being acknowledged.
+    pub fn fallback(&self, max_tries: u32) -> Option<&str> {
+        let pending = self.pending.as_deref()?;
+        let good = self.good.as_deref()?;
+        let failing = self.returned || self.tries >= max_tries;
+        (failing && good != pending).then_some(good)
+    }
+}
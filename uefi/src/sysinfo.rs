@@ -0,0 +1,49 @@
+use alloc::format;
+
+use jvm::NativeArgs;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+
+const CLASS: &str = "efi/SystemInfo";
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "firmwareVendor", firmware_vendor);
+    reg.register(CLASS, "firmwareRevision", firmware_revision);
+    reg.register(CLASS, "uefiRevision", uefi_revision);
+}
+
+fn firmware_vendor(
+    _ctx: &mut NativeContext,
+    _args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let vendor = format!("{}", uefi::system::firmware_vendor());
+    Ok(Some(JvmValue::StringRef(vendor)))
+}
+
+fn firmware_revision(
+    _ctx: &mut NativeContext,
+    _args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(
+        JvmValue::Int(uefi::system::firmware_revision() as i32),
+    ))
+}
+
+fn uefi_revision(
+    _ctx: &mut NativeContext,
+    _args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let rev = uefi::system::uefi_revision();
+    Ok(Some(JvmValue::StringRef(format!(
+        "{}.{}",
+        rev.major(),
+        rev.minor()
+    ))))
+}
@@ -0,0 +1,162 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use jvm::NativeArgs;
+use shared::font;
+use shared::term::{
+    Font,
+    Terminal,
+};
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::boot;
+use uefi::proto::console::gop::{
+    BltOp,
+    BltPixel,
+    BltRegion,
+    GraphicsOutput,
+};
+
+use crate::graphics::unpack_rgb;
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+
+const CLASS: &str = "efi/Terminal";
+const HISTORY: usize = 500;
+
+const FONT: Font = Font {
+    width: font::GLYPH_W,
+    height: font::GLYPH_H,
+    glyph: font::glyph,
+};
+
+struct Window {
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+}
+
+// Console output lands here instead of ConOut while redirected, so text
+// printed under a graphical theme shows up inside one of its windows rather
+// than over the framebuffer.
+pub struct TermState {
+    term: Terminal,
+    window: Option<Window>,
+    redirect: bool,
+    scroll: usize,
+    fg: u32,
+    bg: u32,
+}
+
+impl TermState {
+    pub fn new() -> Self {
+        Self {
+            term: Terminal::new(80, 25, HISTORY),
+            window: None,
+            redirect: false,
+            scroll: 0,
+            fg: 0xC0C0C0,
+            bg: 0x000000,
+        }
+    }
+
+    pub fn redirected(&self) -> bool {
+        self.redirect
+    }
+
+    pub fn write(&mut self, text: &str) {
+        self.term.write(text);
+    }
+}
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "open", open);
+    reg.register(CLASS, "setColors", set_colors);
+    reg.register(CLASS, "redirect", redirect);
+    reg.register(CLASS, "write", write);
+    reg.register(CLASS, "clear", clear);
+    reg.register(CLASS, "scroll", scroll);
+    reg.register(CLASS, "render", render);
+    reg.register(CLASS, "close", close);
+}
+
+// The grid is sized to the pixel rectangle; text already written is kept.
+fn open(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let [x, y, w, h] =
+        [args.int(0)?, args.int(1)?, args.int(2)?, args.int(3)?].map(|v| v.max(0) as usize);
+    let cols = w / font::GLYPH_W;
+    let rows = h / font::GLYPH_H;
+    ctx.terminal.term.resize(cols, rows);
+    ctx.terminal.window = Some(Window { x, y, w, h });
+    ctx.terminal.scroll = 0;
+    Ok(None)
+}
+
+fn set_colors(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    ctx.terminal.fg = args.int(0)? as u32 & 0xFFFFFF;
+    ctx.terminal.bg = args.int(1)? as u32 & 0xFFFFFF;
+    Ok(None)
+}
+
+fn redirect(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    ctx.terminal.redirect = args.boolean(0)?;
+    Ok(None)
+}
+
+fn write(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    ctx.terminal.write(args.string(0)?);
+    Ok(None)
+}
+
+fn clear(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    ctx.terminal.term.clear();
+    ctx.terminal.scroll = 0;
+    Ok(None)
+}
+
+// Moves the view `lines` further back into the history (negative returns
+// toward the live screen) and reports how far back it now is.
+fn scroll(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let t = &mut ctx.terminal;
+    let back = t.scroll as i64 + args.int(0)? as i64;
+    t.scroll = back.clamp(0, t.term.history_len() as i64) as usize;
+    Ok(Some(JvmValue::Int(t.scroll as i32)))
+}
+
+fn render(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let t = &ctx.terminal;
+    if let Some(win) = &t.window
+        && win.w > 0
+        && win.h > 0
+        && let Some(h) = ctx.gfx.gop_handle
+        && let Ok(mut gop) = boot::open_protocol_exclusive::<GraphicsOutput>(h)
+    {
+        let mut pixels = vec![0u32; win.w * win.h];
+        t.term
+            .render(t.scroll, t.fg, t.bg, &FONT, &mut pixels, win.w);
+        let buf: Vec<BltPixel> = pixels
+            .iter()
+            .map(|&c| {
+                let (r, g, b) = unpack_rgb(c as i32);
+                BltPixel::new(r, g, b)
+            })
+            .collect();
+        let _ = gop.blt(BltOp::BufferToVideo {
+            buffer: &buf,
+            src: BltRegion::Full,
+            dest: (win.x, win.y),
+            dims: (win.w, win.h),
+        });
+    }
+    Ok(None)
+}
+
+fn close(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    ctx.terminal.window = None;
+    Ok(None)
+}
@@ -0,0 +1,111 @@
+use alloc::format;
+use alloc::string::String;
+
+use jvm::NativeArgs;
+use log::info;
+use shared::theme::{
+    Selection,
+    Theme,
+};
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::runtime;
+
+use crate::config::Config;
+use crate::fsnat::read_esp_file;
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+
+const CLASS: &str = "efi/Theme";
+const THEME_DIR: &str = "\\EFI\\duke\\themes";
+
+// The theme named by `theme` (\EFI\duke\themes\<name>.theme, else the
+// built-in one) and which of its variants is showing. The RTC is read once
+// at load, so a menu left open across the switch hour keeps its colours.
+pub struct ThemeState {
+    theme: Theme,
+    selection: Selection,
+    hour: Option<u8>,
+}
+
+impl ThemeState {
+    pub fn new() -> Self {
+        Self {
+            theme: Theme::builtin(),
+            selection: Selection::from_settings(|_| None),
+            hour: None,
+        }
+    }
+
+    pub fn load(config: &Config) -> Self {
+        let theme = match config.get("theme") {
+            Some(name) => {
+                let path = format!("{}\\{}.theme", THEME_DIR, name);
+                match read_esp_file(&path) {
+                    Ok(data) => Theme::parse(name, &String::from_utf8_lossy(&data)),
+                    Err(e) => {
+                        crate::warn!("Theme {}: {}", path, e);
+                        Theme::builtin()
+                    }
+                }
+            }
+            None => Theme::builtin(),
+        };
+        let selection = Selection::from_settings(|key| config.get(key));
+        let hour = runtime::get_time().ok().map(|t| t.hour());
+        let state = Self {
+            theme,
+            selection,
+            hour,
+        };
+        info!(
+            "Theme: {} ({})",
+            state.theme.name,
+            state.selection.active(state.hour).name()
+        );
+        state
+    }
+}
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "name", name);
+    reg.register(CLASS, "activeVariant", active_variant);
+    reg.register(CLASS, "color", color);
+    reg.register(CLASS, "toggle", toggle);
+}
+
+fn name(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(JvmValue::StringRef(ctx.theme.theme.name.clone())))
+}
+
+fn active_variant(
+    ctx: &mut NativeContext,
+    _args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    let variant = ctx.theme.selection.active(ctx.theme.hour);
+    Ok(Some(JvmValue::StringRef(String::from(variant.name()))))
+}
+
+// A role the theme doesn't know is a bug in the UI, not the theme.
+fn color(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let role = args.string(0)?;
+    let t = &ctx.theme;
+    let palette = t.theme.palette(t.selection.active(t.hour));
+    match palette.get(role) {
+        Some(c) => Ok(Some(JvmValue::Int(c as i32))),
+        None => Err(JvmError::NativeMethodError(format!(
+            "no theme colour {}",
+            role
+        ))),
+    }
+}
+
+// The hotkey override: pins the other variant for the rest of the session.
+fn toggle(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let variant = ctx.theme.selection.toggle(ctx.theme.hour);
+    Ok(Some(JvmValue::StringRef(String::from(variant.name()))))
+}
@@ -0,0 +1,171 @@
+use jvm::NativeArgs;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+
+use crate::config::Config;
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+use crate::stats::BootStats;
+
+const CLASS: &str = "efi/Timeout";
+
+pub struct TimeoutPolicy {
+    base: u32,
+    floor: u32,
+    streak: u32,
+}
+
+impl TimeoutPolicy {
+    // `timeout` of 0 keeps the menu up until a key is pressed.
+    pub fn from_config(config: &Config) -> Self {
+        let get = |key: &str, default: u32| {
+            config
+                .get(key)
+                .and_then(|v| v.parse::<u32>().ok())
+                .unwrap_or(default)
+        };
+        let base = get("timeout", 0);
+        Self {
+            base,
+            floor: get("timeout_floor", 1).min(base),
+            streak: get("timeout_streak", 3).max(1),
+        }
+    }
+
+    // Halves the countdown for every boot past the streak threshold, never
+    // dropping below the floor. A manual override resets the streak, which
+    // brings the full countdown back.
+    pub fn seconds(&self, streak: u32) -> u32 {
+        if self.base == 0 || streak < self.streak {
+            return self.base;
+        }
+        let shift = (streak - self.streak + 1).min(31);
+        (self.base >> shift).max(self.floor)
+    }
+}
+
+pub struct Timeout {
+    pub stats: BootStats,
+    seconds: u32,
+    touched: bool,
+    // `boot_tries`: starts without an acknowledgement before falling back
+    // to the last good entry; 0 turns fallback off.
+    max_tries: u32,
+}
+
+impl Timeout {
+    pub fn new() -> Self {
+        Self {
+            stats: BootStats::new(),
+            seconds: 0,
+            touched: false,
+            max_tries: 0,
+        }
+    }
+
+    pub fn load(config: &Config) -> Self {
+        let policy = TimeoutPolicy::from_config(config);
+        let mut stats = BootStats::load();
+        stats.runs = stats.runs.wrapping_add(1);
+        stats.save();
+        let seconds = policy.seconds(stats.streak);
+        Self {
+            stats,
+            seconds,
+            touched: false,
+            max_tries: config
+                .get("boot_tries")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+        }
+    }
+
+    pub fn record_boot(&mut self, path: &str) {
+        self.stats.record(path, self.touched);
+        self.stats.save();
+    }
+
+    // The started image gave control back, which counts as a failed boot.
+    pub fn record_return(&mut self) {
+        self.stats.returned = true;
+        self.stats.save();
+    }
+
+    pub fn fallback(&self) -> Option<&str> {
+        match self.max_tries {
+            0 => None,
+            max => self.stats.fallback(max),
+        }
+    }
+}
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "seconds", seconds);
+    reg.register(CLASS, "set", set);
+    reg.register(CLASS, "touch", touch);
+    reg.register(CLASS, "defaultEntry", default_entry);
+    reg.register(CLASS, "markBootSuccess", mark_boot_success);
+    reg.register(CLASS, "fallbackEntry", fallback_entry);
+}
+
+fn seconds(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(JvmValue::Int(ctx.timeout.seconds as i32)))
+}
+
+fn set(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    ctx.timeout.seconds = args.int(0)?.max(0) as u32;
+    Ok(None)
+}
+
+fn touch(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    ctx.timeout.touched = true;
+    Ok(None)
+}
+
+// The entry booted last time, or the first one if it has gone away. One
+// that keeps failing gives way to the last good entry.
+pub fn default_index(ctx: &NativeContext) -> i32 {
+    if let Some(i) = fallback_index(ctx) {
+        return i;
+    }
+    match &ctx.timeout.stats.last_entry {
+        Some(last) => ctx.entries.position(last).unwrap_or(0),
+        None => 0,
+    }
+}
+
+fn default_entry(
+    ctx: &mut NativeContext,
+    _args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(JvmValue::Int(default_index(ctx))))
+}
+
+fn fallback_index(ctx: &NativeContext) -> Option<i32> {
+    ctx.timeout
+        .fallback()
+        .and_then(|good| ctx.entries.position(good))
+}
+
+// What the OS-side DukeBootOk variable does, for a tool started from the
+// menu that has checked the last boot itself.
+fn mark_boot_success(
+    ctx: &mut NativeContext,
+    _args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    ctx.timeout.stats.acknowledge();
+    ctx.timeout.stats.save();
+    Ok(None)
+}
+
+// The entry the default fell back to, or -1 when it didn't.
+fn fallback_entry(
+    ctx: &mut NativeContext,
+    _args: &NativeArgs,
+) -> Result<Option<JvmValue>, JvmError> {
+    Ok(Some(JvmValue::Int(fallback_index(ctx).unwrap_or(-1))))
+}
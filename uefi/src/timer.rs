@@ -0,0 +1,101 @@
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use jvm::{
+    NativeArgs,
+    Upcall,
+};
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::Event;
+use uefi::boot::{
+    self,
+    EventType,
+    TimerTrigger,
+    Tpl,
+};
+
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+
+const CLASS: &str = "efi/Timer";
+
+// A periodic firmware timer that calls a static `void name()` back in Java.
+// The event is only polled when the VM asks for upcalls after a native
+// returns, so the callback runs between native calls, never inside one;
+// ticks missed while Java was busy elsewhere collapse into one.
+pub struct Timer {
+    event: Option<Event>,
+    class_name: String,
+    method_name: String,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Self {
+            event: None,
+            class_name: String::new(),
+            method_name: String::new(),
+        }
+    }
+
+    pub fn poll(&mut self, upcalls: &mut VecDeque<Upcall>) {
+        let Some(event) = &self.event else {
+            return;
+        };
+        let fired = boot::check_event(unsafe { event.unsafe_clone() }).unwrap_or(false);
+        if fired {
+            upcalls.push_back(Upcall {
+                class_name: self.class_name.clone(),
+                method_name: self.method_name.clone(),
+                descriptor: String::from("()V"),
+                args: Vec::new(),
+            });
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        if let Some(event) = self.event.take() {
+            let _ = boot::set_timer(&event, TimerTrigger::Cancel);
+            let _ = boot::close_event(event);
+        }
+    }
+}
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "every", every);
+    reg.register(CLASS, "cancel", cancel);
+}
+
+fn every(ctx: &mut NativeContext, args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let (ms, class_name, method_name) = (args.int(0)?, args.string(1)?, args.string(2)?);
+    ctx.timer.cancel();
+    let event = unsafe { boot::create_event(EventType::TIMER, Tpl::CALLBACK, None, None) }
+        .map_err(|e| JvmError::NativeMethodError(format!("CreateEvent: {:?}", e.status())))?;
+    // The trigger is in 100ns units.
+    let period = ms.max(1) as u64 * 10_000;
+    if let Err(e) = boot::set_timer(&event, TimerTrigger::Periodic(period)) {
+        let _ = boot::close_event(event);
+        return Err(JvmError::NativeMethodError(format!(
+            "SetTimer: {:?}",
+            e.status()
+        )));
+    }
+    ctx.timer = Timer {
+        event: Some(event),
+        class_name: String::from(class_name),
+        method_name: String::from(method_name),
+    };
+    Ok(None)
+}
+
+fn cancel(ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    ctx.timer.cancel();
+    Ok(None)
+}
@@ -0,0 +1,90 @@
+use alloc::format;
+
+use jvm::NativeArgs;
+use log::info;
+use shared::types::{
+    JvmError,
+    JvmValue,
+};
+use uefi::Handle;
+use uefi::boot::{
+    self,
+    ScopedProtocol,
+};
+use uefi::proto::tcg::v2::{
+    HashLogExtendEventFlags,
+    PcrEventInputs,
+    Tcg,
+};
+use uefi::proto::tcg::{
+    EventType,
+    PcrIndex,
+};
+
+use crate::config::Config;
+use crate::natives::{
+    NativeContext,
+    NativeRegistry,
+};
+use crate::proto::open_shared;
+use crate::secureboot;
+
+const CLASS: &str = "efi/Tpm";
+
+// GRUB's PCRs, so existing measured-boot policies carry over: the command
+// line in 8, the files read in 9. The firmware measures the image into
+// PCR 4 on its own as it loads it.
+const PCR_COMMANDS: PcrIndex = PcrIndex(8);
+const PCR_FILES: PcrIndex = PcrIndex(9);
+
+pub fn register(reg: &mut NativeRegistry) {
+    reg.register(CLASS, "present", present);
+}
+
+fn tcg() -> Option<ScopedProtocol<Tcg>> {
+    let handle = boot::get_handle_for_protocol::<Tcg>().ok()?;
+    open_shared::<Tcg>(handle)
+}
+
+fn present(_ctx: &mut NativeContext, _args: &NativeArgs) -> Result<Option<JvmValue>, JvmError> {
+    let present = tcg()
+        .and_then(|mut tcg| tcg.get_capability().ok())
+        .is_some_and(|capability| capability.tpm_present());
+    Ok(Some(JvmValue::Int(present as i32)))
+}
+
+fn extend(tcg: &mut Tcg, pcr: PcrIndex, data: &[u8], description: &str) {
+    let Ok(event) = PcrEventInputs::new_in_box(pcr, EventType::IPL, description.as_bytes()) else {
+        return;
+    };
+    match tcg.hash_log_extend_event(HashLogExtendEventFlags::empty(), data, &event) {
+        Ok(()) => info!("TPM: PCR {}: {}", pcr.0, description),
+        Err(e) => info!("TPM: PCR {}: {}: {:?}", pcr.0, description, e.status()),
+    }
+}
+
+// Extends the PCRs with what is about to run, before it gets control:
+// Duke's settings, the image on `device` (None for a firmware Boot####
+// option, which the firmware loads itself) and its command line. Without a
+// TPM there is nothing to do.
+pub fn measure_boot(config: &Config, device: Option<Handle>, path: &str, options: Option<&str>) {
+    let Some(mut tcg) = tcg() else {
+        return;
+    };
+    extend(
+        &mut tcg,
+        PCR_FILES,
+        config.canonical().as_bytes(),
+        "duke.cfg",
+    );
+    if let Some(device) = device {
+        match secureboot::read_image(device, path) {
+            Ok(image) => extend(&mut tcg, PCR_FILES, &image, path),
+            Err(e) => info!("TPM: {}: {:?}", path, e),
+        }
+    }
+    if let Some(options) = options {
+        let description = format!("kernel_cmdline: {}", options);
+        extend(&mut tcg, PCR_COMMANDS, options.as_bytes(), &description);
+    }
+}